@@ -1,16 +1,25 @@
+use super::{
+    header_policy::{HeaderNames, HeaderPolicy},
+    json_limits::JsonLimits,
+    warmup::CacheWarmup,
+};
 use envconfig::Envconfig;
-use osentities::{cache::CacheConfig, environment::Environment};
+use osentities::{cache::CacheConfig, environment::Environment, passthrough_recording::VcrMode};
 use osentities::{database::DatabaseConfig, secrets::SecretsConfig};
 use std::{
     fmt::{Display, Formatter, Result},
     net::SocketAddr,
 };
 use strum::{AsRefStr, EnumString};
+use unified::egress::AllowedHosts;
 
 #[derive(Envconfig, Clone)]
 pub struct ConnectionsConfig {
     #[envconfig(from = "WORKER_THREADS")]
     pub worker_threads: Option<usize>,
+    /// Upper bound of the runtime's blocking thread pool, Tokio's default when unset
+    #[envconfig(from = "MAX_BLOCKING_THREADS")]
+    pub max_blocking_threads: Option<usize>,
     #[envconfig(from = "INTERNAL_SERVER_ADDRESS", default = "0.0.0.0:3005")]
     pub address: SocketAddr,
     #[envconfig(from = "CACHE_SIZE", default = "100")]
@@ -21,18 +30,50 @@ pub struct ConnectionsConfig {
     pub access_key_whitelist_refresh_interval_secs: u64,
     #[envconfig(from = "CONNECTION_CACHE_TTL_SECS", default = "120")]
     pub connection_cache_ttl_secs: u64,
+    /// How long the previous key of a rotated connection keeps authenticating
+    #[envconfig(from = "CONNECTION_KEY_ROTATION_OVERLAP_SECS", default = "86400")]
+    pub connection_key_rotation_overlap_secs: u64,
     #[envconfig(from = "ENGINEERING_ACCOUNT_ID", default = "engineering_account")]
     pub engineering_account_id: String,
     #[envconfig(from = "CONNECTION_DEFINITION_CACHE_TTL_SECS", default = "86400")]
     pub connection_definition_cache_ttl_secs: u64,
+    #[envconfig(nested = true)]
+    pub cache_warmup: CacheWarmup,
     #[envconfig(from = "CONNECTION_OAUTH_DEFINITION_CACHE_TTL_SECS", default = "86400")]
     pub connection_oauth_definition_cache_ttl_secs: u64,
     #[envconfig(from = "CONNECTION_MODEL_SCHEMA_TTL_SECS", default = "86400")]
     pub connection_model_schema_cache_ttl_secs: u64,
     #[envconfig(from = "CONNECTION_MODEL_DEFINITION_CACHE_TTL_SECS", default = "86400")]
     pub connection_model_definition_cache_ttl_secs: u64,
+    /// Writes through this instance evict the mappings of the definition
+    /// right away, other instances see them once the entry expires
+    #[envconfig(from = "CONNECTION_VARIABLE_MAPPING_CACHE_TTL_SECS", default = "60")]
+    pub connection_variable_mapping_cache_ttl_secs: u64,
     #[envconfig(from = "SECRET_CACHE_TTL_SECS", default = "300")]
     pub secret_cache_ttl_secs: u64,
+    /// Number of blocking workers used to enrich large knowledge reads,
+    /// defaults to the available parallelism
+    #[envconfig(from = "KNOWLEDGE_ENRICHMENT_WORKERS")]
+    pub knowledge_enrichment_workers: Option<usize>,
+    /// Knowledge reads with fewer rows than this are enriched on the request task
+    #[envconfig(from = "KNOWLEDGE_ENRICHMENT_PARALLEL_THRESHOLD", default = "1000")]
+    pub knowledge_enrichment_parallel_threshold: usize,
+    /// What a knowledge read does when the mappings annotating it cannot be
+    /// loaded, either fail with a 503 or flag the response as degraded
+    #[envconfig(from = "KNOWLEDGE_MAPPING_FAILURE", default = "lenient")]
+    pub knowledge_mapping_failure: KnowledgeMappingFailure,
+    /// Definitions tested at once by a single batch test-connection request
+    #[envconfig(from = "TEST_CONNECTION_BATCH_CONCURRENCY", default = "8")]
+    pub test_connection_batch_concurrency: usize,
+    /// Page size of list endpoints when the client gives no `limit`
+    #[envconfig(from = "DEFAULT_PAGE_SIZE", default = "20")]
+    pub default_page_size: u64,
+    /// Largest page a list endpoint returns, bigger `limit`s are clamped to it
+    #[envconfig(from = "MAX_PAGE_SIZE", default = "1000")]
+    pub max_page_size: u64,
+    /// Actions run at once by a single connection health probe
+    #[envconfig(from = "CONNECTION_HEALTH_PROBE_CONCURRENCY", default = "4")]
+    pub connection_health_probe_concurrency: usize,
     #[envconfig(
         from = "EVENT_ACCESS_PASSWORD",
         default = "32KFFT_i4UpkJmyPwY2TGzgHpxfXs7zS"
@@ -46,8 +87,128 @@ pub struct ConnectionsConfig {
     pub event_save_timeout_secs: u64,
     #[envconfig(from = "METRIC_SAVE_CHANNEL_SIZE", default = "2048")]
     pub metric_save_channel_size: usize,
+    /// How long an event or metric waits for room in its full channel before
+    /// it is dropped, request handling never waits for it
+    #[envconfig(from = "TELEMETRY_SEND_TIMEOUT_MILLIS", default = "250")]
+    pub telemetry_send_timeout_millis: u64,
+    /// Persist the events the event channel drops and redeliver them to the
+    /// event store in the background
+    #[envconfig(from = "EVENT_DEAD_LETTER_ENABLED", default = "false")]
+    pub event_dead_letter_enabled: bool,
+    /// Pause between redelivery rounds, also the delay after a first failed
+    /// redelivery, which doubles with every further one
+    #[envconfig(from = "EVENT_DEAD_LETTER_INTERVAL_SECS", default = "60")]
+    pub event_dead_letter_interval_secs: u64,
+    #[envconfig(from = "EVENT_DEAD_LETTER_BATCH_SIZE", default = "100")]
+    pub event_dead_letter_batch_size: u64,
+    /// Events failing this many redeliveries are kept for operators but no
+    /// longer retried
+    #[envconfig(from = "EVENT_DEAD_LETTER_MAX_ATTEMPTS", default = "10")]
+    pub event_dead_letter_max_attempts: u32,
     #[envconfig(from = "METRIC_SYSTEM_ID", default = "Pica-Internal-System")]
     pub metric_system_id: String,
+    /// How long a validated import bundle stays available for commit
+    #[envconfig(from = "STAGED_BUNDLE_TTL_SECS", default = "3600")]
+    pub staged_bundle_ttl_secs: u64,
+    #[envconfig(from = "WEBHOOK_CHANNEL_SIZE", default = "1024")]
+    pub webhook_channel_size: usize,
+    #[envconfig(from = "WEBHOOK_MAX_ATTEMPTS", default = "5")]
+    pub webhook_max_attempts: u32,
+    /// Delay before the first webhook retry, doubled on every further attempt
+    #[envconfig(from = "WEBHOOK_RETRY_BASE_DELAY_MILLIS", default = "500")]
+    pub webhook_retry_base_delay_millis: u64,
+    #[envconfig(from = "WEBHOOK_TIMEOUT_SECS", default = "10")]
+    pub webhook_timeout_secs: u64,
+    /// Lets webhooks reach loopback, private and link-local addresses, which
+    /// are otherwise rejected at registration and on every delivery
+    #[envconfig(from = "WEBHOOK_ALLOW_INTERNAL_HOSTS", default = "false")]
+    pub webhook_allow_internal_hosts: bool,
+    /// Passthrough requests served concurrently, 0 disables admission control
+    #[envconfig(from = "PASSTHROUGH_MAX_CONCURRENCY", default = "512")]
+    pub passthrough_max_concurrency: usize,
+    /// Passthrough requests allowed to wait for a slot before shedding with 429
+    #[envconfig(from = "PASSTHROUGH_MAX_QUEUED", default = "1024")]
+    pub passthrough_max_queued: usize,
+    #[envconfig(from = "PASSTHROUGH_QUEUE_TIMEOUT_MILLIS", default = "5000")]
+    pub passthrough_queue_timeout_millis: u64,
+    #[envconfig(from = "PASSTHROUGH_RETRY_AFTER_SECS", default = "1")]
+    pub passthrough_retry_after_secs: u64,
+    /// Reshape passthrough responses with the definition's extractor unless
+    /// the request opts out with `x-pica-extract: false`
+    #[envconfig(from = "PASSTHROUGH_EXTRACT_RESPONSES", default = "false")]
+    pub passthrough_extract_responses: bool,
+    /// Records (`record`) or replays (`replay`) passthrough requests unless
+    /// the request picks a mode with `x-pica-vcr`
+    #[envconfig(from = "PASSTHROUGH_VCR_MODE", default = "off")]
+    pub passthrough_vcr_mode: VcrMode,
+    /// How far the `PICA-Timestamp` of a signed passthrough request may be
+    /// from the server clock, either way
+    #[envconfig(from = "PASSTHROUGH_SIGNATURE_MAX_SKEW_SECS", default = "300")]
+    pub passthrough_signature_max_skew_secs: u64,
+    /// Nonces of signed passthrough requests remembered to reject replays,
+    /// each for twice the skew window
+    #[envconfig(from = "PASSTHROUGH_NONCE_CACHE_SIZE", default = "100000")]
+    pub passthrough_nonce_cache_size: u64,
+    #[envconfig(nested = true)]
+    pub passthrough_header_policy: HeaderPolicy,
+    /// Platform response headers passthrough returns under their own name
+    /// instead of prefixed with `x-pica-passthrough-`, e.g. `location,etag`
+    /// for clients following pagination links. Allowlisting `set-cookie` hands
+    /// the platform's session cookies to the caller and lets the platform set
+    /// cookies on our domain in browsers calling it directly, so only add it
+    /// when the callers are trusted servers.
+    #[envconfig(from = "PASSTHROUGH_UNPREFIXED_RESPONSE_HEADERS", default = "")]
+    pub passthrough_unprefixed_response_headers: HeaderNames,
+    /// Holds passthrough requests back once a platform reports this many
+    /// requests left or fewer, until its rate limit resets
+    #[envconfig(from = "UPSTREAM_RATE_LIMIT_BACKPRESSURE", default = "true")]
+    pub upstream_rate_limit_backpressure: bool,
+    #[envconfig(from = "UPSTREAM_RATE_LIMIT_MIN_REMAINING", default = "0")]
+    pub upstream_rate_limit_min_remaining: u64,
+    /// Requests are delayed until the reset when it is at most this far off,
+    /// and rejected with 429 otherwise
+    #[envconfig(from = "UPSTREAM_RATE_LIMIT_MAX_DELAY_MILLIS", default = "1000")]
+    pub upstream_rate_limit_max_delay_millis: u64,
+    /// Extra attempts a passthrough request may make across every retry
+    /// source, e.g. resending after a token refresh
+    #[envconfig(from = "PASSTHROUGH_RETRY_BUDGET_MAX_ATTEMPTS", default = "1")]
+    pub passthrough_retry_budget_max_attempts: u32,
+    /// Time a passthrough request may spend waiting for or on retries before
+    /// it fails with 504
+    #[envconfig(from = "PASSTHROUGH_RETRY_BUDGET_MAX_DELAY_MILLIS", default = "5000")]
+    pub passthrough_retry_budget_max_delay_millis: u64,
+    /// Kept short so toggling a platform flag takes effect quickly
+    #[envconfig(from = "PLATFORM_FLAG_CACHE_TTL_SECS", default = "10")]
+    pub platform_flag_cache_ttl_secs: u64,
+    /// `Retry-After` sent for disabled platforms whose flag does not set one
+    #[envconfig(from = "PLATFORM_DISABLED_RETRY_AFTER_SECS", default = "60")]
+    pub platform_disabled_retry_after_secs: u64,
+    /// Rejects definitions whose schemas are malformed or whose samples do not
+    /// match them, instead of only logging a warning
+    #[envconfig(from = "REJECT_INVALID_DEFINITIONS", default = "true")]
+    pub reject_invalid_definitions: bool,
+    /// Applied to passthrough and definition test bodies
+    #[envconfig(nested = true)]
+    pub json_body_limits: JsonLimits,
+    /// HTTP(S) proxy the calls to connected platforms and webhooks go through
+    #[envconfig(from = "EGRESS_PROXY_URL")]
+    pub egress_proxy_url: Option<String>,
+    /// Hosts the calls to connected platforms may go to, `*.example.com`
+    /// allowing every subdomain. Calls to other hosts are rejected with 403,
+    /// every host is allowed when empty.
+    #[envconfig(from = "EGRESS_ALLOWED_HOSTS", default = "")]
+    pub egress_allowed_hosts: AllowedHosts,
+    /// Most redirects of a platform followed in a call. A redirect that is not
+    /// followed is returned to the caller, so 0 returns every redirect.
+    #[envconfig(from = "EGRESS_MAX_REDIRECTS", default = "5")]
+    pub egress_max_redirects: usize,
+    /// Follows redirects of a platform to another host, which must still be
+    /// allowed by `EGRESS_ALLOWED_HOSTS`
+    #[envconfig(from = "EGRESS_REDIRECT_CROSS_HOST", default = "false")]
+    pub egress_redirect_cross_host: bool,
+    /// Only follows the redirects that keep the method and body, 307 and 308
+    #[envconfig(from = "EGRESS_REDIRECT_PRESERVE_METHOD", default = "false")]
+    pub egress_redirect_preserve_method: bool,
     #[envconfig(from = "POSTHOG_WRITE_KEY")]
     pub posthog_write_key: Option<String>,
     #[envconfig(from = "POSTHOG_ENDPOINT")]
@@ -119,6 +280,7 @@ pub struct ConnectionsConfig {
 impl Display for ConnectionsConfig {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         writeln!(f, "WORKER_THREADS: {:?}", self.worker_threads)?;
+        writeln!(f, "MAX_BLOCKING_THREADS: {:?}", self.max_blocking_threads)?;
         writeln!(f, "INTERNAL_SERVER_ADDRESS: {}", self.address)?;
         writeln!(f, "CACHE_SIZE: {}", self.cache_size)?;
         writeln!(
@@ -143,17 +305,55 @@ impl Display for ConnectionsConfig {
             "CONNECTION_CACHE_TTL_SECS: {}",
             self.connection_cache_ttl_secs
         )?;
+        writeln!(
+            f,
+            "CONNECTION_KEY_ROTATION_OVERLAP_SECS: {}",
+            self.connection_key_rotation_overlap_secs
+        )?;
         writeln!(f, "CONNECTIONS_URL: {}", self.connections_url)?;
+        writeln!(
+            f,
+            "KNOWLEDGE_ENRICHMENT_WORKERS: {:?}",
+            self.knowledge_enrichment_workers
+        )?;
+        writeln!(
+            f,
+            "KNOWLEDGE_ENRICHMENT_PARALLEL_THRESHOLD: {}",
+            self.knowledge_enrichment_parallel_threshold
+        )?;
+        writeln!(
+            f,
+            "KNOWLEDGE_MAPPING_FAILURE: {}",
+            self.knowledge_mapping_failure.as_ref()
+        )?;
+        writeln!(
+            f,
+            "TEST_CONNECTION_BATCH_CONCURRENCY: {}",
+            self.test_connection_batch_concurrency
+        )?;
+        writeln!(f, "DEFAULT_PAGE_SIZE: {}", self.default_page_size)?;
+        writeln!(f, "MAX_PAGE_SIZE: {}", self.max_page_size)?;
+        writeln!(
+            f,
+            "CONNECTION_HEALTH_PROBE_CONCURRENCY: {}",
+            self.connection_health_probe_concurrency
+        )?;
         writeln!(
             f,
             "CONNECTION_DEFINITION_CACHE_TTL_SECS: {}",
             self.connection_definition_cache_ttl_secs
         )?;
+        write!(f, "{}", self.cache_warmup)?;
         writeln!(
             f,
             "CONNECTION_OAUTH_DEFINITION_CACHE_TTL_SECS: {}",
             self.connection_oauth_definition_cache_ttl_secs
         )?;
+        writeln!(
+            f,
+            "CONNECTION_VARIABLE_MAPPING_CACHE_TTL_SECS: {}",
+            self.connection_variable_mapping_cache_ttl_secs
+        )?;
         writeln!(
             f,
             "EVENT_SAVE_TIMEOUT_SECS: {}",
@@ -164,6 +364,141 @@ impl Display for ConnectionsConfig {
             "METRIC_SAVE_CHANNEL_SIZE: {}",
             self.metric_save_channel_size
         )?;
+        writeln!(
+            f,
+            "TELEMETRY_SEND_TIMEOUT_MILLIS: {}",
+            self.telemetry_send_timeout_millis
+        )?;
+        writeln!(
+            f,
+            "EVENT_DEAD_LETTER_ENABLED: {}",
+            self.event_dead_letter_enabled
+        )?;
+        writeln!(
+            f,
+            "EVENT_DEAD_LETTER_INTERVAL_SECS: {}",
+            self.event_dead_letter_interval_secs
+        )?;
+        writeln!(
+            f,
+            "EVENT_DEAD_LETTER_BATCH_SIZE: {}",
+            self.event_dead_letter_batch_size
+        )?;
+        writeln!(
+            f,
+            "EVENT_DEAD_LETTER_MAX_ATTEMPTS: {}",
+            self.event_dead_letter_max_attempts
+        )?;
+        writeln!(f, "STAGED_BUNDLE_TTL_SECS: {}", self.staged_bundle_ttl_secs)?;
+        writeln!(f, "WEBHOOK_CHANNEL_SIZE: {}", self.webhook_channel_size)?;
+        writeln!(f, "WEBHOOK_MAX_ATTEMPTS: {}", self.webhook_max_attempts)?;
+        writeln!(
+            f,
+            "WEBHOOK_RETRY_BASE_DELAY_MILLIS: {}",
+            self.webhook_retry_base_delay_millis
+        )?;
+        writeln!(f, "WEBHOOK_TIMEOUT_SECS: {}", self.webhook_timeout_secs)?;
+        writeln!(
+            f,
+            "WEBHOOK_ALLOW_INTERNAL_HOSTS: {}",
+            self.webhook_allow_internal_hosts
+        )?;
+        writeln!(
+            f,
+            "PASSTHROUGH_MAX_CONCURRENCY: {}",
+            self.passthrough_max_concurrency
+        )?;
+        writeln!(f, "PASSTHROUGH_MAX_QUEUED: {}", self.passthrough_max_queued)?;
+        writeln!(
+            f,
+            "PASSTHROUGH_QUEUE_TIMEOUT_MILLIS: {}",
+            self.passthrough_queue_timeout_millis
+        )?;
+        writeln!(
+            f,
+            "PASSTHROUGH_RETRY_AFTER_SECS: {}",
+            self.passthrough_retry_after_secs
+        )?;
+        writeln!(
+            f,
+            "PASSTHROUGH_EXTRACT_RESPONSES: {}",
+            self.passthrough_extract_responses
+        )?;
+        writeln!(
+            f,
+            "PASSTHROUGH_VCR_MODE: {}",
+            self.passthrough_vcr_mode.as_ref()
+        )?;
+        writeln!(
+            f,
+            "PASSTHROUGH_SIGNATURE_MAX_SKEW_SECS: {}",
+            self.passthrough_signature_max_skew_secs
+        )?;
+        writeln!(
+            f,
+            "PASSTHROUGH_NONCE_CACHE_SIZE: {}",
+            self.passthrough_nonce_cache_size
+        )?;
+        write!(f, "{}", self.passthrough_header_policy)?;
+        writeln!(
+            f,
+            "PASSTHROUGH_UNPREFIXED_RESPONSE_HEADERS: {}",
+            self.passthrough_unprefixed_response_headers
+        )?;
+        writeln!(
+            f,
+            "UPSTREAM_RATE_LIMIT_BACKPRESSURE: {}",
+            self.upstream_rate_limit_backpressure
+        )?;
+        writeln!(
+            f,
+            "UPSTREAM_RATE_LIMIT_MIN_REMAINING: {}",
+            self.upstream_rate_limit_min_remaining
+        )?;
+        writeln!(
+            f,
+            "UPSTREAM_RATE_LIMIT_MAX_DELAY_MILLIS: {}",
+            self.upstream_rate_limit_max_delay_millis
+        )?;
+        writeln!(
+            f,
+            "PASSTHROUGH_RETRY_BUDGET_MAX_ATTEMPTS: {}",
+            self.passthrough_retry_budget_max_attempts
+        )?;
+        writeln!(
+            f,
+            "PASSTHROUGH_RETRY_BUDGET_MAX_DELAY_MILLIS: {}",
+            self.passthrough_retry_budget_max_delay_millis
+        )?;
+        writeln!(
+            f,
+            "PLATFORM_FLAG_CACHE_TTL_SECS: {}",
+            self.platform_flag_cache_ttl_secs
+        )?;
+        writeln!(
+            f,
+            "PLATFORM_DISABLED_RETRY_AFTER_SECS: {}",
+            self.platform_disabled_retry_after_secs
+        )?;
+        writeln!(
+            f,
+            "REJECT_INVALID_DEFINITIONS: {}",
+            self.reject_invalid_definitions
+        )?;
+        write!(f, "{}", self.json_body_limits)?;
+        writeln!(f, "EGRESS_PROXY_URL: ***")?;
+        writeln!(f, "EGRESS_ALLOWED_HOSTS: {}", self.egress_allowed_hosts)?;
+        writeln!(f, "EGRESS_MAX_REDIRECTS: {}", self.egress_max_redirects)?;
+        writeln!(
+            f,
+            "EGRESS_REDIRECT_CROSS_HOST: {}",
+            self.egress_redirect_cross_host
+        )?;
+        writeln!(
+            f,
+            "EGRESS_REDIRECT_PRESERVE_METHOD: {}",
+            self.egress_redirect_preserve_method
+        )?;
         writeln!(f, "OTLP_ENDPOINT: ***")?;
         writeln!(f, "METRIC_SYSTEM_ID: {}", self.metric_system_id)?;
         writeln!(f, "POSTHOG_WRITE_KEY: ***")?;
@@ -231,6 +566,18 @@ impl Display for Headers {
     }
 }
 
+/// Whether knowledge is still returned when its mappings cannot be loaded.
+/// Either way the failure is surfaced, so that no mappings is never confused
+/// with mappings that could not be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum KnowledgeMappingFailure {
+    /// The read fails with a 503
+    Strict,
+    /// The knowledge is returned without annotations and `degraded: true`
+    Lenient,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, AsRefStr)]
 #[strum(serialize_all = "kebab-case")]
 pub enum K8sMode {
@@ -0,0 +1,153 @@
+use super::{config::ConnectionsConfig, telemetry::DeadLetter};
+use bson::doc;
+use chrono::Utc;
+use osentities::{algebra::MongoStore, dead_letter::DeadLetterEvent, Event, PicaError};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Persists the events the event channel drops, see [`DeadLetterEvent`]
+#[derive(Debug, Clone)]
+pub struct EventDeadLetter {
+    store: MongoStore<DeadLetterEvent>,
+}
+
+impl EventDeadLetter {
+    pub fn new(store: MongoStore<DeadLetterEvent>) -> Self {
+        Self { store }
+    }
+}
+
+impl DeadLetter<Event> for EventDeadLetter {
+    fn store(&self, event: Event, reason: &str) {
+        let letter = DeadLetterEvent::new(event, reason, Utc::now().timestamp_millis());
+        let store = self.store.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = store.create_one(&letter).await {
+                error!("Could not dead-letter event {}: {e}", letter.id);
+            }
+        });
+    }
+}
+
+/// Backlog of the event dead letter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetterStats {
+    /// Events waiting for redelivery
+    pub pending: u64,
+    /// Events that failed every redelivery and are no longer retried
+    pub exhausted: u64,
+}
+
+pub async fn dead_letter_stats(
+    store: &MongoStore<DeadLetterEvent>,
+    max_attempts: u32,
+) -> Result<DeadLetterStats, PicaError> {
+    let (pending, exhausted) = tokio::try_join!(
+        store.count(doc! { "attempts": { "$lt": max_attempts } }, None),
+        store.count(doc! { "attempts": { "$gte": max_attempts } }, None)
+    )?;
+
+    Ok(DeadLetterStats { pending, exhausted })
+}
+
+/// Redelivers the due dead-lettered events to the event store every
+/// `EVENT_DEAD_LETTER_INTERVAL_SECS`
+pub fn spawn_redelivery(
+    dead_letters: MongoStore<DeadLetterEvent>,
+    events: MongoStore<Event>,
+    config: &ConnectionsConfig,
+) {
+    let interval = Duration::from_secs(config.event_dead_letter_interval_secs.max(1));
+    let batch_size = config.event_dead_letter_batch_size;
+    let max_attempts = config.event_dead_letter_max_attempts;
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+
+            match redeliver_due(
+                &dead_letters,
+                &events,
+                batch_size,
+                max_attempts,
+                interval.as_millis() as u64,
+            )
+            .await
+            {
+                Ok(0) => {}
+                Ok(redelivered) => info!("Redelivered {redelivered} dead-lettered events"),
+                Err(e) => error!("Could not redeliver dead-lettered events: {e}"),
+            }
+        }
+    });
+}
+
+/// Writes up to `batch_size` due events to the event store, oldest due first,
+/// and returns how many made it. Writes are upserts by id, so an event stored
+/// before it was dead-lettered is not duplicated. Failed events are retried
+/// after a doubling delay, until they failed `max_attempts` times.
+pub async fn redeliver_due(
+    dead_letters: &MongoStore<DeadLetterEvent>,
+    events: &MongoStore<Event>,
+    batch_size: u64,
+    max_attempts: u32,
+    base_delay_millis: u64,
+) -> Result<usize, PicaError> {
+    let now = Utc::now().timestamp_millis();
+
+    let due = dead_letters
+        .get_many(
+            Some(doc! {
+                "attempts": { "$lt": max_attempts },
+                "nextAttemptAt": { "$lte": now },
+            }),
+            None,
+            Some(doc! { "nextAttemptAt": 1 }),
+            Some(batch_size),
+            None,
+        )
+        .await?;
+
+    let mut redelivered = 0;
+
+    for letter in due {
+        let id = letter.id.to_string();
+        let delivered = events
+            .collection
+            .replace_one(doc! { "_id": &id }, &letter.event)
+            .upsert(true)
+            .await;
+
+        match delivered {
+            Ok(_) => {
+                dead_letters
+                    .collection
+                    .delete_one(doc! { "_id": &id })
+                    .await?;
+                redelivered += 1;
+            }
+            Err(e) => {
+                let next_attempt_at = letter.next_attempt_after_failure(base_delay_millis, now);
+
+                dead_letters
+                    .update_one(
+                        &id,
+                        doc! {
+                            "$set": {
+                                "attempts": letter.attempts + 1,
+                                "lastError": e.to_string(),
+                                "nextAttemptAt": next_attempt_at,
+                                "updatedAt": now,
+                            }
+                        },
+                    )
+                    .await?;
+            }
+        }
+    }
+
+    Ok(redelivered)
+}
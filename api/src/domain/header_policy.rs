@@ -0,0 +1,152 @@
+use envconfig::Envconfig;
+use http::{header::HOST, HeaderMap, HeaderName};
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+use strum::{AsRefStr, EnumString};
+
+/// Whether passthrough forwards only the allowed headers or every header but
+/// the denied ones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum HeaderPolicyMode {
+    Allow,
+    #[default]
+    Deny,
+}
+
+/// Comma separated list of header names, matched case-insensitively
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderNames(HashSet<HeaderName>);
+
+impl HeaderNames {
+    pub fn contains(&self, name: &HeaderName) -> bool {
+        self.0.contains(name)
+    }
+}
+
+impl FromStr for HeaderNames {
+    type Err = http::header::InvalidHeaderName;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| HeaderName::from_bytes(name.as_bytes()))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Display for HeaderNames {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut names = self.0.iter().map(HeaderName::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+
+        write!(f, "{}", names.join(","))
+    }
+}
+
+/// Decides which of the caller's headers passthrough sends on to the platform.
+/// `Host` is never forwarded, the platform always sees its own host.
+#[derive(Envconfig, Debug, Clone, Default)]
+pub struct HeaderPolicy {
+    #[envconfig(from = "PASSTHROUGH_HEADER_POLICY", default = "deny")]
+    pub mode: HeaderPolicyMode,
+    /// Forwarded in `allow` mode, every other header is dropped
+    #[envconfig(from = "PASSTHROUGH_ALLOWED_HEADERS", default = "accept,content-type")]
+    pub allowed: HeaderNames,
+    /// Dropped in `deny` mode: cookies, proxy and tracing headers of our own
+    /// infrastructure and hop-by-hop headers
+    #[envconfig(
+        from = "PASSTHROUGH_DENIED_HEADERS",
+        default = "cookie,forwarded,x-forwarded-for,x-forwarded-host,x-forwarded-port,x-forwarded-proto,x-real-ip,traceparent,tracestate,baggage,x-amzn-trace-id,x-cloud-trace-context,connection,keep-alive,proxy-authorization,te,trailer,transfer-encoding,upgrade"
+    )]
+    pub denied: HeaderNames,
+}
+
+impl HeaderPolicy {
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        headers.remove(HOST);
+
+        let names = headers.keys().cloned().collect::<Vec<_>>();
+        for name in names {
+            let forwarded = match self.mode {
+                HeaderPolicyMode::Allow => self.allowed.contains(&name),
+                HeaderPolicyMode::Deny => !self.denied.contains(&name),
+            };
+
+            if !forwarded {
+                headers.remove(&name);
+            }
+        }
+    }
+}
+
+impl Display for HeaderPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PASSTHROUGH_HEADER_POLICY: {}", self.mode.as_ref())?;
+        writeln!(f, "PASSTHROUGH_ALLOWED_HEADERS: {}", self.allowed)?;
+        writeln!(f, "PASSTHROUGH_DENIED_HEADERS: {}", self.denied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    fn headers() -> HeaderMap {
+        ["Host", "Cookie", "X-Forwarded-For", "Accept", "X-Custom"]
+            .into_iter()
+            .map(|name| {
+                (
+                    HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                    HeaderValue::from_static("value"),
+                )
+            })
+            .collect()
+    }
+
+    fn names(headers: &HeaderMap) -> Vec<&str> {
+        let mut names = headers.keys().map(HeaderName::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn deny_mode_strips_denied_headers_and_host() {
+        let policy = HeaderPolicy {
+            mode: HeaderPolicyMode::Deny,
+            allowed: HeaderNames::default(),
+            denied: "Cookie, x-forwarded-for".parse().unwrap(),
+        };
+
+        let mut headers = headers();
+        policy.apply(&mut headers);
+
+        assert_eq!(names(&headers), vec!["accept", "x-custom"]);
+    }
+
+    #[test]
+    fn allow_mode_only_forwards_allowed_headers() {
+        let policy = HeaderPolicy {
+            mode: "Allow".parse().unwrap(),
+            allowed: "accept,host".parse().unwrap(),
+            denied: HeaderNames::default(),
+        };
+
+        let mut headers = headers();
+        policy.apply(&mut headers);
+
+        assert_eq!(names(&headers), vec!["accept"]);
+    }
+
+    #[test]
+    fn invalid_header_names_are_rejected() {
+        assert!("accept,not a header".parse::<HeaderNames>().is_err());
+        assert_eq!("".parse::<HeaderNames>().unwrap(), HeaderNames::default());
+    }
+}
@@ -0,0 +1,289 @@
+use envconfig::Envconfig;
+use http::{header::CONTENT_TYPE, HeaderMap};
+use osentities::{ApplicationError, PicaError, PicaErrorCode};
+use std::fmt::{self, Display, Formatter};
+
+/// Bounds the shape of JSON bodies before they are parsed, injected into and
+/// serialized again. The raw bytes are scanned without building the value, so
+/// a pathological body is rejected with a 400 before it can recurse deeply or
+/// allocate per entry.
+#[derive(Envconfig, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonLimits {
+    /// Most arrays and objects nested in one another
+    #[envconfig(from = "JSON_BODY_MAX_DEPTH", default = "64")]
+    pub max_depth: usize,
+    /// Most elements of one array or members of one object
+    #[envconfig(from = "JSON_BODY_MAX_ENTRIES", default = "10000")]
+    pub max_entries: usize,
+}
+
+impl JsonLimits {
+    /// Checks a passthrough body labelled as JSON, or not labelled at all.
+    /// Bodies of other types are never parsed as JSON and pass as they are.
+    pub fn check_request(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), PicaError> {
+        let is_json = headers
+            .get(CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap_or_default())
+            .and_then(|value| value.split(';').next())
+            .is_none_or(|mime| {
+                let mime = mime.trim().to_ascii_lowercase();
+                mime == "application/json" || mime.ends_with("+json")
+            });
+
+        if is_json {
+            self.check(body)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Only keeps a counter per open array or object, so the scan itself
+    /// allocates at most `max_depth` of them. Malformed JSON is left for the
+    /// parser to reject.
+    pub fn check(&self, body: &[u8]) -> Result<(), PicaError> {
+        let mut separators: Vec<usize> = Vec::new();
+        let mut in_string = false;
+        let mut escaped = false;
+
+        for &byte in body {
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => in_string = true,
+                b'[' | b'{' => {
+                    if separators.len() >= self.max_depth {
+                        return Err(too_complex(&format!(
+                            "JSON body is nested deeper than {} levels",
+                            self.max_depth
+                        )));
+                    }
+                    separators.push(0);
+                }
+                b']' | b'}' => {
+                    separators.pop();
+                }
+                b',' => {
+                    if let Some(count) = separators.last_mut() {
+                        *count += 1;
+                        // n separators delimit n + 1 entries
+                        if *count >= self.max_entries {
+                            return Err(too_complex(&format!(
+                                "JSON body has an array or object of more than {} entries",
+                                self.max_entries
+                            )));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn too_complex(message: &str) -> PicaError {
+    ApplicationError::bad_request(message, PicaErrorCode::BodyTooComplex.subtype())
+}
+
+impl Display for JsonLimits {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "JSON_BODY_MAX_DEPTH: {}", self.max_depth)?;
+        writeln!(f, "JSON_BODY_MAX_ENTRIES: {}", self.max_entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+    use osentities::{
+        connection_variable_mapping::{
+            BodyMerge, InjectionStrategy, ParameterLocation, VariableBinding, VariableDataType,
+        },
+        variable_injection::{apply_bindings, RequestParts},
+    };
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+    use serde_json::{json, Map, Value};
+
+    const LIMITS: JsonLimits = JsonLimits {
+        max_depth: 8,
+        max_entries: 16,
+    };
+
+    fn depth(value: &Value) -> usize {
+        match value {
+            Value::Array(items) => 1 + items.iter().map(depth).max().unwrap_or(0),
+            Value::Object(members) => 1 + members.values().map(depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn widest(value: &Value) -> usize {
+        match value {
+            Value::Array(items) => items.iter().map(widest).fold(items.len(), usize::max),
+            Value::Object(members) => members.values().map(widest).fold(members.len(), usize::max),
+            _ => 0,
+        }
+    }
+
+    /// At most `nodes` values, so the tree stays small however deep it goes
+    fn random_value(rng: &mut StdRng, levels: usize, nodes: &mut usize) -> Value {
+        let scalars = [
+            json!(null),
+            json!(true),
+            json!(-1.5e3),
+            json!("plain"),
+            // Structural characters inside strings must not count
+            json!("[{\"],},\\"),
+            json!("\u{1F600}\\\""),
+        ];
+        *nodes = nodes.saturating_sub(1);
+
+        match rng.gen_range(0..4) {
+            _ if levels == 0 || *nodes == 0 => scalars.choose(rng).cloned().unwrap_or_default(),
+            0 => scalars.choose(rng).cloned().unwrap_or_default(),
+            1 | 2 => Value::Array(
+                (0..rng.gen_range(0..24))
+                    .map(|_| random_value(rng, levels - 1, nodes))
+                    .collect(),
+            ),
+            _ => Value::Object(
+                (0..rng.gen_range(0..24))
+                    .map(|i| (format!("k,{i}:"), random_value(rng, levels - 1, nodes)))
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+
+    /// Sends a body through injection and back out as it is dispatched
+    fn inject_and_reserialize(body: &[u8]) {
+        let bindings = [VariableBinding {
+            variable_name: "hotel_id".to_string(),
+            target_param: "reservation.guest.0.id".to_string(),
+            location: ParameterLocation::BodyField,
+            constant: None,
+            strategy: InjectionStrategy::Strict,
+            merge: BodyMerge::Deep,
+            data_type: VariableDataType::String,
+            required: false,
+            condition: None,
+            is_secret: None,
+        }];
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let parts = RequestParts {
+            headers,
+            body: Some(body.to_vec()),
+            ..Default::default()
+        };
+
+        if let Ok(resolved) = apply_bindings(parts, &bindings, &json!({ "hotel_id": "h1" })) {
+            if let Some(body) = resolved.parts.body {
+                let _ = serde_json::from_slice::<Value>(&body).map(|value| value.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_scan_agrees_with_the_parsed_shape() {
+        let mut rng = StdRng::seed_from_u64(1884);
+
+        for _ in 0..2000 {
+            let levels = rng.gen_range(0..12);
+            let value = random_value(&mut rng, levels, &mut 200);
+            let body = value.to_string();
+
+            let within = depth(&value) <= LIMITS.max_depth && widest(&value) <= LIMITS.max_entries;
+            let checked = LIMITS.check(body.as_bytes());
+            assert_eq!(checked.is_ok(), within, "{body}");
+            if let Err(error) = checked {
+                assert_eq!(error.status(), 400);
+                assert_eq!(error.error_code(), Some(PicaErrorCode::BodyTooComplex));
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzzed_bodies_never_panic_when_injected() {
+        let mut rng = StdRng::seed_from_u64(18840);
+        let alphabet = b"[]{}\",:\\ 0123456789.eE-+truefalsnl\xff\x00";
+
+        for _ in 0..5000 {
+            let levels = rng.gen_range(0..6);
+            let mut body = random_value(&mut rng, levels, &mut 100)
+                .to_string()
+                .into_bytes();
+            // Mutates valid JSON into the malformed bodies a fuzzer would send
+            for _ in 0..rng.gen_range(0..8) {
+                let byte = *alphabet.choose(&mut rng).unwrap_or(&b'[');
+                match rng.gen_range(0..3) {
+                    0 if !body.is_empty() => {
+                        let i = rng.gen_range(0..body.len());
+                        body[i] = byte;
+                    }
+                    1 if !body.is_empty() => {
+                        body.remove(rng.gen_range(0..body.len()));
+                    }
+                    _ => body.insert(rng.gen_range(0..=body.len()), byte),
+                }
+            }
+
+            if LIMITS.check(&body).is_ok() {
+                if let Ok(value) = serde_json::from_slice::<Value>(&body) {
+                    assert!(depth(&value) <= LIMITS.max_depth);
+                    assert!(widest(&value) <= LIMITS.max_entries);
+                }
+                inject_and_reserialize(&body);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pathological_bodies_are_rejected_before_parsing() {
+        let limits = JsonLimits {
+            max_depth: 64,
+            max_entries: 10_000,
+        };
+
+        // Deep enough to overflow the stack of a recursive drop once parsed
+        let nested = "[".repeat(1_000_000);
+        assert!(limits.check(nested.as_bytes()).is_err());
+        let nested = format!("{}1{}", "[".repeat(64), "]".repeat(64));
+        assert!(limits.check(nested.as_bytes()).is_ok());
+        inject_and_reserialize(nested.as_bytes());
+
+        let wide = format!("[{}0]", "0,".repeat(10_000));
+        assert!(limits.check(wide.as_bytes()).is_err());
+        let wide = format!("[{}0]", "0,".repeat(9_999));
+        assert!(limits.check(wide.as_bytes()).is_ok());
+
+        // Other bodies are forwarded as they are
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        assert!(limits
+            .check_request(&headers, "[".repeat(65).as_bytes())
+            .is_ok());
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/vnd.api+json"),
+        );
+        assert!(limits
+            .check_request(&headers, "[".repeat(65).as_bytes())
+            .is_err());
+        assert!(limits
+            .check_request(&HeaderMap::new(), "[".repeat(65).as_bytes())
+            .is_err());
+    }
+}
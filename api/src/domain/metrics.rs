@@ -1,7 +1,10 @@
 use chrono::{DateTime, Datelike, Utc};
-use http::HeaderValue;
+use http::{HeaderValue, StatusCode};
 use osentities::{
-    constant::{CREATED_AT_KEY, DAILY_KEY, MONTHLY_KEY, PLATFORMS_KEY, TOTAL_KEY},
+    constant::{
+        CIRCUIT_OPEN_KEY, CREATED_AT_KEY, DAILY_KEY, LATENCIES_KEY, MONTHLY_KEY, PLATFORMS_KEY,
+        RETRIES_KEY, STATUSES_KEY, TOTAL_KEY,
+    },
     destination::Action,
     event_access::EventAccess,
     ownership::Ownership,
@@ -9,7 +12,7 @@ use osentities::{
 };
 use posthog_rs::Event;
 use serde::Deserialize;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 #[derive(Debug, Clone, strum::Display, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -34,11 +37,71 @@ impl MetricType {
     }
 }
 
+/// Class of the status a platform answered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum StatusClass {
+    #[strum(serialize = "1xx")]
+    Informational,
+    #[strum(serialize = "2xx")]
+    Success,
+    #[strum(serialize = "3xx")]
+    Redirection,
+    #[strum(serialize = "4xx")]
+    ClientError,
+    #[strum(serialize = "5xx")]
+    ServerError,
+}
+
+impl From<StatusCode> for StatusClass {
+    fn from(status: StatusCode) -> Self {
+        match status.as_u16() {
+            ..=199 => StatusClass::Informational,
+            200..=299 => StatusClass::Success,
+            300..=399 => StatusClass::Redirection,
+            400..=499 => StatusClass::ClientError,
+            _ => StatusClass::ServerError,
+        }
+    }
+}
+
+/// Range the latency of a platform request falls in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum LatencyBucket {
+    #[strum(serialize = "lt100ms")]
+    Under100Ms,
+    #[strum(serialize = "lt500ms")]
+    Under500Ms,
+    #[strum(serialize = "lt1s")]
+    Under1S,
+    #[strum(serialize = "lt5s")]
+    Under5S,
+    #[strum(serialize = "gte5s")]
+    Over5S,
+}
+
+impl From<Duration> for LatencyBucket {
+    fn from(latency: Duration) -> Self {
+        match latency.as_millis() {
+            ..=99 => LatencyBucket::Under100Ms,
+            100..=499 => LatencyBucket::Under500Ms,
+            500..=999 => LatencyBucket::Under1S,
+            1000..=4999 => LatencyBucket::Under5S,
+            _ => LatencyBucket::Over5S,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Metric {
     pub metric_type: MetricType,
     pub date: DateTime<Utc>,
     pub action: Option<Action>,
+    pub status_class: Option<StatusClass>,
+    pub latency: Option<LatencyBucket>,
+    pub retries: u32,
+    /// The request was held back without reaching the platform, because the
+    /// platform's rate limit was used up
+    pub circuit_open: bool,
 }
 
 impl Metric {
@@ -48,28 +111,52 @@ impl Metric {
 }
 
 impl Metric {
-    pub fn passthrough(connection: Arc<Connection>) -> Self {
+    fn new(metric_type: MetricType, action: Option<Action>) -> Self {
         Self {
-            metric_type: MetricType::Passthrough(connection),
+            metric_type,
             date: Utc::now(),
-            action: None,
+            action,
+            status_class: None,
+            latency: None,
+            retries: 0,
+            circuit_open: false,
         }
     }
 
+    pub fn passthrough(connection: Arc<Connection>) -> Self {
+        Self::new(MetricType::Passthrough(connection), None)
+    }
+
     pub fn unified(connection: Arc<Connection>, action: Action) -> Self {
-        Self {
-            metric_type: MetricType::Unified(connection),
-            date: Utc::now(),
-            action: Some(action),
-        }
+        Self::new(MetricType::Unified(connection), Some(action))
     }
 
     pub fn rate_limited(event_access: Arc<EventAccess>, key: Option<HeaderValue>) -> Self {
-        Self {
-            metric_type: MetricType::RateLimited(event_access, key),
-            date: Utc::now(),
-            action: None,
-        }
+        Self::new(MetricType::RateLimited(event_access, key), None)
+    }
+
+    /// Records the status the platform answered with
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status_class = Some(status.into());
+        self
+    }
+
+    /// Records how long the platform took to answer
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency.into());
+        self
+    }
+
+    /// Records how many times the request was sent again
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Records that the request was held back without reaching the platform
+    pub fn with_circuit_open(mut self) -> Self {
+        self.circuit_open = true;
+        self
     }
 
     pub fn ownership(&self) -> &Ownership {
@@ -98,15 +185,48 @@ impl Metric {
         let year = self.date.year();
         let daily_key = format!("{year}-{month:02}-{day:02}");
         let monthly_key = format!("{year}-{month:02}");
+        let platform_key = format!("{metric_type}.{PLATFORMS_KEY}.{platform}");
+
+        // A request held back never reached the platform, so it is counted
+        // apart from the requests made
+        if self.circuit_open {
+            return bson::doc! {
+                "$inc": {
+                    format!("{metric_type}.{CIRCUIT_OPEN_KEY}"): 1,
+                    format!("{platform_key}.{CIRCUIT_OPEN_KEY}"): 1,
+                },
+                "$setOnInsert": {
+                    CREATED_AT_KEY: self.date.timestamp_millis()
+                }
+            };
+        }
+
+        let mut inc = bson::doc! {
+            format!("{metric_type}.{TOTAL_KEY}"): 1,
+            format!("{platform_key}.{TOTAL_KEY}"): 1,
+            format!("{metric_type}.{DAILY_KEY}.{daily_key}"): 1,
+            format!("{platform_key}.{DAILY_KEY}.{daily_key}"): 1,
+            format!("{metric_type}.{MONTHLY_KEY}.{monthly_key}"): 1,
+            format!("{platform_key}.{MONTHLY_KEY}.{monthly_key}"): 1,
+        };
+
+        if let Some(status_class) = self.status_class {
+            inc.insert(format!("{metric_type}.{STATUSES_KEY}.{status_class}"), 1);
+            inc.insert(format!("{platform_key}.{STATUSES_KEY}.{status_class}"), 1);
+        }
+
+        if let Some(latency) = self.latency {
+            inc.insert(format!("{metric_type}.{LATENCIES_KEY}.{latency}"), 1);
+            inc.insert(format!("{platform_key}.{LATENCIES_KEY}.{latency}"), 1);
+        }
+
+        if self.retries > 0 {
+            inc.insert(format!("{metric_type}.{RETRIES_KEY}"), self.retries as i64);
+            inc.insert(format!("{platform_key}.{RETRIES_KEY}"), self.retries as i64);
+        }
+
         bson::doc! {
-            "$inc": {
-                format!("{metric_type}.{TOTAL_KEY}"): 1,
-                format!("{metric_type}.{PLATFORMS_KEY}.{platform}.{TOTAL_KEY}"): 1,
-                format!("{metric_type}.{DAILY_KEY}.{daily_key}"): 1,
-                format!("{metric_type}.{PLATFORMS_KEY}.{platform}.{DAILY_KEY}.{daily_key}"): 1,
-                format!("{metric_type}.{MONTHLY_KEY}.{monthly_key}"): 1,
-                format!("{metric_type}.{PLATFORMS_KEY}.{platform}.{MONTHLY_KEY}.{monthly_key}"): 1,
-            },
+            "$inc": inc,
             "$setOnInsert": {
                 CREATED_AT_KEY: self.date.timestamp_millis()
             }
@@ -180,3 +300,34 @@ impl Metric {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_status_and_latency() {
+        for (status, class) in [
+            (StatusCode::CONTINUE, "1xx"),
+            (StatusCode::NO_CONTENT, "2xx"),
+            (StatusCode::FOUND, "3xx"),
+            (StatusCode::TOO_MANY_REQUESTS, "4xx"),
+            (StatusCode::BAD_GATEWAY, "5xx"),
+        ] {
+            assert_eq!(StatusClass::from(status).to_string(), class);
+        }
+
+        for (millis, bucket) in [
+            (0, "lt100ms"),
+            (100, "lt500ms"),
+            (999, "lt1s"),
+            (4_999, "lt5s"),
+            (5_000, "gte5s"),
+        ] {
+            assert_eq!(
+                LatencyBucket::from(Duration::from_millis(millis)).to_string(),
+                bucket
+            );
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Data migrations of stored records, run before the server accepts traffic.
+//! Each migration runs once per database: completed ones are recorded in the
+//! migrations collection and skipped on later starts, while one that failed
+//! is retried on the next start. Migrations must be idempotent, as instances
+//! starting together may run the same one concurrently.
+
+use crate::{
+    logic::{connection_key_collation, connection_model_definition::derive_key},
+    server::AppStores,
+};
+use chrono::Utc;
+use futures::{future::BoxFuture, TryStreamExt};
+use mongodb::{
+    bson::{self, doc, Document},
+    options::IndexOptions,
+    IndexModel,
+};
+use osentities::{connection_model_definition::CrudAction, PicaError, Store};
+use serde::Deserialize;
+use std::collections::HashSet;
+use tracing::{error, info, warn};
+
+/// Rewrites the keys of definitions stored before key fields were escaped.
+/// Until it completes, definitions are also looked up by their legacy key.
+pub const ESCAPE_DEFINITION_KEYS: &str = "escape-definition-keys";
+
+/// Backs the uniqueness of the keys of live definitions with an index, so
+/// that concurrent writes under the same key cannot both succeed
+pub const UNIQUE_DEFINITION_KEYS: &str = "unique-definition-keys";
+
+/// Platform mappings used to be stored for the live environment only while
+/// applying to every environment. Resolution now honours the environment, so
+/// they are turned into mappings applying to every environment.
+pub const PLATFORM_MAPPINGS_TO_EVERY_ENVIRONMENT: &str = "platform-mappings-to-every-environment";
+
+/// Backs the uniqueness of mappings per definition, environment and owner with
+/// an index, once platform mappings apply to every environment
+pub const UNIQUE_MAPPINGS: &str = "unique-mappings";
+
+/// Indexes connection keys ignoring case, so that keys whose case was changed
+/// are looked up without scanning every connection of the owner
+pub const CASE_INSENSITIVE_CONNECTION_KEYS: &str = "case-insensitive-connection-keys";
+
+type Migration = for<'a> fn(&'a AppStores) -> BoxFuture<'a, Result<u64, PicaError>>;
+
+/// Migrations in the order they run. Names identify them in the migrations
+/// collection, so they are never changed nor reused.
+const MIGRATIONS: &[(&str, Migration)] = &[
+    (ESCAPE_DEFINITION_KEYS, |stores| {
+        Box::pin(escape_definition_keys(stores))
+    }),
+    (UNIQUE_DEFINITION_KEYS, |stores| {
+        Box::pin(unique_definition_keys(stores))
+    }),
+    (PLATFORM_MAPPINGS_TO_EVERY_ENVIRONMENT, |stores| {
+        Box::pin(platform_mappings_to_every_environment(stores))
+    }),
+    (UNIQUE_MAPPINGS, |stores| Box::pin(unique_mappings(stores))),
+    (CASE_INSENSITIVE_CONNECTION_KEYS, |stores| {
+        Box::pin(case_insensitive_connection_keys(stores))
+    }),
+];
+
+/// Migrations that completed, either before or during this start
+#[derive(Debug, Clone, Default)]
+pub struct AppliedMigrations(HashSet<&'static str>);
+
+impl AppliedMigrations {
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+}
+
+/// Runs the migrations that have not completed yet. Never fails: a migration
+/// that fails is logged and left out of the applied ones, so that the code
+/// depending on it keeps its fallbacks.
+pub async fn run(stores: &AppStores) -> AppliedMigrations {
+    let ledger = stores
+        .db
+        .collection::<Document>(&Store::Migrations.to_string());
+
+    let recorded: HashSet<String> = match ledger.distinct("_id", doc! {}).await {
+        Ok(ids) => ids
+            .into_iter()
+            .filter_map(|id| id.as_str().map(str::to_owned))
+            .collect(),
+        Err(e) => {
+            error!("Could not read applied migrations, none is run: {e}");
+            return AppliedMigrations::default();
+        }
+    };
+
+    let mut applied = AppliedMigrations::default();
+    for (name, migration) in MIGRATIONS {
+        if recorded.contains(*name) {
+            applied.0.insert(name);
+            continue;
+        }
+
+        match migration(stores).await {
+            Ok(modified) => {
+                info!("Applied migration {name}, {modified} records modified");
+
+                let record = doc! {
+                    "_id": name,
+                    "appliedAt": Utc::now().timestamp_millis(),
+                    "modified": modified as i64,
+                };
+                // A concurrent start recording it first is just as good
+                if let Err(e) = ledger.insert_one(record).await {
+                    warn!("Could not record migration {name}: {e}");
+                }
+                applied.0.insert(name);
+            }
+            Err(e) => error!("Migration {name} failed, it is retried on the next start: {e}"),
+        }
+    }
+
+    applied
+}
+
+/// The fields a definition key is derived from
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyFields {
+    #[serde(rename = "_id")]
+    id: String,
+    #[serde(default)]
+    key: String,
+    connection_platform: String,
+    platform_version: String,
+    model_name: String,
+    action_name: CrudAction,
+    path: String,
+    name: String,
+}
+
+async fn escape_definition_keys(stores: &AppStores) -> Result<u64, PicaError> {
+    let collection = stores.model_config.collection.clone_with_type::<Document>();
+
+    let mut cursor = collection
+        .find(doc! {})
+        .projection(doc! {
+            "key": 1,
+            "connectionPlatform": 1,
+            "platformVersion": 1,
+            "modelName": 1,
+            "actionName": 1,
+            "path": 1,
+            "name": 1,
+        })
+        .await?;
+
+    let mut modified = 0;
+    while let Some(document) = cursor.try_next().await? {
+        let fields = match bson::from_document::<KeyFields>(document) {
+            Ok(fields) => fields,
+            Err(e) => {
+                warn!("Skipping the key of a definition that could not be read: {e}");
+                continue;
+            }
+        };
+
+        let key = derive_key(
+            &fields.connection_platform,
+            &fields.platform_version,
+            &fields.model_name,
+            &fields.action_name,
+            &fields.path,
+            &fields.name,
+        );
+        if key == fields.key {
+            continue;
+        }
+
+        collection
+            .update_one(doc! { "_id": &fields.id }, doc! { "$set": { "key": key } })
+            .await?;
+        modified += 1;
+    }
+
+    Ok(modified)
+}
+
+/// Fails while live definitions share a key, which then have to be deleted or
+/// renamed before the next start
+async fn unique_definition_keys(stores: &AppStores) -> Result<u64, PicaError> {
+    let index = IndexModel::builder()
+        .keys(doc! { "key": 1 })
+        .options(
+            IndexOptions::builder()
+                .name("key_unique".to_string())
+                .unique(true)
+                .partial_filter_expression(doc! { "deleted": false })
+                .build(),
+        )
+        .build();
+
+    stores.model_config.collection.create_index(index).await?;
+
+    Ok(0)
+}
+
+async fn platform_mappings_to_every_environment(stores: &AppStores) -> Result<u64, PicaError> {
+    let result = stores
+        .connection_variable_mapping
+        .collection
+        .update_many(
+            doc! { "ownership.buildableId": "", "environment": "live" },
+            doc! { "$set": { "environment": null } },
+        )
+        .await?;
+
+    Ok(result.modified_count)
+}
+
+/// Fails while live mappings of an owner share a definition and environment,
+/// which then have to be deleted before the next start
+async fn unique_mappings(stores: &AppStores) -> Result<u64, PicaError> {
+    let index = IndexModel::builder()
+        .keys(doc! {
+            "connectionModelDefinitionId": 1,
+            "environment": 1,
+            "ownership.buildableId": 1,
+        })
+        .options(
+            IndexOptions::builder()
+                .name("definition_environment_owner_unique".to_string())
+                .unique(true)
+                .partial_filter_expression(doc! { "deleted": false })
+                .build(),
+        )
+        .build();
+
+    stores
+        .connection_variable_mapping
+        .collection
+        .create_index(index)
+        .await?;
+
+    Ok(0)
+}
+
+/// One index per key a connection is looked up by, the current one and the
+/// previous one of a key rotation
+async fn case_insensitive_connection_keys(stores: &AppStores) -> Result<u64, PicaError> {
+    let indexes = [
+        ("key", "owner_key_case_insensitive"),
+        (
+            "keyRotation.previousKey",
+            "owner_previous_key_case_insensitive",
+        ),
+    ]
+    .map(|(key, name)| {
+        IndexModel::builder()
+            .keys(doc! { "ownership.buildableId": 1, key: 1 })
+            .options(
+                IndexOptions::builder()
+                    .name(name.to_string())
+                    .collation(connection_key_collation())
+                    .partial_filter_expression(doc! { "deleted": false })
+                    .build(),
+            )
+            .build()
+    });
+
+    stores.connection.collection.create_indexes(indexes).await?;
+
+    Ok(0)
+}
@@ -1,6 +1,15 @@
 pub mod config;
+pub mod dead_letter;
+pub mod header_policy;
+pub mod json_limits;
 pub mod metrics;
+pub mod migration;
+pub mod nonce;
+pub mod quota;
+pub mod telemetry;
 pub mod track;
+pub mod upstream_limit;
+pub mod warmup;
 
 pub use config::*;
 pub use metrics::*;
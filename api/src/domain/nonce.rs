@@ -0,0 +1,179 @@
+use osentities::{ApplicationError, Id, PicaError, PicaErrorCode};
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+/// Longest nonce accepted, so callers cannot fill the cache with large keys
+const MAX_NONCE_LENGTH: usize = 128;
+
+/// Rejects replays of signed passthrough requests. Their `PICA-Timestamp`
+/// must be within the skew window of the server clock and their `PICA-Nonce`
+/// unused by the connection. Nonces are remembered for twice the skew window,
+/// as long as a timestamp can be accepted, and at most `capacity` of them:
+/// while that many are remembered, signed requests are refused rather than
+/// admitted without recording their nonce. They are kept in memory, so every
+/// instance of the api rejects the replays of the requests it served itself.
+#[derive(Clone)]
+pub struct NonceCache {
+    seen: Arc<Mutex<SeenNonces>>,
+    capacity: usize,
+    max_skew_millis: u64,
+}
+
+/// Nonces in the order they were admitted, which is also the order they
+/// expire in as they are all remembered for as long
+#[derive(Default)]
+struct SeenNonces {
+    expiries: VecDeque<(i64, (Id, String))>,
+    nonces: HashSet<(Id, String)>,
+}
+
+impl SeenNonces {
+    fn evict_expired(&mut self, now: i64) {
+        while let Some((expires_at, _)) = self.expiries.front() {
+            if *expires_at > now {
+                break;
+            }
+            if let Some((_, nonce)) = self.expiries.pop_front() {
+                self.nonces.remove(&nonce);
+            }
+        }
+    }
+}
+
+impl NonceCache {
+    pub fn new(capacity: u64, max_skew_secs: u64) -> Self {
+        Self {
+            seen: Default::default(),
+            capacity: usize::try_from(capacity).unwrap_or(usize::MAX).max(1),
+            max_skew_millis: max_skew_secs.saturating_mul(1000),
+        }
+    }
+
+    /// Admits a request of the connection whose signature, covering its
+    /// timestamp and nonce, was verified. `timestamp` and `now` are epoch
+    /// millis. The nonce is used up by the first request admitted with it.
+    pub fn admit(
+        &self,
+        connection_id: Id,
+        timestamp: &str,
+        nonce: &str,
+        now: i64,
+    ) -> Result<(), PicaError> {
+        let in_window = timestamp
+            .trim()
+            .parse::<i64>()
+            .is_ok_and(|timestamp| timestamp.abs_diff(now) <= self.max_skew_millis);
+
+        if !in_window {
+            return Err(ApplicationError::unauthorized(
+                "Request timestamp is outside the allowed window",
+                PicaErrorCode::SignatureExpired.subtype(),
+            ));
+        }
+
+        if nonce.is_empty() || nonce.len() > MAX_NONCE_LENGTH {
+            return Err(ApplicationError::unauthorized(
+                &format!("Request nonce must be 1 to {MAX_NONCE_LENGTH} characters long"),
+                PicaErrorCode::SignatureInvalid.subtype(),
+            ));
+        }
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.evict_expired(now);
+
+        let key = (connection_id, nonce.to_string());
+        if seen.nonces.contains(&key) {
+            return Err(ApplicationError::unauthorized(
+                "Request nonce was already used",
+                PicaErrorCode::NonceReused.subtype(),
+            ));
+        }
+
+        if seen.nonces.len() >= self.capacity {
+            return Err(ApplicationError::service_unavailable(
+                "Too many signed requests to check for replays, retry later",
+                None,
+            ));
+        }
+
+        let ttl = i64::try_from(self.max_skew_millis.saturating_mul(2)).unwrap_or(i64::MAX);
+        seen.expiries
+            .push_back((now.saturating_add(ttl.max(1)), key.clone()));
+        seen.nonces.insert(key);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use osentities::id::prefix::IdPrefix;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    #[test]
+    fn rejects_reused_nonces_per_connection() {
+        let cache = NonceCache::new(100, 300);
+        let (a, b) = (Id::now(IdPrefix::Connection), Id::now(IdPrefix::Connection));
+
+        assert!(cache.admit(a, &NOW.to_string(), "n-1", NOW).is_ok());
+        assert!(cache.admit(b, &NOW.to_string(), "n-1", NOW).is_ok());
+        assert!(cache.admit(a, &NOW.to_string(), "n-2", NOW).is_ok());
+
+        let error = cache
+            .admit(a, &NOW.to_string(), "n-1", NOW + 1_000)
+            .unwrap_err();
+        assert_eq!(error.status(), 401);
+        assert_eq!(error.error_code(), Some(PicaErrorCode::NonceReused));
+    }
+
+    #[test]
+    fn rejects_timestamps_outside_the_skew_window() {
+        let cache = NonceCache::new(100, 300);
+        let id = Id::now(IdPrefix::Connection);
+
+        for (timestamp, nonce) in [(NOW - 300_000, "early"), (NOW + 300_000, "late")] {
+            assert!(cache.admit(id, &timestamp.to_string(), nonce, NOW).is_ok());
+        }
+
+        for timestamp in [
+            (NOW - 300_001).to_string(),
+            (NOW + 300_001).to_string(),
+            "yesterday".to_string(),
+        ] {
+            let error = cache.admit(id, &timestamp, "n", NOW).unwrap_err();
+            assert_eq!(error.error_code(), Some(PicaErrorCode::SignatureExpired));
+        }
+
+        // A rejected timestamp does not use up the nonce
+        assert!(cache.admit(id, &NOW.to_string(), "n", NOW).is_ok());
+
+        for nonce in [String::new(), "n".repeat(MAX_NONCE_LENGTH + 1)] {
+            let error = cache.admit(id, &NOW.to_string(), &nonce, NOW).unwrap_err();
+            assert_eq!(error.error_code(), Some(PicaErrorCode::SignatureInvalid));
+        }
+    }
+
+    #[test]
+    fn refuses_requests_while_full_until_nonces_expire() {
+        let cache = NonceCache::new(2, 300);
+        let id = Id::now(IdPrefix::Connection);
+
+        assert!(cache.admit(id, &NOW.to_string(), "n-1", NOW).is_ok());
+        assert!(cache.admit(id, &NOW.to_string(), "n-2", NOW).is_ok());
+
+        let error = cache.admit(id, &NOW.to_string(), "n-3", NOW).unwrap_err();
+        assert_eq!(error.status(), 503);
+
+        // Replays are still told apart from a full cache
+        let error = cache.admit(id, &NOW.to_string(), "n-1", NOW).unwrap_err();
+        assert_eq!(error.error_code(), Some(PicaErrorCode::NonceReused));
+
+        let later = NOW + 600_000;
+        assert!(cache.admit(id, &later.to_string(), "n-3", later).is_ok());
+        assert!(cache.admit(id, &later.to_string(), "n-1", later).is_ok());
+    }
+}
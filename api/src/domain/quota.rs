@@ -0,0 +1,179 @@
+use super::metrics::{Metric, MetricType};
+use osentities::{ApplicationError, PicaError, PicaErrorCode, Quota, QuotaScope};
+use std::{collections::HashMap, sync::Mutex};
+
+/// Counters untouched for this many windows are dropped when pruning
+const STALE_WINDOWS: i64 = 2;
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Approximates the number of requests in the last `window` with the counts
+/// of the current and previous fixed windows, weighting the previous count by
+/// how much of it still overlaps the rolling window. This keeps two integers
+/// per counter instead of a timestamp per request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SlidingWindow {
+    window_start: i64,
+    current: u64,
+    previous: u64,
+}
+
+impl SlidingWindow {
+    fn advance(&mut self, now: i64, window: i64) {
+        let start = now - now.rem_euclid(window);
+
+        if start == self.window_start {
+            return;
+        }
+
+        self.previous = if start - self.window_start == window {
+            self.current
+        } else {
+            0
+        };
+        self.current = 0;
+        self.window_start = start;
+    }
+
+    fn estimate(&self, now: i64, window: i64) -> u64 {
+        let overlap = (window - (now - self.window_start)) as f64 / window as f64;
+
+        self.current + (self.previous as f64 * overlap).ceil() as u64
+    }
+}
+
+/// Enforces the passthrough quotas configured on connections. Counts are kept
+/// in memory, so every instance of the api enforces its quotas on its own
+/// share of the traffic.
+#[derive(Debug, Default)]
+pub struct QuotaTracker {
+    windows: Mutex<HashMap<String, (i64, SlidingWindow)>>,
+}
+
+impl QuotaTracker {
+    /// Counts the passthrough request recorded by `metric` against the quota
+    /// of its connection and returns the requests left in the window. Requests
+    /// over the quota are rejected without being counted, and connections
+    /// without a quota are always allowed.
+    pub fn acquire(&self, metric: &Metric) -> Result<Option<u64>, PicaError> {
+        let MetricType::Passthrough(connection) = &metric.metric_type else {
+            return Ok(None);
+        };
+
+        let Some(quota) = connection.quota.filter(|quota| quota.window_secs > 0) else {
+            return Ok(None);
+        };
+
+        let key = match quota.scope {
+            QuotaScope::Connection => connection.id.to_string(),
+            QuotaScope::Platform => {
+                platform_key(&connection.ownership.id, &connection.platform, &quota)
+            }
+        };
+
+        match self.acquire_key(key, &quota, metric.date.timestamp_millis()) {
+            Some(remaining) => Ok(Some(remaining)),
+            None => Err(ApplicationError::too_many_requests(
+                "Passthrough quota exceeded for this connection",
+                PicaErrorCode::QuotaExceeded.subtype(),
+            )),
+        }
+    }
+
+    fn acquire_key(&self, key: String, quota: &Quota, now: i64) -> Option<u64> {
+        let window = quota.window_secs as i64 * 1000;
+
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+
+        if windows.len() >= PRUNE_THRESHOLD {
+            windows.retain(|_, (window, counter)| {
+                now - counter.window_start < *window * STALE_WINDOWS
+            });
+        }
+
+        let (stored_window, counter) = windows.entry(key).or_default();
+
+        // A changed window invalidates the counts kept so far
+        if *stored_window != window {
+            *stored_window = window;
+            *counter = SlidingWindow::default();
+        }
+
+        counter.advance(now, window);
+
+        let used = counter.estimate(now, window);
+        if used >= quota.limit {
+            return None;
+        }
+
+        counter.current += 1;
+
+        Some(quota.limit - used - 1)
+    }
+}
+
+/// Key of the counter shared by the connections of an owner to a platform.
+/// Connections of the platform configured with different windows count
+/// separately, instead of resetting each other's counts on every request.
+fn platform_key(owner: &str, platform: &str, quota: &Quota) -> String {
+    format!("{owner}::{platform}::{}", quota.window_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quota(limit: u64, window_secs: u64) -> Quota {
+        Quota {
+            limit,
+            window_secs,
+            scope: QuotaScope::Connection,
+        }
+    }
+
+    #[test]
+    fn rejects_requests_over_the_limit() {
+        let tracker = QuotaTracker::default();
+        let quota = quota(2, 60);
+
+        assert_eq!(tracker.acquire_key("a".into(), &quota, 1_000), Some(1));
+        assert_eq!(tracker.acquire_key("a".into(), &quota, 2_000), Some(0));
+        assert_eq!(tracker.acquire_key("a".into(), &quota, 3_000), None);
+        assert_eq!(tracker.acquire_key("b".into(), &quota, 3_000), Some(1));
+    }
+
+    #[test]
+    fn previous_window_decays_as_the_window_slides() {
+        let tracker = QuotaTracker::default();
+        let quota = quota(4, 60);
+
+        for _ in 0..4 {
+            assert!(tracker.acquire_key("a".into(), &quota, 30_000).is_some());
+        }
+
+        // Halfway into the next window, half of the previous count still applies
+        assert_eq!(tracker.acquire_key("a".into(), &quota, 90_000), Some(1));
+        assert_eq!(tracker.acquire_key("a".into(), &quota, 90_000), Some(0));
+        assert_eq!(tracker.acquire_key("a".into(), &quota, 90_000), None);
+
+        // Two windows later nothing from the first window is left
+        assert_eq!(tracker.acquire_key("a".into(), &quota, 180_000), Some(3));
+    }
+
+    #[test]
+    fn platform_quotas_with_different_windows_count_separately() {
+        let tracker = QuotaTracker::default();
+        let minute = quota(2, 60);
+        let hour = quota(2, 3600);
+
+        let minute_key = platform_key("owner", "stripe", &minute);
+        let hour_key = platform_key("owner", "stripe", &hour);
+        assert_ne!(minute_key, hour_key);
+
+        assert_eq!(
+            tracker.acquire_key(minute_key.clone(), &minute, 1_000),
+            Some(1)
+        );
+        assert_eq!(tracker.acquire_key(hour_key, &hour, 2_000), Some(1));
+        assert_eq!(tracker.acquire_key(minute_key, &minute, 3_000), Some(0));
+    }
+}
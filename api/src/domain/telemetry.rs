@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc::{
+    error::{SendTimeoutError, TrySendError},
+    Sender,
+};
+use tracing::{trace, warn};
+
+/// Drops are logged on the first one and then once every this many
+const DROP_LOG_INTERVAL: u64 = 1000;
+
+/// Keeps the items a [`TelemetrySender`] cannot deliver, for redelivery
+pub trait DeadLetter<T>: Debug + Send + Sync {
+    fn store(&self, item: T, reason: &str);
+}
+
+/// Sending half of an event or metric channel that never holds up the request
+/// it reports on. Items go into the channel right away when there is room.
+/// When the consumer falls behind, a short-lived task waits up to
+/// `send_timeout` for room and drops the item after that, and at most as many
+/// items as the channel holds wait this way. Items sent once the consumer is
+/// gone, e.g. during shutdown, are dropped at once. Every drop is counted,
+/// and the dropped item handed to the dead letter when there is one.
+#[derive(Debug)]
+pub struct TelemetrySender<T> {
+    name: &'static str,
+    tx: Sender<T>,
+    send_timeout: Duration,
+    counters: Arc<Counters>,
+    dead_letter: Option<Arc<dyn DeadLetter<T>>>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    deferred: AtomicU64,
+    dropped: AtomicU64,
+    dead_lettered: AtomicU64,
+    waiting: AtomicUsize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryChannelStats {
+    pub name: String,
+    pub capacity: usize,
+    pub queued: usize,
+    /// Items waiting for room in the full channel
+    pub waiting: usize,
+    pub sent: u64,
+    /// Items that found the channel full, whether they were sent later or not
+    pub deferred: u64,
+    pub dropped: u64,
+    /// Dropped items handed to the dead letter
+    pub dead_lettered: u64,
+    pub closed: bool,
+}
+
+/// Decrements the number of waiting items even when the waiting task is
+/// cancelled by the runtime shutting down
+struct WaitSlot(Arc<Counters>);
+
+impl Drop for WaitSlot {
+    fn drop(&mut self) {
+        self.0.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<T> Clone for TelemetrySender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            tx: self.tx.clone(),
+            send_timeout: self.send_timeout,
+            counters: self.counters.clone(),
+            dead_letter: self.dead_letter.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> TelemetrySender<T> {
+    pub fn new(name: &'static str, tx: Sender<T>, send_timeout: Duration) -> Self {
+        Self {
+            name,
+            tx,
+            send_timeout,
+            counters: Arc::default(),
+            dead_letter: None,
+        }
+    }
+
+    pub fn with_dead_letter(mut self, dead_letter: Arc<dyn DeadLetter<T>>) -> Self {
+        self.dead_letter = Some(dead_letter);
+        self
+    }
+
+    /// Whether the consumer is gone, anything sent is dropped
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    pub fn send(&self, item: T) {
+        let item = match self.tx.try_send(item) {
+            Ok(()) => {
+                self.counters.sent.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            Err(TrySendError::Closed(item)) => {
+                trace!("Dropping {} item, the channel is closed", self.name);
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                self.dead_letter(item, "channel closed");
+                return;
+            }
+            Err(TrySendError::Full(item)) => item,
+        };
+
+        self.counters.deferred.fetch_add(1, Ordering::Relaxed);
+
+        if self.counters.waiting.fetch_add(1, Ordering::SeqCst) >= self.tx.max_capacity() {
+            self.counters.waiting.fetch_sub(1, Ordering::SeqCst);
+            self.record_drop();
+            self.dead_letter(item, "channel full");
+            return;
+        }
+        let slot = WaitSlot(self.counters.clone());
+
+        let sender = self.clone();
+        tokio::spawn(async move {
+            let _slot = slot;
+
+            match sender.tx.send_timeout(item, sender.send_timeout).await {
+                Ok(()) => {
+                    sender.counters.sent.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(SendTimeoutError::Timeout(item)) => {
+                    sender.record_drop();
+                    sender.dead_letter(item, "timed out waiting for room in the channel");
+                }
+                Err(SendTimeoutError::Closed(item)) => {
+                    sender.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    sender.dead_letter(item, "channel closed");
+                }
+            }
+        });
+    }
+
+    pub fn stats(&self) -> TelemetryChannelStats {
+        let capacity = self.tx.max_capacity();
+
+        TelemetryChannelStats {
+            name: self.name.to_string(),
+            capacity,
+            queued: capacity - self.tx.capacity(),
+            waiting: self.counters.waiting.load(Ordering::SeqCst),
+            sent: self.counters.sent.load(Ordering::Relaxed),
+            deferred: self.counters.deferred.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            dead_lettered: self.counters.dead_lettered.load(Ordering::Relaxed),
+            closed: self.tx.is_closed(),
+        }
+    }
+
+    fn dead_letter(&self, item: T, reason: &str) {
+        if let Some(dead_letter) = &self.dead_letter {
+            self.counters.dead_lettered.fetch_add(1, Ordering::Relaxed);
+            dead_letter.store(item, reason);
+        }
+    }
+
+    fn record_drop(&self) {
+        let dropped = self.counters.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if dropped == 1 || dropped.is_multiple_of(DROP_LOG_INTERVAL) {
+            warn!(
+                "Dropped {dropped} {} items so far, their consumer is falling behind",
+                self.name
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::sync::mpsc::channel;
+
+    #[derive(Debug, Default)]
+    struct Kept(Mutex<Vec<(u32, String)>>);
+
+    impl DeadLetter<u32> for Kept {
+        fn store(&self, item: u32, reason: &str) {
+            self.0.lock().unwrap().push((item, reason.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn full_channel_defers_then_drops() {
+        let (tx, mut rx) = channel(1);
+        let sender = TelemetrySender::new("metric", tx, Duration::from_millis(20));
+
+        sender.send(1);
+        // Waits for room, which the receiver makes below
+        sender.send(2);
+        // Over the number of items allowed to wait
+        sender.send(3);
+
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(rx.recv().await, Some(2));
+
+        // Nobody makes room this time
+        sender.send(4);
+        sender.send(5);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let stats = sender.stats();
+        assert_eq!(stats.sent, 3);
+        assert_eq!(stats.deferred, 3);
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(stats.waiting, 0);
+        assert_eq!(stats.queued, 1);
+    }
+
+    #[tokio::test]
+    async fn closed_channel_drops_without_waiting() {
+        let (tx, rx) = channel(1);
+        let sender = TelemetrySender::new("event", tx, Duration::from_secs(60));
+        drop(rx);
+
+        sender.send(1);
+
+        let stats = sender.stats();
+        assert!(stats.closed);
+        assert!(sender.is_closed());
+        assert_eq!(stats.dropped, 1);
+        assert_eq!(stats.waiting, 0);
+    }
+
+    #[tokio::test]
+    async fn dropped_items_go_to_the_dead_letter() {
+        let kept = Arc::new(Kept::default());
+        let (tx, rx) = channel(1);
+        let sender = TelemetrySender::new("event", tx, Duration::from_millis(20))
+            .with_dead_letter(kept.clone());
+
+        sender.send(1);
+        sender.send(2);
+        sender.send(3);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        drop(rx);
+        sender.send(4);
+
+        assert_eq!(
+            *kept.0.lock().unwrap(),
+            vec![
+                (3, "channel full".to_string()),
+                (2, "timed out waiting for room in the channel".to_string()),
+                (4, "channel closed".to_string()),
+            ]
+        );
+        assert_eq!(sender.stats().dead_lettered, 3);
+        assert_eq!(sender.stats().dropped, 3);
+    }
+}
@@ -0,0 +1,232 @@
+use http::{header::RETRY_AFTER, HeaderMap, StatusCode};
+use osentities::Id;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Mutex};
+
+const LIMIT_HEADERS: [&str; 3] = ["ratelimit-limit", "x-ratelimit-limit", "x-rate-limit-limit"];
+const REMAINING_HEADERS: [&str; 3] = [
+    "ratelimit-remaining",
+    "x-ratelimit-remaining",
+    "x-rate-limit-remaining",
+];
+const RESET_HEADERS: [&str; 3] = ["ratelimit-reset", "x-ratelimit-reset", "x-rate-limit-reset"];
+
+/// Reset values above this are epoch seconds rather than seconds from now
+const EPOCH_SECS_THRESHOLD: f64 = 1_000_000_000.0;
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Rate limit of a connection as last reported by its platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamLimit {
+    pub connection_id: Id,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    pub remaining: u64,
+    /// Epoch millis at which the platform restores the limit, when it says so
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reset_at: Option<i64>,
+    pub observed_at: i64,
+}
+
+impl UpstreamLimit {
+    /// Parses the `RateLimit-*` headers of a platform response, along with
+    /// their `X-RateLimit-*` and `X-Rate-Limit-*` variants. A 429 without them
+    /// counts as no requests left until its `Retry-After`.
+    pub fn from_response(
+        connection_id: Id,
+        status: StatusCode,
+        headers: &HeaderMap,
+        now: i64,
+    ) -> Option<Self> {
+        let reset_at = header_number(headers, &RESET_HEADERS).map(|reset| {
+            if reset > EPOCH_SECS_THRESHOLD {
+                (reset * 1000.0) as i64
+            } else {
+                now + (reset * 1000.0) as i64
+            }
+        });
+
+        let (remaining, reset_at) = match header_number(headers, &REMAINING_HEADERS) {
+            Some(remaining) => (remaining as u64, reset_at),
+            None if status == StatusCode::TOO_MANY_REQUESTS => (
+                0,
+                reset_at.or_else(|| {
+                    header_number(headers, &[RETRY_AFTER.as_str()])
+                        .map(|secs| now + (secs * 1000.0) as i64)
+                }),
+            ),
+            None => return None,
+        };
+
+        Some(Self {
+            connection_id,
+            limit: header_number(headers, &LIMIT_HEADERS).map(|limit| limit as u64),
+            remaining,
+            reset_at,
+            observed_at: now,
+        })
+    }
+}
+
+/// Last known upstream rate limit of every connection, used to hold back
+/// passthrough requests the platform would answer with a 429 anyway. Limits
+/// are kept in memory, each instance of the api learning them from the
+/// responses it proxies.
+#[derive(Debug, Default)]
+pub struct UpstreamLimitTracker {
+    limits: Mutex<HashMap<Id, (String, UpstreamLimit)>>,
+}
+
+impl UpstreamLimitTracker {
+    pub fn observe(&self, ownership_id: &str, limit: UpstreamLimit) {
+        let mut limits = self.limits.lock().unwrap_or_else(|e| e.into_inner());
+
+        if limits.len() >= PRUNE_THRESHOLD {
+            limits.retain(|_, (_, known)| {
+                known
+                    .reset_at
+                    .is_some_and(|reset_at| reset_at > limit.observed_at)
+            });
+        }
+
+        limits.insert(limit.connection_id, (ownership_id.to_string(), limit));
+    }
+
+    pub fn get(&self, connection_id: &Id) -> Option<UpstreamLimit> {
+        let limits = self.limits.lock().unwrap_or_else(|e| e.into_inner());
+
+        limits.get(connection_id).map(|(_, limit)| *limit)
+    }
+
+    /// Known limits of the connections of an account
+    pub fn owned_by(&self, ownership_id: &str) -> Vec<UpstreamLimit> {
+        let limits = self.limits.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut owned: Vec<UpstreamLimit> = limits
+            .values()
+            .filter(|(owner, _)| owner == ownership_id)
+            .map(|(_, limit)| *limit)
+            .collect();
+        owned.sort_by_key(|limit| limit.connection_id);
+
+        owned
+    }
+
+    /// Millis until the platform restores the limit of the connection, when
+    /// it reported `min_remaining` requests left or fewer. Limits without a
+    /// known reset never hold requests back.
+    pub fn backoff(&self, connection_id: &Id, min_remaining: u64, now: i64) -> Option<u64> {
+        let limit = self.get(connection_id)?;
+        let reset_at = limit.reset_at?;
+
+        (limit.remaining <= min_remaining && reset_at > now).then(|| (reset_at - now) as u64)
+    }
+}
+
+/// First of `names` holding a number, ignoring the `;w=` style parameters of
+/// the IETF draft headers
+fn header_number(headers: &HeaderMap, names: &[&str]) -> Option<f64> {
+    names.iter().find_map(|name| {
+        headers
+            .get(*name)?
+            .to_str()
+            .ok()?
+            .split([',', ';'])
+            .next()?
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite() && *number >= 0.0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+    use osentities::prefix::IdPrefix;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn parses_relative_and_epoch_resets() {
+        let id = Id::now(IdPrefix::Connection);
+
+        let relative = UpstreamLimit::from_response(
+            id,
+            StatusCode::OK,
+            &headers(&[
+                ("ratelimit-limit", "100, 100;w=60"),
+                ("ratelimit-remaining", "3"),
+                ("ratelimit-reset", "30"),
+            ]),
+            NOW,
+        )
+        .unwrap();
+        assert_eq!(relative.limit, Some(100));
+        assert_eq!(relative.remaining, 3);
+        assert_eq!(relative.reset_at, Some(NOW + 30_000));
+
+        let epoch = UpstreamLimit::from_response(
+            id,
+            StatusCode::OK,
+            &headers(&[
+                ("x-ratelimit-remaining", "0"),
+                ("x-ratelimit-reset", "1700000060"),
+            ]),
+            NOW,
+        )
+        .unwrap();
+        assert_eq!(epoch.remaining, 0);
+        assert_eq!(epoch.reset_at, Some(NOW + 60_000));
+
+        assert!(UpstreamLimit::from_response(id, StatusCode::OK, &HeaderMap::new(), NOW).is_none());
+    }
+
+    #[test]
+    fn too_many_requests_falls_back_to_retry_after() {
+        let id = Id::now(IdPrefix::Connection);
+        let limit = UpstreamLimit::from_response(
+            id,
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers(&[("retry-after", "5")]),
+            NOW,
+        )
+        .unwrap();
+
+        assert_eq!(limit.remaining, 0);
+        assert_eq!(limit.reset_at, Some(NOW + 5_000));
+    }
+
+    #[test]
+    fn backs_off_until_reset_when_nearly_exhausted() {
+        let tracker = UpstreamLimitTracker::default();
+        let id = Id::now(IdPrefix::Connection);
+
+        tracker.observe(
+            "owner",
+            UpstreamLimit {
+                connection_id: id,
+                limit: Some(10),
+                remaining: 1,
+                reset_at: Some(NOW + 2_000),
+                observed_at: NOW,
+            },
+        );
+
+        assert_eq!(tracker.backoff(&id, 0, NOW), None);
+        assert_eq!(tracker.backoff(&id, 1, NOW + 500), Some(1_500));
+        assert_eq!(tracker.backoff(&id, 1, NOW + 2_000), None);
+
+        assert_eq!(tracker.owned_by("owner").len(), 1);
+        assert!(tracker.owned_by("other").is_empty());
+    }
+}
@@ -0,0 +1,183 @@
+use cache::local::{ConnectionDefinitionCache, ConnectionHeaderCache, LocalCacheExt};
+use envconfig::Envconfig;
+use http::HeaderValue;
+use mongodb::bson::{doc, Document};
+use osentities::{connection_definition::ConnectionDefinition, Connection, MongoStore, PicaError};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+use tokio::time::timeout;
+use tracing::{info, warn};
+
+/// Comma separated list of connection keys
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionKeys(Vec<String>);
+
+impl FromStr for ConnectionKeys {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            s.split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        ))
+    }
+}
+
+impl Display for ConnectionKeys {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join(","))
+    }
+}
+
+/// Prefetches connections and their definitions into the caches before the
+/// server accepts traffic, so the first passthrough requests after a start do
+/// not pay for the lookups
+#[derive(Envconfig, Debug, Clone, Default)]
+pub struct CacheWarmup {
+    #[envconfig(from = "CACHE_WARMUP_ENABLED", default = "false")]
+    pub enabled: bool,
+    /// Connections to prefetch. When empty, the most recently updated
+    /// connections are, as refreshing the tokens of the ones in use keeps
+    /// them recent
+    #[envconfig(from = "CACHE_WARMUP_CONNECTION_KEYS", default = "")]
+    pub connection_keys: ConnectionKeys,
+    #[envconfig(from = "CACHE_WARMUP_LIMIT", default = "100")]
+    pub limit: u64,
+    /// The server starts once this elapses, with whatever was cached so far
+    #[envconfig(from = "CACHE_WARMUP_TIMEOUT_SECS", default = "10")]
+    pub timeout_secs: u64,
+}
+
+impl CacheWarmup {
+    fn filter(&self) -> Document {
+        let mut filter = doc! { "deleted": false };
+        if !self.connection_keys.0.is_empty() {
+            filter.insert("key", doc! { "$in": &self.connection_keys.0 });
+        }
+        filter
+    }
+
+    /// Never fails, connections that could not be cached are looked up on
+    /// first use as usual
+    pub async fn run(
+        &self,
+        connections: &MongoStore<Connection>,
+        connection_definitions: &MongoStore<ConnectionDefinition>,
+        connections_cache: &ConnectionHeaderCache,
+        connection_definitions_cache: &ConnectionDefinitionCache,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let warmup = self.prefetch(
+            connections,
+            connection_definitions,
+            connections_cache,
+            connection_definitions_cache,
+        );
+
+        match timeout(Duration::from_secs(self.timeout_secs), warmup).await {
+            Ok(Ok(count)) => info!("Cache warmup prefetched {count} connections"),
+            Ok(Err(e)) => warn!("Cache warmup could not list connections: {e}"),
+            Err(_) => warn!(
+                "Cache warmup timed out after {} seconds, starting with a partially warm cache",
+                self.timeout_secs
+            ),
+        }
+    }
+
+    async fn prefetch(
+        &self,
+        connections: &MongoStore<Connection>,
+        connection_definitions: &MongoStore<ConnectionDefinition>,
+        connections_cache: &ConnectionHeaderCache,
+        connection_definitions_cache: &ConnectionDefinitionCache,
+    ) -> Result<usize, PicaError> {
+        let connections = connections
+            .get_many(
+                Some(self.filter()),
+                None,
+                Some(doc! { "updatedAt": -1 }),
+                Some(self.limit),
+                None,
+            )
+            .await?;
+
+        let mut count = 0;
+        for connection in connections {
+            let Ok(key) = HeaderValue::from_str(&connection.key) else {
+                warn!("Cache warmup skipped connection {}", connection.id);
+                continue;
+            };
+
+            if let Err(e) = connections_cache
+                .insert(&(connection.ownership.id.clone(), key), &connection)
+                .await
+            {
+                warn!(
+                    "Cache warmup could not cache connection {}: {e}",
+                    connection.id
+                );
+                continue;
+            }
+
+            if let Err(e) = connection_definitions_cache
+                .get_or_insert_with_filter(
+                    &connection.connection_definition_id,
+                    connection_definitions.clone(),
+                    doc! {
+                        "_id": connection.connection_definition_id.to_string(),
+                        "deleted": false
+                    },
+                    None,
+                )
+                .await
+            {
+                warn!(
+                    "Cache warmup could not cache connection definition {}: {e}",
+                    connection.connection_definition_id
+                );
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+impl Display for CacheWarmup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "CACHE_WARMUP_ENABLED: {}", self.enabled)?;
+        writeln!(f, "CACHE_WARMUP_CONNECTION_KEYS: {}", self.connection_keys)?;
+        writeln!(f, "CACHE_WARMUP_LIMIT: {}", self.limit)?;
+        writeln!(f, "CACHE_WARMUP_TIMEOUT_SECS: {}", self.timeout_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_keys_narrow_the_warmup_filter() {
+        let warmup = CacheWarmup {
+            connection_keys: " live::a, ,live::b ".parse().unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(warmup.connection_keys.to_string(), "live::a,live::b");
+        assert_eq!(
+            warmup.filter(),
+            doc! { "deleted": false, "key": { "$in": ["live::a", "live::b"] } }
+        );
+
+        assert_eq!(CacheWarmup::default().filter(), doc! { "deleted": false });
+    }
+}
@@ -0,0 +1,108 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+};
+use osentities::{ApplicationError, PicaError};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use serde_path_to_error::Segment;
+
+/// JSON body extractor whose errors name the field at fault. A body that does
+/// not deserialize into `T`, e.g. because of a malformed method or header
+/// name, is rejected with a 400 carrying the path of the field and the value
+/// sent for it in its meta.
+#[derive(Debug, Clone)]
+pub struct CheckedJson<T>(pub T);
+
+impl<T: DeserializeOwned> CheckedJson<T> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PicaError> {
+        let body: Value = serde_json::from_slice(bytes)
+            .map_err(|e| ApplicationError::bad_request(&format!("Invalid JSON body: {e}"), None))?;
+
+        serde_path_to_error::deserialize(&body)
+            .map(CheckedJson)
+            .map_err(|e| {
+                let field = e.path().to_string();
+                let value = e
+                    .path()
+                    .iter()
+                    .try_fold(&body, |value, segment| match segment {
+                        Segment::Seq { index } => value.get(index),
+                        Segment::Map { key } => value.get(key),
+                        Segment::Enum { .. } => Some(value),
+                        Segment::Unknown => None,
+                    })
+                    .filter(|_| field != ".");
+
+                ApplicationError::bad_request(&format!("Invalid {field}: {}", e.inner()), None)
+                    .set_meta(&json!({
+                        "field": field,
+                        "value": value,
+                    }))
+            })
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for CheckedJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = PicaError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| ApplicationError::bad_request(&e.body_text(), None))?;
+
+        Self::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::{HeaderMap, Method};
+    use osentities::ErrorMeta;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Payload {
+        #[serde(with = "http_serde_ext_ios::method")]
+        action: Method,
+        #[serde(with = "http_serde_ext_ios::header_map::option", default)]
+        headers: Option<HeaderMap>,
+    }
+
+    fn meta(error: PicaError) -> Value {
+        assert_eq!(error.status(), 400);
+        error.meta().map(|meta| *meta).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_errors_name_the_offending_field_and_value() {
+        let body = json!([
+            { "action": "GET", "headers": { "x-ok": "1" } },
+            { "action": "GE T" },
+        ]);
+        let error =
+            CheckedJson::<Vec<Payload>>::from_bytes(body.to_string().as_bytes()).unwrap_err();
+        assert_eq!(
+            meta(error),
+            json!({ "field": "[1].action", "value": "GE T" })
+        );
+
+        let body = json!({ "action": "GET", "headers": { "x-ok": "1", "bad header": "1" } });
+        let error = CheckedJson::<Payload>::from_bytes(body.to_string().as_bytes()).unwrap_err();
+        let meta = meta(error);
+        assert_eq!(meta, json!({ "field": "headers.bad header", "value": "1" }));
+
+        let error = CheckedJson::<Payload>::from_bytes(b"{}").unwrap_err();
+        assert_eq!(error.status(), 400);
+
+        assert!(CheckedJson::<Payload>::from_bytes(b"{\"action\":\"PATCH\"}").is_ok());
+    }
+}
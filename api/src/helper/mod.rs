@@ -1,6 +1,8 @@
+pub mod checked_json;
 pub mod k8s_driver;
 pub mod shape_mongo_filter;
 
+pub use checked_json::*;
 pub use k8s_driver::*;
 pub use shape_mongo_filter::*;
 
@@ -1,3 +1,4 @@
+use crate::domain::config::ConnectionsConfig;
 use axum::extract::Query;
 use http::HeaderMap;
 use mongodb::bson::{doc, Document};
@@ -7,11 +8,42 @@ use osentities::{
 };
 use std::{collections::BTreeMap, sync::Arc};
 
+/// Page size of queries whose client gave no (valid) `limit`, until
+/// [`MongoQuery::with_default_limit`] replaces it
+const DEFAULT_LIMIT: u64 = 20;
+
 #[derive(Debug, Clone)]
 pub struct MongoQuery {
     pub filter: Document,
     pub skip: u64,
     pub limit: u64,
+    /// Whether `limit` was given by the client rather than defaulted
+    pub limit_requested: bool,
+}
+
+impl MongoQuery {
+    /// Caps the page size asked for by the client at `max_page_size`, the
+    /// capped `limit` being the one reported back in the response. A `limit`
+    /// of 0, which mongodb reads as no limit, is raised to 1.
+    pub fn with_max_limit(mut self, max_page_size: u64) -> Self {
+        self.limit = self.limit.clamp(1, max_page_size.max(1));
+        self
+    }
+
+    /// Uses `default_page_size` as the `limit` when the client gave none
+    pub fn with_default_limit(mut self, default_page_size: u64) -> Self {
+        if !self.limit_requested {
+            self.limit = default_page_size.max(1);
+        }
+        self
+    }
+
+    /// Applies the page sizes configured for the deployment, the default one
+    /// when the client gave no `limit`, capped at the largest one
+    pub fn with_page_size(self, config: &ConnectionsConfig) -> Self {
+        self.with_default_limit(config.default_page_size)
+            .with_max_limit(config.max_page_size)
+    }
 }
 
 pub fn shape_mongo_filter(
@@ -21,12 +53,13 @@ pub fn shape_mongo_filter(
 ) -> MongoQuery {
     let mut filter = doc! {};
     let mut skip = 0;
-    let mut limit = 20;
+    let mut limit = None;
 
     if let Some(q) = query {
         for (key, value) in q.0.iter() {
             if key == LIMIT_FILTER {
-                limit = value.parse().unwrap_or(20);
+                // 0 would read every document, it gets the default instead
+                limit = value.parse().ok().filter(|limit| *limit > 0);
             } else if key == CONTAINS_FILTER {
                 let values = string_to_vec(value);
                 let splitted = values.split_first();
@@ -85,8 +118,9 @@ pub fn shape_mongo_filter(
 
     MongoQuery {
         filter,
-        limit,
+        limit: limit.unwrap_or(DEFAULT_LIMIT),
         skip,
+        limit_requested: limit.is_some(),
     }
 }
 
@@ -129,11 +163,13 @@ mod test {
             filter: mut doc,
             skip,
             limit,
+            limit_requested,
         } = shape_mongo_filter(Some(Query(params.clone())), None, None);
         assert_eq!(doc.get_str(OWNERSHIP_FILTER).unwrap(), "foo");
         assert_eq!(doc.get_str(ENVIRONMENT_FILTER).unwrap(), "bar");
         assert!(!doc.get_bool(DELETED_FILTER).unwrap());
         assert_eq!(limit, 10);
+        assert!(limit_requested);
         assert_eq!(skip, 10);
 
         doc.insert(DELETED_FILTER, true);
@@ -161,6 +197,47 @@ mod test {
         assert_eq!(doc.get_str(ENVIRONMENT_FILTER).unwrap(), "test");
     }
 
+    #[test]
+    fn oversized_limits_are_clamped() {
+        let params = BTreeMap::from([(LIMIT_FILTER.to_string(), "100000".to_string())]);
+
+        let query = shape_mongo_filter(Some(Query(params.clone())), None, None);
+        assert_eq!(query.with_max_limit(500).limit, 500);
+
+        let query = shape_mongo_filter(None, None, None);
+        assert_eq!(query.with_max_limit(500).limit, 20);
+    }
+
+    #[test]
+    fn zero_limits_never_read_every_document() {
+        let params = BTreeMap::from([(LIMIT_FILTER.to_string(), "0".to_string())]);
+
+        let query = shape_mongo_filter(Some(Query(params)), None, None);
+        assert!(!query.limit_requested);
+        assert_eq!(query.with_default_limit(50).with_max_limit(500).limit, 50);
+
+        let mut query = shape_mongo_filter(None, None, None);
+        query.limit = 0;
+        assert_eq!(query.with_max_limit(500).limit, 1);
+    }
+
+    #[test]
+    fn default_limit_only_applies_without_a_requested_limit() {
+        let params = BTreeMap::from([(LIMIT_FILTER.to_string(), "10".to_string())]);
+
+        let query = shape_mongo_filter(Some(Query(params)), None, None);
+        assert_eq!(query.with_default_limit(50).limit, 10);
+
+        let query = shape_mongo_filter(None, None, None);
+        assert!(!query.limit_requested);
+        assert_eq!(query.clone().with_default_limit(50).limit, 50);
+        assert_eq!(query.with_default_limit(50).with_max_limit(30).limit, 30);
+
+        let params = BTreeMap::from([(LIMIT_FILTER.to_string(), "ten".to_string())]);
+        let query = shape_mongo_filter(Some(Query(params)), None, None);
+        assert_eq!(query.with_default_limit(50).limit, 50);
+    }
+
     #[test]
     fn requesting_dual_environments() {
         let params = BTreeMap::from([
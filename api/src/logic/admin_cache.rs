@@ -0,0 +1,113 @@
+use super::connection::evict_cached_connection;
+use crate::{router::ServerResponse, server::AppState};
+use axum::{
+    extract::{Path, State},
+    routing::post,
+    Extension, Json, Router,
+};
+use cache::local::LocalCacheExt;
+use mongodb::bson::doc;
+use osentities::{prefix::IdPrefix, ApplicationError, Claims, Id, PicaError};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+pub const CONNECTIONS_CACHE: &str = "connections";
+pub const CONNECTION_DEFINITIONS_CACHE: &str = "connectionDefinitions";
+
+/// Escape hatch for operators who fixed data in Mongo by hand. Caches are
+/// local to each instance, so only the instance serving the call is purged.
+pub fn get_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/purge", post(purge_caches))
+        .route("/purge/:key", post(purge_cache_key))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeResponse {
+    pub caches: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+/// Purging is reserved to service (core) tokens, user tokens are rejected
+fn authorize(claims: Option<Extension<Arc<Claims>>>) -> Result<(), PicaError> {
+    match claims {
+        Some(Extension(claims)) if claims.is_buildable_core => Ok(()),
+        _ => Err(ApplicationError::forbidden(
+            "Only service tokens can purge caches",
+            None,
+        )),
+    }
+}
+
+async fn purge_caches(
+    claims: Option<Extension<Arc<Claims>>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<PurgeResponse>>, PicaError> {
+    authorize(claims)?;
+
+    state.connections_cache.clear().await?;
+    state.connection_definitions_cache.clear().await?;
+
+    info!("Purged the connections and connection definitions caches");
+
+    Ok(Json(ServerResponse::new(
+        "purge",
+        PurgeResponse {
+            caches: vec![
+                CONNECTIONS_CACHE.to_string(),
+                CONNECTION_DEFINITIONS_CACHE.to_string(),
+            ],
+            key: None,
+        },
+    )))
+}
+
+/// Evicts a single entry, `key` being either a connection key or the id of a
+/// connection definition
+async fn purge_cache_key(
+    claims: Option<Extension<Arc<Claims>>>,
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<PurgeResponse>>, PicaError> {
+    authorize(claims)?;
+
+    let mut caches = vec![];
+
+    if let Ok(id) = key.parse::<Id>() {
+        if id.prefix() == IdPrefix::ConnectionDefinition {
+            state.connection_definitions_cache.remove(&id).await?;
+            caches.push(CONNECTION_DEFINITIONS_CACHE.to_string());
+        }
+    }
+
+    // Deleted connections are included, they may still be cached
+    if let Some(connection) = state
+        .app_stores
+        .connection
+        .get_one(doc! { "key": &key })
+        .await?
+    {
+        evict_cached_connection(&state, &connection).await;
+        caches.push(CONNECTIONS_CACHE.to_string());
+    }
+
+    if caches.is_empty() {
+        return Err(ApplicationError::not_found(
+            &format!("{key} is neither a connection key nor a connection definition id"),
+            None,
+        ));
+    }
+
+    info!("Purged {key} from the {} cache(s)", caches.join(", "));
+
+    Ok(Json(ServerResponse::new(
+        "purge",
+        PurgeResponse {
+            caches,
+            key: Some(key),
+        },
+    )))
+}
@@ -0,0 +1,45 @@
+use crate::{
+    middleware::jwt_auth::{JwtState, TokenIntrospection},
+    router::ServerResponse,
+    server::AppState,
+};
+use axum::{extract::State, routing::post, Extension, Json, Router};
+use osentities::{ApplicationError, Claims, PicaError, BEARER_PREFIX};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub fn get_router() -> Router<Arc<AppState>> {
+    Router::new().route("/introspect", post(introspect_token))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntrospectRequest {
+    /// The token, with or without its `Bearer ` prefix
+    pub token: String,
+}
+
+/// Explains why a token is accepted or rejected by the JWT secured routes.
+/// Reserved to service (core) tokens, as the report exposes the claims.
+async fn introspect_token(
+    claims: Option<Extension<Arc<Claims>>>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<IntrospectRequest>,
+) -> Result<Json<ServerResponse<TokenIntrospection>>, PicaError> {
+    match claims {
+        Some(Extension(claims)) if claims.is_buildable_core => {}
+        _ => {
+            return Err(ApplicationError::forbidden(
+                "Only service tokens can introspect tokens",
+                None,
+            ))
+        }
+    }
+
+    let token = payload.token.trim();
+    let token = token.strip_prefix(BEARER_PREFIX).unwrap_or(token);
+
+    let report = JwtState::from_state(&state).introspect(token);
+
+    Ok(Json(ServerResponse::new("introspect", report)))
+}
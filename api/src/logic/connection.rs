@@ -1,5 +1,11 @@
-use super::{delete, read, PublicExt, ReadResponse, RequestExt};
+use super::{
+    connection_model_definition::{actions_sort, restrict_to_actionable, ActionItem, SORT_QUERY},
+    connection_provision,
+    connection_variable_mapping::get_cached_mappings_of_definitions,
+    connection_webhook, delete, PublicExt, ReadResponse, RequestExt,
+};
 use crate::{
+    domain::upstream_limit::UpstreamLimit,
     helper::{shape_mongo_filter, DeploymentSpecParams, ServiceName, ServiceSpecParams},
     logic::event_access::{
         generate_event_access, get_client_throughput, CreateEventAccessPayloadWithOwnership,
@@ -16,15 +22,19 @@ use axum::{
 use cache::local::LocalCacheExt;
 use chrono::Utc;
 use envconfig::Envconfig;
-use http::HeaderMap;
+use futures::{stream, StreamExt};
+use http::{HeaderMap, HeaderValue, StatusCode};
 use k8s_openapi::{
     api::core::v1::{ContainerPort, EnvVar, EnvVarSource, SecretKeySelector, ServicePort},
     apimachinery::pkg::util::intstr::IntOrString,
 };
 use mongodb::bson::doc;
 use osentities::{
-    algebra::MongoStore,
+    algebra::{connection_secret, MongoStore},
     connection_definition::{ConnectionDefinition, ConnectionDefinitionType},
+    connection_model_definition::{ConnectionModelDefinition, CrudAction, PlatformInfo},
+    connection_variable_mapping::ConnectionVariableMapping,
+    connection_webhook::{ConnectionLifecycleEvent, ConnectionLifecycleEventType},
     database::{DatabasePodConfig, PostgresConfig},
     database_secret::DatabaseConnectionSecret,
     domain::configuration::environment::Environment,
@@ -32,28 +42,47 @@ use osentities::{
     event_access::EventAccess,
     id::{prefix::IdPrefix, Id},
     record_metadata::RecordMetadata,
+    secret::secret_leaf_paths,
     settings::Settings,
-    ApplicationError, Connection, ConnectionIdentityType, ConnectionType, InternalError, PicaError,
-    Throughput, APP_LABEL, DATABASE_TYPE_LABEL, DEFAULT_NAMESPACE, JWT_SECRET_REF_KEY,
-    JWT_SECRET_REF_NAME,
+    variable_injection::{
+        describe_bindings, fill_path_placeholders, missing_variables, Annotation, RequestParts,
+    },
+    ApplicationError, Connection, ConnectionIdentityType, ConnectionType, InternalError,
+    KeyRotation, PicaError, Quota, Throughput, APP_LABEL, DATABASE_TYPE_LABEL, DEFAULT_NAMESPACE,
+    JWT_SECRET_REF_KEY, JWT_SECRET_REF_NAME,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{
     collections::{BTreeMap, HashMap},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tracing::error;
+use tracing::{error, warn};
 use uuid::Uuid;
 use validator::Validate;
 
+const MIN_REQUEST_SIGNING_SECRET_LENGTH: usize = 16;
+
 pub fn get_router() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", post(create_connection))
         .route("/", get(get_connections))
+        .route(
+            "/provision",
+            post(connection_provision::provision_connection),
+        )
         .route("/:id", patch(update_connection))
         .route("/:id", axum_delete(delete_connection))
+        .route("/:id/rotate-key", post(rotate_connection_key))
+        .route(
+            "/:id/rotate-key/finalize",
+            post(finalize_connection_key_rotation),
+        )
+        .route("/:id/definitions", get(get_connection_definitions))
+        .route("/:id/effective-config", get(get_effective_config))
+        .route("/:id/validate-secret", get(validate_connection_secret))
+        .route("/:id/health", get(probe_connection_health))
 }
 
 
@@ -151,7 +180,8 @@ async fn get_connections(
             e
         }),
         Some(headers),
-    );
+    )
+    .with_page_size(&state.config);
 
     let connections = state
         .app_stores
@@ -207,12 +237,12 @@ async fn get_connections(
             e
         })?;
 
-    let response = ReadResponse {
-        rows: sanitized_connections,
-        skip: mongo_query.skip,
-        limit: mongo_query.limit,
+    let response = ReadResponse::new(
+        sanitized_connections,
         total,
-    };
+        mongo_query.skip,
+        mongo_query.limit,
+    );
 
     Ok(Json(ServerResponse::new("read", response)))
 }
@@ -285,7 +315,7 @@ async fn test_connection(
             .execute_model_definition(
                 &test_connection_model_config,
                 HeaderMap::new(),
-                &HashMap::new(),
+                &[],
                 &Arc::new(auth_form_data_value.clone()),
                 context,
             )
@@ -458,6 +488,7 @@ pub async fn create_connection(
         platform: connection_config.platform.into(),
         environment: event_access.environment,
         secrets_service_id: secret_result.id(),
+        additional_secrets_service_ids: vec![],
         event_access_id: event_access.id,
         access_key: event_access.access_key,
         settings: connection_config.settings,
@@ -467,6 +498,9 @@ pub async fn create_connection(
         },
         ownership: event_access.ownership,
         oauth: None,
+        key_rotation: None,
+        request_signing_secret: None,
+        quota: None,
         record_metadata: RecordMetadata::default(),
     };
 
@@ -486,7 +520,19 @@ pub async fn create_connection(
         notify_inhotel_tools_refresh(&state, uid.as_str(), "insert");
     }
 
-    Ok(Json(conn.into()))
+    let conn: SanitizedConnection = conn.into();
+
+    connection_webhook::notify(
+        &state,
+        ConnectionLifecycleEvent::new(
+            ConnectionLifecycleEventType::ConnectionCreated,
+            Some(conn.ownership.clone()),
+            Some(conn.environment),
+            serde_json::to_value(&conn).unwrap_or_default(),
+        ),
+    );
+
+    Ok(Json(conn))
 }
 
 async fn generate_k8s_specs_and_secret(
@@ -628,6 +674,15 @@ pub struct UpdateConnectionPayload {
     pub active: Option<bool>,
     pub identity: Option<String>,
     pub identity_type: Option<ConnectionIdentityType>,
+    /// Enables signature verification on passthrough requests, an empty
+    /// string turns it off again
+    pub request_signing_secret: Option<String>,
+    /// Caps the connection's passthrough requests, a limit of zero removes the
+    /// quota again
+    pub quota: Option<Quota>,
+    /// Secrets merged into the connection's own one, which wins where they
+    /// overlap, then each of these in order. An empty list removes them.
+    pub additional_secrets_service_ids: Option<Vec<String>>,
 }
 
 pub async fn update_connection(
@@ -675,6 +730,54 @@ pub async fn update_connection(
         connection.identity_type = Some(identity_type);
     }
 
+    if let Some(secret) = req.request_signing_secret {
+        if !secret.is_empty() && secret.len() < MIN_REQUEST_SIGNING_SECRET_LENGTH {
+            return Err(ApplicationError::bad_request(
+                &format!(
+                    "Request signing secret must be at least {MIN_REQUEST_SIGNING_SECRET_LENGTH} characters long"
+                ),
+                None,
+            ));
+        }
+
+        connection.request_signing_secret = (!secret.is_empty()).then_some(secret);
+    }
+
+    if let Some(quota) = req.quota {
+        if quota.limit > 0 && quota.window_secs == 0 {
+            return Err(ApplicationError::bad_request(
+                "Quota window must be at least one second long",
+                None,
+            ));
+        }
+
+        connection.quota = (quota.limit > 0).then_some(quota);
+    }
+
+    if let Some(secret_ids) = req.additional_secrets_service_ids {
+        // Only secrets of the owner that can be merged are referenced
+        for secret_id in &secret_ids {
+            state
+                .secrets_client
+                .get(secret_id, &connection.ownership.id)
+                .await
+                .and_then(|secret| secret.as_object(&connection.key))
+                .map_err(|e| {
+                    error!(
+                        "Error reading additional secret for connection update: {:?}",
+                        e
+                    );
+
+                    ApplicationError::bad_request(
+                        &format!("Secret {secret_id} cannot be added to the connection"),
+                        None,
+                    )
+                })?;
+        }
+
+        connection.additional_secrets_service_ids = secret_ids;
+    }
+
     if let Some(auth_form_data) = req.auth_form_data {
         let auth_form_data_value = serde_json::to_value(auth_form_data).map_err(|e| {
             error!(
@@ -768,6 +871,8 @@ pub async fn update_connection(
         .await
     {
         Ok(_) => {
+            evict_cached_connection(&state, &connection).await;
+
             // Notify inHotel-backend — `active` / `deleted` toggles via
             // update affect the tools count. See notify_inhotel_tools_refresh.
             if let Some(uid) = connection.ownership.user_id.as_ref() {
@@ -800,6 +905,8 @@ pub async fn delete_connection(
     )
     .await?;
 
+    evict_cached_connection(&state, &connection.args).await;
+
     match connection.args.r#type {
         ConnectionType::DatabaseSql { .. } if connection.args.record_metadata.active => {
             let service_name = ServiceName::from_id(connection.args.id)?;
@@ -814,6 +921,20 @@ pub async fn delete_connection(
         notify_inhotel_tools_refresh(&state, uid.as_str(), "delete");
     }
 
+    connection_webhook::notify(
+        &state,
+        ConnectionLifecycleEvent::new(
+            ConnectionLifecycleEventType::ConnectionDeleted,
+            Some(connection.args.ownership.clone()),
+            Some(connection.args.environment),
+            json!({
+                "id": connection.args.id,
+                "key": connection.args.key,
+                "platform": connection.args.platform,
+            }),
+        ),
+    );
+
     Ok(Json(ServerResponse::new(
         "connection",
         json!({
@@ -822,6 +943,655 @@ pub async fn delete_connection(
     )))
 }
 
+async fn get_owned_connection(
+    state: &Arc<AppState>,
+    event_access: &EventAccess,
+    id: &str,
+) -> Result<Connection, PicaError> {
+    let Some(connection) = state
+        .app_stores
+        .connection
+        .get_one(doc! {
+            "_id": id,
+            "deleted": false
+        })
+        .await
+        .inspect_err(|e| {
+            error!("Error fetching connection {id}: {:?}", e);
+        })?
+    else {
+        return Err(ApplicationError::not_found(
+            &format!("Connection with id {id} not found"),
+            None,
+        ));
+    };
+
+    if connection.ownership != event_access.ownership
+        || connection.environment != event_access.environment
+    {
+        return Err(ApplicationError::forbidden(
+            "You do not have permission to access this connection",
+            None,
+        ));
+    }
+
+    Ok(connection)
+}
+
+/// Generates a fresh key for the connection, keeping the identity suffix of
+/// the current key so the key stays recognizable to its owner.
+fn generate_rotated_key(connection: &Connection) -> String {
+    let uuid = Uuid::new_v4().to_string().replace('-', "");
+
+    let key_suffix = match connection
+        .key
+        .rsplit_once("::")
+        .and_then(|(_, suffix)| suffix.split_once('|'))
+    {
+        Some((_, identity)) => format!("{uuid}|{identity}"),
+        None => uuid,
+    };
+
+    format!(
+        "{}::{}::{}::{}",
+        connection.environment, connection.platform, DEFAULT_NAMESPACE, key_suffix
+    )
+}
+
+async fn replace_connection(
+    state: &Arc<AppState>,
+    connection: &Connection,
+) -> Result<(), PicaError> {
+    state
+        .app_stores
+        .connection
+        .collection
+        .replace_one(doc! { "_id": connection.id.to_string() }, connection)
+        .await
+        .map_err(|e| {
+            error!("Error updating connection key: {:?}", e);
+
+            InternalError::connection_error(&e.to_string(), None)
+        })?;
+
+    Ok(())
+}
+
+/// Drops every cached entry resolving to this connection, including the
+/// previous key of an ongoing rotation, so later lookups hit the database.
+pub(crate) async fn evict_cached_connection(state: &Arc<AppState>, connection: &Connection) {
+    let keys = std::iter::once(&connection.key).chain(
+        connection
+            .key_rotation
+            .as_ref()
+            .map(|rotation| &rotation.previous_key),
+    );
+
+    for key in keys {
+        let Ok(header) = HeaderValue::from_str(key) else {
+            continue;
+        };
+
+        if let Err(e) = state
+            .connections_cache
+            .remove(&(connection.ownership.id.clone(), header))
+            .await
+        {
+            warn!(
+                "Failed to evict connection {} from cache: {:?}",
+                connection.id, e
+            );
+        }
+    }
+
+    // The cached secret holds the additional secrets merged into it
+    if let Err(e) = state
+        .extractor_caller
+        .secrets_cache
+        .remove(connection)
+        .await
+    {
+        warn!(
+            "Failed to evict the secret of connection {} from cache: {:?}",
+            connection.id, e
+        );
+    }
+}
+
+/// Issues a new key for the connection. The previous key keeps resolving to
+/// the same connection for `CONNECTION_KEY_ROTATION_OVERLAP_SECS`, or until the
+/// rotation is finalized.
+pub async fn rotate_connection_key(
+    Extension(event_access): Extension<Arc<EventAccess>>,
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SanitizedConnection>, PicaError> {
+    let mut connection = get_owned_connection(&state, &event_access, &id).await?;
+
+    let now = Utc::now().timestamp_millis();
+
+    if let Some(rotation) = &connection.key_rotation {
+        if rotation.expires_at > now {
+            return Err(ApplicationError::conflict(
+                "A key rotation is already in progress for this connection, finalize it first",
+                None,
+            ));
+        }
+    }
+
+    let overlap_millis = state
+        .config
+        .connection_key_rotation_overlap_secs
+        .saturating_mul(1000) as i64;
+    let key = generate_rotated_key(&connection);
+    let previous_key = connection.key.clone();
+
+    connection.key_rotation = Some(KeyRotation {
+        previous_key: previous_key.clone(),
+        rotated_at: now,
+        expires_at: now.saturating_add(overlap_millis),
+    });
+    connection.key = key.clone().into();
+    connection.throughput.key = key;
+    connection
+        .record_metadata
+        .mark_updated(&event_access.ownership.id);
+
+    replace_connection(&state, &connection).await?;
+
+    // Both keys must resolve to the rotated connection while the overlap lasts,
+    // so a stale cache entry for the previous key is replaced rather than kept
+    for key in [&previous_key, &connection.key] {
+        if let Ok(header) = HeaderValue::from_str(key) {
+            if let Err(e) = state
+                .connections_cache
+                .insert(&(connection.ownership.id.clone(), header), &connection)
+                .await
+            {
+                warn!("Failed to cache rotated connection: {:?}", e);
+            }
+        }
+    }
+
+    Ok(Json(connection.into()))
+}
+
+/// Ends a key rotation early, invalidating the previous key immediately.
+pub async fn finalize_connection_key_rotation(
+    Extension(event_access): Extension<Arc<EventAccess>>,
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<Value>>, PicaError> {
+    let rotated = get_owned_connection(&state, &event_access, &id).await?;
+
+    if rotated.key_rotation.is_none() {
+        return Err(ApplicationError::bad_request(
+            "Connection has no key rotation to finalize",
+            None,
+        ));
+    }
+
+    let mut connection = rotated.clone();
+    connection.key_rotation = None;
+    connection
+        .record_metadata
+        .mark_updated(&event_access.ownership.id);
+
+    replace_connection(&state, &connection).await?;
+
+    evict_cached_connection(&state, &rotated).await;
+
+    Ok(Json(ServerResponse::new(
+        "connection",
+        json!({
+            id: connection.id,
+        }),
+    )))
+}
+
+/// An action that can be called through a connection, with the parameters
+/// its variable mapping fills in
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionActionItem {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    #[serde(flatten)]
+    pub action: ActionItem,
+    pub path: String,
+    pub has_mapping: bool,
+    pub auto_handled_params: Vec<Annotation>,
+}
+
+/// Lists the actionable model definitions of the connection's definition,
+/// flagging those whose parameters are partly filled in by a variable mapping
+pub async fn get_connection_definitions(
+    Extension(event_access): Extension<Arc<EventAccess>>,
+    Path(id): Path<String>,
+    query: Option<Query<BTreeMap<String, String>>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<ReadResponse<ConnectionActionItem>>>, PicaError> {
+    let connection = get_owned_connection(&state, &event_access, &id).await?;
+
+    let mut query = query;
+    let sort = query.as_mut().and_then(|Query(q)| q.remove(SORT_QUERY));
+    let sort = actions_sort(sort.as_deref())?;
+
+    let query = shape_mongo_filter(query, None, None).with_page_size(&state.config);
+    let mut filter = query.filter;
+    filter.insert(
+        "connectionDefinitionId",
+        connection.connection_definition_id.to_string(),
+    );
+    restrict_to_actionable(&mut filter);
+
+    let store = &state.app_stores.model_config;
+    let (total, rows) = tokio::try_join!(
+        store.count(filter.clone(), None),
+        store.get_many(
+            Some(filter),
+            None,
+            Some(sort),
+            Some(query.limit),
+            Some(query.skip),
+        )
+    )?;
+
+    let mappings = get_definition_mappings(&state, &connection, &rows).await?;
+    let items = rows
+        .into_iter()
+        .map(|definition| {
+            let mapping = mappings.get(&definition.id.to_string());
+            action_item(definition, mapping)
+        })
+        .collect();
+
+    Ok(Json(ServerResponse::new(
+        "definitions",
+        ReadResponse::new(items, total, query.skip, query.limit),
+    )))
+}
+
+/// Everything that shapes how calls through a connection are made, for
+/// debugging an integration. Secret values are never included, only the
+/// names of the variables the secret holds.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveConfig {
+    pub connection: SanitizedConnection,
+    pub definitions: Vec<ConnectionActionItem>,
+    pub mappings: Vec<ConnectionVariableMapping>,
+    /// Dotted paths of the values the secret holds, e.g. `CLIENT.ID`
+    pub secret_keys: Vec<String>,
+    /// Rate limit last reported by the platform to this instance, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_limit: Option<UpstreamLimit>,
+}
+
+pub async fn get_effective_config(
+    Extension(event_access): Extension<Arc<EventAccess>>,
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<EffectiveConfig>>, PicaError> {
+    let connection = get_owned_connection(&state, &event_access, &id).await?;
+    let (definitions, secret) = get_definitions_and_secret(&state, &connection).await?;
+
+    let secret_keys = secret_leaf_paths(&secret);
+
+    let mappings = get_definition_mappings(&state, &connection, &definitions).await?;
+    let definitions = definitions
+        .into_iter()
+        .map(|definition| {
+            let mapping = mappings.get(&definition.id.to_string());
+            action_item(definition, mapping)
+        })
+        .collect();
+
+    let mut mappings: Vec<ConnectionVariableMapping> = mappings.into_values().collect();
+    mappings.sort_by_key(|mapping| mapping.id.to_string());
+
+    let upstream_limit = state.upstream_limits.get(&connection.id);
+
+    Ok(Json(ServerResponse::new(
+        "effective-config",
+        EffectiveConfig {
+            connection: connection.into(),
+            definitions,
+            mappings,
+            secret_keys,
+            upstream_limit,
+        },
+    )))
+}
+
+/// Variables that the connection's mappings require but its secret lacks.
+/// Only variable names are reported, never secret values.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretValidation {
+    pub valid: bool,
+    pub missing: Vec<MissingVariable>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingVariable {
+    pub variable_name: String,
+    /// Definitions whose mapping binds the variable
+    pub required_by: Vec<Id>,
+}
+
+pub async fn validate_connection_secret(
+    Extension(event_access): Extension<Arc<EventAccess>>,
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<SecretValidation>>, PicaError> {
+    let connection = get_owned_connection(&state, &event_access, &id).await?;
+    let (definitions, secret) = get_definitions_and_secret(&state, &connection).await?;
+    let mappings = get_definition_mappings(&state, &connection, &definitions).await?;
+
+    let mut missing: BTreeMap<String, Vec<Id>> = BTreeMap::new();
+    for mapping in mappings.values() {
+        for binding in missing_variables(&mapping.bindings, &secret) {
+            let required_by = missing.entry(binding.variable_name.clone()).or_default();
+            if !required_by.contains(&mapping.connection_model_definition_id) {
+                required_by.push(mapping.connection_model_definition_id);
+            }
+        }
+    }
+
+    Ok(Json(ServerResponse::new(
+        "secret-validation",
+        SecretValidation {
+            valid: missing.is_empty(),
+            missing: missing
+                .into_iter()
+                .map(|(variable_name, mut required_by)| {
+                    required_by.sort_by_key(|id| id.to_string());
+                    MissingVariable {
+                        variable_name,
+                        required_by,
+                    }
+                })
+                .collect(),
+        },
+    )))
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthProbeQuery {
+    /// Also runs the actions that may change data on the platform
+    #[serde(default)]
+    pub include_mutating: bool,
+}
+
+/// Outcome of running the connection's actions against the platform. The
+/// connection is healthy when none of the probed actions failed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthProbe {
+    pub healthy: bool,
+    pub passed: usize,
+    pub failed: usize,
+    /// Actions left out because they may change data on the platform
+    pub skipped: usize,
+    /// Actions not sent because their path has placeholders neither a
+    /// mapping nor the secret fills, listed in the results
+    #[serde(default)]
+    pub unresolved: usize,
+    pub results: Vec<ActionProbe>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionProbe {
+    pub id: Id,
+    pub title: String,
+    pub action_name: CrudAction,
+    #[serde(with = "http_serde_ext_ios::method")]
+    pub method: http::Method,
+    pub passed: bool,
+    /// Status the platform answered with, absent when the request failed
+    /// before a response came back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub latency_millis: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Path placeholders left without a value, the action is not sent when
+    /// there are any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unresolved_params: Vec<String>,
+}
+
+/// Runs the connection's read-only actions against the platform with its
+/// stored secret, applying the variable mappings of each definition the way
+/// passthrough does. An action passes when the platform answers with a
+/// success status.
+pub async fn probe_connection_health(
+    Extension(event_access): Extension<Arc<EventAccess>>,
+    Path(id): Path<String>,
+    query: Option<Query<HealthProbeQuery>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<HealthProbe>>, PicaError> {
+    let query = query.map(|Query(q)| q).unwrap_or_default();
+    let connection = get_owned_connection(&state, &event_access, &id).await?;
+    let (definitions, secret) = get_definitions_and_secret(&state, &connection).await?;
+
+    let total = definitions.len();
+    let definitions: Vec<ConnectionModelDefinition> = definitions
+        .into_iter()
+        .filter(|definition| query.include_mutating || definition.action.is_safe())
+        .collect();
+    let mappings = get_definition_mappings(&state, &connection, &definitions).await?;
+
+    let results: Vec<ActionProbe> = stream::iter(definitions)
+        .map(|definition| {
+            let (state, mappings, secret) = (&state, &mappings, &secret);
+
+            async move {
+                let mapping = mappings.get(&definition.id.to_string());
+                probe_action(state, &definition, mapping, secret).await
+            }
+        })
+        .buffered(state.config.connection_health_probe_concurrency.max(1))
+        .collect()
+        .await;
+
+    let passed = results.iter().filter(|result| result.passed).count();
+    let unresolved = results
+        .iter()
+        .filter(|result| !result.unresolved_params.is_empty())
+        .count();
+    let failed = results.len() - passed - unresolved;
+
+    Ok(Json(ServerResponse::new(
+        "health-probe",
+        HealthProbe {
+            healthy: failed == 0,
+            passed,
+            failed,
+            skipped: total - results.len(),
+            unresolved,
+            results,
+        },
+    )))
+}
+
+async fn probe_action(
+    state: &AppState,
+    definition: &ConnectionModelDefinition,
+    mapping: Option<&ConnectionVariableMapping>,
+    secret: &Value,
+) -> ActionProbe {
+    let started = Instant::now();
+    let result = execute_probe(state, definition, mapping, secret).await;
+    let latency_millis = started.elapsed().as_millis() as u64;
+
+    let (status, error, unresolved_params) = match result {
+        Ok(ProbeOutcome::Answered(status)) => (Some(status), None, vec![]),
+        Ok(ProbeOutcome::Unresolved(params)) => (None, None, params),
+        Err(e) => {
+            warn!(
+                "Health probe of model definition {} failed: {e}",
+                definition.id
+            );
+            (None, Some(e.to_string()), vec![])
+        }
+    };
+
+    ActionProbe {
+        id: definition.id,
+        title: definition.title.clone(),
+        action_name: definition.action_name.clone(),
+        method: definition.action.clone(),
+        passed: status.is_some_and(|status| status.is_success()),
+        status: status.map(|status| status.as_u16()),
+        latency_millis,
+        error,
+        unresolved_params,
+    }
+}
+
+enum ProbeOutcome {
+    Answered(StatusCode),
+    /// The path placeholders no value was found for
+    Unresolved(Vec<String>),
+}
+
+/// Sends the action with the definition's test payload as its body unless a
+/// mapping sets one. Path placeholders are filled from the secret the way the
+/// test endpoint does, the action is not sent while any is left.
+async fn execute_probe(
+    state: &AppState,
+    definition: &ConnectionModelDefinition,
+    mapping: Option<&ConnectionVariableMapping>,
+    secret: &Value,
+) -> Result<ProbeOutcome, PicaError> {
+    let mut definition = definition.clone();
+    let mut parts = RequestParts::default();
+    let PlatformInfo::Api(ref mut api_config) = definition.platform_info;
+
+    if let Some(mapping) = mapping {
+        parts.path = std::mem::take(&mut api_config.path);
+
+        parts = mapping.apply(parts, secret)?.parts;
+        api_config.path = std::mem::take(&mut parts.path);
+    }
+
+    let (path, missing) =
+        fill_path_placeholders(&api_config.path, |name| match secret.get(name)? {
+            Value::String(value) => Some(value.clone()),
+            value @ (Value::Number(_) | Value::Bool(_)) => Some(value.to_string()),
+            _ => None,
+        });
+    if !missing.is_empty() {
+        return Ok(ProbeOutcome::Unresolved(missing));
+    }
+    api_config.path = path;
+
+    if parts.body.is_none() {
+        parts.body = definition
+            .test_connection_payload
+            .as_ref()
+            .map(|payload| payload.to_string().into_bytes());
+    }
+
+    let response = state
+        .extractor_caller
+        .execute_model_definition(
+            &definition,
+            parts.headers,
+            &parts.query_params,
+            secret,
+            parts.body,
+        )
+        .await?;
+
+    Ok(ProbeOutcome::Answered(response.status()))
+}
+
+/// Actionable definitions of the connection's platform, in the order actions
+/// are listed, along with the connection's decrypted secret
+async fn get_definitions_and_secret(
+    state: &AppState,
+    connection: &Connection,
+) -> Result<(Vec<ConnectionModelDefinition>, Value), PicaError> {
+    let mut filter = doc! {
+        "connectionDefinitionId": connection.connection_definition_id.to_string(),
+        "deleted": false,
+    };
+    restrict_to_actionable(&mut filter);
+
+    let (definitions, secret) = tokio::try_join!(
+        state.app_stores.model_config.get_many(
+            Some(filter),
+            None,
+            Some(actions_sort(None)?),
+            None,
+            None,
+        ),
+        connection_secret(state.secrets_client.as_ref(), connection)
+    )?;
+
+    Ok((definitions, secret))
+}
+
+/// Variable mappings the connection's requests are made with for the given
+/// definitions, keyed by definition id
+async fn get_definition_mappings(
+    state: &AppState,
+    connection: &Connection,
+    definitions: &[ConnectionModelDefinition],
+) -> Result<HashMap<String, ConnectionVariableMapping>, PicaError> {
+    if definitions.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let definition_ids: Vec<Id> = definitions.iter().map(|d| d.id).collect();
+
+    let mut of_definitions: HashMap<String, Vec<ConnectionVariableMapping>> = HashMap::new();
+    for mapping in get_cached_mappings_of_definitions(state, &definition_ids).await? {
+        of_definitions
+            .entry(mapping.connection_model_definition_id.to_string())
+            .or_default()
+            .push(mapping);
+    }
+
+    Ok(of_definitions
+        .into_iter()
+        .filter_map(|(id, mappings)| {
+            ConnectionVariableMapping::resolve(
+                mappings,
+                connection.environment,
+                &connection.ownership.id,
+            )
+            .map(|mapping| (id, mapping))
+        })
+        .collect())
+}
+
+fn action_item(
+    definition: ConnectionModelDefinition,
+    mapping: Option<&ConnectionVariableMapping>,
+) -> ConnectionActionItem {
+    ConnectionActionItem {
+        id: definition.id,
+        path: definition.platform_info.config().path.clone(),
+        has_mapping: mapping.is_some(),
+        auto_handled_params: mapping
+            .map(|mapping| describe_bindings(&mapping.bindings))
+            .unwrap_or_default(),
+        action: ActionItem {
+            title: definition.title,
+            key: definition.name,
+            method: definition.action,
+            platform: definition.connection_platform,
+        },
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VaultConnection {
@@ -846,7 +1616,8 @@ pub async fn get_vault_connections(
     query: Option<Query<BTreeMap<String, String>>>,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<ServerResponse<ReadResponse<VaultConnection>>>, PicaError> {
-    let mongo_query = shape_mongo_filter(query, Some(access), Some(headers));
+    let mongo_query =
+        shape_mongo_filter(query, Some(access), Some(headers)).with_page_size(&state.config);
 
     let connections = state
         .app_stores
@@ -917,12 +1688,12 @@ pub async fn get_vault_connections(
             e
         })?;
 
-    let response = ReadResponse {
-        rows: vault_connections,
-        skip: mongo_query.skip,
-        limit: mongo_query.limit,
+    let response = ReadResponse::new(
+        vault_connections,
         total,
-    };
+        mongo_query.skip,
+        mongo_query.limit,
+    );
 
     Ok(Json(ServerResponse::new("Vault Connections", response)))
 }
@@ -566,7 +566,8 @@ pub async fn get_available_connectors(
         None
     };
 
-    let query = shape_mongo_filter(cleaned_query, None, Some(headers));
+    let query =
+        shape_mongo_filter(cleaned_query, None, Some(headers)).with_page_size(&state.config);
     let store = state.app_stores.connection_config.clone();
     let mut filter = query.filter.clone();
 
@@ -624,12 +625,12 @@ pub async fn get_available_connectors(
                 })
                 .collect();
 
-            ReadResponse {
-                rows: available_connectors,
-                skip: query.skip,
-                limit: query.limit,
+            ReadResponse::new(
+                available_connectors,
                 total,
-            }
+                query.skip,
+                query.limit,
+            )
         }
         Err(e) => {
             error!("Error reading from store: {e}");
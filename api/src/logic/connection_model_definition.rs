@@ -1,63 +1,800 @@
 use super::{
-    create, delete, read, update, HookExt, PublicExt, ReadResponse, RequestExt, SuccessResponse,
+    connection_model_definition_bundle::{
+        import_bundle, validate_samples, validate_schemas, validate_transform,
+    },
+    connection_model_definition_diff::diff_definitions,
+    connection_webhook, create, delete, is_duplicate_key, parent_filter, read, update, HookExt,
+    PublicExt, ReadResponse, RequestExt, SuccessResponse,
 };
 use crate::{
-    helper::shape_mongo_filter,
+    domain::migration::ESCAPE_DEFINITION_KEYS,
+    helper::{shape_mongo_filter, CheckedJson},
     router::ServerResponse,
     server::{AppState, AppStores},
 };
 use axum::{
+    body::Bytes,
     extract::Query,
     extract::{Path, State},
     http::{HeaderMap, StatusCode},
-    routing::{patch, post},
+    routing::{get, patch, post, put},
     Extension, Json, Router,
 };
 use chrono::Utc;
 use fake::Dummy;
-use mongodb::bson::doc;
+use futures::{stream, StreamExt};
+use mongodb::bson::{doc, Document};
 use osentities::{
-    algebra::MongoStore,
+    algebra::{connection_secret, MongoStore},
     api_model_config::{
         ApiModelConfig, AuthMethod, ModelPaths, ResponseBody, SamplesInput, SchemasInput,
     },
-    connection_definition::ConnectionDefinition,
     connection_model_definition::{
-        ConnectionModelDefinition, CrudAction, CrudMapping, ExtractorConfig, PlatformInfo,
-        TestConnection, TestConnectionState,
+        ConnectionModelDefinition, CrudAction, CrudMapping, DefinitionLifecycle, ExtractorConfig,
+        PlatformInfo, TestConnection, TestConnectionState,
     },
-    connection_model_schema::ConnectionModelSchema,
-    connection_oauth_definition::Settings,
+    connection_model_definition_audit::{AuditActor, ConnectionModelDefinitionAudit},
+    connection_webhook::{ConnectionLifecycleEvent, ConnectionLifecycleEventType},
     event_access::EventAccess,
     id::{prefix::IdPrefix, Id},
-    platform::PlatformData,
-    ApplicationError, InternalError, PicaError,
+    request_transform::RequestTransform,
+    variable_injection::fill_path_placeholders,
+    ApplicationError, Claims, Connection, InternalError, PicaError,
 };
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
 use semver::Version;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::{
     collections::{BTreeMap, HashMap},
     sync::Arc,
 };
 use tokio::try_join;
-use tracing::error;
+use tracing::{error, warn};
 
 pub fn get_router() -> Router<Arc<AppState>> {
     Router::new()
         .route(
             "/",
-            post(create::<CreateRequest, ConnectionModelDefinition>)
+            post(create_model_definition)
                 .get(read::<CreateRequest, ConnectionModelDefinition>)
                 .patch(update_many),
         )
+        .route("/bulk-delete", post(bulk_delete))
+        .route("/bulk-restore", post(bulk_restore))
+        .route("/import", post(import_bundle))
+        .route("/by-key", put(upsert_model_definition_by_key))
+        .route("/diff", get(diff_definitions))
+        .route("/:id/audit", get(read_audit_trail))
+        .route("/:id/lifecycle", post(transition_lifecycle))
         .route(
             "/:id",
-            patch(update::<CreateRequest, ConnectionModelDefinition>)
+            patch(update_model_definition)
                 .delete(delete::<CreateRequest, ConnectionModelDefinition>),
         )
 }
 
+/// Content type selecting the JSON Patch (RFC 6902) mode of `PATCH /:id`
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+async fn create_model_definition(
+    access: Option<Extension<Arc<EventAccess>>>,
+    State(state): State<Arc<AppState>>,
+    CheckedJson(payload): CheckedJson<CreateRequest>,
+) -> Result<Json<ServerResponse<Value>>, PicaError> {
+    check_definition(&state, &payload)?;
+    check_connection_definition(
+        &state,
+        &payload.connection_definition_id,
+        access.as_deref().map(Arc::as_ref),
+    )
+    .await?;
+
+    create::<CreateRequest, ConnectionModelDefinition>(access, State(state), Json(payload)).await
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpsertAction {
+    Created,
+    Updated,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpsertResponse {
+    pub action: UpsertAction,
+    pub definition: ConnectionModelDefinition,
+}
+
+/// Creates the definition, or updates the one stored under the key derived
+/// from the payload while keeping its `_id` and creation metadata. Sending
+/// the `updatedAt` of the stored definition as `If-Match` only lets the
+/// update through when nobody changed it since. A definition written
+/// concurrently under the same key is reported as a conflict instead of
+/// being duplicated or overwritten, so the caller can simply retry.
+async fn upsert_model_definition_by_key(
+    access: Option<Extension<Arc<EventAccess>>>,
+    claims: Option<Extension<Arc<Claims>>>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    CheckedJson(payload): CheckedJson<CreateRequest>,
+) -> Result<Json<ServerResponse<UpsertResponse>>, PicaError> {
+    check_definition(&state, &payload)?;
+    check_connection_definition(
+        &state,
+        &payload.connection_definition_id,
+        access.as_deref().map(Arc::as_ref),
+    )
+    .await?;
+
+    let key = payload.key();
+    let expected_updated_at = if_match_updated_at(&headers)?;
+    let store = &state.app_stores.model_config;
+    let by_key = doc! { "key": &key, "deleted": false };
+
+    let mut stored = store.get_one(by_key.clone()).await?;
+    if stored.is_none() && !state.migrations.contains(ESCAPE_DEFINITION_KEYS) {
+        stored = store
+            .get_one(doc! { "key": legacy_key(&key), "deleted": false })
+            .await?;
+    }
+
+    let Some(previous) = stored else {
+        if expected_updated_at.is_some() {
+            return Err(ApplicationError::conflict(
+                &format!("No definition with key {key} exists"),
+                None,
+            ));
+        }
+
+        let definition = payload.from().ok_or_else(|| {
+            ApplicationError::bad_request("Could not generate output from payload", None)
+        })?;
+        let created_concurrently = || {
+            ApplicationError::conflict(
+                &format!("Definition with key {key} was created concurrently"),
+                None,
+            )
+        };
+        let result = store
+            .collection
+            .update_one(by_key, doc! { "$setOnInsert": to_document(&definition)? })
+            .upsert(true)
+            .await
+            .map_err(|e| {
+                if is_duplicate_key(&e) {
+                    created_concurrently()
+                } else {
+                    PicaError::from(e)
+                }
+            })?;
+
+        if result.upserted_id.is_none() {
+            return Err(created_concurrently());
+        }
+
+        return Ok(Json(ServerResponse::new(
+            "upsert",
+            UpsertResponse {
+                action: UpsertAction::Created,
+                definition,
+            },
+        )));
+    };
+
+    let stored_updated_at = previous.record_metadata.updated_at;
+    if expected_updated_at.is_some_and(|expected| expected != stored_updated_at) {
+        return Err(ApplicationError::conflict(
+            &format!("Definition with key {key} was changed since {stored_updated_at}"),
+            None,
+        )
+        .set_meta(&json!({ "updatedAt": stored_updated_at })));
+    }
+
+    let mut current = payload.update(previous.clone());
+    current.record_metadata.updated = true;
+    current.record_metadata.updated_at = Utc::now().timestamp_millis();
+
+    let result = store
+        .collection
+        .update_one(
+            doc! { "_id": previous.id.to_string(), "updatedAt": stored_updated_at },
+            doc! { "$set": to_document(&current)? },
+        )
+        .await?;
+
+    if result.matched_count == 0 {
+        return Err(ApplicationError::conflict(
+            &format!("Definition with key {key} was changed concurrently"),
+            None,
+        ));
+    }
+
+    let actor = audit_actor(
+        claims.as_deref().map(Arc::as_ref),
+        access.as_deref().map(Arc::as_ref),
+    );
+    notify_test_connection_status_change(
+        &state,
+        &previous,
+        &previous.test_connection_status,
+        &current.test_connection_status,
+    );
+    audit_flag_transitions(&state, &actor, &previous, &current).await;
+
+    Ok(Json(ServerResponse::new(
+        "upsert",
+        UpsertResponse {
+            action: UpsertAction::Updated,
+            definition: current,
+        },
+    )))
+}
+
+/// `updatedAt` the client expects the stored definition to have, sent as an
+/// `If-Match` entity tag with or without quotes
+fn if_match_updated_at(headers: &HeaderMap) -> Result<Option<i64>, PicaError> {
+    let Some(value) = headers.get(http::header::IF_MATCH) else {
+        return Ok(None);
+    };
+
+    value
+        .to_str()
+        .ok()
+        .map(|tag| tag.trim().trim_start_matches("W/").trim_matches('"'))
+        .and_then(|tag| tag.parse().ok())
+        .map(Some)
+        .ok_or_else(|| {
+            ApplicationError::bad_request(
+                "If-Match must hold the updatedAt of the stored definition",
+                None,
+            )
+        })
+}
+
+fn to_document(definition: &ConnectionModelDefinition) -> Result<Document, PicaError> {
+    bson::to_document(definition).map_err(|e| {
+        error!("Could not serialize definition into document: {e}");
+        InternalError::serialize_error(&e.to_string(), None)
+    })
+}
+
+async fn update_model_definition(
+    access: Option<Extension<Arc<EventAccess>>>,
+    claims: Option<Extension<Arc<Claims>>>,
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ServerResponse<SuccessResponse>>, PicaError> {
+    let previous = state
+        .app_stores
+        .model_config
+        .get_one(doc! { "_id": &id, "deleted": false })
+        .await?;
+    let actor = audit_actor(
+        claims.as_deref().map(Arc::as_ref),
+        access.as_deref().map(Arc::as_ref),
+    );
+
+    let (response, current) = if is_json_patch(&headers) {
+        let patch: json_patch::Patch = serde_json::from_slice(&body).map_err(|e| {
+            ApplicationError::bad_request(&format!("Invalid JSON Patch document: {e}"), None)
+        })?;
+        let current = apply_json_patch(&state, access, &id, &patch).await?;
+
+        (
+            Json(ServerResponse::new(
+                "update",
+                SuccessResponse { success: true },
+            )),
+            Some(current),
+        )
+    } else {
+        let CheckedJson(payload) = CheckedJson::<CreateRequest>::from_bytes(&body)?;
+        check_definition(&state, &payload)?;
+        if previous
+            .as_ref()
+            .map(|previous| previous.connection_definition_id)
+            != Some(payload.connection_definition_id)
+        {
+            check_connection_definition(
+                &state,
+                &payload.connection_definition_id,
+                access.as_deref().map(Arc::as_ref),
+            )
+            .await?;
+        }
+
+        let current = previous
+            .as_ref()
+            .map(|previous| payload.update(previous.clone()));
+
+        let response = update::<CreateRequest, ConnectionModelDefinition>(
+            access,
+            Path(id),
+            State(state.clone()),
+            Json(payload),
+        )
+        .await?;
+
+        (response, current)
+    };
+
+    if let (Some(previous), Some(current)) = (previous, current) {
+        notify_test_connection_status_change(
+            &state,
+            &previous,
+            &previous.test_connection_status,
+            &current.test_connection_status,
+        );
+        audit_flag_transitions(&state, &actor, &previous, &current).await;
+    }
+
+    Ok(response)
+}
+
+/// Fails with a 404 when the connection definition a definition belongs to
+/// does not exist for the caller, so no definition is written without its
+/// parent
+async fn check_connection_definition(
+    state: &AppState,
+    id: &Id,
+    access: Option<&EventAccess>,
+) -> Result<(), PicaError> {
+    let exists = state
+        .app_stores
+        .connection_config
+        .count(parent_filter(&id.to_string(), access), Some(1))
+        .await?
+        > 0;
+
+    if exists {
+        Ok(())
+    } else {
+        Err(ApplicationError::not_found(
+            &format!("Connection definition with id {id} not found"),
+            None,
+        ))
+    }
+}
+
+/// Checks the schemas of a definition and its samples against them before it
+/// is written. Unless `REJECT_INVALID_DEFINITIONS` is off, mismatches fail the
+/// request with a 400 listing them, otherwise they are only logged. An
+/// invalid request transform always fails it.
+pub(crate) fn check_definition(state: &AppState, payload: &CreateRequest) -> Result<(), PicaError> {
+    if let Some(transform) = &payload.request_transform {
+        transform.check()?;
+    }
+
+    let mut errors = validate_schemas(&payload.schemas);
+    errors.extend(validate_samples(&payload.schemas, &payload.samples));
+
+    reject_invalid(state, &payload.name, &payload.connection_platform, errors)
+}
+
+/// [`check_definition`] of a stored definition, once an update was merged
+/// into it
+fn check_stored_definition(
+    state: &AppState,
+    record: &ConnectionModelDefinition,
+) -> Result<(), PicaError> {
+    if let Some(transform) = &record.request_transform {
+        transform.check()?;
+    }
+
+    let config = record.platform_info.config();
+    let mut errors = validate_schemas(&config.schemas);
+    errors.extend(validate_samples(&config.schemas, &config.samples));
+
+    reject_invalid(state, &record.name, &record.connection_platform, errors)
+}
+
+fn reject_invalid(
+    state: &AppState,
+    name: &str,
+    platform: &str,
+    errors: Vec<String>,
+) -> Result<(), PicaError> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    if !state.config.reject_invalid_definitions {
+        warn!(
+            "Definition {name} of platform {platform} is invalid: {}",
+            errors.join(", ")
+        );
+
+        return Ok(());
+    }
+
+    Err(ApplicationError::bad_request(
+        &format!("Definition is invalid: {}", errors.join(", ")),
+        None,
+    )
+    .set_meta(&json!({ "errors": errors })))
+}
+
+fn is_json_patch(headers: &HeaderMap) -> bool {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case(JSON_PATCH_CONTENT_TYPE))
+}
+
+/// Applies the patch to the stored definition and replaces it. Unlike the
+/// merge-style update, removed fields are removed from the stored document.
+/// The patched document must still deserialize into a definition with valid
+/// schemas and samples matching them, otherwise nothing is written.
+async fn apply_json_patch(
+    state: &AppState,
+    access: Option<Extension<Arc<EventAccess>>>,
+    id: &str,
+    patch: &json_patch::Patch,
+) -> Result<ConnectionModelDefinition, PicaError> {
+    let access = access.map(|Extension(access)| access);
+    let mut query = shape_mongo_filter(None, access.clone(), None);
+    query.filter.insert("_id", id);
+
+    let store = &state.app_stores.model_config;
+
+    let Some(record) = store.get_one(query.filter).await? else {
+        return Err(ApplicationError::not_found(
+            &format!("Record with id {id} not found"),
+            None,
+        ));
+    };
+
+    let mut document = serde_json::to_value(&record).map_err(|e| {
+        error!("Could not serialize connection model definition {id}: {e}");
+        InternalError::serialize_error(&e.to_string(), None)
+    })?;
+
+    json_patch::patch(&mut document, patch).map_err(|e| {
+        ApplicationError::unprocessable_entity(&format!("Could not apply patch: {e}"), None)
+    })?;
+
+    let mut patched: ConnectionModelDefinition =
+        serde_json::from_value(document).map_err(|e| {
+            ApplicationError::unprocessable_entity(
+                &format!("Patched definition is invalid: {e}"),
+                None,
+            )
+        })?;
+
+    if patched.id != record.id {
+        return Err(ApplicationError::unprocessable_entity(
+            "The _id of a definition cannot be patched",
+            None,
+        ));
+    }
+
+    if patched.lifecycle != record.lifecycle
+        || patched.lifecycle_transition != record.lifecycle_transition
+    {
+        return Err(ApplicationError::unprocessable_entity(
+            "The lifecycle of a definition can only be changed through its lifecycle endpoint",
+            None,
+        ));
+    }
+
+    let config = patched.platform_info.config();
+    let mut errors = validate_schemas(&config.schemas);
+    errors.extend(validate_samples(&config.schemas, &config.samples));
+    errors.extend(validate_transform(patched.request_transform.as_ref()));
+    if !errors.is_empty() {
+        return Err(ApplicationError::unprocessable_entity(
+            &format!("Patched definition is invalid: {}", errors.join(", ")),
+            None,
+        ));
+    }
+
+    if patched.connection_definition_id != record.connection_definition_id {
+        check_connection_definition(state, &patched.connection_definition_id, access.as_deref())
+            .await?;
+    }
+
+    patched.key = definition_key(&patched);
+    patched.record_metadata.updated = true;
+    patched.record_metadata.updated_at = Utc::now().timestamp_millis();
+
+    store
+        .collection
+        .replace_one(doc! { "_id": id }, &patched)
+        .await
+        .map_err(PicaError::from)?;
+
+    CreateRequest::after_update_hook(&patched, &state.app_stores)
+        .await
+        .ok();
+
+    Ok(patched)
+}
+
+/// Key of a stored definition, derived from the same fields as on creation
+fn definition_key(record: &ConnectionModelDefinition) -> String {
+    derive_key(
+        &record.connection_platform,
+        &record.platform_version,
+        &record.model_name,
+        &record.action_name,
+        &record.platform_info.config().path,
+        &record.name,
+    )
+}
+
+/// Joins the fields identifying a definition into its key. Each field is
+/// escaped first so that distinct fields never share a key: `%`, `:`, upper
+/// case and non-ASCII characters are percent-encoded, which keeps the `::`
+/// separator out of the fields and tells fields differing in case apart.
+/// Fields of lower case ASCII without `:` appear as they are, so do actions,
+/// which are lower cased as no two of them differ in case only.
+pub(crate) fn derive_key(
+    platform: &str,
+    platform_version: &str,
+    model_name: &str,
+    action_name: &CrudAction,
+    path: &str,
+    name: &str,
+) -> String {
+    let action_name = action_name.to_string().to_lowercase();
+
+    [
+        "api",
+        platform,
+        platform_version,
+        model_name,
+        &action_name,
+        path,
+        name,
+    ]
+    .map(escape_key_field)
+    .join("::")
+}
+
+fn escape_key_field(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for byte in field.bytes() {
+        match byte {
+            b'%' | b':' | b'A'..=b'Z' | 0x80.. => escaped.push_str(&format!("%{byte:02X}")),
+            _ => escaped.push(char::from(byte)),
+        }
+    }
+    escaped
+}
+
+/// Key a definition was stored under before its fields were escaped: the
+/// fields joined as they are, lower cased. Escaped fields never contain `::`,
+/// so the key splits back into them.
+pub(crate) fn legacy_key(key: &str) -> String {
+    key.split("::")
+        .map(unescape_key_field)
+        .collect::<Vec<_>>()
+        .join("::")
+        .to_lowercase()
+}
+
+fn unescape_key_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escape {
+            Some(byte) => {
+                unescaped.push(byte);
+                index += 3;
+            }
+            None => {
+                unescaped.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+/// Stored definitions by key. Until the key migration has run, definitions
+/// still stored under their legacy key are found as well, keys matching
+/// exactly being preferred.
+pub(crate) async fn get_definitions_by_key(
+    state: &AppState,
+    keys: &[&String],
+) -> Result<HashMap<String, ConnectionModelDefinition>, PicaError> {
+    let legacy = !state.migrations.contains(ESCAPE_DEFINITION_KEYS);
+
+    let mut lookup: HashMap<String, Vec<&String>> = HashMap::new();
+    for key in keys {
+        lookup.entry(key.to_string()).or_default().push(key);
+        if legacy {
+            lookup.entry(legacy_key(key)).or_default().push(key);
+        }
+    }
+
+    let stored = state
+        .app_stores
+        .model_config
+        .get_many(
+            Some(doc! {
+                "key": { "$in": lookup.keys().collect::<Vec<_>>() },
+                "deleted": false,
+            }),
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+    let mut definitions = HashMap::new();
+    for definition in stored {
+        for key in lookup.get(&definition.key).into_iter().flatten() {
+            if definition.key == **key || !definitions.contains_key(*key) {
+                definitions.insert(key.to_string(), definition.clone());
+            }
+        }
+    }
+
+    Ok(definitions)
+}
+
+fn audit_actor(claims: Option<&Claims>, access: Option<&EventAccess>) -> AuditActor {
+    match (claims, access) {
+        (Some(claims), _) => AuditActor {
+            id: claims.id.clone(),
+            email: Some(claims.email.clone()),
+        },
+        (None, Some(access)) => AuditActor {
+            id: access.ownership.id.to_string(),
+            email: None,
+        },
+        (None, None) => AuditActor::system(),
+    }
+}
+
+/// Appends an audit entry for each `supported`/`active` flag the update
+/// flipped. Failing to write the trail does not undo the update.
+async fn audit_flag_transitions(
+    state: &AppState,
+    actor: &AuditActor,
+    previous: &ConnectionModelDefinition,
+    current: &ConnectionModelDefinition,
+) {
+    let entries = ConnectionModelDefinitionAudit::transitions(
+        previous.id,
+        (previous.supported, previous.record_metadata.active),
+        (current.supported, current.record_metadata.active),
+        actor,
+    );
+
+    if entries.is_empty() {
+        return;
+    }
+
+    if let Err(e) = state.app_stores.model_config_audit.create_many(&entries).await {
+        error!(
+            "Could not write audit trail for connection model definition {}: {e}",
+            previous.id
+        );
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LifecycleTransitionRequest {
+    pub lifecycle: DefinitionLifecycle,
+}
+
+/// Moves a definition to the next state of its lifecycle, recording who did it
+/// and when on the definition. The `supported`/`active` flags it implies are
+/// audited like any other flag change.
+async fn transition_lifecycle(
+    claims: Option<Extension<Arc<Claims>>>,
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<LifecycleTransitionRequest>,
+) -> Result<Json<ServerResponse<ConnectionModelDefinition>>, PicaError> {
+    let store = &state.app_stores.model_config;
+
+    let Some(previous) = store.get_one(doc! { "_id": &id, "deleted": false }).await? else {
+        return Err(ApplicationError::not_found(
+            &format!("Record with id {id} not found"),
+            None,
+        ));
+    };
+
+    let actor = audit_actor(claims.as_deref().map(Arc::as_ref), None);
+    let now = Utc::now().timestamp_millis();
+
+    let mut current = previous.clone();
+    current.transition_lifecycle(req.lifecycle, actor.clone(), now)?;
+    current.record_metadata.updated = true;
+    current.record_metadata.updated_at = now;
+
+    store
+        .collection
+        .replace_one(doc! { "_id": &id }, &current)
+        .await
+        .map_err(PicaError::from)?;
+
+    CreateRequest::after_update_hook(&current, &state.app_stores)
+        .await
+        .ok();
+
+    audit_flag_transitions(&state, &actor, &previous, &current).await;
+
+    Ok(Json(ServerResponse::new("lifecycle", current)))
+}
+
+async fn read_audit_trail(
+    Path(id): Path<String>,
+    query: Option<Query<BTreeMap<String, String>>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<ReadResponse<ConnectionModelDefinitionAudit>>>, PicaError> {
+    let mut query = shape_mongo_filter(query, None, None).with_page_size(&state.config);
+    query.filter.insert("connectionModelDefinitionId", id);
+
+    let store = &state.app_stores.model_config_audit;
+    let (rows, total) = try_join!(
+        store.get_many(
+            Some(query.filter.clone()),
+            None,
+            Some(doc! { "changedAt": -1 }),
+            Some(query.limit),
+            Some(query.skip),
+        ),
+        store.count(query.filter, None)
+    )?;
+
+    Ok(Json(ServerResponse::new(
+        "read",
+        ReadResponse::new(
+            rows,
+            total,
+            query.skip,
+            query.limit,
+        ),
+    )))
+}
+
+/// Emits a lifecycle event when the outcome of a model definition's test
+/// connection changes (e.g. from untested to success), not on every re-test.
+fn notify_test_connection_status_change(
+    state: &AppState,
+    record: &ConnectionModelDefinition,
+    previous: &TestConnection,
+    current: &TestConnection,
+) {
+    if previous.state.kind() == current.state.kind() {
+        return;
+    }
+
+    connection_webhook::notify(
+        state,
+        ConnectionLifecycleEvent::new(
+            ConnectionLifecycleEventType::TestConnectionStatusChanged,
+            None,
+            None,
+            json!({
+                "connectionModelDefinitionId": record.id,
+                "connectionPlatform": record.connection_platform,
+                "previousStatus": previous.state.kind(),
+                "status": current.state.kind(),
+                "lastTestedAt": current.last_tested_at,
+            }),
+        ),
+    );
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchUpdateResult {
     pub id: Option<String>,
@@ -65,13 +802,69 @@ pub struct BatchUpdateResult {
     pub error: Option<String>,
 }
 
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchUpdateQuery {
+    #[serde(default)]
+    pub summary: bool,
+    #[serde(default)]
+    pub only_failures: bool,
+}
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Counts over the whole batch, `results` only holding the failed entries
+/// when asked for with `onlyFailures=true`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchUpdateSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BatchUpdateResult>,
+}
+
+/// Batch updates answer with the bare list of results unless a summary or
+/// only the failures are asked for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BatchUpdateResponse {
+    Results(Vec<BatchUpdateResult>),
+    Summary(BatchUpdateSummary),
+}
+
+impl BatchUpdateResponse {
+    pub fn new(results: Vec<BatchUpdateResult>, query: &BatchUpdateQuery) -> Self {
+        if !query.summary && !query.only_failures {
+            return Self::Results(results);
+        }
+
+        let total = results.len();
+        let succeeded = results.iter().filter(|result| result.success).count();
+        let results = if query.only_failures {
+            results
+                .into_iter()
+                .filter(|result| !result.success)
+                .collect()
+        } else {
+            results
+        };
+
+        Self::Summary(BatchUpdateSummary {
+            total,
+            succeeded,
+            failed: total - succeeded,
+            results,
+        })
+    }
+}
+
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct PartialUpdateRequest {
     #[serde(rename = "_id")]
+    #[schemars(with = "Option<String>")]
     pub id: Option<Id>,
     pub connection_platform: Option<String>,
+    #[schemars(with = "Option<String>")]
     pub connection_definition_id: Option<Id>,
     pub platform_version: Option<String>,
     pub title: Option<String>,
@@ -86,12 +879,14 @@ pub struct PartialUpdateRequest {
         rename = "action",
         default
     )]
+    #[schemars(with = "Option<String>")]
     pub http_method: Option<http::Method>,
     #[serde(
         with = "http_serde_ext_ios::header_map::option",
         skip_serializing_if = "Option::is_none",
         default
     )]
+    #[schemars(with = "Option<BTreeMap<String, String>>")]
     pub headers: Option<HeaderMap>,
     pub query_params: Option<BTreeMap<String, String>>,
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
@@ -99,6 +894,7 @@ pub struct PartialUpdateRequest {
     pub schemas: Option<SchemasInput>,
     pub samples: Option<SamplesInput>,
     pub responses: Option<Vec<ResponseBody>>,
+    #[schemars(with = "Option<String>")]
     pub version: Option<Version>,
     pub is_default_crud_mapping: Option<bool>,
     pub test_connection_payload: Option<Value>,
@@ -113,10 +909,17 @@ pub struct PartialUpdateRequest {
 
 pub async fn update_many(
     access: Option<Extension<Arc<EventAccess>>>,
+    claims: Option<Extension<Arc<Claims>>>,
+    query: Option<Query<BatchUpdateQuery>>,
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<Vec<PartialUpdateRequest>>,
-) -> Result<Json<ServerResponse<Vec<BatchUpdateResult>>>, PicaError> {
+    CheckedJson(payload): CheckedJson<Vec<PartialUpdateRequest>>,
+) -> Result<Json<ServerResponse<BatchUpdateResponse>>, PicaError> {
+    let query = query.map(|Query(q)| q).unwrap_or_default();
     let mut results = Vec::new();
+    let actor = audit_actor(
+        claims.as_deref().map(Arc::as_ref),
+        access.as_deref().map(Arc::as_ref),
+    );
     tracing::info!("Starting update_many for {} connection model definitions", payload.len());
 
     for request in payload {
@@ -146,11 +949,29 @@ pub async fn update_many(
 
         match store.get_one(query.filter).await {
             Ok(Some(mut record)) => {
+                let previous = record.clone();
+
                 // Merging Logic
                 if let Some(val) = request.connection_platform {
                     record.connection_platform = val;
                 }
                 if let Some(val) = request.connection_definition_id {
+                    if val != record.connection_definition_id {
+                        if let Err(e) = check_connection_definition(
+                            &state,
+                            &val,
+                            access.as_deref().map(Arc::as_ref),
+                        )
+                        .await
+                        {
+                            results.push(BatchUpdateResult {
+                                id: Some(id_str),
+                                success: false,
+                                error: Some(e.to_string()),
+                            });
+                            continue;
+                        }
+                    }
                     record.connection_definition_id = val;
                 }
                 if let Some(val) = request.platform_version {
@@ -235,27 +1056,16 @@ pub async fn update_many(
                 }
 
                 // Regenerate Key (Same logic as RequestExt)
-                // Note: If fields involved in key generation didn't change, this stays same,
-                // but we regenerate to be safe if any one of them changed.
-                // Key generation relies on: connection_platform, platform_version, model_name, action_name, path, name
-                // We need 'path' which is inside api_config.
-                let path_val = if let PlatformInfo::Api(ref api_config) = record.platform_info {
-                    api_config.path.clone()
-                } else {
-                    String::new()
-                };
-
-                let key = format!(
-                    "api::{}::{}::{}::{}::{}::{}",
-                    record.connection_platform,
-                    record.platform_version,
-                    record.model_name,
-                    record.action_name,
-                    path_val,
-                    record.name
-                )
-                .to_lowercase();
-                record.key = key;
+                record.key = definition_key(&record);
+
+                if let Err(e) = check_stored_definition(&state, &record) {
+                    results.push(BatchUpdateResult {
+                        id: Some(id_str),
+                        success: false,
+                        error: Some(e.to_string()),
+                    });
+                    continue;
+                }
 
                 let bson_result = bson::to_bson_with_options(&record, Default::default());
 
@@ -267,6 +1077,14 @@ pub async fn update_many(
                                 CreateRequest::after_update_hook(&record, &state.app_stores)
                                     .await
                                     .ok();
+                                notify_test_connection_status_change(
+                                    &state,
+                                    &record,
+                                    &previous.test_connection_status,
+                                    &record.test_connection_status,
+                                );
+                                audit_flag_transitions(&state, &actor, &previous, &record)
+                                    .await;
                                 tracing::info!("Successfully updated connection model definition in update_many with id: {}", id_str);
                                 results.push(BatchUpdateResult {
                                     id: Some(id_str),
@@ -312,7 +1130,153 @@ pub async fn update_many(
         }
     }
 
-    Ok(Json(ServerResponse::new("batch_update", results)))
+    Ok(Json(ServerResponse::new(
+        "batch_update",
+        BatchUpdateResponse::new(results, &query),
+    )))
+}
+
+/// Selects the definitions a bulk delete applies to, matching all of the
+/// criteria given. At least one is required, so that an empty body never
+/// selects every definition.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkDeleteFilter {
+    pub platform: Option<String>,
+    pub platform_version: Option<String>,
+    pub ids: Option<Vec<Id>>,
+}
+
+impl BulkDeleteFilter {
+    fn to_document(&self) -> Result<Document, PicaError> {
+        let mut filter = doc! {};
+
+        if let Some(platform) = self.platform.as_ref().filter(|p| !p.is_empty()) {
+            filter.insert("connectionPlatform", platform);
+        }
+        if let Some(version) = self.platform_version.as_ref().filter(|v| !v.is_empty()) {
+            filter.insert("platformVersion", version);
+        }
+        if let Some(ids) = self.ids.as_ref().filter(|ids| !ids.is_empty()) {
+            let ids: Vec<String> = ids.iter().map(Id::to_string).collect();
+            filter.insert("_id", doc! { "$in": ids });
+        }
+
+        if filter.is_empty() {
+            return Err(ApplicationError::bad_request(
+                "A platform, platform version or list of ids is required",
+                None,
+            ));
+        }
+
+        Ok(filter)
+    }
+}
+
+/// The definitions a bulk restore brings back, the ids a bulk delete answered
+/// with. Restoring by platform is not offered, as it would also bring back
+/// the definitions deleted on purpose before.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkRestoreRequest {
+    #[serde(default)]
+    pub ids: Vec<Id>,
+}
+
+impl BulkRestoreRequest {
+    fn to_document(&self) -> Result<Document, PicaError> {
+        if self.ids.is_empty() {
+            return Err(ApplicationError::bad_request(
+                "The ids of the definitions to restore are required",
+                None,
+            ));
+        }
+
+        let ids: Vec<String> = self.ids.iter().map(Id::to_string).collect();
+        Ok(doc! { "_id": { "$in": ids } })
+    }
+}
+
+/// Soft deletes every definition matching the filter, answering with the
+/// counts and the result for each of them. Deleted definitions can be
+/// brought back by passing the ids answered with to `POST /bulk-restore`.
+async fn bulk_delete(
+    access: Option<Extension<Arc<EventAccess>>>,
+    query: Option<Query<BatchUpdateQuery>>,
+    State(state): State<Arc<AppState>>,
+    Json(filter): Json<BulkDeleteFilter>,
+) -> Result<Json<ServerResponse<BatchUpdateResponse>>, PicaError> {
+    let results = set_deleted_in_bulk(&state, access, filter.to_document()?, true).await?;
+
+    Ok(Json(ServerResponse::new(
+        "bulk_delete",
+        BatchUpdateResponse::new(results, &bulk_query(query)),
+    )))
+}
+
+/// Restores the soft deleted definitions with the given ids, undoing
+/// `POST /bulk-delete`
+async fn bulk_restore(
+    access: Option<Extension<Arc<EventAccess>>>,
+    query: Option<Query<BatchUpdateQuery>>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<BulkRestoreRequest>,
+) -> Result<Json<ServerResponse<BatchUpdateResponse>>, PicaError> {
+    let results = set_deleted_in_bulk(&state, access, request.to_document()?, false).await?;
+
+    Ok(Json(ServerResponse::new(
+        "bulk_restore",
+        BatchUpdateResponse::new(results, &bulk_query(query)),
+    )))
+}
+
+/// Bulk operations always answer with the counts
+fn bulk_query(query: Option<Query<BatchUpdateQuery>>) -> BatchUpdateQuery {
+    BatchUpdateQuery {
+        summary: true,
+        ..query.map(|Query(query)| query).unwrap_or_default()
+    }
+}
+
+async fn set_deleted_in_bulk(
+    state: &AppState,
+    access: Option<Extension<Arc<EventAccess>>>,
+    filter: Document,
+    deleted: bool,
+) -> Result<Vec<BatchUpdateResult>, PicaError> {
+    let mut query = shape_mongo_filter(None, access.map(|Extension(e)| e), None);
+    query.filter.extend(filter);
+    query.filter.insert("deleted", !deleted);
+
+    let store = &state.app_stores.model_config;
+    let records = store
+        .get_many(Some(query.filter), None, None, None, None)
+        .await?;
+
+    tracing::info!(
+        "Setting deleted to {deleted} on {} connection model definitions",
+        records.len()
+    );
+
+    let mut results = Vec::with_capacity(records.len());
+    for record in records {
+        let id = record.id.to_string();
+        let result = store
+            .update_one(&id, doc! { "$set": { "deleted": deleted } })
+            .await;
+
+        if let Err(e) = &result {
+            error!("Could not set deleted to {deleted} on connection model definition {id}: {e}");
+        }
+
+        results.push(BatchUpdateResult {
+            id: Some(id),
+            success: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        });
+    }
+
+    Ok(results)
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -362,32 +1326,138 @@ pub async fn test_connection_model_definition(
     Extension(access): Extension<Arc<EventAccess>>,
     Path(id): Path<String>,
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<TestConnectionPayload>,
+    body: Bytes,
 ) -> Result<Json<ServerResponse<TestConnectionResponse>>, PicaError> {
-    let connection = match state
+    state.config.json_body_limits.check(&body)?;
+    let CheckedJson(payload) = CheckedJson::<TestConnectionPayload>::from_bytes(&body)?;
+
+    let connection = get_test_connection(&state, &access, &payload.connection_key).await?;
+    let secret = get_test_secret(&state, &connection).await?;
+
+    let response = run_test_connection(&state, &connection, &secret, id, payload.request).await?;
+
+    Ok(Json(ServerResponse::new(
+        "connection_model_definition",
+        response,
+    )))
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTestConnectionPayload {
+    pub connection_key: String,
+    pub tests: Vec<BatchTestConnectionItem>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTestConnectionItem {
+    pub id: String,
+    pub request: TestConnectionRequest,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BatchTestConnectionResult {
+    pub id: String,
+    pub success: bool,
+    pub result: Option<TestConnectionResponse>,
+    pub error: Option<String>,
+}
+
+/// Tests several inactive definitions against one connection. The secret is
+/// decrypted once for the whole batch and results keep the order of `tests`.
+pub async fn test_connection_model_definitions(
+    Extension(access): Extension<Arc<EventAccess>>,
+    State(state): State<Arc<AppState>>,
+    body: Bytes,
+) -> Result<Json<ServerResponse<Vec<BatchTestConnectionResult>>>, PicaError> {
+    state.config.json_body_limits.check(&body)?;
+    let CheckedJson(payload) = CheckedJson::<BatchTestConnectionPayload>::from_bytes(&body)?;
+
+    let connection = get_test_connection(&state, &access, &payload.connection_key).await?;
+    let secret = get_test_secret(&state, &connection).await?;
+
+    tracing::info!(
+        "Testing {} connection model definitions for connection {}",
+        payload.tests.len(),
+        connection.key
+    );
+
+    let results = stream::iter(payload.tests)
+        .map(|test| {
+            let (state, connection, secret) = (&state, &connection, &secret);
+
+            async move {
+                match run_test_connection(state, connection, secret, test.id.clone(), test.request)
+                    .await
+                {
+                    Ok(result) => BatchTestConnectionResult {
+                        id: test.id,
+                        success: true,
+                        result: Some(result),
+                        error: None,
+                    },
+                    Err(e) => BatchTestConnectionResult {
+                        id: test.id,
+                        success: false,
+                        result: None,
+                        error: Some(e.to_string()),
+                    },
+                }
+            }
+        })
+        .buffered(state.config.test_connection_batch_concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(Json(ServerResponse::new("batch_test_connection", results)))
+}
+
+async fn get_test_connection(
+    state: &AppState,
+    access: &EventAccess,
+    connection_key: &str,
+) -> Result<Connection, PicaError> {
+    match state
         .app_stores
         .connection
         .get_one(doc! {
-            "key": &payload.connection_key,
+            "key": connection_key,
             "ownership.buildableId": access.ownership.id.as_ref(),
             "deleted": false
         })
         .await
     {
-        Ok(Some(data)) => data,
-        Ok(None) => {
-            return Err(ApplicationError::not_found(
-                &format!("Connection with key {} not found", payload.connection_key),
-                None,
-            ));
-        }
+        Ok(Some(data)) => Ok(data),
+        Ok(None) => Err(ApplicationError::not_found(
+            &format!("Connection with key {} not found", connection_key),
+            None,
+        )),
         Err(e) => {
             error!("Error fetching connection in testing endpoint: {:?}", e);
 
-            return Err(e);
+            Err(e)
         }
-    };
+    }
+}
+
+async fn get_test_secret(state: &AppState, connection: &Connection) -> Result<Value, PicaError> {
+    connection_secret(state.secrets_client.as_ref(), connection)
+        .await
+        .map_err(|e| {
+            error!("Error decripting secret for connection: {:?}", e);
+
+            e
+        })
+}
 
+async fn run_test_connection(
+    state: &AppState,
+    connection: &Connection,
+    secret: &Value,
+    id: String,
+    request: TestConnectionRequest,
+) -> Result<TestConnectionResponse, PicaError> {
     let connection_model_definition = match state
         .app_stores
         .model_config
@@ -415,19 +1485,7 @@ pub async fn test_connection_model_definition(
         }
     };
 
-    let secret_result = state
-        .secrets_client
-        .get(&connection.secrets_service_id, &connection.ownership.id)
-        .await
-        .map_err(|e| {
-            error!("Error decripting secret for connection: {:?}", e);
-
-            e
-        })?;
-
-    let mut secret_result = secret_result.as_value()?;
-
-    let request_string: String = serde_json::to_string(&payload.request.clone()).map_err(|e| {
+    let request_string: String = serde_json::to_string(&request).map_err(|e| {
         error!(
             "Error converting request to json string in testing endpoint: {:?}",
             e
@@ -436,23 +1494,56 @@ pub async fn test_connection_model_definition(
         InternalError::script_error("Could not serialize request payload", None)
     })?;
 
+    let mut secret_result = secret.clone();
+    let mut path_params = request.path_params.unwrap_or_default();
+    let mut query_params = request.query_params.unwrap_or_default();
+
+    let mut definition = connection_model_definition.clone();
+    let PlatformInfo::Api(ref mut api_config) = definition.platform_info;
+
+    // Path placeholders not given as path params can be given as query params
+    // instead, which then are not sent as such
+    let from_query: Vec<_> = fill_path_placeholders(&api_config.path, |_| None)
+        .1
+        .into_iter()
+        .filter(|name| !path_params.contains_key(name))
+        .filter_map(|name| query_params.remove_entry(&name))
+        .collect();
+    path_params.extend(from_query);
+
     // Add path params to template context
-    if let Some(path_params) = payload.request.path_params {
-        for (key, val) in path_params {
-            secret_result[key] = Value::String(val);
-        }
+    for (key, val) in path_params {
+        secret_result[key] = Value::String(val);
     }
 
-    let request_body_vec = payload
-        .request
-        .body
-        .map(|body| body.to_string().into_bytes());
+    let (path, missing) =
+        fill_path_placeholders(&api_config.path, |name| match secret_result.get(name)? {
+            Value::String(value) => Some(value.clone()),
+            value @ (Value::Number(_) | Value::Bool(_)) => Some(value.to_string()),
+            _ => None,
+        });
+
+    if !missing.is_empty() {
+        return Err(ApplicationError::unprocessable_entity(
+            &format!(
+                "Path {} has placeholders with no value: {}",
+                api_config.path,
+                missing.join(", ")
+            ),
+            None,
+        )
+        .set_meta(&json!({ "missing": missing })));
+    }
+
+    api_config.path = path;
+
+    let request_body_vec = request.body.map(|body| body.to_string().into_bytes());
     let model_execution_result = state
         .extractor_caller
         .execute_model_definition(
-            &Arc::new(connection_model_definition.clone()),
-            payload.request.headers.unwrap_or_default(),
-            &payload.request.query_params.unwrap_or(HashMap::new()),
+            &Arc::new(definition),
+            request.headers.unwrap_or_default(),
+            &Vec::from_iter(query_params),
             &Arc::new(secret_result),
             request_body_vec,
         )
@@ -515,6 +1606,13 @@ pub async fn test_connection_model_definition(
             e
         })?;
 
+    notify_test_connection_status_change(
+        state,
+        &connection_model_definition,
+        &connection_model_definition.test_connection_status,
+        &status,
+    );
+
     let response = TestConnectionResponse {
         code: status_code,
         status,
@@ -530,19 +1628,36 @@ pub async fn test_connection_model_definition(
         },
     };
 
-    Ok(Json(ServerResponse::new(
-        "connection_model_definition",
-        response,
-    )))
+    Ok(response)
 }
 
 
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Dummy)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestSchemas {
+    pub create_request: RootSchema,
+    pub partial_update_request: RootSchema,
+}
+
+/// JSON Schemas of the payloads accepted by `POST` and `PATCH` on the
+/// connection model definitions, for clients validating a definition before
+/// sending it. Methods are encoded as their name and headers as a map of
+/// names to values, the extractor config fields sit at the top level.
+pub async fn get_request_schemas() -> Json<RequestSchemas> {
+    Json(RequestSchemas {
+        create_request: schema_for!(CreateRequest),
+        partial_update_request: schema_for!(PartialUpdateRequest),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Dummy, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateRequest {
     #[serde(rename = "_id")]
+    #[schemars(with = "Option<String>")]
     pub id: Option<Id>,
     pub connection_platform: String,
+    #[schemars(with = "String")]
     pub connection_definition_id: Id,
     pub platform_version: String,
     pub title: String,
@@ -553,12 +1668,14 @@ pub struct CreateRequest {
     pub auth_method: AuthMethod,
     pub action_name: CrudAction,
     #[serde(with = "http_serde_ext_ios::method", rename = "action")]
+    #[schemars(with = "String")]
     pub http_method: http::Method,
     #[serde(
         with = "http_serde_ext_ios::header_map::option",
         skip_serializing_if = "Option::is_none",
         default
     )]
+    #[schemars(with = "Option<BTreeMap<String, String>>")]
     pub headers: Option<HeaderMap>,
     pub query_params: Option<BTreeMap<String, String>>,
     #[serde(flatten, skip_serializing_if = "Option::is_none")]
@@ -566,6 +1683,7 @@ pub struct CreateRequest {
     pub schemas: SchemasInput,
     pub samples: SamplesInput,
     pub responses: Vec<ResponseBody>,
+    #[schemars(with = "String")]
     pub version: Version, // the event-inc-version
     pub is_default_crud_mapping: Option<bool>,
     pub test_connection_payload: Option<Value>,
@@ -576,6 +1694,23 @@ pub struct CreateRequest {
     pub active: Option<bool>,
     pub knowledge: Option<String>,
     pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[dummy(default)]
+    pub request_transform: Option<RequestTransform>,
+}
+
+impl CreateRequest {
+    /// Key of the definition, derived from the fields that identify it
+    pub fn key(&self) -> String {
+        derive_key(
+            &self.connection_platform,
+            &self.platform_version,
+            &self.model_name,
+            &self.action_name,
+            &self.path,
+            &self.name,
+        )
+    }
 }
 
 impl HookExt<ConnectionModelDefinition> for CreateRequest {}
@@ -585,16 +1720,7 @@ impl RequestExt for CreateRequest {
     type Output = ConnectionModelDefinition;
 
     fn from(&self) -> Option<Self::Output> {
-        let key = format!(
-            "api::{}::{}::{}::{}::{}::{}",
-            self.connection_platform,
-            self.platform_version,
-            self.model_name,
-            self.action_name,
-            self.path,
-            self.name
-        )
-        .to_lowercase();
+        let key = self.key();
 
         let mut record = Self::Output {
             id: self
@@ -629,6 +1755,9 @@ impl RequestExt for CreateRequest {
             record_metadata: Default::default(),
             supported: self.supported.unwrap_or(false),
             knowledge: self.knowledge.clone(),
+            lifecycle: None,
+            lifecycle_transition: None,
+            request_transform: self.request_transform.clone(),
         };
         record.record_metadata.version = self.version.clone();
 
@@ -640,16 +1769,7 @@ impl RequestExt for CreateRequest {
     }
 
     fn update(&self, mut record: Self::Output) -> Self::Output {
-        let key = format!(
-            "api::{}::{}::{}::{}::{}::{}",
-            self.connection_platform,
-            self.platform_version,
-            self.model_name,
-            self.action_name,
-            self.path,
-            self.name
-        )
-        .to_lowercase();
+        let key = self.key();
 
         tracing::info!("Regenerating key for connection model definition. Old key: {}, New key: {}", record.key, key);
         if record.key != key {
@@ -682,6 +1802,7 @@ impl RequestExt for CreateRequest {
         record.mapping.clone_from(&self.mapping);
         record.extractor_config.clone_from(&self.extractor_config);
         record.knowledge.clone_from(&self.knowledge);
+        record.request_transform.clone_from(&self.request_transform);
         record.record_metadata.version.clone_from(&self.version);
 
         if let Some(tags) = &self.tags {
@@ -721,17 +1842,75 @@ pub struct ActionItem {
     pub platform: String,
 }
 
+/// Restricts a definition filter to supported definitions. Only published
+/// definitions are kept unless the filter asks for a lifecycle, definitions
+/// without a lifecycle count as published once supported.
+pub fn restrict_to_actionable(filter: &mut Document) {
+    filter.insert("supported", true);
+
+    if !filter.contains_key("lifecycle") {
+        filter.insert(
+            "lifecycle",
+            doc! { "$in": [DefinitionLifecycle::Published.to_string(), null] },
+        );
+    }
+}
+
+pub const SORT_QUERY: &str = "sort";
+
+/// Sort of action listings, so pages stay stable between requests. `sort`
+/// picks an allowed field, descending with a leading `-` (`-createdAt`);
+/// ties and the default order fall back to model and action names, then `_id`.
+pub fn actions_sort(sort: Option<&str>) -> Result<Document, PicaError> {
+    let mut order = doc! {};
+
+    if let Some(sort) = sort.filter(|sort| !sort.is_empty()) {
+        let (field, direction) = match sort.strip_prefix('-') {
+            Some(field) => (field, -1),
+            None => (sort, 1),
+        };
+
+        let field = match field {
+            "title" => "title",
+            "modelName" | "model_name" => "modelName",
+            "createdAt" => "createdAt",
+            _ => {
+                return Err(ApplicationError::bad_request(
+                    &format!(
+                        "Cannot sort by '{field}', expected one of title, modelName or createdAt"
+                    ),
+                    None,
+                ))
+            }
+        };
+
+        order.insert(field, direction);
+    }
+
+    for field in ["modelName", "actionName", "_id"] {
+        if !order.contains_key(field) {
+            order.insert(field, 1);
+        }
+    }
+
+    Ok(order)
+}
+
 pub async fn get_available_actions(
     headers: HeaderMap,
     Path(platform): Path<String>,
     query: Option<Query<BTreeMap<String, String>>>,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<ServerResponse<ReadResponse<ActionItem>>>, PicaError> {
-    let query = shape_mongo_filter(query, None, Some(headers));
+    let mut query = query;
+    let sort = query.as_mut().and_then(|Query(q)| q.remove(SORT_QUERY));
+    let sort = actions_sort(sort.as_deref())?;
+
+    let query = shape_mongo_filter(query, None, Some(headers)).with_page_size(&state.config);
 
     let mut filter = query.filter;
     filter.insert("connectionPlatform", platform.clone());
-    filter.insert("supported", true);
+    restrict_to_actionable(&mut filter);
 
     let store = state.app_stores.model_config.clone();
 
@@ -741,7 +1920,7 @@ pub async fn get_available_actions(
     let find = store.get_many(
         Some(filter),
         None,
-        None,
+        Some(sort),
         Some(query.limit),
         Some(query.skip),
     );
@@ -758,12 +1937,12 @@ pub async fn get_available_actions(
                 })
                 .collect();
 
-            ReadResponse {
-                rows: action_items,
-                skip: query.skip,
-                limit: query.limit,
+            ReadResponse::new(
+                action_items,
                 total,
-            }
+                query.skip,
+                query.limit,
+            )
         }
         Err(e) => {
             error!("Error reading from store: {e}");
@@ -773,3 +1952,127 @@ pub async fn get_available_actions(
 
     Ok(Json(ServerResponse::new("Available Actions", res)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{seq::SliceRandom, Rng};
+    use std::collections::HashMap;
+    use strum::IntoEnumIterator;
+
+    /// Fields that collided before they were escaped: separators inside a
+    /// field, case differences and the escape character itself
+    const TRICKY_FIELDS: [&str; 9] = ["", "a", "A", ":", "::", "a:", ":a", "%", "%3A"];
+
+    #[test]
+    fn keys_of_plain_lower_case_fields_are_unchanged() {
+        assert_eq!(
+            derive_key(
+                "shopify",
+                "2024-01",
+                "products",
+                &CrudAction::GetMany,
+                "/products/{id}.json",
+                "listproducts"
+            ),
+            "api::shopify::2024-01::products::getmany::/products/{id}.json::listproducts"
+        );
+        assert_eq!(
+            derive_key("s", "v1", "Hotels", &CrudAction::Custom, "/a:b", "ünï"),
+            "api::s::v1::%48otels::custom::/a%3Ab::%C3%BCn%C3%AF"
+        );
+    }
+
+    #[test]
+    fn legacy_keys_are_recovered_from_escaped_keys() {
+        let fields = [
+            (
+                "shopify",
+                "2024-01",
+                "Products",
+                "/products/{productId}.json",
+                "listProducts",
+            ),
+            ("s", "v1", "Hotels", "/a:b", "ünï"),
+            ("%3A", "", ":", "%", "::"),
+        ];
+
+        for (platform, version, model_name, path, name) in fields {
+            let legacy = format!(
+                "api::{platform}::{version}::{model_name}::{}::{path}::{name}",
+                CrudAction::GetMany
+            )
+            .to_lowercase();
+            let key = derive_key(
+                platform,
+                version,
+                model_name,
+                &CrudAction::GetMany,
+                path,
+                name,
+            );
+
+            assert_eq!(legacy_key(&key), legacy, "legacy key of {key}");
+        }
+    }
+
+    #[test]
+    fn distinct_fields_never_share_a_key() {
+        let mut keys: HashMap<String, Vec<String>> = HashMap::new();
+
+        for platform in TRICKY_FIELDS {
+            for model_name in TRICKY_FIELDS {
+                for path in TRICKY_FIELDS {
+                    for name in TRICKY_FIELDS {
+                        for action_name in CrudAction::iter() {
+                            let fields = vec![
+                                platform.to_string(),
+                                model_name.to_string(),
+                                action_name.to_string(),
+                                path.to_string(),
+                                name.to_string(),
+                            ];
+                            let key =
+                                derive_key(platform, "v1", model_name, &action_name, path, name);
+
+                            if let Some(other) = keys.insert(key.clone(), fields.clone()) {
+                                panic!("{fields:?} and {other:?} share the key {key}");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_fields_never_share_a_key() {
+        let alphabet = ['a', 'B', ':', '%', '3', '/', 'é', '{'];
+        let mut rng = rand::thread_rng();
+        let mut field = || {
+            (0..rng.gen_range(0..5))
+                .map(|_| *alphabet.choose(&mut rng).expect("alphabet"))
+                .collect::<String>()
+        };
+
+        let mut keys: HashMap<String, [String; 5]> = HashMap::new();
+        for _ in 0..20_000 {
+            let fields = [field(), field(), field(), field(), field()];
+            let key = derive_key(
+                &fields[0],
+                &fields[1],
+                &fields[2],
+                &CrudAction::GetOne,
+                &fields[3],
+                &fields[4],
+            );
+
+            match keys.get(&key) {
+                Some(other) => assert_eq!(other, &fields, "distinct fields share the key {key}"),
+                None => {
+                    keys.insert(key, fields);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,577 @@
+use super::{
+    connection_model_definition::{get_definitions_by_key, CreateRequest},
+    RequestExt,
+};
+use crate::{router::ServerResponse, server::AppState};
+use axum::extract::{Query, State};
+use axum::Json;
+use chrono::Utc;
+use mongodb::bson::doc;
+use osentities::{
+    api_model_config::{SamplesInput, SchemasInput},
+    connection_model_definition::ConnectionModelDefinition,
+    id::{prefix::IdPrefix, Id},
+    json_schema::JsonSchema,
+    record_metadata::RecordMetadata,
+    request_transform::RequestTransform,
+    ApplicationError, InternalError, PicaError,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tracing::error;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub commit: bool,
+}
+
+/// Body of an import call. The first (dry-run) call carries the `items` to
+/// validate and stage, the `commit=true` call only references the staged bundle.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRequest {
+    #[serde(default)]
+    pub items: Vec<Value>,
+    pub staged_bundle_id: Option<Id>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportAction {
+    Create,
+    Update,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportItemStatus {
+    Valid,
+    Invalid,
+    Committed,
+    Failed,
+    RolledBack,
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportItemReport {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<ImportAction>,
+    pub status: ImportItemStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// A validated bundle waiting for an operator to commit it. Expired bundles
+/// can no longer be committed and are purged on the next staging call.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StagedBundle {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    pub items: Vec<CreateRequest>,
+    pub expires_at: i64,
+    #[serde(flatten, default)]
+    pub record_metadata: RecordMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub staged_bundle_id: Option<Id>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    pub valid: bool,
+    pub committed: bool,
+    pub report: Vec<ImportItemReport>,
+}
+
+/// Two-phase import of connection model definitions. Without `commit` the
+/// bundle is validated as a whole and, when every item is valid, staged for
+/// review. With `commit=true` the staged bundle is re-validated and written;
+/// a failed write rolls back the items already written by that call.
+pub async fn import_bundle(
+    query: Option<Query<ImportQuery>>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ImportRequest>,
+) -> Result<Json<ServerResponse<ImportResponse>>, PicaError> {
+    let commit = query.map(|Query(q)| q.commit).unwrap_or_default();
+
+    let response = if commit {
+        commit_bundle(&state, payload).await?
+    } else {
+        stage_bundle(&state, payload).await?
+    };
+
+    Ok(Json(ServerResponse::new("import", response)))
+}
+
+async fn stage_bundle(
+    state: &AppState,
+    payload: ImportRequest,
+) -> Result<ImportResponse, PicaError> {
+    if payload.items.is_empty() {
+        return Err(ApplicationError::bad_request(
+            "Bundle must contain at least one item",
+            None,
+        ));
+    }
+
+    let now = Utc::now().timestamp_millis();
+    let store = &state.app_stores.staged_bundle;
+
+    if let Err(e) = store
+        .collection
+        .delete_many(doc! { "expiresAt": { "$lte": now } })
+        .await
+    {
+        error!("Could not purge expired staged bundles: {e}");
+    }
+
+    let (items, report, _) = validate_bundle(state, payload.items).await?;
+    let valid = report.iter().all(|r| r.status == ImportItemStatus::Valid);
+
+    if !valid {
+        return Ok(ImportResponse {
+            staged_bundle_id: None,
+            expires_at: None,
+            valid,
+            committed: false,
+            report,
+        });
+    }
+
+    let staged = StagedBundle {
+        id: Id::now(IdPrefix::StagedBundle),
+        items: items.into_iter().flatten().collect(),
+        expires_at: now + state.config.staged_bundle_ttl_secs as i64 * 1000,
+        record_metadata: RecordMetadata::default(),
+    };
+
+    store.create_one(&staged).await.inspect_err(|e| {
+        error!("Could not stage bundle: {e}");
+    })?;
+
+    Ok(ImportResponse {
+        staged_bundle_id: Some(staged.id),
+        expires_at: Some(staged.expires_at),
+        valid,
+        committed: false,
+        report,
+    })
+}
+
+async fn commit_bundle(
+    state: &AppState,
+    payload: ImportRequest,
+) -> Result<ImportResponse, PicaError> {
+    let Some(staged_bundle_id) = payload.staged_bundle_id else {
+        return Err(ApplicationError::bad_request(
+            "stagedBundleId is required to commit a bundle",
+            None,
+        ));
+    };
+
+    let store = &state.app_stores.staged_bundle;
+    let staged = store
+        .get_one(doc! {
+            "_id": staged_bundle_id.to_string(),
+            "expiresAt": { "$gt": Utc::now().timestamp_millis() },
+            "deleted": false,
+        })
+        .await?
+        .ok_or_else(|| ApplicationError::not_found("Staged bundle", None))?;
+
+    // Definitions may have changed since the bundle was staged, so the
+    // actions and references are checked again against the current state
+    let items = staged
+        .items
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            error!("Could not serialize staged bundle items: {e}");
+            InternalError::serialize_error("Could not serialize staged bundle items", None)
+        })?;
+    let (items, mut report, mut existing) = validate_bundle(state, items).await?;
+
+    if report.iter().any(|r| r.status != ImportItemStatus::Valid) {
+        return Ok(ImportResponse {
+            staged_bundle_id: Some(staged.id),
+            expires_at: Some(staged.expires_at),
+            valid: false,
+            committed: false,
+            report,
+        });
+    }
+
+    let model_config = &state.app_stores.model_config;
+    let mut written: Vec<(usize, Id, Option<ConnectionModelDefinition>)> = vec![];
+    let mut failure = None;
+
+    for (index, item) in items.into_iter().flatten().enumerate() {
+        let Some(mut record) = item.from() else {
+            continue;
+        };
+
+        let previous = existing.remove(&record.key);
+        let result = match &previous {
+            Some(previous) => {
+                record.id = previous.id;
+                record.record_metadata.created_at = previous.record_metadata.created_at;
+                record.record_metadata.updated = true;
+                record.record_metadata.updated_at = Utc::now().timestamp_millis();
+
+                model_config
+                    .collection
+                    .replace_one(doc! { "_id": record.id.to_string() }, &record)
+                    .await
+                    .map(|_| ())
+                    .map_err(PicaError::from)
+            }
+            None => model_config.create_one(&record).await,
+        };
+
+        match result {
+            Ok(()) => {
+                report[index].status = ImportItemStatus::Committed;
+                written.push((index, record.id, previous));
+            }
+            Err(e) => {
+                error!("Could not write bundle item {index}: {e}");
+                report[index].status = ImportItemStatus::Failed;
+                report[index].errors.push(e.to_string());
+                failure = Some(index);
+                break;
+            }
+        }
+    }
+
+    if let Some(failed_index) = failure {
+        for (index, id, previous) in written.into_iter().rev() {
+            let result = match previous {
+                Some(previous) => model_config
+                    .collection
+                    .replace_one(doc! { "_id": id.to_string() }, previous)
+                    .await
+                    .map(|_| ()),
+                None => model_config
+                    .collection
+                    .delete_one(doc! { "_id": id.to_string() })
+                    .await
+                    .map(|_| ()),
+            };
+
+            match result {
+                Ok(()) => report[index].status = ImportItemStatus::RolledBack,
+                Err(e) => {
+                    error!("Could not roll back bundle item {index}: {e}");
+                    report[index].errors.push(format!("Rollback failed: {e}"));
+                }
+            }
+        }
+
+        for item in report.iter_mut().skip(failed_index + 1) {
+            item.status = ImportItemStatus::Skipped;
+        }
+
+        return Ok(ImportResponse {
+            staged_bundle_id: Some(staged.id),
+            expires_at: Some(staged.expires_at),
+            valid: true,
+            committed: false,
+            report,
+        });
+    }
+
+    if let Err(e) = store
+        .collection
+        .delete_one(doc! { "_id": staged.id.to_string() })
+        .await
+    {
+        error!(
+            "Could not remove committed staged bundle {}: {e}",
+            staged.id
+        );
+    }
+
+    Ok(ImportResponse {
+        staged_bundle_id: Some(staged.id),
+        expires_at: None,
+        valid: true,
+        committed: true,
+        report,
+    })
+}
+
+/// Validates every item of the bundle without writing anything. Returns the
+/// parsed items (`None` when an item could not be parsed), the per-item report
+/// and the existing definitions the bundle would replace, keyed by key.
+async fn validate_bundle(
+    state: &AppState,
+    items: Vec<Value>,
+) -> Result<
+    (
+        Vec<Option<CreateRequest>>,
+        Vec<ImportItemReport>,
+        HashMap<String, ConnectionModelDefinition>,
+    ),
+    PicaError,
+> {
+    let mut report = Vec::with_capacity(items.len());
+    let mut parsed = Vec::with_capacity(items.len());
+    let mut keys: HashMap<String, usize> = HashMap::new();
+
+    for (index, item) in items.into_iter().enumerate() {
+        let mut item_report = ImportItemReport {
+            index,
+            key: None,
+            action: None,
+            status: ImportItemStatus::Valid,
+            errors: vec![],
+        };
+
+        match serde_json::from_value::<CreateRequest>(item) {
+            Ok(request) => {
+                if let Some(record) = request.from() {
+                    if let Some(first) = keys.get(&record.key) {
+                        item_report
+                            .errors
+                            .push(format!("Duplicate key, already used by item {first}"));
+                    } else {
+                        keys.insert(record.key.clone(), index);
+                    }
+                    item_report.key = Some(record.key);
+                }
+
+                item_report
+                    .errors
+                    .extend(validate_schemas(&request.schemas));
+                item_report
+                    .errors
+                    .extend(validate_samples(&request.schemas, &request.samples));
+                item_report
+                    .errors
+                    .extend(validate_transform(request.request_transform.as_ref()));
+                parsed.push(Some(request));
+            }
+            Err(e) => {
+                item_report.errors.push(format!("Invalid item: {e}"));
+                parsed.push(None);
+            }
+        }
+
+        report.push(item_report);
+    }
+
+    let definition_ids: Vec<String> = parsed
+        .iter()
+        .flatten()
+        .map(|r| r.connection_definition_id.to_string())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let known_definitions: HashSet<String> = state
+        .app_stores
+        .connection_config
+        .get_many(
+            Some(doc! { "_id": { "$in": &definition_ids }, "deleted": false }),
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|d| d.id.to_string())
+        .collect();
+
+    let existing = get_definitions_by_key(state, &keys.keys().collect::<Vec<_>>()).await?;
+
+    for (item_report, request) in report.iter_mut().zip(&parsed) {
+        if let Some(request) = request {
+            let definition_id = request.connection_definition_id.to_string();
+            if !known_definitions.contains(&definition_id) {
+                item_report.errors.push(format!(
+                    "Connection definition {definition_id} does not exist"
+                ));
+            }
+        }
+
+        item_report.action = item_report.key.as_ref().map(|key| {
+            if existing.contains_key(key) {
+                ImportAction::Update
+            } else {
+                ImportAction::Create
+            }
+        });
+
+        if !item_report.errors.is_empty() {
+            item_report.status = ImportItemStatus::Invalid;
+        }
+    }
+
+    Ok((parsed, report, existing))
+}
+
+/// A transform over its limits would fail every request it is applied to, so
+/// it is reported whether or not invalid definitions are rejected
+pub(crate) fn validate_transform(transform: Option<&RequestTransform>) -> Vec<String> {
+    transform
+        .and_then(|transform| transform.check().err())
+        .map(|e| e.to_string())
+        .into_iter()
+        .collect()
+}
+
+pub(crate) fn validate_schemas(schemas: &SchemasInput) -> Vec<String> {
+    [
+        ("headers", &schemas.headers),
+        ("queryParams", &schemas.query_params),
+        ("pathParams", &schemas.path_params),
+        ("body", &schemas.body),
+    ]
+    .into_iter()
+    .filter_map(|(name, schema)| schema.as_ref().map(|schema| (name, schema)))
+    .flat_map(|(name, schema)| validate_schema(name, schema))
+    .collect()
+}
+
+fn validate_schema(name: &str, schema: &JsonSchema) -> Vec<String> {
+    let mut errors = vec![];
+
+    if schema.type_name.trim().is_empty() {
+        errors.push(format!("Schema {name} has no type"));
+    }
+
+    for required in schema.required.iter().flatten() {
+        if !schema.properties.contains_key(required) {
+            errors.push(format!(
+                "Schema {name} requires undeclared property {required}"
+            ));
+        }
+    }
+
+    errors
+}
+
+/// Checks each sample against the schema of the same name, samples without
+/// a schema are not checked
+pub(crate) fn validate_samples(schemas: &SchemasInput, samples: &SamplesInput) -> Vec<String> {
+    let headers = samples.headers.as_ref().map(|headers| {
+        Value::Object(
+            headers
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        Value::String(String::from_utf8_lossy(value.as_bytes()).into_owned()),
+                    )
+                })
+                .collect(),
+        )
+    });
+
+    [
+        ("headers", &schemas.headers, headers.as_ref()),
+        (
+            "queryParams",
+            &schemas.query_params,
+            samples.query_params.as_ref(),
+        ),
+        (
+            "pathParams",
+            &schemas.path_params,
+            samples.path_params.as_ref(),
+        ),
+        ("body", &schemas.body, samples.body.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(name, schema, sample)| Some((name, schema.as_ref()?, sample?)))
+    .flat_map(|(name, schema, sample)| {
+        schema
+            .validate(sample)
+            .into_iter()
+            .map(move |error| format!("Sample {name} does not match its schema: {error}"))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_schema_reports_undeclared_required_properties() {
+        let mut schema = JsonSchema::empty();
+        schema.properties.insert(
+            "hotelId".to_string(),
+            serde_json::from_value(json!({ "type": "string" })).unwrap(),
+        );
+        schema.required = Some(vec!["hotelId".to_string(), "roomId".to_string()]);
+
+        assert_eq!(
+            validate_schema("body", &schema),
+            vec!["Schema body requires undeclared property roomId".to_string()]
+        );
+
+        schema.required = Some(vec!["hotelId".to_string()]);
+        assert!(validate_schema("body", &schema).is_empty());
+    }
+
+    #[test]
+    fn test_validate_samples_against_schemas() {
+        let schemas = SchemasInput {
+            headers: None,
+            query_params: Some(
+                serde_json::from_value(json!({
+                    "type": "object",
+                    "required": ["limit"],
+                    "properties": { "limit": { "type": "integer" } }
+                }))
+                .unwrap(),
+            ),
+            path_params: None,
+            body: None,
+        };
+        let mut samples = SamplesInput {
+            headers: None,
+            query_params: Some(json!({ "limit": 10 })),
+            path_params: Some(json!({ "id": 1 })),
+            body: Some(json!("unchecked")),
+        };
+
+        assert!(validate_samples(&schemas, &samples).is_empty());
+
+        samples.query_params = Some(json!({ "limit": "ten" }));
+        assert_eq!(
+            validate_samples(&schemas, &samples),
+            vec![
+                "Sample queryParams does not match its schema: $.limit should be of type integer"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_schema_requires_type() {
+        let schema = JsonSchema::new(String::new());
+
+        assert_eq!(
+            validate_schema("headers", &schema),
+            vec!["Schema headers has no type".to_string()]
+        );
+    }
+}
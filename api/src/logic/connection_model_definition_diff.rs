@@ -0,0 +1,239 @@
+use crate::{router::ServerResponse, server::AppState};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use mongodb::bson::doc;
+use osentities::{
+    connection_model_definition::ConnectionModelDefinition, ApplicationError, Id, InternalError,
+    PicaError,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::BTreeSet, sync::Arc};
+
+/// Bookkeeping fields that differ between any two records and would only
+/// bury the changes worth reviewing
+const IGNORED_FIELDS: [&str; 6] = [
+    "_id",
+    "createdAt",
+    "updatedAt",
+    "updated",
+    "lastModifiedBy",
+    "changeLog",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiffQuery {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A field present in only one of the definitions, or holding different
+/// values in both. `path` is dotted, with array items as `[index]`, e.g.
+/// `responses[0].statusCode`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DefinitionDiff {
+    pub from: Id,
+    pub to: Id,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Field level diff of two definitions as they are stored, typically a
+/// definition and the new version cloned from it, reviewed before the new
+/// version is marked `supported` or `active`
+pub async fn diff_definitions(
+    Query(query): Query<DiffQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<DefinitionDiff>>, PicaError> {
+    let (from, to) = tokio::try_join!(
+        get_definition(&state, &query.from),
+        get_definition(&state, &query.to)
+    )?;
+
+    let changes = diff_values(&to_value(&from)?, &to_value(&to)?);
+
+    Ok(Json(ServerResponse::new(
+        "diff",
+        DefinitionDiff {
+            from: from.id,
+            to: to.id,
+            changes,
+        },
+    )))
+}
+
+async fn get_definition(
+    state: &AppState,
+    id: &str,
+) -> Result<ConnectionModelDefinition, PicaError> {
+    state
+        .app_stores
+        .model_config
+        .get_one(doc! { "_id": id, "deleted": false })
+        .await?
+        .ok_or_else(|| {
+            ApplicationError::not_found(
+                &format!("Connection model definition with id {id} not found"),
+                None,
+            )
+        })
+}
+
+fn to_value(definition: &ConnectionModelDefinition) -> Result<Value, PicaError> {
+    let mut value = serde_json::to_value(definition)
+        .map_err(|e| InternalError::serialize_error(&e.to_string(), None))?;
+
+    if let Value::Object(fields) = &mut value {
+        for field in IGNORED_FIELDS {
+            fields.remove(field);
+        }
+    }
+
+    Ok(value)
+}
+
+/// Changes turning `from` into `to`, ordered by path. Objects are compared
+/// key by key and arrays item by item, any other pair of differing values is
+/// reported as a single change.
+pub fn diff_values(from: &Value, to: &Value) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    diff_at(String::new(), from, to, &mut changes);
+
+    changes
+}
+
+fn diff_at(path: String, from: &Value, to: &Value, changes: &mut Vec<FieldChange>) {
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => diff_objects(&path, from, to, changes),
+        (Value::Array(from), Value::Array(to)) => {
+            for index in 0..from.len().max(to.len()) {
+                let item_path = format!("{path}[{index}]");
+
+                match (from.get(index), to.get(index)) {
+                    (Some(from), Some(to)) => diff_at(item_path, from, to, changes),
+                    (from, to) => changes.push(change(item_path, from, to)),
+                }
+            }
+        }
+        (from, to) if from != to => changes.push(change(path, Some(from), Some(to))),
+        _ => {}
+    }
+}
+
+fn diff_objects(
+    path: &str,
+    from: &Map<String, Value>,
+    to: &Map<String, Value>,
+    changes: &mut Vec<FieldChange>,
+) {
+    let keys: BTreeSet<&String> = from.keys().chain(to.keys()).collect();
+
+    for key in keys {
+        let field_path = if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        };
+
+        match (from.get(key), to.get(key)) {
+            (Some(from), Some(to)) => diff_at(field_path, from, to, changes),
+            (from, to) => changes.push(change(field_path, from, to)),
+        }
+    }
+}
+
+fn change(path: String, from: Option<&Value>, to: Option<&Value>) -> FieldChange {
+    let kind = match (from, to) {
+        (None, _) => ChangeKind::Added,
+        (_, None) => ChangeKind::Removed,
+        _ => ChangeKind::Changed,
+    };
+
+    FieldChange {
+        path,
+        kind,
+        from: from.cloned(),
+        to: to.cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_marks_added_removed_and_changed_fields() {
+        let from = json!({
+            "baseUrl": "https://api.example.com/v1",
+            "headers": { "accept": "application/json", "x-legacy": "1" },
+            "responses": [{ "statusCode": 200 }],
+            "supported": false,
+        });
+        let to = json!({
+            "baseUrl": "https://api.example.com/v2",
+            "headers": { "accept": "application/json" },
+            "responses": [{ "statusCode": 200 }, { "statusCode": 404 }],
+            "supported": false,
+            "tags": ["beta"],
+        });
+
+        assert_eq!(
+            diff_values(&from, &to),
+            vec![
+                FieldChange {
+                    path: "baseUrl".to_string(),
+                    kind: ChangeKind::Changed,
+                    from: Some(json!("https://api.example.com/v1")),
+                    to: Some(json!("https://api.example.com/v2")),
+                },
+                FieldChange {
+                    path: "headers.x-legacy".to_string(),
+                    kind: ChangeKind::Removed,
+                    from: Some(json!("1")),
+                    to: None,
+                },
+                FieldChange {
+                    path: "responses[1]".to_string(),
+                    kind: ChangeKind::Added,
+                    from: None,
+                    to: Some(json!({ "statusCode": 404 })),
+                },
+                FieldChange {
+                    path: "tags".to_string(),
+                    kind: ChangeKind::Added,
+                    from: None,
+                    to: Some(json!(["beta"])),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_of_equal_values_is_empty() {
+        let value = json!({ "path": "/customers", "queryParams": { "limit": "10" } });
+
+        assert!(diff_values(&value, &value).is_empty());
+    }
+}
@@ -71,7 +71,8 @@ where
             e
         }),
         None,
-    );
+    )
+    .with_page_size(&state.config);
 
     query.filter.remove("ownership.buildableId");
     query.filter.remove("environment");
@@ -88,12 +89,7 @@ where
     );
 
     let res = match try_join!(count, find) {
-        Ok((total, rows)) => ReadResponse {
-            rows,
-            skip: query.skip,
-            limit: query.limit,
-            total,
-        },
+        Ok((total, rows)) => ReadResponse::new(rows, total, query.skip, query.limit),
         Err(e) => {
             error!("Error reading from store: {e}");
             return Err(e);
@@ -0,0 +1,480 @@
+use super::{
+    connection::{create_connection, delete_connection, CreateConnectionPayload},
+    connection_model_definition::{
+        check_definition, get_definitions_by_key, CreateRequest as DefinitionRequest,
+    },
+    connection_variable_mapping::{
+        evict_cached_mappings, BindingRequest, CreateRequest as MappingRequest,
+    },
+    RequestExt,
+};
+use crate::{router::ServerResponse, server::AppState};
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use mongodb::bson::{self, doc};
+use osentities::{
+    configuration::environment::Environment,
+    connection_model_definition::ConnectionModelDefinition,
+    connection_variable_mapping::ConnectionVariableMapping,
+    domain::connection::SanitizedConnection, event_access::EventAccess, id::Id, InternalError,
+    PicaError,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, sync::Arc};
+use tracing::error;
+
+/// Everything a newly connected account needs: the connection, the model
+/// definitions it calls and the variable mappings of those definitions.
+/// Definitions are shared by every account of a platform, so only stored
+/// ones can be provisioned, while mappings are created for the caller only.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvisionRequest {
+    pub connection: CreateConnectionPayload,
+    /// Either `{ "key": ... }` referencing a stored definition, or a complete
+    /// definition whose key is stored, which reuses the stored one
+    #[serde(default)]
+    pub definitions: Vec<Value>,
+    #[serde(default)]
+    pub mappings: Vec<ProvisionMapping>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvisionMapping {
+    /// Key of one of the definitions of the request
+    pub definition_key: String,
+    pub bindings: Vec<BindingRequest>,
+    #[serde(default)]
+    pub environment: Option<Environment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProvisionItemKind {
+    Connection,
+    Definition,
+    Mapping,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProvisionItemStatus {
+    Valid,
+    Invalid,
+    Created,
+    Reused,
+    Failed,
+    RolledBack,
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvisionItemReport {
+    pub kind: ProvisionItemKind,
+    /// Position of the item among the items of its kind
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    pub status: ProvisionItemStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl ProvisionItemReport {
+    fn new(kind: ProvisionItemKind, index: usize) -> Self {
+        Self {
+            kind,
+            index,
+            key: None,
+            id: None,
+            status: ProvisionItemStatus::Valid,
+            errors: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvisionResponse {
+    pub provisioned: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection: Option<SanitizedConnection>,
+    /// The connection first, then the definitions and the mappings in the
+    /// order they were sent
+    pub report: Vec<ProvisionItemReport>,
+}
+
+#[derive(Debug, Clone)]
+enum ProvisionDefinition {
+    Reference(String),
+    Inline(Box<DefinitionRequest>),
+}
+
+impl ProvisionDefinition {
+    fn parse(item: Value) -> Result<Self, String> {
+        match item.as_object() {
+            Some(object) if object.len() == 1 => match object.get("key") {
+                Some(Value::String(key)) => Ok(Self::Reference(key.clone())),
+                _ => Err("A reference must be an object with a single key".to_string()),
+            },
+            _ => serde_json::from_value(item)
+                .map(|request| Self::Inline(Box::new(request)))
+                .map_err(|e| format!("Invalid definition: {e}")),
+        }
+    }
+
+    fn key(&self) -> String {
+        match self {
+            Self::Reference(key) => key.clone(),
+            Self::Inline(request) => request.key(),
+        }
+    }
+}
+
+/// Creates the connection and the mappings of the request in one call,
+/// resolving its definitions and reporting the outcome of each. The whole
+/// request is validated before anything is written. The connection is
+/// created first, so invalid credentials leave nothing behind, and a mapping
+/// that cannot be written rolls back what this call created, including the
+/// connection. Stored mappings of the caller are reused and never rolled
+/// back.
+pub async fn provision_connection(
+    Extension(access): Extension<Arc<EventAccess>>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ProvisionRequest>,
+) -> Result<Json<ServerResponse<ProvisionResponse>>, PicaError> {
+    let definition_count = payload.definitions.len();
+    let (definitions, existing, mut report) = validate_provision(&state, &payload).await?;
+
+    let response = |report, connection| {
+        Json(ServerResponse::new(
+            "provision",
+            ProvisionResponse {
+                provisioned: false,
+                connection,
+                report,
+            },
+        ))
+    };
+
+    if report
+        .iter()
+        .any(|item| item.status == ProvisionItemStatus::Invalid)
+    {
+        return Ok(response(report, None));
+    }
+
+    let connection = match create_connection(
+        Extension(access.clone()),
+        State(state.clone()),
+        Json(payload.connection),
+    )
+    .await
+    {
+        Ok(Json(connection)) => connection,
+        Err(e) => {
+            report[0].status = ProvisionItemStatus::Failed;
+            report[0].errors.push(e.to_string());
+            skip_after(&mut report, 0);
+
+            return Ok(response(report, None));
+        }
+    };
+    report[0].status = ProvisionItemStatus::Created;
+    report[0].id = Some(connection.id);
+
+    // Definitions of the request by key, with the platform their mappings
+    // belong to
+    let mut resolved: HashMap<String, (Id, String)> = HashMap::new();
+    for (index, definition) in definitions.iter().enumerate() {
+        let Some(key) = definition.as_ref().map(ProvisionDefinition::key) else {
+            continue;
+        };
+        let Some(stored) = existing.get(&key) else {
+            continue;
+        };
+        report[index + 1].status = ProvisionItemStatus::Reused;
+        report[index + 1].id = Some(stored.id);
+        resolved.insert(key, (stored.id, stored.connection_platform.clone()));
+    }
+
+    let mut created = vec![];
+    let mut failure = None;
+
+    for (index, mapping) in payload.mappings.iter().enumerate() {
+        let position = index + 1 + definition_count;
+
+        match provision_mapping(&state, &access, mapping, &resolved).await {
+            Ok((id, true)) => {
+                report[position].status = ProvisionItemStatus::Created;
+                report[position].id = Some(id);
+                created.push((position, id));
+            }
+            Ok((id, false)) => {
+                report[position].status = ProvisionItemStatus::Reused;
+                report[position].id = Some(id);
+            }
+            Err(e) => {
+                report[position].status = ProvisionItemStatus::Failed;
+                report[position].errors.push(e.to_string());
+                failure = Some(position);
+                break;
+            }
+        }
+    }
+
+    let Some(failed_position) = failure else {
+        return Ok(Json(ServerResponse::new(
+            "provision",
+            ProvisionResponse {
+                provisioned: true,
+                connection: Some(connection),
+                report,
+            },
+        )));
+    };
+
+    skip_after(&mut report, failed_position);
+
+    for (position, id) in created.into_iter().rev() {
+        let result = match state
+            .app_stores
+            .connection_variable_mapping
+            .collection
+            .find_one_and_delete(doc! { "_id": id.to_string() })
+            .await
+        {
+            Ok(Some(mapping)) => {
+                evict_cached_mappings(&state, &mapping.connection_model_definition_id).await;
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => report[position].status = ProvisionItemStatus::RolledBack,
+            Err(e) => {
+                error!("Could not roll back provisioned mapping {id}: {e}");
+                report[position]
+                    .errors
+                    .push(format!("Rollback failed: {e}"));
+            }
+        }
+    }
+
+    match delete_connection(
+        Extension(access),
+        Path(connection.id.to_string()),
+        State(state),
+    )
+    .await
+    {
+        Ok(_) => report[0].status = ProvisionItemStatus::RolledBack,
+        Err(e) => {
+            error!(
+                "Could not roll back provisioned connection {}: {e}",
+                connection.id
+            );
+            report[0].errors.push(format!("Rollback failed: {e}"));
+        }
+    }
+
+    Ok(response(report, None))
+}
+
+/// Creates the mapping for the caller unless they already have one for the
+/// definition and environment. Returns its id and whether it was created.
+async fn provision_mapping(
+    state: &AppState,
+    access: &Arc<EventAccess>,
+    mapping: &ProvisionMapping,
+    resolved: &HashMap<String, (Id, String)>,
+) -> Result<(Id, bool), PicaError> {
+    let (definition_id, platform) = resolved.get(&mapping.definition_key).ok_or_else(|| {
+        InternalError::key_not_found(
+            &format!("Definition {} was not provisioned", mapping.definition_key),
+            None,
+        )
+    })?;
+
+    let environment = bson::to_bson(&mapping.environment).map_err(|e| {
+        error!("Could not serialize mapping environment: {e}");
+        InternalError::serialize_error(e.to_string().as_str(), None)
+    })?;
+
+    let store = &state.app_stores.connection_variable_mapping;
+    if let Some(stored) = store
+        .get_one(doc! {
+            "connectionModelDefinitionId": definition_id.to_string(),
+            "environment": environment,
+            "ownership.buildableId": access.ownership.id.as_ref(),
+            "deleted": false,
+        })
+        .await?
+    {
+        return Ok((stored.id, false));
+    }
+
+    let request = MappingRequest {
+        id: None,
+        connection_model_definition_id: *definition_id,
+        connection_platform: platform.clone(),
+        bindings: mapping.bindings.clone(),
+        environment: mapping.environment,
+        content_type: None,
+    };
+    let Some(mut record) = request.access(access.clone()) else {
+        return Err(InternalError::invalid_argument(
+            "Could not build the mapping",
+            None,
+        ));
+    };
+    record.environment = mapping.environment;
+
+    store.create_one(&record).await?;
+    evict_cached_mappings(state, definition_id).await;
+
+    Ok((record.id, true))
+}
+
+/// Validates the whole request without writing anything. Returns the parsed
+/// definitions (`None` when one could not be parsed), the stored definitions
+/// the request references or repeats, keyed by key, and the report.
+async fn validate_provision(
+    state: &AppState,
+    payload: &ProvisionRequest,
+) -> Result<
+    (
+        Vec<Option<ProvisionDefinition>>,
+        HashMap<String, ConnectionModelDefinition>,
+        Vec<ProvisionItemReport>,
+    ),
+    PicaError,
+> {
+    let mut report = vec![ProvisionItemReport::new(ProvisionItemKind::Connection, 0)];
+    let mut definitions = Vec::with_capacity(payload.definitions.len());
+    let mut keys: HashMap<String, usize> = HashMap::new();
+
+    for (index, item) in payload.definitions.iter().cloned().enumerate() {
+        let mut item_report = ProvisionItemReport::new(ProvisionItemKind::Definition, index);
+
+        match ProvisionDefinition::parse(item) {
+            Ok(definition) => {
+                let key = definition.key();
+                if let Some(first) = keys.get(&key) {
+                    item_report
+                        .errors
+                        .push(format!("Duplicate key, already used by definition {first}"));
+                } else {
+                    keys.insert(key.clone(), index);
+                }
+                item_report.key = Some(key);
+                definitions.push(Some(definition));
+            }
+            Err(e) => {
+                item_report.errors.push(e);
+                definitions.push(None);
+            }
+        }
+
+        report.push(item_report);
+    }
+
+    let existing = get_definitions_by_key(state, &keys.keys().collect::<Vec<_>>()).await?;
+
+    for (item_report, definition) in report.iter_mut().skip(1).zip(&definitions) {
+        let Some(definition) = definition else {
+            continue;
+        };
+        let key = definition.key();
+
+        match existing.get(&key) {
+            None => item_report.errors.push(format!(
+                "Definition {key} does not exist, definitions are created through the definitions API"
+            )),
+            Some(stored)
+                if stored.connection_definition_id
+                    != payload.connection.connection_definition_id =>
+            {
+                item_report.errors.push(format!(
+                    "Definition belongs to connection definition {}, not to the one of the connection",
+                    stored.connection_definition_id
+                ));
+            }
+            Some(_) => (),
+        }
+
+        if let ProvisionDefinition::Inline(request) = definition {
+            if let Err(e) = check_definition(state, request) {
+                item_report.errors.push(e.to_string());
+            }
+        }
+    }
+
+    for (index, mapping) in payload.mappings.iter().enumerate() {
+        let mut item_report = ProvisionItemReport::new(ProvisionItemKind::Mapping, index);
+        item_report.key = Some(mapping.definition_key.clone());
+
+        if !keys.contains_key(&mapping.definition_key) {
+            item_report.errors.push(format!(
+                "Definition {} is not one of the definitions of the request",
+                mapping.definition_key
+            ));
+        }
+
+        let bindings = mapping
+            .bindings
+            .iter()
+            .map(BindingRequest::binding)
+            .collect::<Vec<_>>();
+        if let Err(e) = ConnectionVariableMapping::check_bindings(&bindings) {
+            item_report.errors.push(e.to_string());
+        }
+
+        report.push(item_report);
+    }
+
+    for item in report.iter_mut() {
+        if !item.errors.is_empty() {
+            item.status = ProvisionItemStatus::Invalid;
+        }
+    }
+
+    Ok((definitions, existing, report))
+}
+
+fn skip_after(report: &mut [ProvisionItemReport], position: usize) {
+    for item in report.iter_mut().skip(position + 1) {
+        item.status = ProvisionItemStatus::Skipped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn definitions_are_referenced_by_key_or_sent_complete() {
+        let definition =
+            ProvisionDefinition::parse(json!({ "key": "api::hotels::v1::rooms" })).unwrap();
+        assert!(
+            matches!(definition, ProvisionDefinition::Reference(ref key) if key == "api::hotels::v1::rooms")
+        );
+
+        assert!(ProvisionDefinition::parse(json!({ "key": 1 })).is_err());
+        assert!(
+            ProvisionDefinition::parse(json!({ "key": "a", "title": "Rooms" }))
+                .unwrap_err()
+                .starts_with("Invalid definition")
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use super::{HookExt, PublicExt, ReadResponse, RequestExt, SuccessResponse};
+use super::{
+    connection_model_definition::TestConnectionRequest,
+    connection_variable_mapping_round_trip::validate_round_trip, is_duplicate_key, parent_filter,
+    HookExt, PublicExt, ReadResponse, RequestExt, SuccessResponse,
+};
 use crate::{
     helper::shape_mongo_filter,
     router::ServerResponse,
@@ -8,29 +12,38 @@ use axum::{
     extract::{Json, Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::{delete, get, patch, post},
+    routing::{get, patch, post, put},
     Extension, Router,
 };
 use bson::doc;
+use cache::local::LocalCacheExt;
 use chrono::Utc;
-use http::HeaderMap;
+use http::{header::CONTENT_TYPE, HeaderMap};
+use mongodb::options::ReturnDocument;
 use osentities::{
-    algebra::MongoStore,
+    algebra::{connection_secret, MongoStore},
     configuration::environment::Environment,
+    connection_model_definition::{ConnectionModelDefinition, PlatformInfo},
     connection_variable_mapping::{
-        ConnectionVariableMapping, InjectionStrategy, ParameterLocation, VariableBinding,
-        VariableDataType,
+        BindingCondition, BodyMerge, ConnectionVariableMapping, InjectionStrategy,
+        ParameterLocation, VariableBinding, VariableDataType,
     },
     event_access::EventAccess,
     id::{prefix::IdPrefix, Id},
     ownership::Ownership,
     record_metadata::RecordMetadata,
-    ApplicationError, InternalError, PicaError,
+    variable_injection::{
+        BodyEncoding, RequestParts, ResolvedRequest as InjectedRequest, UnmetCondition,
+    },
+    ApplicationError, InternalError, PicaError, REDACTED,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{collections::BTreeMap, sync::Arc};
-use tracing::error;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tracing::{error, warn};
 
 pub fn get_router() -> Router<Arc<AppState>> {
     Router::new()
@@ -44,6 +57,119 @@ pub fn get_router() -> Router<Arc<AppState>> {
             patch(update_mapping)  // Custom handler without ownership filtering
                 .delete(delete_mapping), // Custom handler without ownership filtering
         )
+        .route(
+            "/by-definition/:definition_id",
+            put(replace_bindings_by_definition),
+        )
+        .route("/by-platform/:platform", get(read_mappings_by_platform))
+        .route("/round-trip", post(validate_round_trip))
+}
+
+/// A model definition together with every mapping that targets it
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MappingGroup {
+    pub connection_model_definition_id: Id,
+    /// `None` when the model definition no longer exists
+    pub model_name: Option<String>,
+    pub title: Option<String>,
+    pub mappings: Vec<Value>,
+}
+
+/// Returns the mappings of a platform grouped by model definition, so that
+/// everything injected for an integration can be audited at once. Pagination
+/// applies to the mappings, which are sorted by model definition so that a
+/// group is only split across pages when it is larger than the page itself.
+async fn read_mappings_by_platform(
+    access: Option<Extension<Arc<EventAccess>>>,
+    headers: HeaderMap,
+    Path(platform): Path<String>,
+    query: Option<Query<BTreeMap<String, String>>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<ReadResponse<MappingGroup>>>, PicaError> {
+    let mut query_params =
+        shape_mongo_filter(query, access.map(|e| e.0), Some(headers)).with_page_size(&state.config);
+    query_params.filter.insert("connectionPlatform", platform);
+
+    let store = state.app_stores.connection_variable_mapping.clone();
+
+    // Pages hold whole groups, so they are taken from the definitions the
+    // matching mappings are of and `total` counts groups
+    let mut grouped_ids = store
+        .collection
+        .distinct("connectionModelDefinitionId", query_params.filter.clone())
+        .await?
+        .into_iter()
+        .filter_map(|id| id.as_str().map(str::to_string))
+        .collect::<Vec<_>>();
+    grouped_ids.sort();
+
+    let total = grouped_ids.len() as u64;
+    let definition_ids = grouped_ids
+        .into_iter()
+        .skip(query_params.skip as usize)
+        .take(query_params.limit as usize)
+        .collect::<Vec<_>>();
+
+    let mut filter = query_params.filter;
+    filter.insert(
+        "connectionModelDefinitionId",
+        doc! { "$in": &definition_ids },
+    );
+
+    let rows = store
+        .get_many(
+            Some(filter),
+            None,
+            Some(doc! { "connectionModelDefinitionId": 1, "_id": 1 }),
+            None,
+            None,
+        )
+        .await?;
+
+    let definitions = state
+        .app_stores
+        .model_config
+        .get_many(
+            Some(doc! { "_id": { "$in": &definition_ids } }),
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?
+        .into_iter()
+        .map(|definition| (definition.id, definition))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut groups: Vec<MappingGroup> = Vec::new();
+    for row in rows {
+        match groups.last_mut() {
+            Some(group)
+                if group.connection_model_definition_id == row.connection_model_definition_id =>
+            {
+                group.mappings.push(CreateRequest::public(row));
+            }
+            _ => {
+                let definition = definitions.get(&row.connection_model_definition_id);
+                groups.push(MappingGroup {
+                    connection_model_definition_id: row.connection_model_definition_id,
+                    model_name: definition.map(|d| d.model_name.clone()),
+                    title: definition.map(|d| d.title.clone()),
+                    mappings: vec![CreateRequest::public(row)],
+                });
+            }
+        }
+    }
+
+    let res = ReadResponse::new(
+        groups,
+        total,
+        query_params.skip,
+        query_params.limit,
+    );
+
+    Ok(Json(ServerResponse::new("read", res)))
 }
 
 /// Custom read handler that returns ALL platform-level mappings without ownership filtering.
@@ -54,7 +180,7 @@ async fn read_mappings(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<ServerResponse<ReadResponse<Value>>>, PicaError> {
     // Pass None for event_access to bypass ownership filtering
-    let query_params = shape_mongo_filter(query, None, Some(headers));
+    let query_params = shape_mongo_filter(query, None, Some(headers)).with_page_size(&state.config);
 
     let store = state.app_stores.connection_variable_mapping.clone();
 
@@ -70,12 +196,12 @@ async fn read_mappings(
 
     let total = store.count(query_params.filter, None).await?;
 
-    let res = ReadResponse {
-        rows: rows.into_iter().map(CreateRequest::public).collect(),
-        skip: query_params.skip,
-        limit: query_params.limit,
+    let res = ReadResponse::new(
+        rows.into_iter().map(CreateRequest::public).collect(),
         total,
-    };
+        query_params.skip,
+        query_params.limit,
+    );
 
     Ok(Json(ServerResponse::new("read", res)))
 }
@@ -83,10 +209,13 @@ async fn read_mappings(
 /// Custom update handler without ownership filtering.
 /// Platform-level mappings can be updated by any authenticated user.
 async fn update_mapping(
+    access: Option<Extension<Arc<EventAccess>>>,
     Path(id): Path<String>,
     State(state): State<Arc<AppState>>,
     Json(payload): Json<CreateRequest>,
 ) -> Result<Json<ServerResponse<SuccessResponse>>, PicaError> {
+    payload.check()?;
+
     let store = state.app_stores.connection_variable_mapping.clone();
 
     // Platform-level: no ownership filter, just find by ID
@@ -102,7 +231,15 @@ async fn update_mapping(
         ));
     };
 
-    let updated_record = payload.update(record);
+    let updated_record = payload.update(record.clone());
+    if updated_record.connection_model_definition_id != record.connection_model_definition_id {
+        check_model_definition(
+            &state,
+            &updated_record.connection_model_definition_id,
+            access.as_deref().map(Arc::as_ref),
+        )
+        .await?;
+    }
 
     let bson = bson::to_bson_with_options(&updated_record, Default::default()).map_err(|e| {
         error!("Could not serialize record into document: {e}");
@@ -112,6 +249,8 @@ async fn update_mapping(
     let document = doc! { "$set": bson };
 
     store.update_one(&id, document).await?;
+    evict_cached_mappings(&state, &record.connection_model_definition_id).await;
+    evict_cached_mappings(&state, &updated_record.connection_model_definition_id).await;
 
     Ok(Json(ServerResponse::new(
         "update",
@@ -151,19 +290,551 @@ async fn delete_mapping(
             },
         )
         .await?;
+    evict_cached_mappings(&state, &record.connection_model_definition_id).await;
 
     Ok(Json(ServerResponse::new("delete", CreateRequest::public(record))))
 }
 
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceBindingsRequest {
+    pub bindings: Vec<BindingRequest>,
+    /// Environment of the mapping whose bindings are replaced. When left out,
+    /// the definition's only mapping is replaced, or else the one applying to
+    /// every environment.
+    #[serde(default)]
+    pub environment: Option<Environment>,
+}
+
+/// Sets the complete binding list of the mapping of a model definition,
+/// creating the mapping when the definition has none yet. The bindings are
+/// replaced in a single write, so concurrent readers see either the previous
+/// or the new list.
+async fn replace_bindings_by_definition(
+    access: Option<Extension<Arc<EventAccess>>>,
+    Path(definition_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ReplaceBindingsRequest>,
+) -> Result<Json<ServerResponse<Value>>, PicaError> {
+    let stores = &state.app_stores;
+
+    let bindings = payload
+        .bindings
+        .iter()
+        .map(BindingRequest::binding)
+        .collect::<Vec<_>>();
+    ConnectionVariableMapping::check_bindings(&bindings)?;
+
+    let Some(definition) = stores
+        .model_config
+        .get_one(parent_filter(
+            &definition_id,
+            access.as_deref().map(Arc::as_ref),
+        ))
+        .await?
+    else {
+        return Err(ApplicationError::not_found(
+            &format!("Connection model definition with id {definition_id} not found"),
+            None,
+        ));
+    };
+
+    let serialize_error = |e: bson::ser::Error| {
+        error!("Could not serialize mapping into document: {e}");
+        InternalError::serialize_error(e.to_string().as_str(), None)
+    };
+
+    let platform_mappings = doc! {
+        "connectionModelDefinitionId": definition.id.to_string(),
+        "ownership.buildableId": "",
+        "deleted": false,
+    };
+
+    // Without an environment, the definition's only mapping is replaced
+    // whatever its environment, so that a wildcard is not added next to it
+    let environment = match payload.environment {
+        Some(environment) => Some(environment),
+        None => {
+            let existing = stores
+                .connection_variable_mapping
+                .get_many(Some(platform_mappings.clone()), None, None, Some(2), None)
+                .await?;
+
+            match existing.as_slice() {
+                [mapping] => mapping.environment,
+                [] => None,
+                _ if existing.iter().any(|mapping| mapping.environment.is_none()) => None,
+                _ => {
+                    return Err(ApplicationError::conflict(
+                        &format!(
+                            "Model definition {definition_id} has a mapping per environment, \
+                             the environment of the one to replace is required"
+                        ),
+                        None,
+                    ));
+                }
+            }
+        }
+    };
+
+    let mut filter = platform_mappings;
+    filter.insert(
+        "environment",
+        bson::to_bson(&environment).map_err(serialize_error)?,
+    );
+
+    // Everything but what the replacement sets is only written when the
+    // mapping is created
+    let mut on_insert = bson::to_document(
+        &CreateRequest {
+            id: None,
+            connection_model_definition_id: definition.id,
+            connection_platform: definition.connection_platform.clone(),
+            bindings: vec![],
+            environment,
+            content_type: None,
+        }
+        .create_platform_record(),
+    )
+    .map_err(serialize_error)?;
+    for key in ["bindings", "updatedAt", "updated"] {
+        on_insert.remove(key);
+    }
+
+    let update = doc! {
+        "$set": {
+            "bindings": bson::to_bson(&bindings).map_err(serialize_error)?,
+            "updatedAt": Utc::now().timestamp_millis(),
+            "updated": true,
+        },
+        "$setOnInsert": on_insert,
+    };
+
+    let mapping = stores
+        .connection_variable_mapping
+        .collection
+        .find_one_and_update(filter, update)
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| {
+            if is_duplicate_key(&e) {
+                ApplicationError::conflict(
+                    &format!(
+                        "Mapping of model definition {definition_id} was created concurrently"
+                    ),
+                    None,
+                )
+            } else {
+                PicaError::from(e)
+            }
+        })?
+        .ok_or_else(|| InternalError::unknown("Upserted mapping was not returned", None))?;
+    evict_cached_mappings(&state, &definition.id).await;
+
+    Ok(Json(ServerResponse::new(
+        "update",
+        CreateRequest::public(mapping),
+    )))
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestMappingPayload {
+    pub connection_key: String,
+    #[serde(default)]
+    pub request: Option<TestConnectionRequest>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestMappingResponse {
+    pub request: ResolvedRequest,
+    pub response: UpstreamResponse,
+}
+
+/// The request as it leaves the injection step, with the values of secret
+/// bindings replaced by [`REDACTED`]. Authentication added by the definition's
+/// templates is rendered at dispatch time and is not part of it.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedRequest {
+    pub method: String,
+    pub base_url: String,
+    pub path: String,
+    pub headers: BTreeMap<String, String>,
+    /// Keys appended to by repeating them are shown as an array of their values
+    pub query_params: BTreeMap<String, Value>,
+    /// Form bodies are shown as an object of their fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// Conditional bindings left out because the request did not match them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unmet_conditions: Vec<UnmetCondition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamResponse {
+    pub code: u16,
+    pub body: String,
+}
+
+/// Applies the mapping to its model definition using the connection's live
+/// secret and dispatches the resulting request, so that an integration author
+/// can verify injection and connectivity in one call.
+pub async fn test_mapping(
+    Extension(access): Extension<Arc<EventAccess>>,
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<TestMappingPayload>,
+) -> Result<Json<ServerResponse<TestMappingResponse>>, PicaError> {
+    let stores = &state.app_stores;
+
+    let Some(mapping) = stores
+        .connection_variable_mapping
+        .get_one(doc! { "_id": &id, "deleted": false })
+        .await?
+    else {
+        return Err(ApplicationError::not_found(
+            &format!("Mapping with id {id} not found"),
+            None,
+        ));
+    };
+
+    if !mapping.applies_to_owner(&access.ownership.id) {
+        return Err(ApplicationError::not_found(
+            &format!("Mapping with id {id} not found"),
+            None,
+        ));
+    }
+
+    if !mapping.applies_to(access.environment) {
+        return Err(ApplicationError::bad_request(
+            &format!(
+                "Mapping with id {id} does not apply to {} connections",
+                access.environment
+            ),
+            None,
+        ));
+    }
+
+    let Some(connection) = stores
+        .connection
+        .get_one(doc! {
+            "key": &payload.connection_key,
+            "ownership.buildableId": access.ownership.id.as_ref(),
+            "deleted": false
+        })
+        .await?
+    else {
+        return Err(ApplicationError::not_found(
+            &format!("Connection with key {} not found", payload.connection_key),
+            None,
+        ));
+    };
+
+    let Some(mut config) = stores
+        .model_config
+        .get_one(doc! {
+            "_id": mapping.connection_model_definition_id.to_string(),
+            "deleted": false
+        })
+        .await?
+    else {
+        return Err(ApplicationError::not_found(
+            &format!(
+                "Connection model definition {} not found",
+                mapping.connection_model_definition_id
+            ),
+            None,
+        ));
+    };
+
+    let mut secret = connection_secret(state.secrets_client.as_ref(), &connection)
+        .await
+        .inspect_err(|e| error!("Error decrypting secret for connection: {:?}", e))?;
+
+    let injected = inject_test_request(&mut config, &mapping, &mut secret, payload.request)?;
+    let secret_values = injected.secret_values;
+    let unmet_conditions = injected.unmet_conditions;
+    let RequestParts {
+        headers,
+        query_params,
+        body: context,
+        ..
+    } = injected.parts;
+
+    let resolved = ResolvedRequest {
+        method: config.action.to_string(),
+        base_url: config.platform_info.config().base_url.clone(),
+        path: redact(&config.platform_info.config().path, &secret_values),
+        headers: headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    redact(&String::from_utf8_lossy(value.as_bytes()), &secret_values),
+                )
+            })
+            .collect(),
+        query_params: query_params
+            .iter()
+            .fold(BTreeMap::new(), |mut params, (key, value)| {
+                let value = Value::String(redact(value, &secret_values));
+                match params.get_mut(key) {
+                    Some(Value::Array(values)) => values.push(value),
+                    Some(first) => *first = Value::Array(vec![first.take(), value]),
+                    None => {
+                        params.insert(key.clone(), value);
+                    }
+                }
+                params
+            }),
+        body: context
+            .as_deref()
+            .and_then(|body| BodyEncoding::of(&headers).decode(body))
+            .map(|body| redact_value(body, &secret_values)),
+        unmet_conditions,
+    };
+
+    let response = state
+        .extractor_caller
+        .execute_model_definition(&config, headers, &query_params, &secret, context)
+        .await
+        .inspect_err(|e| error!("Error executing mapping test for {id}: {:?}", e))?;
+
+    let code = response.status().as_u16();
+    let body = response.text().await.map_err(|e| {
+        error!("Could not read upstream response of mapping test for {id}: {e}");
+
+        InternalError::unknown("Could not read upstream response", None)
+    })?;
+
+    Ok(Json(ServerResponse::new(
+        "test",
+        TestMappingResponse {
+            request: resolved,
+            response: UpstreamResponse { code, body },
+        },
+    )))
+}
+
+/// Applies the mapping to a test request of the model definition, leaving
+/// the injected path on the definition. Path params of the request are added
+/// to `secret`, which the definition's templates are rendered with.
+pub(crate) fn inject_test_request(
+    config: &mut ConnectionModelDefinition,
+    mapping: &ConnectionVariableMapping,
+    secret: &mut Value,
+    request: Option<TestConnectionRequest>,
+) -> Result<InjectedRequest, PicaError> {
+    let request = request.unwrap_or(TestConnectionRequest {
+        headers: None,
+        query_params: None,
+        path_params: None,
+        body: None,
+    });
+
+    if let (Some(path_params), Some(template_context)) =
+        (request.path_params, secret.as_object_mut())
+    {
+        for (key, val) in path_params {
+            template_context.insert(key, Value::String(val));
+        }
+    }
+
+    let PlatformInfo::Api(ref mut api_config) = config.platform_info;
+    let mut parts = RequestParts {
+        path: std::mem::take(&mut api_config.path),
+        headers: request.headers.unwrap_or_default(),
+        query_params: request
+            .query_params
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
+        body: None,
+    }
+    .with_definition_content_type(api_config.headers.as_ref());
+    parts.body = request
+        .body
+        .map(|body| BodyEncoding::of(&parts.headers).encode(&body));
+
+    if mapping.content_type.is_some() {
+        if let Some(headers) = api_config.headers.as_mut() {
+            headers.remove(CONTENT_TYPE);
+        }
+    }
+
+    let injected = mapping.apply(parts, secret)?;
+    api_config.path.clone_from(&injected.parts.path);
+
+    Ok(injected)
+}
+
+/// Mappings of the given definitions, looked up `batch_size` definitions at a
+/// time so that a large set of definitions is never fetched in one query
+pub async fn get_mappings_of_definitions(
+    store: &MongoStore<ConnectionVariableMapping>,
+    definition_ids: &[String],
+    batch_size: u64,
+) -> Result<Vec<ConnectionVariableMapping>, PicaError> {
+    let mut mappings = Vec::new();
+
+    for batch in definition_ids.chunks(batch_size.max(1) as usize) {
+        let page = store
+            .get_many(
+                Some(doc! {
+                    "connectionModelDefinitionId": { "$in": batch },
+                    "deleted": false,
+                }),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        mappings.extend(page);
+    }
+
+    Ok(mappings)
+}
+
+/// Mappings of the given definitions, only the definitions whose mappings are
+/// not cached being looked up. Those are cached afterwards, definitions
+/// without mappings included.
+pub async fn get_cached_mappings_of_definitions(
+    state: &AppState,
+    definition_ids: &[Id],
+) -> Result<Vec<ConnectionVariableMapping>, PicaError> {
+    let cache = &state.extractor_caller.connection_variable_mappings_cache;
+
+    let mut mappings = Vec::new();
+    let mut uncached = HashMap::new();
+    for id in definition_ids {
+        match cache.get(id).await? {
+            Some(cached) => mappings.extend(cached),
+            None => {
+                uncached.insert(*id, Vec::new());
+            }
+        }
+    }
+
+    if uncached.is_empty() {
+        return Ok(mappings);
+    }
+
+    let uncached_ids = uncached.keys().map(Id::to_string).collect::<Vec<_>>();
+    for mapping in get_mappings_of_definitions(
+        &state.app_stores.connection_variable_mapping,
+        &uncached_ids,
+        state.config.max_page_size,
+    )
+    .await?
+    {
+        if let Some(of_definition) = uncached.get_mut(&mapping.connection_model_definition_id) {
+            of_definition.push(mapping);
+        }
+    }
+
+    for (id, of_definition) in uncached {
+        cache.insert(&id, &of_definition).await?;
+        mappings.extend(of_definition);
+    }
+
+    Ok(mappings)
+}
+
+/// Drops the cached mappings of a model definition, so that requests made with
+/// it right after a mapping is written already apply it
+pub(crate) async fn evict_cached_mappings(state: &AppState, connection_model_definition_id: &Id) {
+    if let Err(e) = state
+        .extractor_caller
+        .connection_variable_mappings_cache
+        .remove(connection_model_definition_id)
+        .await
+    {
+        warn!(
+            "Failed to evict mappings of definition {} from cache: {:?}",
+            connection_model_definition_id, e
+        );
+    }
+}
+
+fn redact(text: &str, values: &[String]) -> String {
+    values
+        .iter()
+        .fold(text.to_string(), |text, value| text.replace(value, REDACTED))
+}
+
+fn redact_value(value: Value, values: &[String]) -> Value {
+    match value {
+        Value::String(s) => Value::String(redact(&s, values)),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| redact_value(item, values))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, item)| (key, redact_value(item, values)))
+                .collect(),
+        ),
+        scalar if values.contains(&scalar.to_string()) => Value::String(REDACTED.to_string()),
+        scalar => scalar,
+    }
+}
+
+/// Fails with a 404 when the model definition a mapping is of does not exist
+/// for the caller, so no mapping is written without its parent
+async fn check_model_definition(
+    state: &AppState,
+    id: &Id,
+    access: Option<&EventAccess>,
+) -> Result<(), PicaError> {
+    let exists = state
+        .app_stores
+        .model_config
+        .count(parent_filter(&id.to_string(), access), Some(1))
+        .await?
+        > 0;
+
+    if exists {
+        Ok(())
+    } else {
+        Err(ApplicationError::not_found(
+            &format!("Connection model definition with id {id} not found"),
+            None,
+        ))
+    }
+}
+
 async fn create_mapping(
+    access: Option<Extension<Arc<EventAccess>>>,
     State(state): State<Arc<AppState>>,
     Json(payload): Json<CreateRequest>,
 ) -> Result<impl IntoResponse, PicaError> {
+    payload.check()?;
+    check_model_definition(
+        &state,
+        &payload.connection_model_definition_id,
+        access.as_deref().map(Arc::as_ref),
+    )
+    .await?;
+
     let stores = &state.app_stores;
-    // Check if mapping already exists for this definition (platform-level, no ownership filter)
-    // Mappings are shared across all users of a platform, so uniqueness is global
+
+    // Platform-level mappings are shared across all users of a platform, so
+    // there is at most one per definition and environment
+    let environment = bson::to_bson(&payload.environment).map_err(|e| {
+        error!("Could not serialize environment: {e}");
+        InternalError::serialize_error(e.to_string().as_str(), None)
+    })?;
     let filter = doc! {
         "connectionModelDefinitionId": payload.connection_model_definition_id.to_string(),
+        "environment": environment,
+        "ownership.buildableId": "",
         "deleted": false,
     };
 
@@ -182,8 +853,13 @@ async fn create_mapping(
     if !existing.is_empty() {
         return Err(ApplicationError::conflict(
             &format!(
-                "Mapping already exists for model definition {}",
-                payload.connection_model_definition_id
+                "Mapping already exists for model definition {} in {}",
+                payload.connection_model_definition_id,
+                payload
+                    .environment
+                    .map_or("every environment".to_string(), |environment| format!(
+                        "the {environment} environment"
+                    ))
             ),
             None,
         ).into());
@@ -197,7 +873,8 @@ async fn create_mapping(
         .create_one(&record)
         .await
         .map_err(PicaError::from)?;
-    
+    evict_cached_mappings(&state, &record.connection_model_definition_id).await;
+
     Ok((StatusCode::CREATED, Json(ServerResponse::new("create", CreateRequest::public(record)))))
 }
 
@@ -215,12 +892,24 @@ pub struct CreateRequest {
 
     /// List of variable-to-parameter bindings
     pub bindings: Vec<BindingRequest>,
+
+    /// Environment of the connections the mapping applies to, every
+    /// environment when left out on creation. Updates leaving it out keep
+    /// the stored one.
+    #[serde(default)]
+    pub environment: Option<Environment>,
+
+    /// `Content-Type` requests are sent with, the body being re-encoded to
+    /// match. The caller's, or the definition's, is kept when left out.
+    #[serde(default)]
+    pub content_type: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BindingRequest {
     /// Name of the variable in secrets (e.g., "hotel_id", "SALESFORCE_DOMAIN")
+    #[serde(default)]
     pub variable_name: String,
 
     /// Target parameter name in the API call (e.g., "id", "domain")
@@ -229,18 +918,76 @@ pub struct BindingRequest {
     /// Where to inject the value
     pub location: ParameterLocation,
 
+    /// Fixed value injected instead of a secret variable
+    #[serde(default)]
+    pub constant: Option<Value>,
+
     /// How to inject the value
     #[serde(default)]
     pub strategy: InjectionStrategy,
 
+    /// How an injected JSON object combines with the one the client sent
+    #[serde(default)]
+    pub merge: BodyMerge,
+
     /// Data type of the variable (for conversion)
     #[serde(default)]
     pub data_type: VariableDataType,
+
+    /// Whether connections must have the variable in their secret
+    #[serde(default = "default_required")]
+    pub required: bool,
+
+    /// Only inject the variable into requests matching the condition
+    #[serde(default)]
+    pub condition: Option<BindingCondition>,
+
+    /// Whether the injected value is redacted from diagnostics, by default
+    /// only for headers named like credentials
+    #[serde(default)]
+    pub is_secret: Option<bool>,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+impl BindingRequest {
+    pub fn binding(&self) -> VariableBinding {
+        VariableBinding {
+            variable_name: self.variable_name.clone(),
+            target_param: self.target_param.clone(),
+            location: self.location.clone(),
+            constant: self.constant.clone(),
+            strategy: self.strategy.clone(),
+            merge: self.merge,
+            data_type: self.data_type.clone(),
+            required: self.required,
+            condition: self.condition.clone(),
+            is_secret: self.is_secret,
+        }
+    }
 }
 
 impl CreateRequest {
+    pub(crate) fn check(&self) -> Result<(), PicaError> {
+        let bindings = self
+            .bindings
+            .iter()
+            .map(BindingRequest::binding)
+            .collect::<Vec<_>>();
+
+        ConnectionVariableMapping::check_bindings(&bindings)?;
+
+        if let Some(content_type) = &self.content_type {
+            ConnectionVariableMapping::check_content_type(content_type)?;
+        }
+
+        Ok(())
+    }
+
     /// Creates a platform-level record without requiring EventAccess.
-    /// Platform-level mappings use default ownership and Live environment.
+    /// Platform-level mappings use default ownership.
     pub fn create_platform_record(&self) -> ConnectionVariableMapping {
         ConnectionVariableMapping {
             id: self
@@ -248,21 +995,11 @@ impl CreateRequest {
                 .unwrap_or_else(|| Id::now(IdPrefix::ConnectionVariableMapping)),
             connection_model_definition_id: self.connection_model_definition_id,
             connection_platform: self.connection_platform.clone(),
-            bindings: self
-                .bindings
-                .iter()
-                .map(|b| VariableBinding {
-                    variable_name: b.variable_name.clone(),
-                    target_param: b.target_param.clone(),
-                    location: b.location.clone(),
-                    strategy: b.strategy.clone(),
-                    data_type: b.data_type.clone(),
-                })
-                .collect(),
+            bindings: self.bindings.iter().map(BindingRequest::binding).collect(),
             // Platform-level mappings use default ownership
             ownership: Ownership::default(),
-            // Use Live environment as default for platform-level mappings
-            environment: Environment::Live,
+            environment: self.environment,
+            content_type: self.content_type.clone(),
             record_metadata: RecordMetadata::default(),
         }
     }
@@ -281,19 +1018,10 @@ impl RequestExt for CreateRequest {
                 .unwrap_or_else(|| Id::now(IdPrefix::ConnectionVariableMapping)),
             connection_model_definition_id: self.connection_model_definition_id,
             connection_platform: self.connection_platform.clone(),
-            bindings: self
-                .bindings
-                .iter()
-                .map(|b| VariableBinding {
-                    variable_name: b.variable_name.clone(),
-                    target_param: b.target_param.clone(),
-                    location: b.location.clone(),
-                    strategy: b.strategy.clone(),
-                    data_type: b.data_type.clone(),
-                })
-                .collect(),
+            bindings: self.bindings.iter().map(BindingRequest::binding).collect(),
             ownership: event_access.ownership.clone(),
-            environment: event_access.environment.clone(),
+            environment: self.environment.or(Some(event_access.environment)),
+            content_type: self.content_type.clone(),
             record_metadata: RecordMetadata::default(),
         })
     }
@@ -304,17 +1032,12 @@ impl RequestExt for CreateRequest {
     fn update(&self, mut record: Self::Output) -> Self::Output {
         record.connection_model_definition_id = self.connection_model_definition_id;
         record.connection_platform = self.connection_platform.clone();
-        record.bindings = self
-            .bindings
-            .iter()
-            .map(|b| VariableBinding {
-                variable_name: b.variable_name.clone(),
-                target_param: b.target_param.clone(),
-                location: b.location.clone(),
-                strategy: b.strategy.clone(),
-                data_type: b.data_type.clone(),
-            })
-            .collect();
+        record.bindings = self.bindings.iter().map(BindingRequest::binding).collect();
+        // Left out, the mapping keeps applying where it did
+        if self.environment.is_some() {
+            record.environment = self.environment;
+        }
+        record.content_type.clone_from(&self.content_type);
         record.record_metadata.updated_at = Utc::now().timestamp_millis();
         record.record_metadata.updated = true;
 
@@ -325,3 +1048,33 @@ impl RequestExt for CreateRequest {
         stores.connection_variable_mapping.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_value_hides_injected_values() {
+        let values = vec!["h-123".to_string(), "42".to_string()];
+
+        let redacted = redact_value(
+            json!({
+                "hotel": { "id": "h-123", "rooms": [42, 7] },
+                "filter": "hotel=h-123",
+                "name": "Grand",
+            }),
+            &values,
+        );
+
+        assert_eq!(
+            redacted,
+            json!({
+                "hotel": { "id": REDACTED, "rooms": [REDACTED, 7] },
+                "filter": format!("hotel={REDACTED}"),
+                "name": "Grand",
+            })
+        );
+        assert_eq!(redact("/hotels/h-123", &values), "/hotels/***");
+    }
+}
@@ -0,0 +1,419 @@
+use super::{
+    connection_model_definition::{
+        CreateRequest as DefinitionCreateRequest, TestConnectionRequest,
+    },
+    connection_variable_mapping::{inject_test_request, BindingRequest, CreateRequest},
+    RequestExt,
+};
+use crate::{router::ServerResponse, server::AppState};
+use axum::{extract::State, Json};
+use http::HeaderMap;
+use osentities::{
+    connection_model_definition::PlatformInfo,
+    variable_injection::BodyEncoding,
+    variable_injection::{missing_variables, UnmetCondition},
+    InternalError, PicaError,
+};
+use reqwest::{Client, Request};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, sync::Arc};
+use unified::{client::CallerClient, unified::render_model_definition};
+
+/// A connector checked without live credentials: the definition and mapping
+/// are applied to the sample request using a synthetic secret, the way a
+/// passthrough request is built, and nothing is sent.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundTripPayload {
+    /// The model definition, as it is created
+    pub definition: DefinitionCreateRequest,
+    #[serde(default)]
+    pub mapping: Option<RoundTripMapping>,
+    /// Stands in for the secret of a connection
+    #[serde(default)]
+    pub secret: Map<String, Value>,
+    #[serde(default)]
+    pub request: Option<TestConnectionRequest>,
+    /// Fixture the built request is compared to
+    #[serde(default)]
+    pub expected: Option<ExpectedRequest>,
+}
+
+/// The parts of a mapping applied to a request
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundTripMapping {
+    #[serde(default)]
+    pub bindings: Vec<BindingRequest>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+}
+
+/// Only the parts given are compared. Headers changing on every request,
+/// like OAuth 1.0 signatures, are best left out.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpectedRequest {
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Query params included
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Headers the request must have, their names compared ignoring case.
+    /// Other headers of the request are not compared.
+    #[serde(default)]
+    pub headers: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    pub body: Option<Value>,
+}
+
+/// The request as it would be sent, authentication included
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuiltRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    /// JSON and form bodies are shown as a value, others as text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unmet_conditions: Vec<UnmetCondition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mismatch {
+    /// `method`, `url`, `body` or `headers.<name>`
+    pub field: String,
+    pub expected: Value,
+    /// `null` when the request has no such header or body
+    pub actual: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundTripResponse {
+    pub request: BuiltRequest,
+    /// Whether the secret has every required variable and the request
+    /// matches the expectation, if one is given
+    pub passed: bool,
+    /// Required variables of the mapping the secret does not have, which a
+    /// dispatch skips the bindings of
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_variables: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Builds the request a definition and mapping make of a sample request and
+/// compares it to the expected one, so that connectors can be validated in
+/// CI. Nothing is read from the database nor sent over the network.
+pub async fn validate_round_trip(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RoundTripPayload>,
+) -> Result<Json<ServerResponse<RoundTripResponse>>, PicaError> {
+    Ok(Json(ServerResponse::new(
+        "validate",
+        round_trip(&state.http_client, payload)?,
+    )))
+}
+
+pub fn round_trip(
+    client: &Client,
+    payload: RoundTripPayload,
+) -> Result<RoundTripResponse, PicaError> {
+    let mut config = payload.definition.from().ok_or_else(|| {
+        InternalError::invalid_argument("Could not build the model definition", None)
+    })?;
+
+    let RoundTripMapping {
+        bindings,
+        content_type,
+    } = payload.mapping.unwrap_or_default();
+    let mapping = CreateRequest {
+        id: None,
+        connection_model_definition_id: config.id,
+        connection_platform: config.connection_platform.clone(),
+        bindings,
+        environment: None,
+        content_type,
+    };
+    mapping.check()?;
+    let mapping = mapping.create_platform_record();
+
+    let mut secret = Value::Object(payload.secret);
+    let mut missing = missing_variables(&mapping.bindings, &secret)
+        .into_iter()
+        .map(|binding| binding.variable_name.clone())
+        .collect::<Vec<_>>();
+    missing.dedup();
+
+    let injected = inject_test_request(&mut config, &mapping, &mut secret, payload.request)?;
+
+    let config = render_model_definition(&config, &secret)?;
+    let parts = config.transform_request(injected.parts, &secret)?;
+    let PlatformInfo::Api(ref api_config) = config.platform_info;
+    let request = CallerClient::new(api_config, config.action.clone(), client).build_request(
+        parts.body,
+        Some(&secret),
+        Some(parts.headers),
+        Some(&parts.query_params),
+    )?;
+
+    let request = built(&request, injected.unmet_conditions);
+    let mismatches = payload
+        .expected
+        .map(|expected| compare(&expected, &request))
+        .unwrap_or_default();
+
+    Ok(RoundTripResponse {
+        request,
+        passed: missing.is_empty() && mismatches.is_empty(),
+        missing_variables: missing,
+        mismatches,
+    })
+}
+
+fn built(request: &Request, unmet_conditions: Vec<UnmetCondition>) -> BuiltRequest {
+    BuiltRequest {
+        method: request.method().to_string(),
+        url: request.url().to_string(),
+        headers: headers(request.headers()),
+        body: request.body().and_then(|body| body.as_bytes()).map(|body| {
+            BodyEncoding::of(request.headers())
+                .decode(body)
+                .unwrap_or_else(|| Value::String(String::from_utf8_lossy(body).into_owned()))
+        }),
+        unmet_conditions,
+    }
+}
+
+/// Values of a header set more than once are joined the way they would be
+/// folded into one
+fn headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .fold(BTreeMap::new(), |mut map, (name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            map.entry(name.to_string())
+                .and_modify(|existing: &mut String| {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                })
+                .or_insert(value);
+            map
+        })
+}
+
+fn compare(expected: &ExpectedRequest, request: &BuiltRequest) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    let mut check = |field: String, expected: Value, actual: Value| {
+        if expected != actual {
+            mismatches.push(Mismatch {
+                field,
+                expected,
+                actual,
+            });
+        }
+    };
+
+    if let Some(method) = &expected.method {
+        check(
+            "method".to_string(),
+            Value::String(method.to_ascii_uppercase()),
+            Value::String(request.method.clone()),
+        );
+    }
+
+    if let Some(url) = &expected.url {
+        check(
+            "url".to_string(),
+            Value::String(url.clone()),
+            Value::String(request.url.clone()),
+        );
+    }
+
+    for (name, value) in expected.headers.iter().flatten() {
+        let name = name.to_ascii_lowercase();
+        let actual = request
+            .headers
+            .get(&name)
+            .map(|actual| Value::String(actual.clone()))
+            .unwrap_or_default();
+        check(
+            format!("headers.{name}"),
+            Value::String(value.clone()),
+            actual,
+        );
+    }
+
+    if let Some(body) = &expected.body {
+        check(
+            "body".to_string(),
+            body.clone(),
+            request.body.clone().unwrap_or_default(),
+        );
+    }
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn payload(expected: Value) -> RoundTripPayload {
+        serde_json::from_value(json!({
+            "definition": {
+                "connectionPlatform": "blaze",
+                "connectionDefinitionId": "conn_def::AAAAAAAAAAA::AAAAAAAAAAAAAAAAAAAAAA",
+                "platformVersion": "v1",
+                "title": "Get Reservations",
+                "name": "getReservations",
+                "modelName": "Reservation",
+                "baseUrl": "https://{region}.blaze.example/api",
+                "path": "hotels/{{hotel}}/reservations",
+                "authMethod": { "type": "BearerToken", "value": "{{ACCESS_TOKEN}}" },
+                "actionName": "getMany",
+                "action": "POST",
+                "headers": { "content-type": "application/json" },
+                "queryParams": { "limit": "10" },
+                "schemas": {},
+                "samples": {},
+                "responses": [],
+                "version": "1.0.0",
+            },
+            "mapping": {
+                "bindings": [
+                    {
+                        "variableName": "PROPERTY_ID",
+                        "targetParam": "X-Property-Id",
+                        "location": "Header",
+                    },
+                    {
+                        "variableName": "PROPERTY_ID",
+                        "targetParam": "filter.propertyId",
+                        "location": "BodyField",
+                    },
+                ],
+            },
+            "secret": {
+                "ACCESS_TOKEN": "synthetic-token",
+                "PROPERTY_ID": "p-1",
+                "region": "eu",
+            },
+            "request": {
+                "pathParams": { "hotel": "h-1" },
+                "body": { "filter": { "status": "confirmed" } },
+            },
+            "expected": expected,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_builds_the_dispatched_request() {
+        let response = round_trip(
+            &Client::new(),
+            payload(json!({
+                "method": "post",
+                "url": "https://eu.blaze.example/api/hotels/h-1/reservations?limit=10",
+                "headers": {
+                    "Authorization": "Bearer synthetic-token",
+                    "X-Property-Id": "p-1",
+                },
+                "body": { "filter": { "status": "confirmed", "propertyId": "p-1" } },
+            })),
+        )
+        .unwrap();
+
+        assert!(response.passed, "{:?}", response.mismatches);
+        assert_eq!(
+            response
+                .request
+                .headers
+                .get("content-type")
+                .map(String::as_str),
+            Some("application/json")
+        );
+    }
+
+    #[test]
+    fn test_round_trip_reports_every_mismatch() {
+        let response = round_trip(
+            &Client::new(),
+            payload(json!({
+                "method": "GET",
+                "headers": {
+                    "x-property-id": "p-2",
+                    "x-missing": "value",
+                },
+                "body": { "filter": { "status": "confirmed" } },
+            })),
+        )
+        .unwrap();
+
+        assert!(!response.passed);
+        assert_eq!(
+            response
+                .mismatches
+                .iter()
+                .map(|mismatch| mismatch.field.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "method",
+                "headers.x-missing",
+                "headers.x-property-id",
+                "body"
+            ]
+        );
+        assert_eq!(response.mismatches[1].actual, Value::Null);
+    }
+
+    #[test]
+    fn test_round_trip_fails_on_missing_variables() {
+        let mut payload = payload(Value::Null);
+        payload.secret.remove("PROPERTY_ID");
+
+        let response = round_trip(&Client::new(), payload.clone()).unwrap();
+        assert!(!response.passed);
+        assert_eq!(response.missing_variables, ["PROPERTY_ID"]);
+        assert!(!response.request.headers.contains_key("x-property-id"));
+
+        payload.mapping = None;
+        let response = round_trip(&Client::new(), payload).unwrap();
+        assert!(response.passed);
+        assert!(response.mismatches.is_empty());
+    }
+
+    #[test]
+    fn test_round_trip_applies_the_request_transform() {
+        let mut payload = payload(json!({
+            "headers": { "x-property": "p-1" },
+            "body": { "search": { "status": "confirmed", "propertyId": "p-1" } },
+        }));
+        payload.definition.request_transform = serde_json::from_value(json!({
+            "steps": [
+                { "op": "rename", "location": "BodyField", "param": "filter", "to": "search" },
+                { "op": "rename", "location": "Header", "param": "x-property-id", "to": "x-property" },
+            ],
+        }))
+        .unwrap();
+
+        let response = round_trip(&Client::new(), payload.clone()).unwrap();
+        assert!(response.passed, "{:?}", response.mismatches);
+
+        payload.definition.request_transform = serde_json::from_value(json!({
+            "steps": [
+                { "op": "set", "location": "Header", "param": "x-key", "value": { "variable": "API_KEY" } },
+            ],
+        }))
+        .unwrap();
+
+        let error = round_trip(&Client::new(), payload).unwrap_err();
+        assert_eq!(error.status(), 422);
+    }
+}
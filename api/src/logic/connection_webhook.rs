@@ -0,0 +1,336 @@
+use super::{create, delete, read, update, HookExt, PublicExt, RequestExt};
+use crate::{
+    domain::ConnectionsConfig,
+    router::ServerResponse,
+    server::{AppState, AppStores},
+};
+use axum::{
+    extract::{Path, State},
+    routing::{patch, post},
+    Extension, Json, Router,
+};
+use chrono::Utc;
+use http::StatusCode;
+use mongodb::bson::doc;
+use osentities::{
+    algebra::MongoStore,
+    connection_webhook::{
+        ConnectionLifecycleEvent, ConnectionLifecycleEventType, ConnectionWebhook, WebhookDelivery,
+        WebhookDeliveryStatus,
+    },
+    event_access::EventAccess,
+    id::{prefix::IdPrefix, Id},
+    record_metadata::RecordMetadata,
+    ApplicationError, InternalError, PicaError, PICA_WEBHOOK_EVENT_HEADER, PICA_WEBHOOK_ID_HEADER,
+    PICA_WEBHOOK_SIGNATURE_HEADER, PICA_WEBHOOK_TIMESTAMP_HEADER,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+use tracing::{debug, error, warn};
+use unified::egress::{self, EgressConfig};
+
+const MIN_SIGNING_SECRET_LENGTH: usize = 16;
+
+pub fn get_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/",
+            post(create_webhook).get(read::<CreateRequest, ConnectionWebhook>),
+        )
+        .route(
+            "/:id",
+            patch(update_webhook).delete(delete::<CreateRequest, ConnectionWebhook>),
+        )
+}
+
+async fn create_webhook(
+    access: Option<Extension<Arc<EventAccess>>>,
+    state: State<Arc<AppState>>,
+    Json(payload): Json<CreateRequest>,
+) -> Result<Json<ServerResponse<Value>>, PicaError> {
+    payload.validate(&state.config).await?;
+
+    create::<CreateRequest, ConnectionWebhook>(access, state, Json(payload)).await
+}
+
+async fn update_webhook(
+    access: Option<Extension<Arc<EventAccess>>>,
+    id: Path<String>,
+    state: State<Arc<AppState>>,
+    Json(payload): Json<CreateRequest>,
+) -> Result<Json<ServerResponse<super::SuccessResponse>>, PicaError> {
+    payload.validate(&state.config).await?;
+
+    update::<CreateRequest, ConnectionWebhook>(access, id, state, Json(payload)).await
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateRequest {
+    pub url: String,
+    pub event_types: Vec<ConnectionLifecycleEventType>,
+    pub signing_secret: String,
+}
+
+impl CreateRequest {
+    async fn validate(&self, config: &ConnectionsConfig) -> Result<(), PicaError> {
+        let url = Url::parse(&self.url).map_err(|e| {
+            ApplicationError::bad_request(&format!("Invalid webhook url: {e}"), None)
+        })?;
+
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(ApplicationError::bad_request(
+                "Webhook url must use http or https",
+                None,
+            ));
+        }
+
+        // Checked again on every delivery, as the host may resolve elsewhere later
+        if !config.webhook_allow_internal_hosts {
+            egress::resolve_public(&url).await?;
+        }
+
+        if self.event_types.is_empty() {
+            return Err(ApplicationError::bad_request(
+                "At least one event type is required",
+                None,
+            ));
+        }
+
+        if self.signing_secret.len() < MIN_SIGNING_SECRET_LENGTH {
+            return Err(ApplicationError::bad_request(
+                &format!(
+                    "Signing secret must be at least {MIN_SIGNING_SECRET_LENGTH} characters long"
+                ),
+                None,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl HookExt<ConnectionWebhook> for CreateRequest {}
+
+impl PublicExt<ConnectionWebhook> for CreateRequest {
+    fn public(input: ConnectionWebhook) -> Value {
+        let mut value = serde_json::to_value(input).unwrap_or_default();
+        if let Some(object) = value.as_object_mut() {
+            object.remove("signingSecret");
+        }
+
+        value
+    }
+}
+
+impl RequestExt for CreateRequest {
+    type Output = ConnectionWebhook;
+
+    fn access(&self, event_access: Arc<EventAccess>) -> Option<Self::Output> {
+        Some(Self::Output {
+            id: Id::now(IdPrefix::ConnectionWebhook),
+            url: self.url.clone(),
+            event_types: self.event_types.clone(),
+            signing_secret: self.signing_secret.clone(),
+            ownership: event_access.ownership.clone(),
+            environment: event_access.environment,
+            last_delivery: None,
+            record_metadata: RecordMetadata::default(),
+        })
+    }
+
+    fn update(&self, mut record: Self::Output) -> Self::Output {
+        record.url.clone_from(&self.url);
+        record.event_types.clone_from(&self.event_types);
+        record.signing_secret.clone_from(&self.signing_secret);
+
+        record
+    }
+
+    fn get_store(stores: AppStores) -> MongoStore<Self::Output> {
+        stores.connection_webhook.clone()
+    }
+}
+
+/// Queues a lifecycle event for delivery to the subscribed webhooks. Never
+/// blocks the caller: when the queue is full the event is dropped and logged.
+pub fn notify(state: &AppState, event: ConnectionLifecycleEvent) {
+    if let Err(e) = state.webhook_tx.try_send(event) {
+        warn!("Could not queue connection lifecycle event: {e}");
+    }
+}
+
+/// Delivers the event to every active webhook subscribed to its type. Events
+/// carrying ownership only reach webhooks of the same owner and environment.
+pub async fn dispatch(
+    store: MongoStore<ConnectionWebhook>,
+    egress: Arc<EgressConfig>,
+    config: Arc<ConnectionsConfig>,
+    event: ConnectionLifecycleEvent,
+) {
+    let mut filter = doc! {
+        "eventTypes": event.r#type.as_ref(),
+        "active": true,
+        "deleted": false,
+    };
+    if let Some(ownership) = &event.ownership {
+        filter.insert("ownership.buildableId", ownership.id.as_ref());
+    }
+    if let Some(environment) = &event.environment {
+        filter.insert("environment", environment.to_string());
+    }
+
+    let webhooks = match store.get_many(Some(filter), None, None, None, None).await {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            error!("Could not fetch webhooks for event {}: {e}", event.id);
+            return;
+        }
+    };
+
+    let body = Arc::new(
+        json!({
+            "id": event.id,
+            "type": event.r#type,
+            "environment": event.environment,
+            "occurredAt": event.occurred_at,
+            "data": event.data,
+        })
+        .to_string(),
+    );
+    let event = Arc::new(event);
+
+    for webhook in webhooks {
+        let store = store.clone();
+        let egress = egress.clone();
+        let config = config.clone();
+        let body = body.clone();
+        let event = event.clone();
+
+        tokio::spawn(async move {
+            let delivery = deliver(&egress, &config, &webhook, &event, &body).await;
+
+            let Ok(delivery) = bson::to_bson(&delivery) else {
+                error!("Could not serialize delivery of webhook {}", webhook.id);
+                return;
+            };
+
+            if let Err(e) = store
+                .update_one(
+                    &webhook.id.to_string(),
+                    doc! { "$set": { "lastDelivery": delivery } },
+                )
+                .await
+            {
+                error!("Could not record delivery of webhook {}: {e}", webhook.id);
+            }
+        });
+    }
+}
+
+async fn deliver(
+    egress: &EgressConfig,
+    config: &ConnectionsConfig,
+    webhook: &ConnectionWebhook,
+    event: &ConnectionLifecycleEvent,
+    body: &str,
+) -> WebhookDelivery {
+    let max_attempts = config.webhook_max_attempts.max(1);
+    let mut delay = Duration::from_millis(config.webhook_retry_base_delay_millis);
+    let mut attempts = 0;
+
+    let (status_code, error) = loop {
+        attempts += 1;
+
+        let timestamp = Utc::now().timestamp_millis();
+        let signature = webhook.sign(timestamp, body.as_bytes());
+
+        let client = match webhook_client(egress, config, &webhook.url).await {
+            Ok(client) => client,
+            Err(e) => {
+                warn!(
+                    "Not delivering event {} to webhook {}: {e}",
+                    event.id, webhook.id
+                );
+                break (None, Some(e.to_string()));
+            }
+        };
+
+        let result = client
+            .post(&webhook.url)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(PICA_WEBHOOK_ID_HEADER, event.id.to_string())
+            .header(PICA_WEBHOOK_EVENT_HEADER, event.r#type.as_ref())
+            .header(PICA_WEBHOOK_TIMESTAMP_HEADER, timestamp.to_string())
+            .header(PICA_WEBHOOK_SIGNATURE_HEADER, format!("sha256={signature}"))
+            .timeout(Duration::from_secs(config.webhook_timeout_secs))
+            .body(body.to_owned())
+            .send()
+            .await;
+
+        let (status_code, error) = match result {
+            Ok(res) if res.status().is_success() => {
+                debug!("Delivered event {} to webhook {}", event.id, webhook.id);
+                break (Some(res.status()), None);
+            }
+            Ok(res) => (
+                Some(res.status()),
+                Some(format!("Webhook responded with {}", res.status())),
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        if attempts >= max_attempts {
+            warn!(
+                "Giving up delivering event {} to webhook {} after {attempts} attempts",
+                event.id, webhook.id
+            );
+            break (status_code, error);
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = delay.saturating_mul(2);
+    };
+
+    WebhookDelivery {
+        event_id: event.id,
+        event_type: event.r#type,
+        status: if error.is_none() {
+            WebhookDeliveryStatus::Delivered
+        } else {
+            WebhookDeliveryStatus::Failed
+        },
+        attempts,
+        status_code: status_code.as_ref().map(StatusCode::as_u16),
+        error,
+        delivered_at: Utc::now().timestamp_millis(),
+    }
+}
+
+/// Client going through the egress proxy to the public addresses `url` resolves
+/// to right now, pinned so that the host cannot resolve to an internal address
+/// between the check and the call. Redirects are not followed, as their target
+/// is not checked.
+async fn webhook_client(
+    egress: &EgressConfig,
+    config: &ConnectionsConfig,
+    url: &str,
+) -> Result<reqwest::Client, PicaError> {
+    let mut builder = egress
+        .builder()?
+        .redirect(reqwest::redirect::Policy::none());
+
+    if !config.webhook_allow_internal_hosts {
+        let url = Url::parse(url).map_err(|e| {
+            ApplicationError::bad_request(&format!("Invalid webhook url: {e}"), None)
+        })?;
+        let addrs = egress::resolve_public(&url).await?;
+        builder = builder.resolve_to_addrs(url.host_str().unwrap_or_default(), &addrs);
+    }
+
+    builder.build().map_err(|e| {
+        InternalError::configuration_error(&format!("Could not build webhook client: {e}"), None)
+    })
+}
@@ -1,44 +1,118 @@
-use super::{ReadResponse, HookExt, PublicExt, RequestExt};
+use super::{
+    connection_variable_mapping::get_cached_mappings_of_definitions, create, delete, read, update,
+    HookExt, PublicExt, ReadResponse, RequestExt,
+};
 use crate::{
+    domain::config::KnowledgeMappingFailure,
     helper::shape_mongo_filter,
     router::ServerResponse,
     server::{AppState, AppStores},
 };
 use axum::{
     extract::{Query, State},
-    routing::get,
-    Json, Router,
+    routing::{get, patch, post},
+    Extension, Json, Router,
 };
-use bson::doc;
+use bson::{doc, Bson, Document};
 use fake::Dummy;
+use futures::{stream, StreamExt, TryStreamExt};
 use http::HeaderMap;
 use osentities::{
-    connection_variable_mapping::{ConnectionVariableMapping, InjectionStrategy},
-    record_metadata::RecordMetadata,
-    Id, MongoStore,
+    algebra::MongoStore, connection_variable_mapping::ConnectionVariableMapping,
+    event_access::EventAccess, id::prefix::IdPrefix, knowledge_override::KnowledgeOverride,
+    record_metadata::RecordMetadata, variable_injection::describe_bindings, ApplicationError, Id,
+    InternalError, PicaError, PicaErrorCode,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{collections::BTreeMap, sync::Arc};
-use tracing::error;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tracing::{error, warn};
+
+const ONLY_MAPPED_FILTER: &str = "onlyMapped";
+const CONNECTION_KEY_FILTER: &str = "connectionKey";
 
 pub fn get_router() -> Router<Arc<AppState>> {
-    Router::new().route("/", get(read_knowledge))
+    Router::new()
+        .route("/", get(read_knowledge))
+        .route(
+            "/overrides",
+            post(create_override).get(read::<CreateOverrideRequest, KnowledgeOverride>),
+        )
+        .route(
+            "/overrides/:id",
+            patch(update::<CreateOverrideRequest, KnowledgeOverride>)
+                .delete(delete::<CreateOverrideRequest, KnowledgeOverride>),
+        )
 }
 
-/// Custom read handler that enriches knowledge with mapping annotations
+/// A page of knowledge. `degraded` is set when the mappings of the page could
+/// not be loaded and its records lack their annotations.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct KnowledgePage {
+    #[serde(flatten)]
+    pub page: ReadResponse<Value>,
+    #[serde(default)]
+    pub degraded: bool,
+}
+
+/// Custom read handler that enriches knowledge with mapping annotations.
+/// With `onlyMapped=true`, only records that have a variable mapping are returned.
+/// With `connectionKey`, the knowledge overrides of that connection are
+/// appended to the platform knowledge of their definitions.
 async fn read_knowledge(
+    access: Option<Extension<Arc<EventAccess>>>,
     headers: HeaderMap,
     query: Option<Query<BTreeMap<String, String>>>,
     State(state): State<Arc<AppState>>,
-) -> Result<Json<ServerResponse<ReadResponse<Value>>>, osentities::PicaError> {
-    let query_params = shape_mongo_filter(query, None, Some(headers));
+) -> Result<Json<ServerResponse<KnowledgePage>>, PicaError> {
+    let mut query = query;
+    let only_mapped = query
+        .as_mut()
+        .and_then(|Query(q)| q.remove(ONLY_MAPPED_FILTER))
+        .is_some_and(|value| value == "true");
+    let connection_key = query
+        .as_mut()
+        .and_then(|Query(q)| q.remove(CONNECTION_KEY_FILTER));
+
+    let mut query_params =
+        shape_mongo_filter(query, None, Some(headers)).with_page_size(&state.config);
 
+    let caller = access.as_ref().map(|Extension(access)| access.as_ref());
     let store = state.app_stores.knowledge.clone();
     let mapping_store = state.app_stores.connection_variable_mapping.clone();
 
+    // Restrict the query itself to mapped definitions so that skip, limit and
+    // total all apply to the filtered set. Only the mappings of definitions
+    // matching the rest of the query are looked at.
+    if only_mapped {
+        let matching_ids = store
+            .collection
+            .distinct("_id", query_params.filter.clone())
+            .await?;
+        let mut mapped_filter = doc! {
+            "connectionModelDefinitionId": { "$in": matching_ids },
+            "deleted": false,
+        };
+        mapped_filter.extend(applicable_mappings_filter(caller)?);
+        let mapped_ids = mapping_store
+            .collection
+            .distinct("connectionModelDefinitionId", mapped_filter)
+            .await?;
+
+        let only_mapped = doc! { "_id": { "$in": mapped_ids } };
+        match query_params.filter.get_array_mut("$and") {
+            Ok(conditions) => conditions.push(only_mapped.into()),
+            Err(_) => {
+                query_params.filter.insert("$and", vec![only_mapped]);
+            }
+        }
+    }
+
     // Fetch knowledge records
-    let rows: Vec<Knowledge> = store
+    let mut rows: Vec<Knowledge> = store
         .get_many(
             Some(query_params.filter.clone()),
             None,
@@ -50,82 +124,259 @@ async fn read_knowledge(
 
     let total = store.count(query_params.filter, None).await?;
 
-    // Batch fetch ALL mappings for these definitions in a single query
+    // Batch fetch the mappings of these definitions that are not cached, a
+    // page of them at a time
     let definition_ids: Vec<String> = rows.iter().map(|r| r.id.to_string()).collect();
-    let all_mappings: Vec<ConnectionVariableMapping> = if !definition_ids.is_empty() {
-        mapping_store
-            .get_many(
-                Some(doc! {
-                    "connectionModelDefinitionId": { "$in": &definition_ids },
-                    "deleted": false,
-                }),
-                None,
-                None,
-                None, // No limit - get all
+    let row_ids: Vec<Id> = rows.iter().map(|r| r.id).collect();
+    let (all_mappings, degraded) = mappings_or_degraded(
+        get_cached_mappings_of_definitions(&state, &row_ids).await,
+        state.config.knowledge_mapping_failure,
+    )?;
+
+    let mapping_map = resolve_mappings(all_mappings, caller);
+
+    // The query already kept mapped records only, none would be left here
+    if only_mapped && !degraded {
+        rows.retain(|record| mapping_map.contains_key(&record.id.to_string()));
+    }
+
+    if let Some(connection_key) = connection_key {
+        let access = access.ok_or_else(|| {
+            ApplicationError::bad_request(
+                "Knowledge of a connection can only be read with an access key",
                 None,
             )
-            .await
-            .unwrap_or_else(|e| {
-                error!("Error batch fetching mappings: {:?}", e);
-                Vec::new()
-            })
-    } else {
-        Vec::new()
-    };
+        })?;
+        let overrides =
+            get_overrides_of_connection(&state, &access, &connection_key, &definition_ids).await?;
 
-    // Build HashMap for O(1) lookup
-    let mapping_map: std::collections::HashMap<String, ConnectionVariableMapping> = all_mappings
-        .into_iter()
-        .map(|m| (m.connection_model_definition_id.to_string(), m))
-        .collect();
-
-    // Enrich each record with mapping annotations
-    let mut enriched_rows: Vec<Value> = Vec::with_capacity(rows.len());
-    for mut record in rows {
-        // O(1) lookup
-        if let Some(m) = mapping_map.get(&record.id.to_string()) {
-            let mut annotations = String::from("IMPORTANT: ");
-            let mut param_list: Vec<String> = Vec::new();
-            for binding in &m.bindings {
-                match binding.strategy {
-                    InjectionStrategy::Strict => {
-                        param_list.push(format!("'{}' (auto-filled, do NOT ask user)", binding.target_param));
-                    }
-                    InjectionStrategy::Fallback => {
-                        param_list.push(format!("'{}' (has default, only ask if user wants to override)", binding.target_param));
-                    }
-                    InjectionStrategy::Append => {
-                        param_list.push(format!("'{}' (partially pre-filled, user may add more)", binding.target_param));
-                    }
-                }
+        for record in rows.iter_mut() {
+            if let Some(knowledge_override) = overrides.get(&record.id) {
+                record.knowledge = Some(knowledge_override.merge_into(record.knowledge.take()));
             }
-            annotations.push_str(&format!(
-                "The following parameters are automatically handled by the system and do NOT need to be retrieved or asked for: {}.\n\n",
-                param_list.join(", ")
-            ));
-            // Prepend to existing knowledge
-            record.knowledge = Some(
-                record
-                    .knowledge
-                    .map(|k| format!("{}{}", annotations, k))
-                    .unwrap_or(annotations),
-            );
         }
-
-        enriched_rows.push(serde_json::to_value(&record).unwrap_or_default());
     }
 
+    let enriched_rows = enrich_knowledge(
+        rows,
+        Arc::new(mapping_map),
+        state
+            .config
+            .knowledge_enrichment_workers
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            }),
+        state.config.knowledge_enrichment_parallel_threshold,
+    )
+    .await?;
+
     Ok(Json(ServerResponse::new(
         "read",
-        ReadResponse {
-            rows: enriched_rows,
-            skip: query_params.skip,
-            limit: query_params.limit,
-            total,
+        KnowledgePage {
+            page: ReadResponse::new(enriched_rows, total, query_params.skip, query_params.limit),
+            degraded,
         },
     )))
 }
 
+/// Filter of the mappings that can apply to the caller's connections: its own
+/// and platform-level ones, of its environment or of every environment.
+/// Without an access key, only platform-level mappings of every environment.
+fn applicable_mappings_filter(access: Option<&EventAccess>) -> Result<Document, PicaError> {
+    let Some(access) = access else {
+        return Ok(doc! { "ownership.buildableId": "", "environment": Bson::Null });
+    };
+    let environment = bson::to_bson(&access.environment).map_err(|e| {
+        error!("Could not serialize environment: {e}");
+        InternalError::serialize_error("Could not serialize environment", None)
+    })?;
+
+    Ok(doc! {
+        "ownership.buildableId": { "$in": ["", access.ownership.id.as_ref()] },
+        "environment": { "$in": [Bson::Null, environment] },
+    })
+}
+
+/// The mapping of each definition that applies to the caller's connections,
+/// by definition id
+fn resolve_mappings(
+    mappings: Vec<ConnectionVariableMapping>,
+    access: Option<&EventAccess>,
+) -> HashMap<String, ConnectionVariableMapping> {
+    let mut of_definitions: HashMap<String, Vec<ConnectionVariableMapping>> = HashMap::new();
+    for mapping in mappings {
+        of_definitions
+            .entry(mapping.connection_model_definition_id.to_string())
+            .or_default()
+            .push(mapping);
+    }
+
+    of_definitions
+        .into_iter()
+        .filter_map(|(id, mappings)| {
+            let mapping = match access {
+                Some(access) => ConnectionVariableMapping::resolve(
+                    mappings,
+                    access.environment,
+                    &access.ownership.id,
+                ),
+                None => mappings.into_iter().find(|mapping| {
+                    mapping.ownership.id.is_empty() && mapping.environment.is_none()
+                }),
+            };
+            mapping.map(|mapping| (id, mapping))
+        })
+        .collect()
+}
+
+/// The mappings annotating a knowledge read, along with whether they could
+/// not be loaded and the read goes on without them
+fn mappings_or_degraded(
+    mappings: Result<Vec<ConnectionVariableMapping>, PicaError>,
+    on_failure: KnowledgeMappingFailure,
+) -> Result<(Vec<ConnectionVariableMapping>, bool), PicaError> {
+    match (mappings, on_failure) {
+        (Ok(mappings), _) => Ok((mappings, false)),
+        (Err(e), KnowledgeMappingFailure::Strict) => {
+            error!("Error batch fetching mappings: {:?}", e);
+
+            Err(ApplicationError::service_unavailable(
+                "Variable mappings could not be loaded",
+                PicaErrorCode::MappingsUnavailable.subtype(),
+            ))
+        }
+        (Err(e), KnowledgeMappingFailure::Lenient) => {
+            warn!(
+                "Reading knowledge without annotations, error batch fetching mappings: {:?}",
+                e
+            );
+
+            Ok((Vec::new(), true))
+        }
+    }
+}
+
+/// The knowledge overrides of the connection with `connection_key`, by model
+/// definition
+async fn get_overrides_of_connection(
+    state: &AppState,
+    access: &EventAccess,
+    connection_key: &str,
+    definition_ids: &[String],
+) -> Result<HashMap<Id, KnowledgeOverride>, PicaError> {
+    let Some(connection) = state
+        .app_stores
+        .connection
+        .get_one(doc! {
+            "key": connection_key,
+            "ownership.buildableId": access.ownership.id.as_ref(),
+            "deleted": false
+        })
+        .await?
+    else {
+        return Err(ApplicationError::not_found(
+            &format!("Connection with key {connection_key} not found"),
+            None,
+        ));
+    };
+
+    let overrides = state
+        .app_stores
+        .knowledge_override
+        .get_many(
+            Some(doc! {
+                "connectionId": connection.id.to_string(),
+                "connectionModelDefinitionId": { "$in": definition_ids },
+                "deleted": false
+            }),
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+    Ok(overrides
+        .into_iter()
+        .map(|o| (o.connection_model_definition_id, o))
+        .collect())
+}
+
+/// Enriches knowledge records with their mapping annotations, preserving the
+/// input order. Reads below `parallel_threshold` rows are enriched inline since
+/// spawning blocking tasks costs more than the work itself at that size.
+async fn enrich_knowledge(
+    rows: Vec<Knowledge>,
+    mapping_map: Arc<HashMap<String, ConnectionVariableMapping>>,
+    workers: usize,
+    parallel_threshold: usize,
+) -> Result<Vec<Value>, PicaError> {
+    let workers = workers.max(1);
+
+    if workers == 1 || rows.len() < parallel_threshold {
+        return Ok(rows
+            .into_iter()
+            .map(|record| enrich_record(record, &mapping_map))
+            .collect());
+    }
+
+    let chunk_size = rows.len().div_ceil(workers);
+    let mut rows = rows.into_iter();
+    let chunks = std::iter::from_fn(|| {
+        let chunk: Vec<Knowledge> = rows.by_ref().take(chunk_size).collect();
+        (!chunk.is_empty()).then_some(chunk)
+    });
+
+    let enriched = stream::iter(chunks)
+        .map(|chunk| {
+            let mapping_map = mapping_map.clone();
+            tokio::task::spawn_blocking(move || {
+                chunk
+                    .into_iter()
+                    .map(|record| enrich_record(record, &mapping_map))
+                    .collect::<Vec<Value>>()
+            })
+        })
+        .buffered(workers)
+        .try_collect::<Vec<Vec<Value>>>()
+        .await
+        .map_err(|e| {
+            error!("Knowledge enrichment worker failed: {:?}", e);
+            InternalError::unknown("Knowledge enrichment worker failed", None)
+        })?;
+
+    Ok(enriched.into_iter().flatten().collect())
+}
+
+fn enrich_record(
+    mut record: Knowledge,
+    mapping_map: &HashMap<String, ConnectionVariableMapping>,
+) -> Value {
+    // O(1) lookup
+    if let Some(m) = mapping_map.get(&record.id.to_string()) {
+        let mut annotations = String::from("IMPORTANT: ");
+        let param_list: Vec<String> = describe_bindings(&m.bindings)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        annotations.push_str(&format!(
+            "The following parameters are automatically handled by the system and do NOT need to be retrieved or asked for: {}.\n\n",
+            param_list.join(", ")
+        ));
+        // Prepend to existing knowledge
+        record.knowledge = Some(
+            record
+                .knowledge
+                .map(|k| format!("{}{}", annotations, k))
+                .unwrap_or(annotations),
+        );
+    }
+
+    serde_json::to_value(&record).unwrap_or_default()
+}
+
 struct ReadRequest;
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Dummy)]
@@ -151,3 +402,260 @@ impl RequestExt for ReadRequest {
         stores.knowledge
     }
 }
+
+/// Creates the knowledge override of one of the caller's connections for a
+/// model definition. A connection has at most one override per definition.
+async fn create_override(
+    access: Option<Extension<Arc<EventAccess>>>,
+    state: State<Arc<AppState>>,
+    Json(payload): Json<CreateOverrideRequest>,
+) -> Result<Json<ServerResponse<Value>>, PicaError> {
+    let Some(Extension(event_access)) = access.as_ref() else {
+        return Err(ApplicationError::bad_request(
+            "Knowledge overrides can only be created with an access key",
+            None,
+        ));
+    };
+    let stores = &state.app_stores;
+
+    let connection_exists = stores
+        .connection
+        .count(
+            doc! {
+                "_id": payload.connection_id.to_string(),
+                "ownership.buildableId": event_access.ownership.id.as_ref(),
+                "deleted": false
+            },
+            Some(1),
+        )
+        .await?
+        > 0;
+    if !connection_exists {
+        return Err(ApplicationError::not_found(
+            &format!("Connection with id {} not found", payload.connection_id),
+            None,
+        ));
+    }
+
+    let definition_exists = stores
+        .model_config
+        .count(
+            doc! {
+                "_id": payload.connection_model_definition_id.to_string(),
+                "deleted": false
+            },
+            Some(1),
+        )
+        .await?
+        > 0;
+    if !definition_exists {
+        return Err(ApplicationError::not_found(
+            &format!(
+                "Connection model definition with id {} not found",
+                payload.connection_model_definition_id
+            ),
+            None,
+        ));
+    }
+
+    let existing = stores
+        .knowledge_override
+        .count(
+            doc! {
+                "connectionId": payload.connection_id.to_string(),
+                "connectionModelDefinitionId": payload.connection_model_definition_id.to_string(),
+                "deleted": false
+            },
+            Some(1),
+        )
+        .await?;
+    if existing > 0 {
+        return Err(ApplicationError::conflict(
+            &format!(
+                "Connection {} already has a knowledge override for model definition {}",
+                payload.connection_id, payload.connection_model_definition_id
+            ),
+            None,
+        ));
+    }
+
+    create::<CreateOverrideRequest, KnowledgeOverride>(access, state, Json(payload)).await
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOverrideRequest {
+    pub connection_id: Id,
+    pub connection_model_definition_id: Id,
+    pub knowledge: String,
+}
+
+impl HookExt<KnowledgeOverride> for CreateOverrideRequest {}
+impl PublicExt<KnowledgeOverride> for CreateOverrideRequest {}
+
+impl RequestExt for CreateOverrideRequest {
+    type Output = KnowledgeOverride;
+
+    fn access(&self, event_access: Arc<EventAccess>) -> Option<Self::Output> {
+        Some(Self::Output {
+            id: Id::now(IdPrefix::KnowledgeOverride),
+            connection_id: self.connection_id,
+            connection_model_definition_id: self.connection_model_definition_id,
+            knowledge: self.knowledge.clone(),
+            ownership: event_access.ownership.clone(),
+            environment: event_access.environment,
+            record_metadata: RecordMetadata::default(),
+        })
+    }
+
+    fn update(&self, mut record: Self::Output) -> Self::Output {
+        record.knowledge.clone_from(&self.knowledge);
+
+        record
+    }
+
+    fn get_store(stores: AppStores) -> MongoStore<Self::Output> {
+        stores.knowledge_override.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fake::{Fake, Faker};
+    use osentities::{
+        connection_definition::{ConnectionDefinitionType, Paths},
+        connection_variable_mapping::{
+            BodyMerge, InjectionStrategy, ParameterLocation, VariableBinding, VariableDataType,
+        },
+        environment::Environment,
+        ownership::Ownership,
+        prefix::IdPrefix,
+    };
+
+    fn knowledge_with_mappings(
+        count: usize,
+    ) -> (Vec<Knowledge>, HashMap<String, ConnectionVariableMapping>) {
+        let rows: Vec<Knowledge> = (0..count).map(|_| Faker.fake()).collect();
+        let mapping_map = rows
+            .iter()
+            .step_by(2)
+            .map(|record| {
+                let mapping = ConnectionVariableMapping {
+                    id: Id::test(IdPrefix::ConnectionVariableMapping),
+                    connection_model_definition_id: record.id,
+                    connection_platform: record.connection_platform.clone(),
+                    bindings: vec![VariableBinding {
+                        variable_name: "hotel_id".to_string(),
+                        target_param: "hotelId".to_string(),
+                        location: ParameterLocation::QueryParam,
+                        constant: None,
+                        strategy: InjectionStrategy::Strict,
+                        merge: BodyMerge::Shallow,
+                        data_type: VariableDataType::String,
+                        required: true,
+                        condition: None,
+                        is_secret: None,
+                    }],
+                    ownership: Ownership::default(),
+                    environment: Some(Environment::Live),
+                    content_type: None,
+                    record_metadata: RecordMetadata::default(),
+                };
+                (record.id.to_string(), mapping)
+            })
+            .collect();
+
+        (rows, mapping_map)
+    }
+
+    #[tokio::test]
+    async fn test_parallel_enrichment_preserves_order() {
+        let (rows, mapping_map) = knowledge_with_mappings(101);
+        let mapping_map = Arc::new(mapping_map);
+
+        let serial = enrich_knowledge(rows.clone(), mapping_map.clone(), 1, 0)
+            .await
+            .unwrap();
+        let parallel = enrich_knowledge(rows.clone(), mapping_map, 4, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(serial, parallel);
+        for (record, value) in rows.iter().zip(&parallel) {
+            assert_eq!(value["_id"], record.id.to_string());
+        }
+        assert!(parallel[0]["knowledge"]
+            .as_str()
+            .unwrap()
+            .starts_with("IMPORTANT: "));
+    }
+
+    #[test]
+    fn test_mapping_failures_are_surfaced() {
+        let failure = || Err(InternalError::connection_error("Mongo is down", None));
+
+        let (mappings, degraded) =
+            mappings_or_degraded(Ok(vec![]), KnowledgeMappingFailure::Strict).unwrap();
+        assert!(mappings.is_empty());
+        assert!(!degraded);
+
+        let (mappings, degraded) =
+            mappings_or_degraded(failure(), KnowledgeMappingFailure::Lenient).unwrap();
+        assert!(mappings.is_empty());
+        assert!(degraded);
+
+        let error = mappings_or_degraded(failure(), KnowledgeMappingFailure::Strict).unwrap_err();
+        assert_eq!(error.status(), 503);
+        assert_eq!(error.error_code(), Some(PicaErrorCode::MappingsUnavailable));
+    }
+
+    #[test]
+    fn test_mappings_of_other_owners_do_not_annotate_knowledge() {
+        let (_, mapping_map) = knowledge_with_mappings(1);
+        let platform = mapping_map.into_values().next().unwrap();
+        let mut other_owner = platform.clone();
+        other_owner.ownership = Ownership::new("other".to_string());
+        let mut other_environment = platform.clone();
+        other_environment.ownership = Ownership::new("owner".to_string());
+        other_environment.environment = Some(Environment::Test);
+        let definition_id = platform.connection_model_definition_id.to_string();
+
+        let access = EventAccess {
+            id: Id::now(IdPrefix::EventAccess),
+            name: "name".to_string(),
+            key: "key".to_string(),
+            namespace: "default".to_string(),
+            platform: "stripe".to_string(),
+            r#type: ConnectionDefinitionType::Api,
+            group: "group".to_string(),
+            ownership: Ownership::new("owner".to_string()),
+            paths: Paths::default(),
+            access_key: "access_key".to_string(),
+            environment: Environment::Live,
+            record_metadata: RecordMetadata::default(),
+            throughput: 1000,
+        };
+
+        let resolved = resolve_mappings(
+            vec![
+                other_owner.clone(),
+                platform.clone(),
+                other_environment.clone(),
+            ],
+            Some(&access),
+        );
+        assert_eq!(resolved.get(&definition_id), Some(&platform));
+
+        let resolved = resolve_mappings(vec![other_owner, other_environment], Some(&access));
+        assert!(resolved.is_empty());
+
+        assert_eq!(
+            applicable_mappings_filter(Some(&access)).unwrap(),
+            doc! {
+                "ownership.buildableId": { "$in": ["", "owner"] },
+                "environment": { "$in": [null, "live"] },
+            }
+        );
+    }
+}
@@ -1,5 +1,14 @@
 use super::ReadResponse;
-use crate::{router::ServerResponse, server::AppState};
+use crate::{
+    domain::{
+        dead_letter::{dead_letter_stats, DeadLetterStats},
+        telemetry::TelemetryChannelStats,
+        upstream_limit::UpstreamLimit,
+    },
+    middleware::admission::AdmissionStats,
+    router::ServerResponse,
+    server::AppState,
+};
 use axum::{
     extract::{Path, Query, State},
     routing::get,
@@ -20,6 +29,55 @@ pub fn get_router() -> Router<Arc<AppState>> {
         .route("/", get(get_metrics))
         .route("/:client_id", get(get_metrics))
         .route("/total", get(get_full_record))
+        .route("/admission", get(get_admission_metrics))
+        .route("/upstream-limits", get(get_upstream_limits))
+        .route("/telemetry", get(get_telemetry_metrics))
+        .route("/dead-letters", get(get_dead_letter_metrics))
+}
+
+/// Current load of the passthrough admission controller
+pub async fn get_admission_metrics(
+    state: State<Arc<AppState>>,
+) -> Json<ServerResponse<AdmissionStats>> {
+    Json(ServerResponse::new(
+        "metrics",
+        state.passthrough_admission.stats(),
+    ))
+}
+
+/// Backlog and drops of the event and metric channels, which shed items
+/// rather than hold up requests when their consumers fall behind
+pub async fn get_telemetry_metrics(
+    state: State<Arc<AppState>>,
+) -> Json<ServerResponse<Vec<TelemetryChannelStats>>> {
+    Json(ServerResponse::new(
+        "metrics",
+        vec![state.event_tx.stats(), state.metric_tx.stats()],
+    ))
+}
+
+/// Events waiting in the dead letter for redelivery, and those given up on
+pub async fn get_dead_letter_metrics(
+    state: State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<DeadLetterStats>>, PicaError> {
+    let stats = dead_letter_stats(
+        &state.app_stores.dead_letter_event,
+        state.config.event_dead_letter_max_attempts,
+    )
+    .await?;
+
+    Ok(Json(ServerResponse::new("metrics", stats)))
+}
+
+/// Rate limits last reported by the platforms of the caller's connections
+pub async fn get_upstream_limits(
+    state: State<Arc<AppState>>,
+    Extension(access): Extension<Arc<EventAccess>>,
+) -> Json<ServerResponse<Vec<UpstreamLimit>>> {
+    Json(ServerResponse::new(
+        "metrics",
+        state.upstream_limits.owned_by(&access.ownership.id),
+    ))
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -84,12 +142,7 @@ pub async fn get_full_record(
 
     Ok(Json(ServerResponse::new(
         "metrics",
-        ReadResponse {
-            rows: vec![doc],
-            total: 1,
-            skip: 0,
-            limit: 1,
-        },
+        ReadResponse::new(vec![doc], 1, 0, 1),
     )))
 }
 
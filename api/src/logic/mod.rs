@@ -7,13 +7,17 @@ use axum::{
     extract::{Path, Query, State},
     Extension, Json,
 };
-use bson::doc;
+use bson::{doc, Bson, Document};
 use cache::local::{ConnectionHeaderCache, LocalCacheExt};
-use http::{HeaderMap, HeaderValue};
-use mongodb::options::FindOneOptions;
+use chrono::Utc;
+use http::{HeaderMap, HeaderValue, StatusCode};
+use mongodb::{
+    error::{ErrorKind, WriteFailure},
+    options::{Collation, CollationStrength, FindOneOptions},
+};
 use osentities::{
     algebra::MongoStore, event_access::EventAccess, ApplicationError, Connection, InternalError,
-    OAuth, PicaError, Store, Unit,
+    OAuth, PicaError, PicaErrorCode, Store, Unit,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
@@ -21,14 +25,21 @@ use std::{collections::BTreeMap, fmt::Debug, future::Future, sync::Arc};
 use tokio::try_join;
 use tracing::error;
 
+pub mod admin_cache;
+pub mod auth;
 pub mod common_enum;
 pub mod common_model;
 pub mod connection;
 pub mod connection_definition;
 pub mod connection_model_definition;
+pub mod connection_model_definition_bundle;
+pub mod connection_model_definition_diff;
 pub mod connection_model_schema;
 pub mod connection_oauth_definition;
+pub mod connection_provision;
 pub mod connection_variable_mapping;
+pub mod connection_variable_mapping_round_trip;
+pub mod connection_webhook;
 pub mod event_access;
 pub mod event_callback;
 pub mod events;
@@ -37,7 +48,9 @@ pub mod metrics;
 pub mod oauth;
 pub mod openapi;
 pub mod passthrough;
+pub mod passthrough_recording;
 pub mod platform;
+pub mod platform_flag;
 pub mod platform_page;
 pub mod schema_generator;
 pub mod secrets;
@@ -158,6 +171,32 @@ pub struct ReadResponse<T> {
     pub total: u64,
     pub skip: u64,
     pub limit: u64,
+    #[serde(default, rename = "hasMore")]
+    pub has_more: bool,
+    #[serde(default, rename = "totalPages")]
+    pub total_pages: u64,
+}
+
+impl<T> ReadResponse<T> {
+    /// Builds a page of results, deriving `hasMore` and `totalPages` from the
+    /// window. A `limit` of zero means the rows were not paginated.
+    pub fn new(rows: Vec<T>, total: u64, skip: u64, limit: u64) -> Self {
+        let total_pages = match (total, limit) {
+            (0, _) => 0,
+            (_, 0) => 1,
+            (total, limit) => total.div_ceil(limit),
+        };
+        let has_more = limit > 0 && skip.saturating_add(limit) < total;
+
+        Self {
+            rows,
+            total,
+            skip,
+            limit,
+            has_more,
+            total_pages,
+        }
+    }
 }
 
 pub async fn read<T, U>(
@@ -340,20 +379,69 @@ async fn get_connection(
     stores: &AppStores,
     cache: &ConnectionHeaderCache,
 ) -> Result<Arc<Connection>, PicaError> {
-    let connection = cache
-        .get_or_insert_with_filter(
-            &(access.ownership.id.clone(), connection_key.clone()),
-            stores.connection.clone(),
-            doc! {
-                "key": connection_key.to_str().map_err(|_| {
-                    ApplicationError::bad_request("Invalid connection key header", None)
-                })?,
-                "ownership.buildableId": access.ownership.id.as_ref(),
-                "deleted": false
-            },
-            None,
+    let invalid_key = || {
+        ApplicationError::bad_request(
+            "Invalid connection key header",
+            PicaErrorCode::InvalidConnectionKey.subtype(),
         )
-        .await?;
+    };
+
+    // Keys are often copy-pasted from dashboards along with surrounding spaces
+    let key = connection_key.to_str().map_err(|_| invalid_key())?.trim();
+    if key.is_empty() {
+        return Err(ApplicationError::bad_request(
+            "Connection key header is empty",
+            PicaErrorCode::ConnectionHeaderEmpty.subtype(),
+        ));
+    }
+
+    let cache_key = (
+        access.ownership.id.clone(),
+        HeaderValue::from_str(key).map_err(|_| invalid_key())?,
+    );
+    let now = Utc::now().timestamp_millis();
+
+    // During a key rotation the previous key keeps resolving to the same
+    // connection until the overlap window closes
+    let filter = doc! {
+        "$or": [
+            { "key": key },
+            {
+                "keyRotation.previousKey": key,
+                "keyRotation.expiresAt": { "$gt": now }
+            }
+        ],
+        "ownership.buildableId": access.ownership.id.as_ref(),
+        "deleted": false
+    };
+
+    let connection = match cache
+        .get_or_insert_with_filter(&cache_key, stores.connection.clone(), filter.clone(), None)
+        .await
+    {
+        Ok(connection) => connection,
+        // Keys whose case was changed are matched ignoring case as a fallback.
+        // Such lookups are not cached, so evicting a connection's exact keys
+        // still drops every cached entry of it.
+        Err(e) if e.status() == StatusCode::NOT_FOUND.as_u16() => stores
+            .connection
+            .collection
+            .find_one(filter)
+            .with_options(
+                FindOneOptions::builder()
+                    .collation(connection_key_collation())
+                    .build(),
+            )
+            .await
+            .map_err(PicaError::from)?
+            .ok_or_else(connection_not_found)?,
+        Err(e) => return Err(e),
+    };
+
+    if !connection.accepts_key(matching_key(&connection, key), now) {
+        cache.remove(&cache_key).await?;
+        return Err(connection_not_found());
+    }
 
     // If Oauth is enabled, fetching the latest secret (due to refresh, cache can't be used)
     if let Some(OAuth::Enabled { .. }) = connection.oauth {
@@ -374,7 +462,7 @@ async fn get_connection(
 
         let sparse_connection = match collection.find_one(filter).with_options(options).await {
             Ok(Some(data)) => data,
-            Ok(None) => return Err(ApplicationError::not_found("Connection", None)),
+            Ok(None) => return Err(connection_not_found()),
             Err(e) => {
                 error!("Error fetching connection: {:?}", e);
                 return Err(InternalError::unknown("Error fetching connection", None));
@@ -390,6 +478,64 @@ async fn get_connection(
     Ok(Arc::new(connection))
 }
 
+/// The stored key of the connection that `key` designates, ignoring case
+fn matching_key<'a>(connection: &'a Connection, key: &'a str) -> &'a str {
+    std::iter::once(&connection.key)
+        .chain(
+            connection
+                .key_rotation
+                .as_ref()
+                .map(|rotation| &rotation.previous_key),
+        )
+        .map(AsRef::as_ref)
+        .find(|candidate: &&str| candidate.eq_ignore_ascii_case(key))
+        .unwrap_or(key)
+}
+
+/// Filter of the live parent record `id` a request references. Parents are
+/// platform records shared by every caller unless they carry an ownership,
+/// in which case only the caller's own are found.
+pub(crate) fn parent_filter(id: &str, access: Option<&EventAccess>) -> Document {
+    let mut filter = doc! { "_id": id, "deleted": false };
+
+    if let Some(access) = access {
+        filter.insert(
+            "$or",
+            vec![
+                doc! { "ownership": Bson::Null },
+                doc! { "ownership.buildableId": access.ownership.id.as_ref() },
+            ],
+        );
+    }
+
+    filter
+}
+
+/// Compares connection keys ignoring case. The indexes of the
+/// `case-insensitive-connection-keys` migration use it too, as a query only
+/// uses indexes of the same collation.
+pub(crate) fn connection_key_collation() -> Collation {
+    Collation::builder()
+        .locale("en")
+        .strength(CollationStrength::Secondary)
+        .build()
+}
+
+/// Whether the write failed on a unique index
+pub(crate) fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    const DUPLICATE_KEY: i32 = 11000;
+
+    match &*error.kind {
+        ErrorKind::Write(WriteFailure::WriteError(e)) => e.code == DUPLICATE_KEY,
+        ErrorKind::Command(e) => e.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+fn connection_not_found() -> PicaError {
+    ApplicationError::not_found("Connection", PicaErrorCode::ConnectionNotFound.subtype())
+}
+
 async fn read_common<T, U>(
     headers: HeaderMap,
     access: Option<Extension<Arc<EventAccess>>>,
@@ -408,7 +554,8 @@ where
             e
         }),
         Some(headers),
-    );
+    )
+    .with_page_size(&state.config);
 
     let store = T::get_store(state.app_stores.clone());
 
@@ -430,12 +577,12 @@ where
     );
 
     let res = match try_join!(find, total) {
-        Ok((rows, total)) => ReadResponse {
-            rows: rows.into_iter().map(T::public).collect(),
-            skip: query.skip,
-            limit: query.limit,
+        Ok((rows, total)) => ReadResponse::new(
+            rows.into_iter().map(T::public).collect(),
             total,
-        },
+            query.skip,
+            query.limit,
+        ),
         Err(e) => {
             error!("Error reading from store: {e}");
             return Err(e);
@@ -444,3 +591,80 @@ where
 
     Ok(Json(ServerResponse::new("read", res)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use osentities::{
+        connection_definition::{ConnectionDefinitionType, Paths},
+        environment::Environment,
+        id::{prefix::IdPrefix, Id},
+        ownership::Ownership,
+        record_metadata::RecordMetadata,
+    };
+
+    #[test]
+    fn test_read_response_pagination_metadata() {
+        let empty = ReadResponse::<Value>::new(vec![], 0, 0, 20);
+        assert!(!empty.has_more);
+        assert_eq!(empty.total_pages, 0);
+
+        let first = ReadResponse::<Value>::new(vec![], 40, 0, 20);
+        assert!(first.has_more);
+        assert_eq!(first.total_pages, 2);
+
+        let last = ReadResponse::<Value>::new(vec![], 40, 20, 20);
+        assert!(!last.has_more);
+        assert_eq!(last.total_pages, 2);
+
+        let partial = ReadResponse::<Value>::new(vec![], 41, 20, 20);
+        assert!(partial.has_more);
+        assert_eq!(partial.total_pages, 3);
+
+        let unpaginated = ReadResponse::<Value>::new(vec![], 5, 0, 0);
+        assert!(!unpaginated.has_more);
+        assert_eq!(unpaginated.total_pages, 1);
+    }
+
+    #[test]
+    fn test_parent_filter_finds_shared_parents_and_the_callers_own() {
+        assert_eq!(
+            parent_filter("id", None),
+            doc! { "_id": "id", "deleted": false }
+        );
+
+        let access = EventAccess {
+            id: Id::now(IdPrefix::EventAccess),
+            name: "name".to_string(),
+            key: "key".to_string(),
+            namespace: "default".to_string(),
+            platform: "stripe".to_string(),
+            r#type: ConnectionDefinitionType::Api,
+            group: "group".to_string(),
+            ownership: Ownership::new("owner".to_string()),
+            paths: Paths::default(),
+            access_key: "access_key".to_string(),
+            environment: Environment::Test,
+            record_metadata: RecordMetadata::default(),
+            throughput: 1000,
+        };
+        assert_eq!(
+            parent_filter("id", Some(&access)),
+            doc! {
+                "_id": "id",
+                "deleted": false,
+                "$or": [
+                    { "ownership": null },
+                    { "ownership.buildableId": "owner" },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_response_serializes_camel_case_metadata() {
+        let value = serde_json::to_value(ReadResponse::<Value>::new(vec![], 3, 0, 2)).unwrap();
+        assert_eq!(value["hasMore"], true);
+        assert_eq!(value["totalPages"], 2);
+    }
+}
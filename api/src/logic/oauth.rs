@@ -5,16 +5,14 @@ use axum::{
     routing::post,
     Extension, Json, Router,
 };
-use chrono::{Duration, Utc};
+use chrono::Utc;
 use fake::Dummy;
-use http::{HeaderMap, HeaderName, HeaderValue};
 use mongodb::bson::doc;
 use osentities::{
     algebra::{MongoStore, TemplateExt},
-    api_model_config::ContentType,
     connection_definition::ConnectionDefinition,
     connection_oauth_definition::{
-        Computation, ConnectionOAuthDefinition, OAuthResponse, PlatformSecret, Settings,
+        ConnectionOAuthDefinition, OAuthResponse, PlatformSecret, Settings,
     },
     event_access::EventAccess,
     id::{prefix::IdPrefix, Id},
@@ -25,13 +23,10 @@ use osentities::{
 };
 use reqwest::Request;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use serde_json::{to_string_pretty, Value};
-use std::{
-    collections::{BTreeMap, HashMap},
-    str::FromStr,
-    sync::Arc,
-};
+use serde_json::Value;
+use std::sync::Arc;
 use tracing::{debug, error};
+use unified::oauth::{expires_at, oauth_request};
 use uuid::Uuid;
 
 pub fn get_router() -> Router<Arc<AppState>> {
@@ -137,11 +132,16 @@ async fn oauth_handler(
         conn_oauth_definition
     };
 
-    let request =
-        request(&conn_oauth_definition, &oauth_payload, &state.template).map_err(|e| {
-            error!("Failed to create oauth request: {}", e);
-            e
-        })?;
+    let request = request(
+        &state.http_client,
+        &conn_oauth_definition,
+        &oauth_payload,
+        &state.template,
+    )
+    .map_err(|e| {
+        error!("Failed to create oauth request: {}", e);
+        e
+    })?;
 
     debug!("Request: {:?}", request);
     let response = state
@@ -247,6 +247,7 @@ async fn oauth_handler(
         environment: user_event_access.environment,
         platform: platform.into(),
         secrets_service_id: secret.id(),
+        additional_secrets_service_ids: vec![],
         event_access_id: event_access.id,
         access_key: event_access.access_key,
         identity: Some(identity),
@@ -262,15 +263,11 @@ async fn oauth_handler(
         oauth: Some(OAuth::Enabled {
             connection_oauth_definition_id: conn_oauth_definition.id,
             expires_in: Some(oauth_secret.expires_in),
-            expires_at: Some(
-                chrono::Utc::now()
-                    .checked_add_signed(Duration::seconds(oauth_secret.expires_in as i64))
-                    .unwrap_or_else(chrono::Utc::now)
-                    .checked_sub_signed(Duration::seconds(120))
-                    .unwrap_or_else(chrono::Utc::now)
-                    .timestamp(),
-            ),
+            expires_at: Some(expires_at(oauth_secret.expires_in)),
         }),
+        key_rotation: None,
+        request_signing_secret: None,
+        quota: None,
         record_metadata: Default::default(),
     };
 
@@ -288,6 +285,7 @@ async fn oauth_handler(
 }
 
 fn request(
+    client: &reqwest::Client,
     oauth_definition: &ConnectionOAuthDefinition,
     payload: &OAuthPayload,
     template: &impl TemplateExt,
@@ -296,168 +294,14 @@ fn request(
         error!("Failed to serialize oauth payload: {}", e);
         InternalError::serialize_error(&e.to_string(), None)
     })?;
-    let computation = oauth_definition
-        .compute
-        .init
-        .computation
-        .clone()
-        .map(|computation| computation.compute::<Computation>(&payload))
-        .transpose()
-        .map_err(|e| {
-            error!("Failed to compute oauth payload: {}", e);
-            InternalError::script_error(e.message().as_ref(), None)
-        })?;
-
-    let headers = header(oauth_definition, computation.as_ref(), template)?;
-    let query = query(oauth_definition, computation.as_ref(), template)?;
-    let body = body(&payload, computation.as_ref(), template)?;
-
-    let request = reqwest::Client::new()
-        .post(oauth_definition.configuration.init.uri())
-        .headers(headers);
-
-    let request = match oauth_definition.configuration.init.content {
-        Some(ContentType::Json) => request.json(&body).query(&query),
-        Some(ContentType::Form) => request.form(&body).query(&query),
-        _ => request.query(&query),
-    };
 
-    request.build().map_err(|e| {
-        error!("Failed to build static request: {}", e);
-        InternalError::unknown(&e.to_string(), None)
-    })
-}
-
-fn query(
-    oauth_definition: &ConnectionOAuthDefinition,
-    computation: Option<&Computation>,
-    template: &impl TemplateExt,
-) -> Result<Option<Value>, PicaError> {
-    let query_params = oauth_definition
-        .configuration
-        .init
-        .query_params
-        .as_ref()
-        .map(|query_params| {
-            let mut map = HashMap::new();
-            for (key, value) in query_params {
-                let key = key.to_string();
-                let value = value.as_str();
-
-                map.insert(key, value.to_string());
-            }
-            map
-        });
-
-    match query_params {
-        Some(query_params) => {
-            let payload = computation.and_then(|computation| computation.clone().query_params);
-
-            let query_params_str = to_string_pretty(&query_params).map_err(|e| {
-                error!("Failed to serialize query params: {}", e);
-                InternalError::serialize_error(&e.to_string(), None)
-            })?;
-
-            let query_params = template.render(&query_params_str, payload.as_ref())?;
-
-            let query_params: BTreeMap<String, String> = serde_json::from_str(&query_params)
-                .map_err(|e| {
-                    error!("Failed to deserialize query params: {}", e);
-                    InternalError::deserialize_error(&e.to_string(), None)
-                })?;
-
-            Ok(Some(serde_json::to_value(query_params).map_err(|e| {
-                error!("Failed to serialize query params: {}", e);
-                InternalError::serialize_error(&e.to_string(), None)
-            })?))
-        }
-        None => Ok(None),
-    }
-}
-
-fn body(
-    payload: &Value,
-    computation: Option<&Computation>,
-    template: &impl TemplateExt,
-) -> Result<Option<Value>, PicaError> {
-    let body = computation.and_then(|computation| computation.clone().body);
-
-    match body {
-        Some(body) => {
-            let body_str = to_string_pretty(&body).map_err(|e| {
-                error!("Failed to serialize body: {}", e);
-                InternalError::serialize_error(&e.to_string(), None)
-            })?;
-
-            let body = template.render(&body_str, Some(payload))?;
-
-            Ok(Some(serde_json::from_str(&body).map_err(|e| {
-                error!("Failed to deserialize body: {}", e);
-                InternalError::deserialize_error(&e.to_string(), None)
-            })?))
-        }
-        None => Ok(None),
-    }
-}
-
-fn header(
-    conn_oauth_definition: &ConnectionOAuthDefinition,
-    computation: Option<&Computation>,
-    template: &impl TemplateExt,
-) -> Result<HeaderMap, PicaError> {
-    let headers = conn_oauth_definition
-        .configuration
-        .init
-        .headers
-        .as_ref()
-        .and_then(|headers| {
-            let mut map = HashMap::new();
-            for (key, value) in headers {
-                let key = key.to_string();
-                let value = value.to_str().ok()?;
-
-                map.insert(key, value.to_string());
-            }
-            Some(map)
-        });
-
-    match headers {
-        Some(headers) => {
-            let payload = computation.and_then(|computation| computation.clone().headers);
-
-            let headers_str = to_string_pretty(&headers).map_err(|e| {
-                error!("Failed to serialize headers: {}", e);
-                InternalError::serialize_error(&e.to_string(), None)
-            })?;
-
-            let headers = template.render(&headers_str, payload.as_ref())?;
-
-            let headers: BTreeMap<String, String> =
-                serde_json::from_str(&headers).map_err(|e| {
-                    error!("Failed to deserialize headers: {}", e);
-                    InternalError::deserialize_error(&e.to_string(), None)
-                })?;
-
-            headers
-                .iter()
-                .try_fold(HeaderMap::new(), |mut header_map, (key, value)| {
-                    let key = HeaderName::from_str(key).map_err(|e| {
-                        error!("Failed to parse header name: {}", e);
-                        InternalError::invalid_argument(&e.to_string(), None)
-                    })?;
-
-                    let value = HeaderValue::from_str(value).map_err(|e| {
-                        error!("Failed to parse header value: {}", e);
-                        InternalError::invalid_argument(&e.to_string(), None)
-                    })?;
-
-                    header_map.insert(key, value);
-
-                    Ok(header_map)
-                })
-        }
-        None => Ok(HeaderMap::new()),
-    }
+    oauth_request(
+        client,
+        &oauth_definition.configuration.init,
+        &oauth_definition.compute.init,
+        &payload,
+        template,
+    )
 }
 
 async fn get_conn_definition(
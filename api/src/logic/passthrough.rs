@@ -1,30 +1,59 @@
-use super::get_connection;
-use crate::{domain::metrics::Metric, server::AppState};
+use super::{
+    get_connection,
+    passthrough_recording::{find_recording, save_recording, vcr_mode},
+    platform_flag::get_platform_flag,
+};
+use crate::{
+    domain::{header_policy::HeaderNames, metrics::Metric, upstream_limit::UpstreamLimit},
+    server::AppState,
+};
 use axum::{
     extract::{Query, State},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     routing::get,
     Extension, Router,
 };
-use bson::doc;
+use bson::{doc, Document};
 use chrono::Utc;
-use http::{header::CONTENT_LENGTH, HeaderMap, HeaderName, Method, Uri};
+use futures::StreamExt;
+use http::{
+    header::{CONTENT_LENGTH, RETRY_AFTER},
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri,
+};
 use hyper::body::Bytes;
-use mongodb::options::FindOneOptions;
+use mongodb::options::{FindOneOptions, FindOptions};
 use osentities::{
-    constant::PICA_PASSTHROUGH_HEADER,
+    constant::{
+        PICA_EXTRACT_HEADER, PICA_EXTRACT_WARNING_HEADER, PICA_NONCE_HEADER,
+        PICA_PASSTHROUGH_HEADER, PICA_QUOTA_REMAINING_HEADER, PICA_RETRY_BUDGET_HEADER,
+        PICA_SIGNATURE_HEADER, PICA_TIMESTAMP_HEADER, PICA_VCR_HEADER,
+    },
     destination::{Action, Destination},
     encrypted_access_key::EncryptedAccessKey,
     event_access::EventAccess,
+    passthrough_recording::{
+        request_fingerprint, secret_param_names, PassthroughRecording, RecordedRequest,
+        RecordedResponse, VcrMode,
+    },
     prefix::IdPrefix,
-    AccessKey, ApplicationError, Event, Id, InternalError, Store, META, PASSWORD_LENGTH,
-    QUERY_BY_ID_PASSTHROUGH,
+    request_signature::{verify_request_signature, SignedRequest},
+    telemetry::{inject_trace_context, set_trace_parent},
+    AccessKey, ApplicationError, Connection, ErrorMeta, Event, Id, InternalError, PicaError,
+    PicaErrorCode, Store, META, PASSWORD_LENGTH, QUERY_BY_ID_PASSTHROUGH,
 };
+use semver::Version;
 use serde::Deserialize;
-use serde_json::json;
-use std::{collections::HashMap, sync::Arc};
-use tracing::{error, info};
-use unified::domain::UnifiedMetadataBuilder;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{error, field, info, info_span, warn, Instrument};
+use unified::{
+    domain::{RequestRetries, UnifiedMetadataBuilder},
+    retry_budget::RetryBudget,
+};
 
 pub fn get_router() -> Router<Arc<AppState>> {
     Router::new().route(
@@ -45,18 +74,18 @@ pub async fn passthrough_request(
     uri: Uri,
     method: Method,
     body: Bytes,
-) -> impl IntoResponse {
+) -> Result<Response, PicaError> {
     let Some(connection_key_header) = headers.get(&state.config.headers.connection_header) else {
         return Err(ApplicationError::bad_request(
             "Connection header not found",
-            None,
+            PicaErrorCode::ConnectionHeaderMissing.subtype(),
         ));
     };
 
     let Some(connection_secret_header) = headers.get(&state.config.headers.auth_header) else {
         return Err(ApplicationError::bad_request(
-            "Connection header not found",
-            None,
+            "Auth header not found",
+            PicaErrorCode::AuthHeaderMissing.subtype(),
         ));
     };
 
@@ -73,6 +102,80 @@ pub async fn passthrough_request(
     )
     .await?;
 
+    let flag = get_platform_flag(&state, &connection.platform).await;
+    if flag.passthrough_disabled {
+        let reason = flag
+            .reason
+            .as_deref()
+            .unwrap_or("Passthrough is temporarily disabled");
+
+        warn!(
+            "Rejecting passthrough for disabled platform {}: {reason}",
+            connection.platform
+        );
+
+        let mut res = ApplicationError::service_unavailable(
+            &format!(
+                "Passthrough is disabled for platform {}",
+                connection.platform
+            ),
+            PicaErrorCode::PlatformDisabled.subtype(),
+        )
+        .set_meta(&json!({
+            "platform": connection.platform,
+            "reason": reason,
+        }))
+        .into_response();
+        res.headers_mut().insert(
+            RETRY_AFTER,
+            flag.retry_after_secs
+                .unwrap_or(state.config.platform_disabled_retry_after_secs)
+                .into(),
+        );
+
+        return Ok(res);
+    }
+
+    // Connections with a signing secret only accept requests whose method,
+    // platform path (relative to the passthrough route), query string,
+    // timestamp, nonce and body are signed, so proxies in between cannot alter them, and only
+    // once within the skew window, so captured requests cannot be replayed
+    if let Some(secret) = connection.request_signing_secret.as_deref() {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+        };
+        let signed = SignedRequest {
+            method: method.as_str(),
+            path: uri.path(),
+            query: uri.query().unwrap_or_default(),
+            timestamp: header(PICA_TIMESTAMP_HEADER),
+            nonce: header(PICA_NONCE_HEADER),
+            body: &body,
+        };
+
+        if !verify_request_signature(secret, &signed, header(PICA_SIGNATURE_HEADER)) {
+            return Err(ApplicationError::unauthorized(
+                "Invalid or missing request signature",
+                PicaErrorCode::SignatureInvalid.subtype(),
+            ));
+        }
+
+        state.passthrough_nonces.admit(
+            connection.id,
+            signed.timestamp,
+            signed.nonce,
+            Utc::now().timestamp_millis(),
+        )?;
+    }
+
+    state
+        .config
+        .json_body_limits
+        .check_request(&headers, &body)?;
+
     let id = headers
         .get(QUERY_BY_ID_PASSTHROUGH)
         .and_then(|h| h.to_str().ok());
@@ -93,10 +196,107 @@ pub async fn passthrough_request(
 
     let Query(query_params) = query_params.unwrap_or_default();
 
+    let extract = headers
+        .get(PICA_EXTRACT_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.eq_ignore_ascii_case("true"))
+        .unwrap_or(state.config.passthrough_extract_responses);
+
+    headers.remove(PICA_EXTRACT_HEADER);
     headers.remove(&state.config.headers.auth_header);
     headers.remove(&state.config.headers.connection_header);
+    headers.remove(PICA_SIGNATURE_HEADER);
+    headers.remove(PICA_TIMESTAMP_HEADER);
+    headers.remove(PICA_NONCE_HEADER);
+
+    let vcr = vcr_mode(&headers, state.config.passthrough_vcr_mode)?;
+    headers.remove(PICA_VCR_HEADER);
+
+    // The caller's trace headers are read before the header policy drops
+    // them, the platform gets ours instead so its spans nest under the dispatch
+    let span = info_span!(
+        "passthrough",
+        platform = %connection.platform,
+        action = %format_args!("{method} {}", uri.path()),
+        status = field::Empty,
+        latency_ms = field::Empty,
+    );
+    set_trace_parent(&span, &headers);
+
+    state.config.passthrough_header_policy.apply(&mut headers);
+    inject_trace_context(&span, &mut headers);
+
+    // Replayed requests never reach the platform, so they are left out of the
+    // quota, metrics and events
+    if vcr == VcrMode::Replay {
+        let fingerprint =
+            request_fingerprint(&connection.id, &method, uri.path(), &query_params, &body);
+        let recording = find_recording(&state, &fingerprint).await?;
+
+        let status = StatusCode::from_u16(recording.response.status)
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let headers = recording
+            .response
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((
+                    HeaderName::try_from(name.as_str()).ok()?,
+                    HeaderValue::from_str(value).ok()?,
+                ))
+            })
+            .collect();
+        let bytes = Bytes::from(recording.response.body_bytes());
+
+        return Ok(respond(
+            &state,
+            &destination,
+            extract,
+            status,
+            headers,
+            bytes,
+            connection.id,
+        )
+        .await);
+    }
+
+    let recorded_request = if vcr == VcrMode::Record {
+        let secret_names = secret_names_of_platform(&state, &connection.platform).await?;
+        let request = RecordedRequest::new(
+            &method,
+            uri.path(),
+            &query_params,
+            &headers,
+            &body,
+            &secret_names,
+        );
+
+        Some((
+            request_fingerprint(&connection.id, &method, uri.path(), &query_params, &body),
+            request,
+            secret_names,
+        ))
+    } else {
+        None
+    };
+
+    // Every retry source spends from the same budget, so their retries and
+    // waits cannot stack up past it
+    let mut retry_budget = RetryBudget::new(
+        state.config.passthrough_retry_budget_max_attempts,
+        state.config.passthrough_retry_budget_max_delay_millis,
+    );
 
-    let model_execution_result = state
+    if let Some(res) = hold_for_upstream_limit(&state, &connection, &mut retry_budget).await {
+        return Ok(res);
+    }
+
+    // The quota counts the same requests the passthrough metric records
+    let metric = Metric::passthrough(connection.clone());
+    let quota_remaining = state.passthrough_quotas.acquire(&metric)?;
+
+    let started = Instant::now();
+    let dispatched = state
         .extractor_caller
         .dispatch_destination_request(
             Some(connection.clone()),
@@ -104,31 +304,65 @@ pub async fn passthrough_request(
             headers.clone(),
             query_params,
             Some(body.to_vec()),
+            &mut retry_budget,
         )
-        .await
-        .map_err(|e| {
+        .instrument(span.clone())
+        .await;
+
+    span.record(
+        "status",
+        match &dispatched {
+            Ok(result) => result.status().as_u16(),
+            Err(e) => e.status(),
+        },
+    );
+    span.record(
+        "latency_ms",
+        u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+    );
+
+    let model_execution_result = match dispatched {
+        Ok(result) => result,
+        Err(e) if e.error_code() == Some(PicaErrorCode::RetryBudgetExhausted) => {
+            warn!(
+                "Passthrough for connection {} used up its retry budget",
+                connection.id
+            );
+
+            return Ok(with_retry_budget(e.into_response(), &retry_budget));
+        }
+        Err(e) => {
             error!("Failed to execute connection model definition in passthrough endpoint. ID: {}, Error: {}", connection.id, e);
 
-            e
-        })?;
+            return Err(e);
+        }
+    };
 
-    let mut headers = HeaderMap::new();
+    let metric = metric
+        .with_status(model_execution_result.status())
+        .with_latency(started.elapsed())
+        .with_retries(
+            model_execution_result
+                .extensions()
+                .get::<RequestRetries>()
+                .map_or(0, |retries| retries.0),
+        );
 
-    model_execution_result
-        .headers()
-        .into_iter()
-        .for_each(|(key, value)| match key {
-            &CONTENT_LENGTH => {
-                headers.insert(CONTENT_LENGTH, value.clone());
-            }
-            _ => {
-                if let Ok(header_name) =
-                    HeaderName::try_from(format!("{PICA_PASSTHROUGH_HEADER}-{key}"))
-                {
-                    headers.insert(header_name, value.clone());
-                };
-            }
-        });
+    if let Some(limit) = UpstreamLimit::from_response(
+        connection.id,
+        model_execution_result.status(),
+        model_execution_result.headers(),
+        Utc::now().timestamp_millis(),
+    ) {
+        state
+            .upstream_limits
+            .observe(&connection.ownership.id, limit);
+    }
+
+    let mut headers = platform_response_headers(
+        model_execution_result.headers(),
+        &state.config.passthrough_unprefixed_response_headers,
+    );
 
     let connection_platform = connection.platform.to_string();
     let connection_platform_version = connection.platform_version.to_string();
@@ -141,43 +375,80 @@ pub async fn passthrough_request(
     let event_tx_c = state.event_tx.clone();
 
     tokio::spawn(async move {
+        // Nothing would receive the event, e.g. the server is shutting down
+        if event_tx_c.is_closed() {
+            return;
+        }
+
         let connection_secret_header: Option<String> =
             connection_secret_header.to_str().map(|a| a.to_owned()).ok();
 
-        let options = FindOneOptions::builder()
-            .projection(doc! {
-                "connectionPlatform": 1,
-                "connectionDefinitionId": 1,
-                "platformVersion": 1,
-                "key": 1,
-                "title": 1,
-                "name": 1,
-                "path": 1,
-                "action": 1,
-                "actionName": 1
-            })
-            .build();
+        let projection = doc! {
+            "connectionPlatform": 1,
+            "connectionDefinitionId": 1,
+            "platformVersion": 1,
+            "key": 1,
+            "title": 1,
+            "name": 1,
+            "path": 1,
+            "action": 1,
+            "actionName": 1
+        };
 
-        let query = if let Some(id) = id_str {
-            doc! {
-                "_id": id.to_string(),
-            }
+        let collection =
+            database_c.collection::<SparseCMD>(&Store::ConnectionModelDefinitions.to_string());
+
+        let cmd = if let Some(id) = id_str {
+            collection
+                .find_one(doc! {
+                    "_id": id.to_string(),
+                })
+                .with_options(FindOneOptions::builder().projection(projection).build())
+                .await
+                .ok()
+                .flatten()
         } else {
-            doc! {
-                "connectionPlatform": connection_platform.clone(),
-                "path": uri.path().to_string(),
-                "action": method.to_string().to_uppercase()
-            }
+            // Versions of a platform may share a path, so every one is fetched
+            // and the connection's own version is preferred. A document that
+            // cannot be read is skipped rather than losing the others.
+            let candidates = match collection
+                .clone_with_type::<Document>()
+                .find(doc! {
+                    "connectionPlatform": connection_platform.clone(),
+                    "path": uri.path().to_string(),
+                    "action": method.to_string().to_uppercase(),
+                    "deleted": false
+                })
+                .with_options(FindOptions::builder().projection(projection).build())
+                .await
+            {
+                Ok(cursor) => {
+                    cursor
+                        .filter_map(|document| async move {
+                            match document.map(bson::from_document::<SparseCMD>) {
+                                Ok(Ok(cmd)) => Some(cmd),
+                                Ok(Err(e)) => {
+                                    warn!("Skipping unreadable connection model definition: {e}");
+                                    None
+                                }
+                                Err(e) => {
+                                    warn!("Could not read connection model definitions: {e}");
+                                    None
+                                }
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .await
+                }
+                Err(e) => {
+                    warn!("Could not find the connection model definitions of the request: {e}");
+                    vec![]
+                }
+            };
+
+            select_definition(candidates, &connection_platform_version)
         };
 
-        let cmd = database_c
-            .collection::<SparseCMD>(&Store::ConnectionModelDefinitions.to_string())
-            .find_one(query)
-            .with_options(options.clone())
-            .await
-            .ok()
-            .flatten();
-
         if let (Some(cmd), Some(encrypted_access_key)) = (cmd, connection_secret_header) {
             if let Ok(encrypted_access_key) = EncryptedAccessKey::parse(&encrypted_access_key) {
                 tracing::info!("encrypted_access_key: {:?}", encrypted_access_key);
@@ -235,9 +506,7 @@ pub async fn passthrough_request(
                                 body,
                             );
 
-                            if let Err(e) = event_tx_c.send(event).await {
-                                error!("Could not send event to receiver: {e}");
-                            }
+                            event_tx_c.send(event);
                         } else {
                             tracing::error!("Error generating event for passthrough")
                         }
@@ -248,10 +517,8 @@ pub async fn passthrough_request(
         };
     });
 
-    let metric = Metric::passthrough(connection);
-    if let Err(e) = state.metric_tx.send(metric).await {
-        error!("Could not send metric to receiver: {e}");
-    }
+    let connection_id = connection.id;
+    state.metric_tx.send(metric);
 
     let bytes = model_execution_result.bytes().await.map_err(|e| {
         error!(
@@ -262,7 +529,180 @@ pub async fn passthrough_request(
         InternalError::script_error("Error retrieving bytes from response", None)
     })?;
 
-    Ok((request_status_code, headers, bytes))
+    if let Some((fingerprint, request, secret_names)) = recorded_request {
+        let response = RecordedResponse::new(
+            request_status_code.as_u16(),
+            &headers,
+            &bytes,
+            &secret_names,
+        );
+        save_recording(
+            &state,
+            PassthroughRecording::new(&connection, fingerprint, request, response),
+        );
+    }
+
+    if let Some(remaining) = quota_remaining {
+        headers.insert(PICA_QUOTA_REMAINING_HEADER, remaining.into());
+    }
+
+    let res = respond(
+        &state,
+        &destination,
+        extract,
+        request_status_code,
+        headers,
+        bytes,
+        connection_id,
+    )
+    .await;
+
+    Ok(with_retry_budget(res, &retry_budget))
+}
+
+/// Params of the platform's secret bindings, redacted from recordings along
+/// with the names that look like credentials
+async fn secret_names_of_platform(
+    state: &AppState,
+    platform: &str,
+) -> Result<Vec<String>, PicaError> {
+    let mappings = state
+        .app_stores
+        .connection_variable_mapping
+        .get_many(
+            Some(doc! { "connectionPlatform": platform, "deleted": false }),
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+    Ok(secret_param_names(
+        mappings.iter().flat_map(|mapping| &mapping.bindings),
+    ))
+}
+
+/// Waits out, or rejects with a `Retry-After`, requests to a connection whose
+/// platform reported its rate limit as (nearly) used up
+async fn hold_for_upstream_limit(
+    state: &AppState,
+    connection: &Arc<Connection>,
+    retry_budget: &mut RetryBudget,
+) -> Option<Response> {
+    if !state.config.upstream_rate_limit_backpressure {
+        return None;
+    }
+
+    let wait = state.upstream_limits.backoff(
+        &connection.id,
+        state.config.upstream_rate_limit_min_remaining,
+        Utc::now().timestamp_millis(),
+    )?;
+
+    if wait <= state.config.upstream_rate_limit_max_delay_millis {
+        if let Err(e) = retry_budget.delay(wait) {
+            return Some(with_retry_budget(e.into_response(), retry_budget));
+        }
+
+        tokio::time::sleep(Duration::from_millis(wait)).await;
+        return None;
+    }
+
+    warn!(
+        "Holding back passthrough for connection {} until its platform rate limit resets in {wait}ms",
+        connection.id
+    );
+
+    state.metric_tx.send(
+        Metric::passthrough(connection.clone())
+            .with_status(StatusCode::TOO_MANY_REQUESTS)
+            .with_circuit_open(),
+    );
+
+    let mut res = ApplicationError::too_many_requests(
+        "The platform rate limit for this connection is used up",
+        PicaErrorCode::UpstreamRateLimited.subtype(),
+    )
+    .set_meta(&json!({
+        "platform": connection.platform,
+        "retryAfterMillis": wait,
+    }))
+    .into_response();
+    res.headers_mut()
+        .insert(RETRY_AFTER, wait.div_ceil(1000).into());
+
+    Some(res)
+}
+
+/// Tells the caller how much of its retry budget a request consumed
+fn with_retry_budget(mut res: Response, retry_budget: &RetryBudget) -> Response {
+    if let Ok(consumed) = HeaderValue::from_str(&retry_budget.to_string()) {
+        res.headers_mut().insert(PICA_RETRY_BUDGET_HEADER, consumed);
+    }
+
+    res
+}
+
+/// Returns a platform response, extracting its data when asked to
+async fn respond(
+    state: &AppState,
+    destination: &Destination,
+    extract: bool,
+    status: StatusCode,
+    mut headers: HeaderMap,
+    bytes: Bytes,
+    connection_id: Id,
+) -> Response {
+    if !extract || !status.is_success() {
+        return (status, headers, bytes).into_response();
+    }
+
+    // Extraction is best effort, the raw body is returned when it fails
+    match extract_response(state, destination, &bytes).await {
+        Ok(extracted) => {
+            headers.remove(CONTENT_LENGTH);
+
+            (status, headers, extracted).into_response()
+        }
+        Err(e) => {
+            warn!(
+                "Could not extract passthrough response for connection {}: {e}",
+                connection_id
+            );
+
+            let warning = HeaderValue::from_str(e.message().as_ref())
+                .unwrap_or_else(|_| HeaderValue::from_static("Could not extract response"));
+            headers.insert(PICA_EXTRACT_WARNING_HEADER, warning);
+
+            (status, headers, bytes).into_response()
+        }
+    }
+}
+
+async fn extract_response(
+    state: &AppState,
+    destination: &Destination,
+    bytes: &Bytes,
+) -> Result<Bytes, PicaError> {
+    let extractor = state
+        .extractor_caller
+        .get_connection_model_definition(destination)
+        .await?
+        .and_then(|definition| definition.extractor_config)
+        .ok_or_else(|| {
+            InternalError::key_not_found("No extractor is configured for this action", None)
+        })?;
+
+    let body: Value = serde_json::from_slice(bytes).map_err(|e| {
+        InternalError::deserialize_error(&format!("Response is not JSON: {e}"), None)
+    })?;
+
+    let data = extractor.extract_data(&body)?;
+
+    serde_json::to_vec(&data)
+        .map(Bytes::from)
+        .map_err(|e| InternalError::serialize_error(&e.to_string(), None))
 }
 
 #[derive(Deserialize, Debug)]
@@ -279,3 +719,135 @@ pub struct SparseCMD {
     pub action: Method,
     pub action_name: String,
 }
+
+/// Prefixes the platform's response headers so they cannot collide with our
+/// own, except `Content-Length` and the allowlisted ones, which keep their name
+fn platform_response_headers(platform: &HeaderMap, unprefixed: &HeaderNames) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    platform.into_iter().for_each(|(key, value)| match key {
+        &CONTENT_LENGTH => {
+            headers.insert(CONTENT_LENGTH, value.clone());
+        }
+        // Appended, a platform may send several of these, e.g. `Set-Cookie`
+        key if unprefixed.contains(key) => {
+            headers.append(key, value.clone());
+        }
+        _ => {
+            if let Ok(header_name) =
+                HeaderName::try_from(format!("{PICA_PASSTHROUGH_HEADER}-{key}"))
+            {
+                headers.insert(header_name, value.clone());
+            };
+        }
+    });
+
+    headers
+}
+
+/// Picks the definition of the connection's platform version among those
+/// sharing a path and action, or of the latest version when none matches.
+/// Versions that are not semver sort below those that are.
+fn select_definition(candidates: Vec<SparseCMD>, platform_version: &str) -> Option<SparseCMD> {
+    let (exact, others): (Vec<_>, Vec<_>) = candidates
+        .into_iter()
+        .partition(|cmd| cmd.platform_version == platform_version);
+
+    exact.into_iter().next().or_else(|| {
+        others
+            .into_iter()
+            .max_by_key(|cmd| Version::parse(&cmd.platform_version).ok())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(platform_version: &str) -> SparseCMD {
+        SparseCMD {
+            connection_platform: "shopify".to_string(),
+            connection_definition_id: Id::now(IdPrefix::ConnectionDefinition),
+            platform_version: platform_version.to_string(),
+            key: format!("api::shopify::v{platform_version}::products::getMany"),
+            title: "List Products".to_string(),
+            name: "products".to_string(),
+            path: "/products".to_string(),
+            action: Method::GET,
+            action_name: "getMany".to_string(),
+        }
+    }
+
+    #[test]
+    fn selects_the_definition_of_the_connection_platform_version() {
+        let candidates = || vec![definition("2.0.0"), definition("1.0.0")];
+
+        let selected = select_definition(candidates(), "1.0.0").expect("definition");
+        assert_eq!(selected.platform_version, "1.0.0");
+
+        let selected = select_definition(candidates(), "2.0.0").expect("definition");
+        assert_eq!(selected.platform_version, "2.0.0");
+    }
+
+    #[test]
+    fn falls_back_to_the_latest_version_without_an_exact_match() {
+        let candidates = vec![
+            definition("1.10.0"),
+            definition("legacy"),
+            definition("1.9.0"),
+        ];
+
+        let selected = select_definition(candidates, "3.0.0").expect("definition");
+        assert_eq!(selected.platform_version, "1.10.0");
+
+        assert!(select_definition(vec![], "1.0.0").is_none());
+    }
+
+    #[test]
+    fn allowlisted_platform_headers_keep_their_name() {
+        let platform = HeaderMap::from_iter([
+            (
+                HeaderName::from_static("location"),
+                HeaderValue::from_static("https://platform.test/products?page=2"),
+            ),
+            (
+                HeaderName::from_static("etag"),
+                HeaderValue::from_static("\"v1\""),
+            ),
+            (
+                HeaderName::from_static("x-request-id"),
+                HeaderValue::from_static("req_1"),
+            ),
+            (CONTENT_LENGTH, HeaderValue::from_static("2")),
+        ]);
+
+        let headers = platform_response_headers(&platform, &"Location,ETag".parse().unwrap());
+
+        assert_eq!(
+            headers.get("location").unwrap(),
+            "https://platform.test/products?page=2"
+        );
+        assert_eq!(headers.get("etag").unwrap(), "\"v1\"");
+        assert_eq!(headers.get(CONTENT_LENGTH).unwrap(), "2");
+        assert_eq!(
+            headers.get("x-pica-passthrough-x-request-id").unwrap(),
+            "req_1"
+        );
+        assert!(headers.get("x-request-id").is_none());
+    }
+
+    #[test]
+    fn platform_headers_are_prefixed_by_default() {
+        let mut platform = HeaderMap::new();
+        platform.insert("location", HeaderValue::from_static("/next"));
+        platform.append("set-cookie", HeaderValue::from_static("a=1"));
+        platform.append("set-cookie", HeaderValue::from_static("b=2"));
+
+        let headers = platform_response_headers(&platform, &HeaderNames::default());
+        assert!(headers.get("location").is_none());
+        assert_eq!(headers.get("x-pica-passthrough-location").unwrap(), "/next");
+
+        let headers = platform_response_headers(&platform, &"set-cookie".parse().unwrap());
+        assert_eq!(headers.get_all("set-cookie").iter().count(), 2);
+    }
+}
@@ -0,0 +1,108 @@
+use super::{delete, read, PublicExt, RequestExt};
+use crate::server::{AppState, AppStores};
+use axum::{
+    routing::{delete as axum_delete, get},
+    Router,
+};
+use http::HeaderMap;
+use mongodb::bson::{doc, to_document};
+use osentities::{
+    algebra::MongoStore,
+    passthrough_recording::{PassthroughRecording, VcrMode},
+    ApplicationError, InternalError, PicaError, PICA_VCR_HEADER,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::error;
+
+pub fn get_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(read::<RecordingRequest, PassthroughRecording>))
+        .route(
+            "/:id",
+            axum_delete(delete::<RecordingRequest, PassthroughRecording>),
+        )
+}
+
+/// Recordings are only written by passthrough, this type only exposes them to
+/// the generic read and delete handlers
+pub struct RecordingRequest;
+
+impl PublicExt<PassthroughRecording> for RecordingRequest {}
+
+impl RequestExt for RecordingRequest {
+    type Output = PassthroughRecording;
+
+    fn get_store(stores: AppStores) -> MongoStore<Self::Output> {
+        stores.passthrough_recording.clone()
+    }
+}
+
+/// Mode of a passthrough request, `x-pica-vcr` taking precedence over the
+/// configured default
+pub fn vcr_mode(headers: &HeaderMap, default: VcrMode) -> Result<VcrMode, PicaError> {
+    let Some(header) = headers.get(PICA_VCR_HEADER) else {
+        return Ok(default);
+    };
+
+    header
+        .to_str()
+        .ok()
+        .and_then(|mode| mode.parse().ok())
+        .ok_or_else(|| {
+            ApplicationError::bad_request(
+                &format!("{PICA_VCR_HEADER} must be one of off, record or replay"),
+                None,
+            )
+        })
+}
+
+pub async fn find_recording(
+    state: &AppState,
+    fingerprint: &str,
+) -> Result<PassthroughRecording, PicaError> {
+    state
+        .app_stores
+        .passthrough_recording
+        .get_one(doc! { "fingerprint": fingerprint, "deleted": false })
+        .await?
+        .ok_or_else(|| {
+            ApplicationError::not_found("No recording matches this passthrough request", None)
+                .set_meta(&json!({ "fingerprint": fingerprint }))
+        })
+}
+
+/// Stores a recording in the background, replacing an earlier recording of
+/// the same request while keeping its id
+pub fn save_recording(state: &AppState, recording: PassthroughRecording) {
+    let store = state.app_stores.passthrough_recording.clone();
+
+    tokio::spawn(async move {
+        let result = async {
+            let mut document = to_document(&recording)
+                .map_err(|e| InternalError::serialize_error(&e.to_string(), None))?;
+            document.remove("_id");
+
+            store
+                .collection
+                .update_one(
+                    doc! { "fingerprint": &recording.fingerprint },
+                    doc! {
+                        "$set": document,
+                        "$setOnInsert": { "_id": recording.id.to_string() },
+                    },
+                )
+                .upsert(true)
+                .await
+                .map_err(PicaError::from)
+        }
+        .await;
+
+        if let Err(e) = result {
+            error!(
+                "Could not save passthrough recording for connection {}: {e}",
+                recording.connection_id
+            );
+        }
+    });
+}
@@ -1,19 +1,28 @@
-use super::{create, delete, read, update, HookExt, PublicExt, RequestExt};
-use crate::server::{AppState, AppStores};
+use super::{create, delete, read, update, HookExt, PublicExt, ReadResponse, RequestExt};
+use crate::{
+    helper::shape_mongo_filter,
+    router::ServerResponse,
+    server::{AppState, AppStores},
+};
 use axum::{
-    routing::{patch, post},
-    Router,
+    extract::{Query, State},
+    routing::{get, patch, post},
+    Extension, Json, Router,
 };
 use fake::Dummy;
-use mongodb::bson::doc;
+use http::HeaderMap;
+use mongodb::bson::{self, doc, Document};
 use osentities::{
     algebra::MongoStore,
+    event_access::EventAccess,
     id::{prefix::IdPrefix, Id},
     ownership::Owners,
-    PlatformData,
+    InternalError, PicaError, PlatformData, Store,
 };
+use semver::Version;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
+use tracing::error;
 
 pub fn get_router() -> Router<Arc<AppState>> {
     Router::new()
@@ -21,6 +30,7 @@ pub fn get_router() -> Router<Arc<AppState>> {
             "/",
             post(create::<CreateRequest, PlatformData>).get(read::<CreateRequest, PlatformData>),
         )
+        .route("/summary", get(get_platforms_summary))
         .route(
             "/:id",
             patch(update::<CreateRequest, PlatformData>)
@@ -75,3 +85,151 @@ impl RequestExt for CreateRequest {
         stores.platform.clone()
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformSummary {
+    pub platform: String,
+    pub supported_actions: u64,
+    pub latest_platform_version: Option<String>,
+    pub has_variable_mapping: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlatformGroup {
+    #[serde(rename = "_id")]
+    platform: String,
+    supported_actions: u64,
+    platform_versions: Vec<String>,
+    mappings: Vec<Document>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PlatformPage {
+    rows: Vec<PlatformGroup>,
+    total: Vec<Document>,
+}
+
+impl From<PlatformGroup> for PlatformSummary {
+    fn from(group: PlatformGroup) -> Self {
+        Self {
+            platform: group.platform,
+            supported_actions: group.supported_actions,
+            latest_platform_version: latest_version(group.platform_versions),
+            has_variable_mapping: !group.mappings.is_empty(),
+        }
+    }
+}
+
+/// Highest of the versions by semver, those that are not semver sorting below
+fn latest_version(versions: Vec<String>) -> Option<String> {
+    versions.into_iter().max_by(|a, b| {
+        Version::parse(a)
+            .ok()
+            .cmp(&Version::parse(b).ok())
+            .then_with(|| a.cmp(b))
+    })
+}
+
+/// Every platform with connection model definitions, sorted by name, with the
+/// number of its supported actions, its latest version and whether any
+/// variable mapping was set up for it. Query params filter the definitions.
+pub async fn get_platforms_summary(
+    headers: HeaderMap,
+    access: Option<Extension<Arc<EventAccess>>>,
+    query: Option<Query<BTreeMap<String, String>>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<ReadResponse<PlatformSummary>>>, PicaError> {
+    let query = shape_mongo_filter(query, access.map(|Extension(e)| e), Some(headers))
+        .with_page_size(&state.config);
+
+    let mut rows = vec![doc! { "$skip": query.skip as i64 }];
+    if query.limit > 0 {
+        rows.push(doc! { "$limit": query.limit as i64 });
+    }
+    rows.push(doc! {
+        "$lookup": {
+            "from": Store::ConnectionVariableMappings.to_string(),
+            "let": { "platform": "$_id" },
+            "pipeline": [
+                {
+                    "$match": {
+                        "$expr": { "$eq": ["$connectionPlatform", "$$platform"] },
+                        "deleted": false,
+                    }
+                },
+                { "$limit": 1 },
+                { "$project": { "_id": 1 } },
+            ],
+            "as": "mappings",
+        }
+    });
+
+    let pipeline = vec![
+        doc! { "$match": query.filter },
+        doc! {
+            "$group": {
+                "_id": "$connectionPlatform",
+                "supportedActions": {
+                    "$sum": { "$cond": [{ "$eq": ["$supported", true] }, 1, 0] }
+                },
+                "platformVersions": { "$addToSet": "$platformVersion" },
+            }
+        },
+        doc! { "$sort": { "_id": 1 } },
+        doc! {
+            "$facet": {
+                "rows": rows,
+                "total": [{ "$count": "count" }],
+            }
+        },
+    ];
+
+    let page = state
+        .app_stores
+        .model_config
+        .aggregate(pipeline)
+        .await?
+        .into_iter()
+        .next()
+        .map(bson::from_document::<PlatformPage>)
+        .transpose()
+        .map_err(|e| {
+            error!("Could not deserialize platforms summary: {e}");
+            InternalError::deserialize_error(&e.to_string(), None)
+        })?
+        .unwrap_or_default();
+
+    let total = page
+        .total
+        .first()
+        .and_then(|total| total.get_i32("count").ok())
+        .unwrap_or_default() as u64;
+    let rows = page.rows.into_iter().map(PlatformSummary::from).collect();
+
+    Ok(Json(ServerResponse::new(
+        "platforms",
+        ReadResponse::new(rows, total, query.skip, query.limit),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_version_compares_by_semver() {
+        let versions = |versions: &[&str]| versions.iter().map(|v| v.to_string()).collect();
+
+        assert_eq!(
+            latest_version(versions(&["1.9.0", "1.10.0", "legacy"])),
+            Some("1.10.0".to_string())
+        );
+        assert_eq!(
+            latest_version(versions(&["beta", "alpha"])),
+            Some("beta".to_string())
+        );
+        assert_eq!(latest_version(vec![]), None);
+    }
+}
@@ -0,0 +1,175 @@
+use super::ReadResponse;
+use crate::{helper::shape_mongo_filter, router::ServerResponse, server::AppState};
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Extension, Json, Router,
+};
+use cache::local::LocalCacheExt;
+use chrono::Utc;
+use mongodb::bson::doc;
+use osentities::{
+    connection_model_definition_audit::AuditActor, flag::PlatformFlag, ApplicationError, Claims,
+    PicaError,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::try_join;
+use tracing::{error, warn};
+
+pub fn get_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(read_flags))
+        .route("/:platform", get(read_flag).put(update_flag))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateFlagRequest {
+    pub passthrough_disabled: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Flag of a platform as seen by the passthrough hot path. Lookups are cached
+/// for `PLATFORM_FLAG_CACHE_TTL_SECS`, including platforms without a flag, and
+/// a failing store never blocks passthrough.
+pub async fn get_platform_flag(state: &AppState, platform: &str) -> PlatformFlag {
+    let key: Arc<str> = Arc::from(platform);
+
+    state
+        .platform_flags_cache
+        .get_or_insert_with_fn(&key, || async {
+            Ok(state
+                .app_stores
+                .platform_flag
+                .get_one(doc! { "platform": platform })
+                .await?
+                .unwrap_or_else(|| PlatformFlag::enabled(platform)))
+        })
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Could not read the flag of platform {platform}: {e}");
+            PlatformFlag::enabled(platform)
+        })
+}
+
+async fn read_flags(
+    query: Option<Query<BTreeMap<String, String>>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<ReadResponse<PlatformFlag>>>, PicaError> {
+    let mut query = shape_mongo_filter(query, None, None).with_page_size(&state.config);
+    // Flags are overwritten in place and never soft deleted
+    query.filter.remove("deleted");
+
+    let store = &state.app_stores.platform_flag;
+    let (rows, total) = try_join!(
+        store.get_many(
+            Some(query.filter.clone()),
+            None,
+            Some(doc! { "platform": 1 }),
+            Some(query.limit),
+            Some(query.skip),
+        ),
+        store.count(query.filter, None)
+    )?;
+
+    Ok(Json(ServerResponse::new(
+        "read",
+        ReadResponse::new(rows, total, query.skip, query.limit),
+    )))
+}
+
+async fn read_flag(
+    Path(platform): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ServerResponse<PlatformFlag>>, PicaError> {
+    let flag = state
+        .app_stores
+        .platform_flag
+        .get_one(doc! { "platform": &platform })
+        .await?
+        .unwrap_or_else(|| PlatformFlag::enabled(&platform));
+
+    Ok(Json(ServerResponse::new("read", flag)))
+}
+
+/// Flags apply to every customer of a platform, so only service tokens may
+/// change them
+fn authorize(claims: Option<&Claims>) -> Result<(), PicaError> {
+    match claims {
+        Some(claims) if claims.is_buildable_core => Ok(()),
+        _ => Err(ApplicationError::forbidden(
+            "Only service tokens can update platform flags",
+            None,
+        )),
+    }
+}
+
+async fn update_flag(
+    claims: Option<Extension<Arc<Claims>>>,
+    Path(platform): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UpdateFlagRequest>,
+) -> Result<Json<ServerResponse<PlatformFlag>>, PicaError> {
+    authorize(claims.as_ref().map(|Extension(claims)| claims.as_ref()))?;
+
+    let flag = PlatformFlag {
+        platform: platform.clone(),
+        passthrough_disabled: req.passthrough_disabled,
+        reason: req.reason,
+        retry_after_secs: req.retry_after_secs,
+        updated_by: Some(
+            claims
+                .map(|Extension(claims)| AuditActor {
+                    id: claims.id.clone(),
+                    email: Some(claims.email.clone()),
+                })
+                .unwrap_or_else(AuditActor::system),
+        ),
+        updated_at: Utc::now().timestamp_millis(),
+    };
+
+    state
+        .app_stores
+        .platform_flag
+        .collection
+        .replace_one(doc! { "platform": &platform }, &flag)
+        .upsert(true)
+        .await
+        .map_err(PicaError::from)?;
+
+    // Other instances pick the change up once their cached entry expires
+    if let Err(e) = state
+        .platform_flags_cache
+        .remove(&Arc::from(platform.as_str()))
+        .await
+    {
+        error!("Could not invalidate the flag of platform {platform}: {e}");
+    }
+
+    Ok(Json(ServerResponse::new("update", flag)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_service_tokens_update_flags() {
+        let user = Claims {
+            buildable_id: "buildable-1".to_string(),
+            ..Default::default()
+        };
+        let service = Claims {
+            is_buildable_core: true,
+            ..Default::default()
+        };
+
+        assert_eq!(authorize(Some(&user)).unwrap_err().status(), 403);
+        assert_eq!(authorize(None).unwrap_err().status(), 403);
+        assert!(authorize(Some(&service)).is_ok());
+    }
+}
@@ -200,12 +200,7 @@ pub async fn get_common_models_projections(
 
     let len = common_models.len();
 
-    Ok(Json(ReadResponse {
-        rows: common_models,
-        total: len as u64,
-        skip: 0,
-        limit: 0,
-    }))
+    Ok(Json(ReadResponse::new(common_models, len as u64, 0, 0)))
 }
 
 #[derive(Debug, Deserialize)]
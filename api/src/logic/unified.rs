@@ -19,7 +19,7 @@ use osentities::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Instant};
 use tracing::error;
 use unified::domain::RequestCrudBuilder;
 
@@ -256,6 +256,7 @@ pub async fn process_request(
         connection.platform, connection.platform_version, model_name, action_name,
     );
 
+    let started = Instant::now();
     let mut response = state
         .extractor_caller
         .dispatch_unified_request(
@@ -283,6 +284,8 @@ pub async fn process_request(
             );
         })?;
 
+    let latency = started.elapsed();
+
     *response.response.headers_mut() = response
         .response
         .headers()
@@ -352,16 +355,14 @@ pub async fn process_request(
                 parts.headers.clone(),
                 body,
             );
-            if let Err(e) = state.event_tx.send(event).await {
-                error!("Could not send event to receiver: {e}");
-            }
+            state.event_tx.send(event);
         }
     };
 
-    let metric = Metric::unified(connection.clone(), action);
-    if let Err(e) = state.metric_tx.send(metric).await {
-        error!("Could not send metric to receiver: {e}");
-    }
+    let metric = Metric::unified(connection.clone(), action)
+        .with_status(parts.status)
+        .with_latency(latency);
+    state.metric_tx.send(metric);
 
     let response = Response::from_parts(parts, ());
 
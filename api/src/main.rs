@@ -12,10 +12,14 @@ fn main() -> Result<()> {
         otlp_url: config.otlp_endpoint.clone(),
     };
 
-    let runtime = tokio::runtime::Builder::new_multi_thread()
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder
         .worker_threads(config.worker_threads.unwrap_or(num_cpus::get()))
-        .enable_all()
-        .build()?;
+        .enable_all();
+    if let Some(max_blocking_threads) = config.max_blocking_threads {
+        builder.max_blocking_threads(max_blocking_threads);
+    }
+    let runtime = builder.build()?;
 
     runtime.block_on(async move {
         let subscriber = get_subscriber(
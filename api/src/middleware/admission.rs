@@ -0,0 +1,186 @@
+use crate::domain::config::ConnectionsConfig;
+use axum::{
+    body::Body,
+    extract::State,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header::RETRY_AFTER, Request};
+use osentities::ApplicationError;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::warn;
+
+/// Bounds the number of requests served concurrently. Requests over the
+/// ceiling wait in a bounded queue for a slot and are shed with a 429 once the
+/// queue is full or their wait times out, instead of piling up unboundedly.
+#[derive(Debug)]
+pub struct AdmissionController {
+    semaphore: Arc<Semaphore>,
+    max_concurrency: usize,
+    max_queued: usize,
+    queued: AtomicUsize,
+    queue_timeout: Duration,
+    retry_after_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmissionStats {
+    pub in_flight: usize,
+    pub queued: usize,
+    pub max_concurrency: usize,
+    pub max_queued: usize,
+}
+
+/// Decrements the queue length even when the waiting request is cancelled
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl AdmissionController {
+    pub fn new(
+        max_concurrency: usize,
+        max_queued: usize,
+        queue_timeout: Duration,
+        retry_after_secs: u64,
+    ) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_concurrency)),
+            max_concurrency,
+            max_queued,
+            queued: AtomicUsize::new(0),
+            queue_timeout,
+            retry_after_secs,
+        }
+    }
+
+    pub fn from_config(config: &ConnectionsConfig) -> Self {
+        Self::new(
+            config.passthrough_max_concurrency,
+            config.passthrough_max_queued,
+            Duration::from_millis(config.passthrough_queue_timeout_millis),
+            config.passthrough_retry_after_secs,
+        )
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_concurrency > 0
+    }
+
+    pub fn stats(&self) -> AdmissionStats {
+        AdmissionStats {
+            in_flight: self.max_concurrency - self.semaphore.available_permits(),
+            queued: self.queued.load(Ordering::SeqCst),
+            max_concurrency: self.max_concurrency,
+            max_queued: self.max_queued,
+        }
+    }
+
+    /// Returns a permit held for the lifetime of the request, or `None` when
+    /// the request has to be shed
+    pub async fn admit(&self) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return Some(permit);
+        }
+
+        if self.queued.fetch_add(1, Ordering::SeqCst) >= self.max_queued {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        let _slot = QueueSlot(&self.queued);
+
+        tokio::time::timeout(self.queue_timeout, self.semaphore.clone().acquire_owned())
+            .await
+            .ok()?
+            .ok()
+    }
+}
+
+pub async fn admission_middleware(
+    State(controller): State<Arc<AdmissionController>>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, Response> {
+    if !controller.is_enabled() {
+        return Ok(next.run(req).await);
+    }
+
+    let Some(_permit) = controller.admit().await else {
+        let stats = controller.stats();
+        warn!(
+            "Shedding {} {}: {} in flight, {} queued",
+            req.method(),
+            req.uri().path(),
+            stats.in_flight,
+            stats.queued
+        );
+
+        let mut res =
+            ApplicationError::too_many_requests("Too many concurrent requests, retry later", None)
+                .into_response();
+        res.headers_mut()
+            .insert(RETRY_AFTER, controller.retry_after_secs.into());
+
+        return Err(res);
+    };
+
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_admit_queues_then_sheds() {
+        let controller = Arc::new(AdmissionController::new(1, 1, Duration::from_millis(50), 1));
+
+        let permit = controller.admit().await.expect("first request is admitted");
+        assert_eq!(controller.stats().in_flight, 1);
+
+        // Waits in the queue and times out while the slot is held
+        let waiting = tokio::spawn({
+            let controller = controller.clone();
+            async move { controller.admit().await.is_some() }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(controller.stats().queued, 1);
+
+        // The queue is full
+        assert!(controller.admit().await.is_none());
+
+        assert!(!waiting.await.unwrap());
+        assert_eq!(controller.stats().queued, 0);
+
+        drop(permit);
+        assert_eq!(controller.stats().in_flight, 0);
+        assert!(controller.admit().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_gets_released_slot() {
+        let controller = Arc::new(AdmissionController::new(1, 1, Duration::from_secs(5), 1));
+
+        let permit = controller.admit().await.unwrap();
+        let waiting = tokio::spawn({
+            let controller = controller.clone();
+            async move { controller.admit().await.is_some() }
+        });
+        tokio::task::yield_now().await;
+
+        drop(permit);
+        assert!(waiting.await.unwrap());
+    }
+}
@@ -1,13 +1,14 @@
 use crate::server::AppState;
 use axum::{body::Body, extract::State, middleware::Next, response::Response};
 use http::Request;
-use jsonwebtoken::{DecodingKey, Validation};
+use jsonwebtoken::{errors::ErrorKind, DecodingKey, Validation};
 use osentities::{
     constant::{DEFAULT_AUDIENCE, DEFAULT_ISSUER, FALLBACK_AUDIENCE, FALLBACK_ISSUER},
-    ApplicationError, Claims, PicaError, BEARER_PREFIX,
+    ApplicationError, Claims, ErrorMeta, PicaError, PicaErrorCode, BEARER_PREFIX,
 };
-use serde::Deserialize;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashSet, sync::Arc};
 use tracing::{info, warn};
 
 /// Minimal claims struct for peeking at isBuildableCore without full validation
@@ -62,28 +63,182 @@ impl JwtState {
         let token_data = jsonwebtoken::decode::<PartialClaims>(token, &dummy_key, &peek_validation)
             .map_err(|e| {
                 warn!("Failed to decode token claims: {:?}", e);
-                ApplicationError::unauthorized("Invalid token format", None)
+                match e.kind() {
+                    ErrorKind::ExpiredSignature => ApplicationError::unauthorized(
+                        "Token has expired",
+                        PicaErrorCode::TokenExpired.subtype(),
+                    ),
+                    _ => ApplicationError::unauthorized(
+                        "Invalid token format",
+                        PicaErrorCode::TokenMalformed.subtype(),
+                    ),
+                }
             })?;
 
-        if token_data.claims.is_buildable_core {
+        self.select_key(token_data.claims).map(|(_, key)| key)
+    }
+
+    fn select_key(&self, claims: PartialClaims) -> Result<(SecretBranch, DecodingKey), PicaError> {
+        if claims.is_buildable_core {
             // isBuildableCore: true → Core token (service-to-service)
             // Uses: BUILDABLE_SECRET + JWT_SECRET
             info!("Token type: core (isBuildableCore: true)");
-            Ok(self.core_decoding_key.clone())
+            Ok((SecretBranch::Core, self.core_decoding_key.clone()))
         } else {
             // isBuildableCore: false → User token
             // Uses: JWT_SECRET + buildableId
-            let buildable_id = token_data.claims.buildable_id.ok_or_else(|| {
+            let buildable_id = claims.buildable_id.ok_or_else(|| {
                 warn!("User token missing buildableId");
-                ApplicationError::unauthorized("Invalid token: missing buildableId", None)
+                ApplicationError::unauthorized(
+                    "Invalid token: missing buildableId",
+                    PicaErrorCode::TokenMalformed.subtype(),
+                )
             })?;
             info!("Token type: user (buildableId: {})", buildable_id);
             let secret = format!("{}{}", self.base_jwt_secret, buildable_id);
-            Ok(DecodingKey::from_secret(secret.as_bytes()))
+            Ok((
+                SecretBranch::User { buildable_id },
+                DecodingKey::from_secret(secret.as_bytes()),
+            ))
+        }
+    }
+
+    /// Validates the token the way every JWT secured route does
+    pub fn validate(&self, token: &str) -> Result<Claims, PicaError> {
+        // Get the appropriate decoding key based on token type (direct matching)
+        let decoding_key = self.get_decoding_key(token)?;
+
+        // Validate the token with the selected key
+        match jsonwebtoken::decode::<Claims>(token, &decoding_key, &self.validation) {
+            Ok(decoded_token) => {
+                info!("JWT token validated successfully");
+                Ok(decoded_token.claims)
+            }
+            Err(e) => {
+                warn!("JWT validation failed: {:?}", e);
+                match e.kind() {
+                    ErrorKind::ExpiredSignature => Err(ApplicationError::unauthorized(
+                        "Token has expired",
+                        PicaErrorCode::TokenExpired.subtype(),
+                    )),
+                    _ => Err(ApplicationError::forbidden(
+                        "You are not authorized to access this resource",
+                        PicaErrorCode::TokenInvalid.subtype(),
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Reports each check `validate` makes on the token separately, for
+    /// debugging rejected tokens. Claims are decoded without verification, the
+    /// secrets themselves are never part of the report.
+    pub fn introspect(&self, token: &str) -> TokenIntrospection {
+        let rejection = self.validate(token).err().map(|e| TokenRejection {
+            status: e.status(),
+            error_code: e.error_code(),
+            message: e.message().as_ref().to_string(),
+        });
+
+        let algorithm = jsonwebtoken::decode_header(token)
+            .ok()
+            .map(|header| format!("{:?}", header.alg));
+
+        // Every check but the one under test is turned off
+        let mut unchecked = Validation::default();
+        unchecked.algorithms = self.validation.algorithms.clone();
+        unchecked.validate_exp = false;
+        unchecked.required_spec_claims = HashSet::new();
+        let mut unsigned = unchecked.clone();
+        unsigned.insecure_disable_signature_validation();
+
+        let dummy_key = DecodingKey::from_secret(b"dummy");
+        let claims = jsonwebtoken::decode::<Value>(token, &dummy_key, &unsigned)
+            .ok()
+            .map(|data| data.claims);
+
+        let matches = |validation: Validation| {
+            claims
+                .as_ref()
+                .map(|_| jsonwebtoken::decode::<Value>(token, &dummy_key, &validation).is_ok())
+        };
+        let mut audience = unsigned.clone();
+        audience.aud = self.validation.aud.clone();
+        let audience_matches = matches(audience);
+        let mut issuer = unsigned;
+        issuer.iss = self.validation.iss.clone();
+        let issuer_matches = matches(issuer);
+
+        let expires_at = claims
+            .as_ref()
+            .and_then(|claims| claims.get("exp"))
+            .and_then(Value::as_i64);
+        // Same clock and leeway as the validation of the token
+        let now = jsonwebtoken::get_current_timestamp().saturating_sub(self.validation.leeway);
+        let expired = expires_at.map(|exp| exp < now as i64);
+
+        let selected = claims
+            .clone()
+            .and_then(|claims| serde_json::from_value::<PartialClaims>(claims).ok())
+            .and_then(|claims| self.select_key(claims).ok());
+        let signature_valid = selected
+            .as_ref()
+            .is_some_and(|(_, key)| jsonwebtoken::decode::<Value>(token, key, &unchecked).is_ok());
+
+        TokenIntrospection {
+            valid: rejection.is_none(),
+            rejection,
+            algorithm,
+            secret: selected.map(|(branch, _)| branch),
+            signature_valid,
+            expires_at,
+            expired,
+            audience_matches,
+            issuer_matches,
+            claims,
         }
     }
 }
 
+/// Secret a token is verified with, picked from its claims
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SecretBranch {
+    /// Service tokens (`isBuildableCore`), `BUILDABLE_SECRET` + `JWT_SECRET`
+    Core,
+    /// User tokens, `JWT_SECRET` + `buildableId`
+    #[serde(rename_all = "camelCase")]
+    User { buildable_id: String },
+}
+
+/// Response the JWT secured routes give a rejected token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenRejection {
+    pub status: u16,
+    pub error_code: Option<PicaErrorCode>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenIntrospection {
+    /// Whether the JWT secured routes accept the token
+    pub valid: bool,
+    pub rejection: Option<TokenRejection>,
+    /// Algorithm named in the token header
+    pub algorithm: Option<String>,
+    /// None when the claims do not say which secret applies
+    pub secret: Option<SecretBranch>,
+    pub signature_valid: bool,
+    pub expires_at: Option<i64>,
+    pub expired: Option<bool>,
+    pub audience_matches: Option<bool>,
+    pub issuer_matches: Option<bool>,
+    /// Claims as sent, None when the token cannot be decoded
+    pub claims: Option<Value>,
+}
+
 pub async fn jwt_auth_middleware(
     State(state): State<Arc<JwtState>>,
     mut req: Request<Body>,
@@ -93,7 +248,7 @@ pub async fn jwt_auth_middleware(
         info!("missing authorization header");
         return Err(ApplicationError::unauthorized(
             "You are not authorized to access this resource",
-            None,
+            PicaErrorCode::TokenMissing.subtype(),
         ));
     };
 
@@ -101,7 +256,7 @@ pub async fn jwt_auth_middleware(
         info!("invalid authorization header");
         return Err(ApplicationError::unauthorized(
             "You are not authorized to access this resource",
-            None,
+            PicaErrorCode::TokenMalformed.subtype(),
         ));
     };
 
@@ -109,28 +264,116 @@ pub async fn jwt_auth_middleware(
         info!("invalid authorization header");
         return Err(ApplicationError::unauthorized(
             "You are not authorized to access this resource",
-            None,
+            PicaErrorCode::TokenMalformed.subtype(),
         ));
     }
 
     let token = &auth_header[BEARER_PREFIX.len()..];
 
-    // Get the appropriate decoding key based on token type (direct matching)
-    let decoding_key = state.get_decoding_key(token)?;
+    let claims = state.validate(token)?;
+    req.extensions_mut().insert(Arc::new(claims));
 
-    // Validate the token with the selected key
-    match jsonwebtoken::decode::<Claims>(token, &decoding_key, &state.validation) {
-        Ok(decoded_token) => {
-            info!("JWT token validated successfully");
-            req.extensions_mut().insert(Arc::new(decoded_token.claims));
-            Ok(next.run(req).await)
-        }
-        Err(e) => {
-            warn!("JWT validation failed: {:?}", e);
-            Err(ApplicationError::forbidden(
-                "You are not authorized to access this resource",
-                None,
-            ))
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    const JWT_SECRET: &str = "jwt-secret";
+
+    fn state() -> JwtState {
+        let mut validation = Validation::default();
+        validation.set_audience(&[DEFAULT_AUDIENCE, FALLBACK_AUDIENCE]);
+        validation.set_issuer(&[DEFAULT_ISSUER, FALLBACK_ISSUER]);
+
+        JwtState {
+            validation,
+            core_decoding_key: DecodingKey::from_secret(JWT_SECRET.as_bytes()),
+            base_jwt_secret: JWT_SECRET.to_string(),
         }
     }
+
+    fn user_token(secret: &str, exp: i64) -> String {
+        let claims = Claims {
+            buildable_id: "buildable-1".to_string(),
+            exp,
+            aud: DEFAULT_AUDIENCE.to_string(),
+            iss: DEFAULT_ISSUER.to_string(),
+            ..Default::default()
+        };
+
+        jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn introspect_reports_valid_user_tokens() {
+        let exp = jsonwebtoken::get_current_timestamp() as i64 + 60;
+        let token = user_token(&format!("{JWT_SECRET}buildable-1"), exp);
+
+        let report = state().introspect(&token);
+
+        assert!(report.valid, "{report:?}");
+        assert_eq!(report.rejection, None);
+        assert_eq!(report.algorithm.as_deref(), Some("HS256"));
+        assert_eq!(
+            report.secret,
+            Some(SecretBranch::User {
+                buildable_id: "buildable-1".to_string()
+            })
+        );
+        assert!(report.signature_valid);
+        assert_eq!(report.expired, Some(false));
+        assert_eq!(report.audience_matches, Some(true));
+        assert_eq!(report.issuer_matches, Some(true));
+    }
+
+    #[test]
+    fn introspect_pinpoints_why_a_token_is_rejected() {
+        let token = user_token("wrong-secret", i64::MAX / 2);
+
+        let report = state().introspect(&token);
+
+        assert!(!report.valid);
+        assert!(!report.signature_valid);
+        assert_eq!(report.expired, Some(false));
+        assert_eq!(
+            report.rejection.map(|r| (r.status, r.error_code)),
+            Some((403, Some(PicaErrorCode::TokenInvalid)))
+        );
+
+        let report = state().introspect("not.a.token");
+
+        assert!(!report.valid);
+        assert_eq!(report.claims, None);
+        assert_eq!(report.secret, None);
+        assert_eq!(report.audience_matches, None);
+        assert_eq!(
+            report.rejection.and_then(|r| r.error_code),
+            Some(PicaErrorCode::TokenMalformed)
+        );
+
+        let claims = json!({ "isBuildableCore": false, "aud": "other", "iss": DEFAULT_ISSUER });
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+        )
+        .unwrap();
+
+        let report = state().introspect(&token);
+
+        assert!(!report.valid);
+        assert_eq!(report.secret, None);
+        assert_eq!(report.audience_matches, Some(false));
+        assert_eq!(report.issuer_matches, Some(true));
+        assert_eq!(report.expires_at, None);
+    }
 }
@@ -1,3 +1,4 @@
+pub mod admission;
 pub mod header_auth;
 pub mod header_blocker;
 pub mod header_passthrough;
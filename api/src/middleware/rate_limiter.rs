@@ -1,4 +1,7 @@
-use crate::{domain::metrics::Metric, server::AppState};
+use crate::{
+    domain::{metrics::Metric, telemetry::TelemetrySender},
+    server::AppState,
+};
 use anyhow::{Context, Result};
 use axum::{
     body::Body,
@@ -25,7 +28,7 @@ pub struct RateLimiter {
     limit_header_name: HeaderName,
     remaining_header_name: HeaderName,
     reset_header_name: HeaderName,
-    metric_tx: Sender<Metric>,
+    metric_tx: TelemetrySender<Metric>,
 }
 
 impl RateLimiter {
@@ -107,13 +110,10 @@ pub async fn rate_limit_middleware(
         .await;
 
     if count >= throughput {
-        let _ = state
-            .metric_tx
-            .send(Metric::rate_limited(
-                event_access.clone(),
-                req.headers().get(&state.key_header_name).cloned(),
-            ))
-            .await;
+        state.metric_tx.send(Metric::rate_limited(
+            event_access.clone(),
+            req.headers().get(&state.key_header_name).cloned(),
+        ));
         let mut res =
             ApplicationError::too_many_requests("Rate limit exceeded", None).into_response();
 
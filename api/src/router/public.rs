@@ -3,7 +3,7 @@ use crate::{
     logic::{
         common_enum, common_model,
         connection_definition::{self, GetPublicConnectionDetailsRequest},
-        connection_model_schema, connection_oauth_definition,
+        connection_model_definition, connection_model_schema, connection_oauth_definition,
         event_access::create_event_access_for_new_user,
         openapi, read, schema_generator, tracker,
     },
@@ -38,6 +38,10 @@ pub fn get_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
                 ),
         )
         .nest("/schemas", schema_generator::get_router())
+        .route(
+            "/schema/create-request",
+            get(connection_model_definition::get_request_schemas),
+        )
         .nest("/mark", tracker::get_router())
         .route(
             "/connection-data",
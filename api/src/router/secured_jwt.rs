@@ -1,9 +1,9 @@
 use crate::{
     logic::{
-        common_enum, common_model, connection_definition,
+        admin_cache, auth, common_enum, common_model, connection_definition,
         connection_model_definition::{self},
         connection_model_schema, connection_oauth_definition, connection_variable_mapping,
-        event_callback, openapi, platform, platform_page, secrets,
+        event_callback, openapi, platform, platform_flag, platform_page, secrets,
     },
     middleware::jwt_auth::{self, JwtState},
     server::AppState,
@@ -40,11 +40,14 @@ pub async fn get_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
         .nest("/event-callbacks", event_callback::get_router())
         .nest("/platform-pages", platform_page::get_router())
         .nest("/platforms", platform::get_router())
+        .nest("/platform-flags", platform_flag::get_router())
         .nest(
             "/connection-variable-mappings",
             connection_variable_mapping::get_router(),
         )
+        .nest("/admin/cache", admin_cache::get_router())
         .route("/admin/connection/:id", get(secrets::get_admin_secret))
+        .nest("/auth", auth::get_router())
         .route("/openapi", post(openapi::refresh_openapi));
 
     routes
@@ -1,14 +1,19 @@
 use crate::{
     logic::{
         connection, connection_definition,
-        connection_model_definition::{get_available_actions, test_connection_model_definition},
+        connection_model_definition::{
+            get_available_actions, test_connection_model_definition,
+            test_connection_model_definitions,
+        },
         connection_model_schema::{
             public_get_connection_model_schema, PublicGetConnectionModelSchema,
         },
-        event_access, events, knowledge, metrics, oauth, passthrough, secrets, tasks, unified,
-        vault_connection,
+        connection_variable_mapping::test_mapping,
+        connection_webhook, event_access, events, knowledge, metrics, oauth, passthrough,
+        passthrough_recording, secrets, tasks, unified, vault_connection,
     },
     middleware::{
+        admission::admission_middleware,
         header_auth,
         header_blocker::{handle_blocked_error, BlockInvalidHeaders},
         header_passthrough,
@@ -35,20 +40,36 @@ pub async fn get_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
     let routes = Router::new()
         .layer(TraceLayer::new_for_http())
         .nest("/connections", connection::get_router())
+        .nest("/connection-webhooks", connection_webhook::get_router())
         .nest("/event-access", event_access::get_router())
         .nest("/events", events::get_router())
         .nest("/knowledge", knowledge::get_router())
         .nest("/tasks", tasks::get_router())
         .nest("/metrics", metrics::get_router())
         .nest("/oauth", oauth::get_router())
-        .nest("/passthrough", passthrough::get_router())
+        .nest(
+            "/passthrough",
+            passthrough::get_router().layer(from_fn_with_state(
+                state.passthrough_admission.clone(),
+                admission_middleware,
+            )),
+        )
+        .nest(
+            "/passthrough-recordings",
+            passthrough_recording::get_router(),
+        )
         .nest("/secrets", secrets::get_router())
         .nest("/unified", unified::get_router())
         .nest("/vault/connections", vault_connection::get_router())
+        .route(
+            "/connection-model-definitions/test",
+            post(test_connection_model_definitions),
+        )
         .route(
             "/connection-model-definitions/test/:id",
             post(test_connection_model_definition),
         )
+        .route("/connection-variable-mappings/:id/test", post(test_mapping))
         .route(
             "/connection-model-schema",
             get(public_get_connection_model_schema::<
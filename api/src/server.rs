@@ -1,20 +1,28 @@
 use crate::{
     domain::{
+        dead_letter::{self, EventDeadLetter},
+        migration::{self, AppliedMigrations},
+        nonce::NonceCache,
+        quota::QuotaTracker,
+        telemetry::TelemetrySender,
         track::{LoggerTracker, PosthogTracker, Track, TrackedMetric},
+        upstream_limit::UpstreamLimitTracker,
         ConnectionsConfig, K8sMode, Metric,
     },
     helper::{K8sDriver, K8sDriverImpl, K8sDriverLogger},
     logic::{
-        connection_oauth_definition::FrontendOauthConnectionDefinition, knowledge::Knowledge,
-        openapi::OpenAPIData,
+        connection_model_definition_bundle::StagedBundle,
+        connection_oauth_definition::FrontendOauthConnectionDefinition, connection_webhook,
+        knowledge::Knowledge, openapi::OpenAPIData,
     },
+    middleware::admission::AdmissionController,
     router,
 };
 use anyhow::{anyhow, Context, Result};
 use axum::Router;
 use cache::local::{
     ConnectionDefinitionCache, ConnectionHeaderCache, ConnectionOAuthDefinitionCache,
-    EventAccessCache,
+    EventAccessCache, PlatformFlagCache,
 };
 use mongodb::{options::UpdateOptions, Client, Database};
 use osentities::{
@@ -22,11 +30,17 @@ use osentities::{
     common_model::{CommonEnum, CommonModel},
     connection_definition::{ConnectionDefinition, PublicConnectionDetails},
     connection_model_definition::ConnectionModelDefinition,
+    connection_model_definition_audit::ConnectionModelDefinitionAudit,
     connection_model_schema::{ConnectionModelSchema, PublicConnectionModelSchema},
     connection_oauth_definition::{ConnectionOAuthDefinition, Settings},
     connection_variable_mapping::ConnectionVariableMapping,
+    connection_webhook::{ConnectionLifecycleEvent, ConnectionWebhook},
+    dead_letter::DeadLetterEvent,
     event_access::EventAccess,
+    flag::PlatformFlag,
+    knowledge_override::KnowledgeOverride,
     page::PlatformPage,
+    passthrough_recording::PassthroughRecording,
     secret::Secret,
     secrets::SecretServiceProvider,
     task::Task,
@@ -36,7 +50,10 @@ use osentities::{
 use std::{sync::Arc, time::Duration};
 use tokio::{net::TcpListener, sync::mpsc::Sender, time::timeout, try_join};
 use tracing::{error, info, trace, warn};
-use unified::unified::{UnifiedCacheTTLs, UnifiedDestination};
+use unified::{
+    egress::{EgressConfig, RedirectPolicy},
+    unified::{UnifiedCacheTTLs, UnifiedDestination},
+};
 
 #[derive(Clone)]
 pub struct AppStores {
@@ -46,22 +63,29 @@ pub struct AppStores {
     pub connection: MongoStore<Connection>,
     pub connection_config: MongoStore<ConnectionDefinition>,
     pub db: Database,
+    pub dead_letter_event: MongoStore<DeadLetterEvent>,
     pub event: MongoStore<Event>,
     pub event_access: MongoStore<EventAccess>,
     pub frontend_oauth_config: MongoStore<FrontendOauthConnectionDefinition>,
     pub model_config: MongoStore<ConnectionModelDefinition>,
+    pub model_config_audit: MongoStore<ConnectionModelDefinitionAudit>,
     pub model_schema: MongoStore<ConnectionModelSchema>,
     pub oauth_config: MongoStore<ConnectionOAuthDefinition>,
+    pub passthrough_recording: MongoStore<PassthroughRecording>,
     pub platform: MongoStore<PlatformData>,
+    pub platform_flag: MongoStore<PlatformFlag>,
     pub platform_page: MongoStore<PlatformPage>,
     pub public_connection: MongoStore<PublicConnection>,
     pub public_connection_details: MongoStore<PublicConnectionDetails>,
     pub public_model_schema: MongoStore<PublicConnectionModelSchema>,
     pub knowledge: MongoStore<Knowledge>,
+    pub knowledge_override: MongoStore<KnowledgeOverride>,
     pub secrets: MongoStore<Secret>,
     pub settings: MongoStore<Settings>,
+    pub staged_bundle: MongoStore<StagedBundle>,
     pub tasks: MongoStore<Task>,
     pub connection_variable_mapping: MongoStore<ConnectionVariableMapping>,
+    pub connection_webhook: MongoStore<ConnectionWebhook>,
 }
 
 #[derive(Clone)]
@@ -72,15 +96,22 @@ pub struct AppState {
     pub connection_oauth_definitions_cache: ConnectionOAuthDefinitionCache,
     pub connections_cache: ConnectionHeaderCache,
     pub event_access_cache: EventAccessCache,
-    pub event_tx: Sender<Event>,
+    pub event_tx: TelemetrySender<Event>,
     pub extractor_caller: UnifiedDestination,
     pub http_client: reqwest::Client,
     pub k8s_client: Arc<dyn K8sDriver>,
-    pub metric_tx: Sender<Metric>,
+    pub metric_tx: TelemetrySender<Metric>,
+    pub migrations: AppliedMigrations,
     pub openapi_data: OpenAPIData,
+    pub passthrough_admission: Arc<AdmissionController>,
+    pub passthrough_nonces: NonceCache,
+    pub passthrough_quotas: Arc<QuotaTracker>,
+    pub upstream_limits: Arc<UpstreamLimitTracker>,
+    pub platform_flags_cache: PlatformFlagCache,
     pub secrets_client: Arc<dyn SecretExt>,
     pub tracker_client: Arc<dyn Track<TrackedMetric>>,
     pub template: DefaultTemplate,
+    pub webhook_tx: Sender<ConnectionLifecycleEvent>,
 }
 
 #[derive(Clone)]
@@ -97,6 +128,8 @@ impl Server {
             .timeout(Duration::from_secs(config.http_client_timeout_secs))
             .build()?;
         let model_config = MongoStore::new(&db, &Store::ConnectionModelDefinitions).await?;
+        let model_config_audit =
+            MongoStore::new(&db, &Store::ConnectionModelDefinitionAudits).await?;
         let oauth_config = MongoStore::new(&db, &Store::ConnectionOAuthDefinitions).await?;
         let frontend_oauth_config =
             MongoStore::new(&db, &Store::ConnectionOAuthDefinitions).await?;
@@ -109,10 +142,12 @@ impl Server {
         let connection = MongoStore::new(&db, &Store::Connections).await?;
         let public_connection = MongoStore::new(&db, &Store::Connections).await?;
         let platform = MongoStore::new(&db, &Store::Platforms).await?;
+        let platform_flag = MongoStore::new(&db, &Store::PlatformFlags).await?;
         let platform_page = MongoStore::new(&db, &Store::PlatformPages).await?;
         let public_connection_details =
             MongoStore::new(&db, &Store::PublicConnectionDetails).await?;
         let settings = MongoStore::new(&db, &Store::Settings).await?;
+        let staged_bundle = MongoStore::new(&db, &Store::StagedBundles).await?;
         let connection_config = MongoStore::new(&db, &Store::ConnectionDefinitions).await?;
         let event_access = MongoStore::new(&db, &Store::EventAccess).await?;
         let event = MongoStore::new(&db, &Store::Events).await?;
@@ -122,6 +157,10 @@ impl Server {
         let tasks = MongoStore::new(&db, &Store::Tasks).await?;
         let connection_variable_mapping =
             MongoStore::new(&db, &Store::ConnectionVariableMappings).await?;
+        let connection_webhook = MongoStore::new(&db, &Store::ConnectionWebhooks).await?;
+        let passthrough_recording = MongoStore::new(&db, &Store::PassthroughRecordings).await?;
+        let dead_letter_event = MongoStore::new(&db, &Store::DeadLetterEvents).await?;
+        let knowledge_override = MongoStore::new(&db, &Store::KnowledgeOverrides).await?;
 
         let secrets_client: Arc<dyn SecretExt + Sync + Send> = match config.secrets_config.provider
         {
@@ -143,6 +182,16 @@ impl Server {
             _ => Arc::new(LoggerTracker),
         };
 
+        let egress = EgressConfig {
+            proxy_url: config.egress_proxy_url.clone(),
+            allowed_hosts: config.egress_allowed_hosts.clone(),
+            redirects: RedirectPolicy {
+                max_redirects: config.egress_max_redirects,
+                cross_host: config.egress_redirect_cross_host,
+                preserve_method: config.egress_redirect_preserve_method,
+            },
+        };
+
         let extractor_caller = UnifiedDestination::new(
             config.db_config.clone(),
             config.cache_size,
@@ -153,8 +202,11 @@ impl Server {
                     .connection_model_schema_cache_ttl_secs,
                 connection_model_definition_cache_ttl_secs: config
                     .connection_model_definition_cache_ttl_secs,
+                connection_variable_mapping_cache_ttl_secs: config
+                    .connection_variable_mapping_cache_ttl_secs,
                 secret_cache_ttl_secs: config.secret_cache_ttl_secs,
             },
+            egress.clone(),
         )
         .await
         .with_context(|| "Could not initialize extractor caller")?;
@@ -162,6 +214,7 @@ impl Server {
         let app_stores = AppStores {
             db: db.clone(),
             model_config,
+            model_config_audit,
             oauth_config,
             platform_page,
             frontend_oauth_config,
@@ -169,7 +222,9 @@ impl Server {
             model_schema,
             public_model_schema,
             platform,
+            platform_flag,
             settings,
+            staged_bundle,
             common_model,
             common_enum,
             connection,
@@ -178,12 +233,18 @@ impl Server {
             connection_config,
             event_access,
             knowledge,
+            knowledge_override,
             event,
             clients,
             tasks,
             connection_variable_mapping,
+            connection_webhook,
+            passthrough_recording,
+            dead_letter_event,
         };
 
+        let migrations = migration::run(&app_stores).await;
+
         let event_access_cache =
             EventAccessCache::new(config.cache_size, config.access_key_cache_ttl_secs);
         let connections_cache =
@@ -196,6 +257,8 @@ impl Server {
             config.cache_size,
             config.connection_oauth_definition_cache_ttl_secs,
         );
+        let platform_flags_cache =
+            PlatformFlagCache::new(config.cache_size, config.platform_flag_cache_ttl_secs);
         let openapi_data = OpenAPIData::default();
         openapi_data.spawn_openapi_generation(
             app_stores.common_model.clone(),
@@ -310,6 +373,44 @@ impl Server {
             }
         });
 
+        // Deliver connection lifecycle events to the subscribed webhooks
+        let (webhook_tx, mut receiver) =
+            tokio::sync::mpsc::channel::<ConnectionLifecycleEvent>(config.webhook_channel_size);
+        let webhook_store = app_stores.connection_webhook.clone();
+        let webhook_egress = Arc::new(egress);
+        let webhook_config = Arc::new(config.clone());
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                connection_webhook::dispatch(
+                    webhook_store.clone(),
+                    webhook_egress.clone(),
+                    webhook_config.clone(),
+                    event,
+                )
+                .await;
+            }
+        });
+
+        let passthrough_admission = Arc::new(AdmissionController::from_config(&config));
+        let passthrough_nonces = NonceCache::new(
+            config.passthrough_nonce_cache_size,
+            config.passthrough_signature_max_skew_secs,
+        );
+
+        let telemetry_send_timeout = Duration::from_millis(config.telemetry_send_timeout_millis);
+        let mut event_tx = TelemetrySender::new("event", event_tx, telemetry_send_timeout);
+        if config.event_dead_letter_enabled {
+            event_tx = event_tx.with_dead_letter(Arc::new(EventDeadLetter::new(
+                app_stores.dead_letter_event.clone(),
+            )));
+            dead_letter::spawn_redelivery(
+                app_stores.dead_letter_event.clone(),
+                app_stores.event.clone(),
+                &config,
+            );
+        }
+        let metric_tx = TelemetrySender::new("metric", metric_tx, telemetry_send_timeout);
+
         Ok(Self {
             state: Arc::new(AppState {
                 app_stores,
@@ -323,15 +424,33 @@ impl Server {
                 http_client,
                 k8s_client,
                 metric_tx,
+                migrations,
                 openapi_data,
+                passthrough_admission,
+                passthrough_nonces,
+                passthrough_quotas: Arc::new(QuotaTracker::default()),
+                upstream_limits: Arc::new(UpstreamLimitTracker::default()),
+                platform_flags_cache,
                 secrets_client,
                 tracker_client,
                 template,
+                webhook_tx,
             }),
         })
     }
 
     pub async fn run(&self) -> Result<()> {
+        self.state
+            .config
+            .cache_warmup
+            .run(
+                &self.state.app_stores.connection,
+                &self.state.app_stores.connection_config,
+                &self.state.connections_cache,
+                &self.state.connection_definitions_cache,
+            )
+            .await;
+
         let app = router::get_router(&self.state).await;
 
         let app: Router<()> = app.with_state(self.state.clone());
@@ -111,6 +111,13 @@ impl TestServer {
     }
 
     pub async fn new_with_cache(db_name: Option<String>, cache_size: Option<String>) -> Self {
+        let cache_size = cache_size.unwrap_or_else(|| "0".to_string());
+
+        Self::new_with_env(db_name, &[("CACHE_SIZE", &cache_size)]).await
+    }
+
+    /// Starts a server whose config is overridden by `env`
+    pub async fn new_with_env(db_name: Option<String>, env: &[(&str, &str)]) -> Self {
         // init tracing once
         TRACING.get_or_init(|| {
             let filter = EnvFilter::builder()
@@ -142,7 +149,7 @@ impl TestServer {
         let db_name = db_name.unwrap_or_else(|| Uuid::new_v4().to_string());
         let token_secret = "Qsfb9YUkdjwUULX.u96HdTCX4q7GuB".to_string();
 
-        let config = ConnectionsConfig::init_from_hashmap(&HashMap::from([
+        let mut vars = HashMap::from([
             ("CONTROL_DATABASE_URL".to_string(), db.clone()),
             ("CONTROL_DATABASE_NAME".to_string(), db_name.clone()),
             ("CONTEXT_DATABASE_URL".to_string(), db.clone()),
@@ -155,15 +162,18 @@ impl TestServer {
             ),
             ("OPENAI_API_KEY".to_string(), "".to_string()),
             ("MOCK_LLM".to_string(), "true".to_string()),
-            ("CACHE_SIZE".to_string(), cache_size.unwrap_or_else(|| "0".to_string())),
+            ("CACHE_SIZE".to_string(), "0".to_string()),
             ("REDIS_URL".to_string(), redis),
             ("JWT_SECRET".to_string(), token_secret.clone()),
             (
                 "SECRETS_SERVICE_PROVIDER".to_string(),
                 "ios-kms".to_string(),
             ),
-        ]))
-        .expect("Could not create envconfig");
+        ]);
+        vars.extend(env.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+
+        let config =
+            ConnectionsConfig::init_from_hashmap(&vars).expect("Could not create envconfig");
 
         let secrets_client = Arc::new(MockSecretsClient);
 
@@ -310,14 +320,15 @@ impl TestServer {
         if let Some(key) = key {
             req = req.header(&self.config.headers.auth_header, key);
         }
-        if let Some(payload) = payload {
-            req = req.json(payload);
-        }
+        // Headers go first so that an explicit content type wins over the JSON default
         if let Some(headers) = headers {
             for (k, v) in headers {
                 req = req.header(k, v);
             }
         }
+        if let Some(payload) = payload {
+            req = req.json(payload);
+        }
 
         let res = req.send().await?;
 
@@ -349,6 +360,25 @@ impl TestServer {
             .await
     }
 
+    /// Creates a connection definition for model definitions to belong to
+    pub async fn create_connection_definition(&self) -> ConnectionDefinition {
+        let payload: CreateConnectionDefinitionRequest = Faker.fake();
+
+        let res = self
+            .send_request::<CreateConnectionDefinitionRequest, ConnectionDefinition>(
+                "v1/connection-definitions",
+                http::Method::POST,
+                Some(&self.live_key),
+                Some(&payload),
+            )
+            .await
+            .unwrap();
+
+        assert!(res.code.is_success());
+
+        res.data
+    }
+
     pub async fn create_connection(
         &mut self,
         environment: Environment,
@@ -364,9 +394,26 @@ impl TestServer {
         let template: String = Faker.fake();
         let handlebar_template = format!("{{{{{template}}}}}");
 
-        let mut connection_def: CreateConnectionDefinitionRequest = Faker.fake();
-        connection_def.r#type = ConnectionDefinitionType::Api;
+        let mut connection_def_payload: CreateConnectionDefinitionRequest = Faker.fake();
+        connection_def_payload.r#type = ConnectionDefinitionType::Api;
+        connection_def_payload.test_connection = None;
+
+        let res = self
+            .send_request::<Value, Value>(
+                "v1/connection-definitions",
+                http::Method::POST,
+                Some(key),
+                Some(&to_value(&connection_def_payload).unwrap()),
+            )
+            .await
+            .unwrap();
+
+        assert!(res.code.is_success());
+
+        let connection_def = from_value::<ConnectionDefinition>(res.data).unwrap();
+
         let mut test_connection: CreateConnectionModelDefinitionRequest = Faker.fake();
+        test_connection.connection_definition_id = connection_def.id;
         test_connection.base_url = self.mock_server.url();
         test_connection.auth_method = AuthMethod::BearerToken {
             value: handlebar_template.clone(),
@@ -427,24 +474,21 @@ impl TestServer {
             .create_async()
             .await;
 
-        connection_def.test_connection = Some(test_connection.id);
-
-        let payload = to_value(&connection_def).unwrap();
+        // The test connection can only be attached once its definition exists
+        connection_def_payload.test_connection = Some(test_connection.id);
 
         let res = self
             .send_request::<Value, Value>(
-                "v1/connection-definitions",
-                http::Method::POST,
+                &format!("v1/connection-definitions/{}", connection_def.id),
+                http::Method::PATCH,
                 Some(key),
-                Some(&payload),
+                Some(&to_value(&connection_def_payload).unwrap()),
             )
             .await
             .unwrap();
 
         assert!(res.code.is_success());
 
-        let connection_def = from_value::<ConnectionDefinition>(res.data).unwrap();
-
         let res = self
             .send_request::<Value, Value>(
                 &format!("v1/public/connection-definitions?_id={}", connection_def.id),
@@ -535,6 +579,7 @@ impl TestServer {
             active: Some(true),
             knowledge: None,
             tags: None,
+            request_transform: None,
         };
 
         let res = self
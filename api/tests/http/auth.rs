@@ -1,5 +1,7 @@
 use crate::context::{ApiResponse, TestServer, PUBLIC_PATHS};
 use http::{Method, StatusCode};
+use jsonwebtoken::EncodingKey;
+use osentities::DEFAULT_ISSUER;
 use serde_json::{json, Value};
 
 #[tokio::test]
@@ -45,3 +47,64 @@ async fn test_404() {
         );
     }
 }
+
+#[tokio::test]
+async fn test_introspect_token() {
+    let server = TestServer::new(None).await;
+
+    let introspect = |token: String| {
+        let server = &server;
+        async move {
+            server
+                .send_request::<Value, Value>(
+                    "v1/auth/introspect",
+                    Method::POST,
+                    None,
+                    Some(&json!({ "token": token })),
+                )
+                .await
+                .unwrap()
+        }
+    };
+
+    let res = introspect(server.token.clone()).await;
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["valid"], true);
+    assert_eq!(res.data["secret"]["type"], "core");
+    assert_eq!(res.data["signatureValid"], true);
+    assert_eq!(res.data["expired"], false);
+    assert_eq!(res.data["audienceMatches"], true);
+    assert_eq!(res.data["issuerMatches"], true);
+    assert_eq!(res.data["claims"]["isBuildableCore"], true);
+
+    let forged = jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &json!({
+            "buildableId": "buildable-1",
+            "isBuildableCore": false,
+            "exp": 1,
+            "aud": "someone-else",
+            "iss": DEFAULT_ISSUER,
+        }),
+        &EncodingKey::from_secret(b"not the secret"),
+    )
+    .unwrap();
+
+    let res = introspect(forged).await;
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["valid"], false);
+    assert_eq!(res.data["rejection"]["status"], 401);
+    assert_eq!(res.data["secret"]["type"], "user");
+    assert_eq!(res.data["secret"]["buildableId"], "buildable-1");
+    assert_eq!(res.data["signatureValid"], false);
+    assert_eq!(res.data["expired"], true);
+    assert_eq!(res.data["audienceMatches"], false);
+    assert_eq!(res.data["issuerMatches"], true);
+
+    let res = introspect("not a token".to_string()).await;
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["valid"], false);
+    assert_eq!(res.data["rejection"]["errorCode"], "token_malformed");
+    assert_eq!(res.data["claims"], Value::Null);
+    assert!(!res.data.to_string().contains("Qsfb9YUkdjwUULX"));
+}
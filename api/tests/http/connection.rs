@@ -1,6 +1,22 @@
 use crate::context::TestServer;
-use http::{Method, StatusCode};
-use serde_json::Value;
+use api::logic::connection_model_definition::CreateRequest as CreateConnectionModelDefinitionRequest;
+use chrono::Utc;
+use fake::{faker::filesystem::raw::DirPath, locales::EN, Fake, Faker};
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+};
+use mockito::{Matcher, Mock, Server, ServerGuard};
+use osentities::{
+    api_model_config::{AuthMethod, SamplesInput, SchemasInput},
+    connection_model_definition::{ConnectionModelDefinition, CrudAction},
+    environment::Environment,
+    request_signature::{sign_request, SignedRequest},
+    SanitizedConnection, PICA_NONCE_HEADER, PICA_SIGNATURE_HEADER, PICA_TIMESTAMP_HEADER,
+    PICA_VCR_HEADER,
+};
+use serde_json::{json, Value};
+use std::time::Duration;
 
 #[tokio::test]
 async fn test_connection_data_models_api() {
@@ -11,3 +27,885 @@ async fn test_connection_data_models_api() {
         .unwrap();
     assert_eq!(res.code, StatusCode::OK);
 }
+
+async fn call_passthrough(server: &TestServer, connection_key: &str) -> StatusCode {
+    call_passthrough_with_headers(server, connection_key, vec![]).await
+}
+
+async fn call_passthrough_with_headers(
+    server: &TestServer,
+    connection_key: &str,
+    extra_headers: Vec<(String, String)>,
+) -> StatusCode {
+    server
+        .send_request_with_headers::<Value, Value>(
+            "v1/passthrough/customers",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+            Some(
+                vec![
+                    (CONTENT_TYPE.to_string(), "application/json".to_string()),
+                    (
+                        "x-pica-connection-key".to_string(),
+                        connection_key.to_string(),
+                    ),
+                ]
+                .into_iter()
+                .chain(extra_headers)
+                .collect(),
+            ),
+        )
+        .await
+        .expect("Failed to call passthrough API")
+        .code
+}
+
+/// Registers a supported `GET customers` model definition for the connection's
+/// platform, backed by a mock upstream expecting `hits` calls.
+async fn mock_customers_endpoint(
+    server: &TestServer,
+    connection: &SanitizedConnection,
+    conn_def: &ConnectionModelDefinition,
+    hits: usize,
+) -> (ServerGuard, Mock) {
+    mock_customers_endpoint_matching(server, connection, conn_def, hits, vec![], vec![], None).await
+}
+
+/// Same as `mock_customers_endpoint`, the upstream only answering requests
+/// whose headers match `headers` and responding with `response_headers`. The
+/// definition sends `definition_headers` when given.
+async fn mock_customers_endpoint_matching(
+    server: &TestServer,
+    connection: &SanitizedConnection,
+    conn_def: &ConnectionModelDefinition,
+    hits: usize,
+    headers: Vec<(&str, Matcher)>,
+    response_headers: Vec<(&str, &str)>,
+    definition_headers: Option<HeaderMap>,
+) -> (ServerGuard, Mock) {
+    let mut mock_server = Server::new_async().await;
+    let secret_key = Faker.fake::<String>();
+    let url_path: String = DirPath(EN).fake();
+
+    let mock = headers
+        .into_iter()
+        .fold(
+            mock_server.mock("GET", format!("{url_path}/customers").as_str()),
+            |mock, (name, matcher)| mock.match_header(name, matcher),
+        )
+        .match_header(
+            AUTHORIZATION.as_str(),
+            format!("Bearer {secret_key}").as_str(),
+        )
+        .expect(hits);
+    let mock = response_headers
+        .into_iter()
+        .fold(mock, |mock, (name, value)| mock.with_header(name, value))
+        .with_status(200)
+        .with_body("{}")
+        .create();
+
+    let create_model_definition_payload = CreateConnectionModelDefinitionRequest {
+        id: None,
+        connection_platform: connection.platform.to_string(),
+        connection_definition_id: conn_def.connection_definition_id,
+        platform_version: conn_def.record_metadata.version.to_string(),
+        title: Faker.fake(),
+        name: Faker.fake(),
+        model_name: Faker.fake(),
+        action_name: CrudAction::Create,
+        base_url: mock_server.url() + &url_path,
+        path: "customers".to_string(),
+        auth_method: AuthMethod::BearerToken {
+            value: secret_key.to_string(),
+        },
+        http_method: http::Method::GET,
+        headers: definition_headers,
+        query_params: None,
+        extractor_config: None,
+        version: "1.0.0".parse().unwrap(),
+        schemas: SchemasInput {
+            headers: None,
+            query_params: None,
+            path_params: None,
+            body: None,
+        },
+        samples: SamplesInput {
+            headers: None,
+            query_params: None,
+            path_params: None,
+            body: None,
+        },
+        paths: None,
+        responses: vec![],
+        is_default_crud_mapping: None,
+        test_connection_payload: None,
+        test_connection_status: None,
+        mapping: None,
+        supported: Some(true),
+        active: Some(true),
+        knowledge: None,
+        tags: None,
+        request_transform: None,
+    };
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&create_model_definition_payload).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    (mock_server, mock)
+}
+
+#[tokio::test]
+async fn test_connection_key_rotation() {
+    let mut server = TestServer::new_with_cache(None, Some("100".to_string())).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint(&server, &connection, &conn_def, 4).await;
+
+    let old_key = connection.key.to_string();
+    assert_eq!(call_passthrough(&server, &old_key).await, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}/rotate-key", connection.id),
+            Method::POST,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let new_key = res.data["key"].as_str().unwrap().to_string();
+    assert_ne!(new_key, old_key);
+    assert_eq!(res.data["keyRotation"]["previousKey"], old_key.as_str());
+
+    // Both keys authenticate while the rotation overlap window is open
+    assert_eq!(call_passthrough(&server, &old_key).await, StatusCode::OK);
+    assert_eq!(call_passthrough(&server, &new_key).await, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}/rotate-key/finalize", connection.id),
+            Method::POST,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    assert_eq!(
+        call_passthrough(&server, &old_key).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(call_passthrough(&server, &new_key).await, StatusCode::OK);
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_deleted_connection_is_evicted_from_cache() {
+    let mut server = TestServer::new_with_cache(None, Some("100".to_string())).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint(&server, &connection, &conn_def, 1).await;
+
+    // Resolving the connection once populates the connections cache
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::OK
+    );
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}", connection.id),
+            Method::DELETE,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::NOT_FOUND
+    );
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_passthrough_requires_signature_when_configured() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint(&server, &connection, &conn_def, 1).await;
+
+    let secret = "a-very-secret-signing-key";
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}", connection.id),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!({ "requestSigningSecret": secret })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let now = Utc::now().timestamp_millis();
+
+    for tampered in [
+        signed_headers(secret, "/orders", "", now, "n-1"),
+        signed_headers(secret, "/customers", "limit=10", now, "n-1"),
+    ] {
+        assert_eq!(
+            call_passthrough_with_headers(&server, &connection.key, tampered).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    let stale = signed_headers(secret, "/customers", "", now - 3_600_000, "n-1");
+    assert_eq!(
+        call_passthrough_with_headers(&server, &connection.key, stale).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let signed = signed_headers(secret, "/customers", "", now, "n-1");
+    assert_eq!(
+        call_passthrough_with_headers(&server, &connection.key, signed.clone()).await,
+        StatusCode::OK
+    );
+
+    // The same signed request sent again is a replay
+    assert_eq!(
+        call_passthrough_with_headers(&server, &connection.key, signed).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_connection_merges_additional_secrets_of_its_owner() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    // Only the additional secret holds the key the definition sends
+    let (_mock_server, mock) = mock_customers_endpoint_matching(
+        &server,
+        &connection,
+        &conn_def,
+        1,
+        vec![("x-api-key", Matcher::Exact("api-key".to_string()))],
+        vec![],
+        Some(HeaderMap::from_iter([(
+            HeaderName::from_static("x-api-key"),
+            HeaderValue::from_static("{{API_KEY}}"),
+        )])),
+    )
+    .await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}", connection.id),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!({ "additionalSecretsServiceIds": ["missing-secret"] })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::BAD_REQUEST);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/secrets",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({ "secret": { "API_KEY": "api-key" } })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let secret_id = res.data["_id"].as_str().unwrap().to_string();
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}", connection.id),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!({ "additionalSecretsServiceIds": [secret_id] })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::OK
+    );
+
+    mock.assert_async().await;
+}
+
+fn signed_headers(
+    secret: &str,
+    path: &str,
+    query: &str,
+    timestamp: i64,
+    nonce: &str,
+) -> Vec<(String, String)> {
+    let timestamp = timestamp.to_string();
+    let signature = sign_request(
+        secret,
+        &SignedRequest {
+            method: "GET",
+            path,
+            query,
+            timestamp: &timestamp,
+            nonce,
+            body: b"",
+        },
+    );
+
+    vec![
+        (PICA_SIGNATURE_HEADER.to_string(), signature),
+        (PICA_TIMESTAMP_HEADER.to_string(), timestamp),
+        (PICA_NONCE_HEADER.to_string(), nonce.to_string()),
+    ]
+}
+
+#[tokio::test]
+async fn test_passthrough_rejects_requests_over_quota() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint(&server, &connection, &conn_def, 2).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}", connection.id),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!({ "quota": { "limit": 2, "windowSecs": 3600 } })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_passthrough_rejects_disabled_platforms() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint(&server, &connection, &conn_def, 1).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/platform-flags/{}", connection.platform),
+            Method::PUT,
+            None,
+            Some(&json!({ "passthroughDisabled": true, "reason": "Upstream incident" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/platform-flags/{}", connection.platform),
+            Method::PUT,
+            None,
+            Some(&json!({ "passthroughDisabled": false })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::OK
+    );
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_passthrough_normalizes_connection_keys() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint(&server, &connection, &conn_def, 2).await;
+
+    assert_eq!(
+        call_passthrough(&server, &format!("  {}\t", connection.key)).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        call_passthrough(&server, &connection.key.to_uppercase()).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        call_passthrough(&server, "   ").await,
+        StatusCode::BAD_REQUEST
+    );
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_passthrough_strips_denied_headers() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint_matching(
+        &server,
+        &connection,
+        &conn_def,
+        1,
+        vec![
+            ("cookie", Matcher::Missing),
+            ("x-forwarded-for", Matcher::Missing),
+            ("x-custom", Matcher::Exact("kept".to_string())),
+        ],
+        vec![],
+        None,
+    )
+    .await;
+
+    let headers = [
+        ("cookie", "session=abc"),
+        ("x-forwarded-for", "10.0.0.1"),
+        ("x-custom", "kept"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect();
+
+    assert_eq!(
+        call_passthrough_with_headers(&server, &connection.key, headers).await,
+        StatusCode::OK
+    );
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_passthrough_backs_off_when_upstream_limit_is_used_up() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint_matching(
+        &server,
+        &connection,
+        &conn_def,
+        1,
+        vec![],
+        vec![
+            ("ratelimit-limit", "10"),
+            ("ratelimit-remaining", "0"),
+            ("ratelimit-reset", "60"),
+        ],
+        None,
+    )
+    .await;
+
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::OK
+    );
+    // The reset is further off than requests are ever delayed
+    assert_eq!(
+        call_passthrough(&server, &connection.key).await,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    mock.assert_async().await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}/effective-config", connection.id),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["upstreamLimit"]["remaining"], 0);
+    assert_eq!(res.data["upstreamLimit"]["limit"], 10);
+}
+
+#[tokio::test]
+async fn test_passthrough_records_and_replays_requests() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint(&server, &connection, &conn_def, 1).await;
+
+    let vcr = |mode: &str| vec![(PICA_VCR_HEADER.to_string(), mode.to_string())];
+
+    assert_eq!(
+        call_passthrough_with_headers(&server, &connection.key, vcr("replay")).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        call_passthrough_with_headers(&server, &connection.key, vcr("record")).await,
+        StatusCode::OK
+    );
+
+    // Recordings are saved in the background
+    let mut res = None;
+    for _ in 0..50 {
+        let listed = server
+            .send_request::<Value, Value>(
+                "v1/passthrough-recordings",
+                Method::GET,
+                Some(&server.live_key),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(listed.code, StatusCode::OK);
+
+        if listed.data["total"] != 0 {
+            res = Some(listed);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let res = res.expect("the recording was never saved");
+    assert_eq!(res.data["total"], 1);
+
+    assert_eq!(
+        call_passthrough_with_headers(&server, &connection.key, vcr("replay")).await,
+        StatusCode::OK
+    );
+    mock.assert_async().await;
+    assert_eq!(res.data["rows"][0]["response"]["status"], 200);
+    assert!(!res.data["rows"][0].to_string().contains(&server.live_key));
+
+    let path = format!(
+        "v1/passthrough-recordings/{}",
+        res.data["rows"][0]["_id"].as_str().unwrap()
+    );
+    let res = server
+        .send_request::<Value, Value>(&path, Method::DELETE, Some(&server.live_key), None)
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    assert_eq!(
+        call_passthrough_with_headers(&server, &connection.key, vcr("replay")).await,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn test_list_connection_definitions() {
+    let mut server = TestServer::new(None).await;
+    let (connection, _) = server.create_connection(Environment::Live).await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = connection.connection_definition_id;
+    definition.connection_platform = connection.platform.to_string();
+    definition.supported = Some(true);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let definition_id = res.data["_id"].clone();
+
+    let path = format!("v1/connections/{}/definitions", connection.id);
+    let res = server
+        .send_request::<Value, Value>(&path, Method::GET, Some(&server.live_key), None)
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 1);
+    assert_eq!(res.data["rows"][0]["_id"], definition_id);
+    assert_eq!(res.data["rows"][0]["hasMapping"], false);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionModelDefinitionId": definition_id,
+                "connectionPlatform": connection.platform,
+                "bindings": [{
+                    "variableName": "hotel_id",
+                    "targetParam": "hotelId",
+                    "location": "QueryParam"
+                }]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+
+    let res = server
+        .send_request::<Value, Value>(&path, Method::GET, Some(&server.live_key), None)
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let item = &res.data["rows"][0];
+    assert_eq!(item["hasMapping"], true);
+    assert_eq!(item["autoHandledParams"][0]["targetParam"], "hotelId");
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connections/conn::AAAAAAAAAAA::AAAAAAAAAAAAAAAAAAAAAA/definitions",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_connection_effective_config() {
+    let mut server = TestServer::new(None).await;
+    let (connection, _) = server.create_connection(Environment::Live).await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = connection.connection_definition_id;
+    definition.connection_platform = connection.platform.to_string();
+    definition.supported = Some(true);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let definition_id = res.data["_id"].clone();
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionModelDefinitionId": definition_id,
+                "connectionPlatform": connection.platform,
+                "bindings": [{
+                    "variableName": "hotel_id",
+                    "targetParam": "hotelId",
+                    "location": "QueryParam"
+                }]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}/effective-config", connection.id),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["connection"]["_id"], connection.id.to_string());
+    assert_eq!(res.data["definitions"][0]["_id"], definition_id);
+    assert_eq!(res.data["definitions"][0]["hasMapping"], true);
+    assert_eq!(
+        res.data["mappings"][0]["connectionModelDefinitionId"],
+        definition_id
+    );
+    assert!(res.data["secretKeys"].is_array());
+    assert!(res.data["connection"].get("requestSigningSecret").is_none());
+}
+
+#[tokio::test]
+async fn test_connection_secret_validation() {
+    let mut server = TestServer::new(None).await;
+    let (connection, _) = server.create_connection(Environment::Live).await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = connection.connection_definition_id;
+    definition.connection_platform = connection.platform.to_string();
+    definition.supported = Some(true);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let definition_id = res.data["_id"].clone();
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionModelDefinitionId": definition_id,
+                "connectionPlatform": connection.platform,
+                "bindings": [{
+                    "variableName": "hotel_id",
+                    "targetParam": "hotelId",
+                    "location": "QueryParam"
+                }, {
+                    "variableName": "brand_id",
+                    "targetParam": "brandId",
+                    "location": "QueryParam",
+                    "required": false
+                }]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}/validate-secret", connection.id),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["valid"], false);
+    assert_eq!(
+        res.data["missing"],
+        json!([{ "variableName": "hotel_id", "requiredBy": [definition_id] }])
+    );
+}
+
+#[tokio::test]
+async fn test_connection_health_probe_skips_mutating_actions() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+    let (_mock_server, mock) = mock_customers_endpoint(&server, &connection, &conn_def, 1).await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = connection.connection_definition_id;
+    definition.connection_platform = connection.platform.to_string();
+    definition.http_method = Method::POST;
+    definition.supported = Some(true);
+    definition.active = Some(true);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}/health", connection.id),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["healthy"], true);
+    assert_eq!(res.data["passed"], 1);
+    assert_eq!(res.data["failed"], 0);
+    assert_eq!(res.data["skipped"], 1);
+    assert_eq!(res.data["results"][0]["method"], "GET");
+    assert_eq!(res.data["results"][0]["status"], 200);
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_connection_health_probe_reports_unresolved_path_params() {
+    let mut server = TestServer::new(None).await;
+    let (connection, _) = server.create_connection(Environment::Live).await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = connection.connection_definition_id;
+    definition.connection_platform = connection.platform.to_string();
+    definition.http_method = Method::GET;
+    definition.path = "customers/{customerId}".to_string();
+    definition.supported = Some(true);
+    definition.active = Some(true);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connections/{}/health", connection.id),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["healthy"], true);
+    assert_eq!(res.data["failed"], 0);
+    assert_eq!(res.data["unresolved"], 1);
+    assert_eq!(
+        res.data["results"][0]["unresolvedParams"],
+        json!(["customerId"])
+    );
+}
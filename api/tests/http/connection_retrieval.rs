@@ -1,9 +1,11 @@
 use crate::context::TestServer;
 use api::logic::{connection_definition::CreateRequest, ReadResponse};
 use fake::{Fake, Faker};
-use http::Method;
+use http::{Method, StatusCode};
+use mongodb::{bson::doc, Client};
 use osentities::{
     connection_definition::ConnectionDefinition, environment::Environment, SanitizedConnection,
+    Store,
 };
 use serde_json::Value;
 
@@ -128,3 +130,80 @@ async fn test_update_connection_definition_cache_invalidation() {
     assert_eq!(fetched_conn.connection_definition_name.as_ref().unwrap(), &new_name);
     assert_ne!(fetched_conn.connection_definition_name.as_ref().unwrap(), &original_name);
 }
+
+#[tokio::test]
+async fn test_purge_caches_after_manual_fix() {
+    let mut server = TestServer::new_with_cache(None, Some("100".to_string())).await;
+    let (connection, _model_def) = server.create_connection(Environment::Live).await;
+
+    let server = &server;
+    let connection_id = connection.id;
+    let definition_name = || async move {
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connections",
+                Method::GET,
+                Some(&server.live_key),
+                None,
+            )
+            .await
+            .unwrap();
+        let res = serde_json::from_value::<ReadResponse<SanitizedConnection>>(res.data).unwrap();
+        res.rows
+            .into_iter()
+            .find(|c| c.id == connection_id)
+            .and_then(|c| c.connection_definition_name)
+            .unwrap()
+    };
+
+    // Populates the cache, then changes the definition behind its back
+    let original_name = definition_name().await;
+
+    let db = Client::with_uri_str(&server.config.db_config.control_db_url)
+        .await
+        .unwrap()
+        .database(&server.config.db_config.control_db_name);
+    db.collection::<ConnectionDefinition>(&Store::ConnectionDefinitions.to_string())
+        .update_one(
+            doc! { "_id": connection.connection_definition_id.to_string() },
+            doc! { "$set": { "name": "Fixed By Hand" } },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(definition_name().await, original_name);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!(
+                "v1/admin/cache/purge/{}",
+                connection.connection_definition_id
+            ),
+            Method::POST,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["caches"][0], "connectionDefinitions");
+
+    assert_eq!(definition_name().await, "Fixed By Hand");
+
+    let res = server
+        .send_request::<Value, Value>("v1/admin/cache/purge", Method::POST, None, None)
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/admin/cache/purge/not-a-cached-key",
+            Method::POST,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,138 @@
+use crate::context::TestServer;
+use http::{Method, StatusCode};
+use mockito::{Matcher, Server};
+use osentities::{environment::Environment, PICA_WEBHOOK_EVENT_HEADER};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_connection_created_webhook_is_delivered() {
+    // The receiver listens on localhost
+    let mut server =
+        TestServer::new_with_env(None, &[("WEBHOOK_ALLOW_INTERNAL_HOSTS", "true")]).await;
+    let mut receiver = Server::new_async().await;
+
+    let mock = receiver
+        .mock("POST", "/hooks")
+        .match_header(PICA_WEBHOOK_EVENT_HEADER, "connection.created")
+        .match_header(
+            "x-pica-webhook-signature",
+            Matcher::Regex("^sha256=[0-9a-f]{64}$".into()),
+        )
+        .match_body(Matcher::PartialJson(
+            json!({ "type": "connection.created" }),
+        ))
+        .with_status(200)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-webhooks",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "url": format!("{}/hooks", receiver.url()),
+                "eventTypes": ["connection.created"],
+                "signingSecret": "a-very-secret-signing-key",
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert!(res.data.get("signingSecret").is_none());
+
+    let (connection, _) = server.create_connection(Environment::Live).await;
+
+    let mut delivered = false;
+    for _ in 0..50 {
+        if mock.matched_async().await {
+            delivered = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(
+        delivered,
+        "webhook for {} was never delivered",
+        connection.key
+    );
+
+    // The delivery outcome is recorded right after the POST completes
+    let mut last_delivery = Value::Null;
+    for _ in 0..50 {
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-webhooks",
+                Method::GET,
+                Some(&server.live_key),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::OK);
+
+        let row = &res.data["rows"][0];
+        assert!(row.get("signingSecret").is_none());
+
+        last_delivery = row["lastDelivery"].clone();
+        if !last_delivery.is_null() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(last_delivery["status"], "delivered");
+    assert_eq!(last_delivery["eventType"], "connection.created");
+    assert_eq!(last_delivery["attempts"], 1);
+}
+
+#[tokio::test]
+async fn test_create_webhook_rejects_short_secret() {
+    let server = TestServer::new(None).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-webhooks",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "url": "https://example.com/hooks",
+                "eventTypes": ["connection.deleted"],
+                "signingSecret": "short",
+            })),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(res.code, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_create_webhook_rejects_internal_hosts() {
+    let server = TestServer::new(None).await;
+
+    for url in [
+        "http://127.0.0.1:8080/hooks",
+        "http://localhost/hooks",
+        "http://169.254.169.254/latest/meta-data",
+        "http://[::1]/hooks",
+    ] {
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-webhooks",
+                Method::POST,
+                Some(&server.live_key),
+                Some(&json!({
+                    "url": url,
+                    "eventTypes": ["connection.created"],
+                    "signingSecret": "a-very-secret-signing-key",
+                })),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(res.code, StatusCode::BAD_REQUEST, "{url}");
+    }
+}
@@ -1,12 +1,16 @@
 use crate::context::TestServer;
 use api::logic::{common_model, ReadResponse};
-use api::logic::{connection_definition, connection_model_definition, connection_model_schema};
+use api::logic::{
+    connection_definition, connection_model_definition, connection_model_definition_diff,
+    connection_model_schema,
+};
 use fake::{Fake, Faker};
 use http::{Method, StatusCode};
 use osentities::{
     common_model::CommonModel, connection_definition::ConnectionDefinition,
     connection_model_definition::ConnectionModelDefinition,
     connection_model_schema::ConnectionModelSchema,
+    id::{prefix::IdPrefix, Id},
 };
 use osentities::{
     common_model::{DataType, Expandable, Field},
@@ -157,13 +161,15 @@ async fn test_get_expanded_common_model() {
 }
 
 macro_rules! crud {
-    ($(#[$m:meta])*, $test:ident, $model:ty, $path:ident, $endpoint:expr) => {
+    ($(#[$m:meta])*, $test:ident, $model:ty, $path:ident, $endpoint:expr $(, parent: $parent:literal)?) => {
         $(#[$m])*
         async fn $test() {
             let server = TestServer::new(None).await;
 
             let payload: $path::CreateRequest = Faker.fake();
-            let payload = serde_json::to_value(&payload).unwrap();
+            #[allow(unused_mut)]
+            let mut payload = serde_json::to_value(&payload).unwrap();
+            $(payload[$parent] = json!(server.create_connection_definition().await.id);)?
 
             const ENDPOINT: &str = $endpoint;
 
@@ -236,7 +242,8 @@ crud!(
     test_connection_model_definitions_crud,
     ConnectionModelDefinition,
     connection_model_definition,
-    "v1/connection-model-definitions"
+    "v1/connection-model-definitions",
+    parent: "connectionDefinitionId"
 );
 
 crud!(
@@ -333,7 +340,8 @@ async fn test_connection_model_definitions_batch_update() {
     let server = TestServer::new(None).await;
 
     // 1. Create two Connection Model Definitions
-    let payload1: connection_model_definition::CreateRequest = Faker.fake();
+    let mut payload1: connection_model_definition::CreateRequest = Faker.fake();
+    payload1.connection_definition_id = server.create_connection_definition().await.id;
     let payload1_json = serde_json::to_value(&payload1).unwrap();
     let res1 = server
         .send_request::<Value, Value>(
@@ -347,7 +355,8 @@ async fn test_connection_model_definitions_batch_update() {
     assert_eq!(res1.code, StatusCode::OK);
     let model1: ConnectionModelDefinition = serde_json::from_value(res1.data).expect("Failed to deserialize model 1");
 
-    let payload2: connection_model_definition::CreateRequest = Faker.fake();
+    let mut payload2: connection_model_definition::CreateRequest = Faker.fake();
+    payload2.connection_definition_id = server.create_connection_definition().await.id;
     let payload2_json = serde_json::to_value(&payload2).unwrap();
     let res2 = server
         .send_request::<Value, Value>(
@@ -427,3 +436,818 @@ async fn test_connection_model_definitions_batch_update() {
     let updated_model2 = response2.rows.first().expect("Model 2 not found");
     assert_eq!(updated_model2.connection_platform, "UpdatedPlatform2");
 }
+
+#[tokio::test]
+async fn test_connection_model_definitions_batch_update_summary() {
+    let server = TestServer::new(None).await;
+
+    let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+    payload.connection_definition_id = server.create_connection_definition().await.id;
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&payload).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let model: ConnectionModelDefinition = serde_json::from_value(res.data).unwrap();
+
+    let batch = json!([
+        { "_id": model.id, "connectionPlatform": "UpdatedPlatform" },
+        { "connectionPlatform": "MissingId" },
+    ]);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions?onlyFailures=true",
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&batch),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let summary: connection_model_definition::BatchUpdateSummary =
+        serde_json::from_value(res.data).unwrap();
+    assert_eq!(summary.total, 2);
+    assert_eq!(summary.succeeded, 1);
+    assert_eq!(summary.failed, 1);
+    assert_eq!(summary.results.len(), 1);
+    assert!(!summary.results[0].success);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions?summary=true",
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&batch),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let summary: connection_model_definition::BatchUpdateSummary =
+        serde_json::from_value(res.data).unwrap();
+    assert_eq!(summary.total, 2);
+    assert_eq!(summary.results.len(), 2);
+}
+
+#[tokio::test]
+async fn test_connection_model_definition_upsert_by_key() {
+    let server = TestServer::new(None).await;
+
+    let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+    payload.connection_definition_id = server.create_connection_definition().await.id;
+    payload.id = None;
+
+    let upsert = |payload: &connection_model_definition::CreateRequest,
+                  if_match: Option<String>| {
+        let payload = serde_json::to_value(payload).unwrap();
+        let headers = if_match.map(|tag| [("If-Match".to_string(), tag)].into());
+
+        let server = &server;
+        async move {
+            server
+                .send_request_with_headers::<Value, Value>(
+                    "v1/connection-model-definitions/by-key",
+                    Method::PUT,
+                    Some(&server.live_key),
+                    Some(&payload),
+                    headers,
+                )
+                .await
+                .unwrap()
+        }
+    };
+
+    let res = upsert(&payload, None).await;
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["action"], "created");
+    let created: ConnectionModelDefinition =
+        serde_json::from_value(res.data["definition"].clone()).unwrap();
+    assert_eq!(created.key, payload.key());
+
+    let created_tag = created.record_metadata.updated_at.to_string();
+    payload.title = "Updated by sync".to_string();
+    let res = upsert(&payload, Some(created_tag.clone())).await;
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["action"], "updated");
+    let updated: ConnectionModelDefinition =
+        serde_json::from_value(res.data["definition"].clone()).unwrap();
+    assert_eq!(updated.id, created.id);
+    assert_eq!(updated.title, "Updated by sync");
+    assert_eq!(
+        updated.record_metadata.created_at,
+        created.record_metadata.created_at
+    );
+
+    // The tag of the first version is stale once the second one is written
+    let res = upsert(&payload, Some(created_tag)).await;
+    assert_eq!(res.code, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_connection_model_definition_of_unknown_connection_definition_is_not_found() {
+    let server = TestServer::new(None).await;
+
+    let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+    payload.id = None;
+    payload.connection_definition_id = Id::now(IdPrefix::ConnectionDefinition);
+    let payload = serde_json::to_value(&payload).unwrap();
+
+    for (path, method) in [
+        ("v1/connection-model-definitions", Method::POST),
+        ("v1/connection-model-definitions/by-key", Method::PUT),
+    ] {
+        let res = server
+            .send_request::<Value, Value>(path, method, Some(&server.live_key), Some(&payload))
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::NOT_FOUND);
+    }
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let definitions: ReadResponse<ConnectionModelDefinition> =
+        serde_json::from_value(res.data).unwrap();
+    assert!(definitions.rows.is_empty());
+}
+
+#[tokio::test]
+async fn test_connection_model_definition_with_invalid_method_or_header_names_the_field() {
+    let server = TestServer::new(None).await;
+
+    let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+    payload.connection_definition_id = server.create_connection_definition().await.id;
+    let payload = serde_json::to_value(&payload).unwrap();
+
+    let mut bad_method = payload.clone();
+    bad_method["action"] = json!("GE T");
+    let mut bad_header = payload.clone();
+    bad_header["headers"] = json!({ "x-api-version": "2", "bad header": "1" });
+
+    for (body, field, value) in [
+        (&bad_method, "action", "GE T"),
+        (&bad_header, "headers.bad header", "1"),
+    ] {
+        for (path, method) in [
+            ("v1/connection-model-definitions", Method::POST),
+            ("v1/connection-model-definitions/by-key", Method::PUT),
+        ] {
+            let res = server
+                .send_request::<Value, Value>(path, method, Some(&server.live_key), Some(body))
+                .await
+                .unwrap();
+            assert_eq!(res.code, StatusCode::BAD_REQUEST);
+            assert_eq!(res.data["meta"]["field"], field);
+            assert_eq!(res.data["meta"]["value"], value);
+        }
+    }
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!([{ "_id": Id::now(IdPrefix::ConnectionModelDefinition), "action": "" }])),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::BAD_REQUEST);
+    assert_eq!(res.data["meta"]["field"], "[0].action");
+    assert_eq!(res.data["meta"]["value"], "");
+}
+
+#[tokio::test]
+async fn test_platforms_summary_aggregates_definitions_by_platform() {
+    let server = TestServer::new(None).await;
+
+    let mut mapped = None;
+    for (platform, version, supported) in [
+        ("alpha", "1.9.0", true),
+        ("alpha", "1.10.0", true),
+        ("alpha", "1.10.0", false),
+        ("beta", "2.0.0", true),
+    ] {
+        let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+        payload.connection_definition_id = server.create_connection_definition().await.id;
+        payload.connection_platform = platform.to_string();
+        payload.platform_version = version.to_string();
+        payload.supported = Some(supported);
+
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-model-definitions",
+                Method::POST,
+                Some(&server.live_key),
+                Some(&serde_json::to_value(&payload).unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::OK);
+
+        if platform == "beta" {
+            let definition: ConnectionModelDefinition = serde_json::from_value(res.data).unwrap();
+            mapped = Some(definition);
+        }
+    }
+
+    let mapped = mapped.unwrap();
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionModelDefinitionId": mapped.id,
+                "connectionPlatform": mapped.connection_platform,
+                "bindings": [{
+                    "variableName": "hotel_id",
+                    "targetParam": "hotelId",
+                    "location": "QueryParam"
+                }]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/platforms/summary",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 2);
+    assert_eq!(
+        res.data["rows"],
+        json!([
+            {
+                "platform": "alpha",
+                "supportedActions": 2,
+                "latestPlatformVersion": "1.10.0",
+                "hasVariableMapping": false,
+            },
+            {
+                "platform": "beta",
+                "supportedActions": 1,
+                "latestPlatformVersion": "2.0.0",
+                "hasVariableMapping": true,
+            },
+        ])
+    );
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/platforms/summary?limit=1&skip=1",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 2);
+    assert_eq!(res.data["hasMore"], false);
+    assert_eq!(res.data["rows"][0]["platform"], "beta");
+}
+
+#[tokio::test]
+async fn test_bulk_delete_and_restore_connection_model_definitions() {
+    let server = TestServer::new(None).await;
+
+    let mut ids = vec![];
+    for (platform, path) in [("legacy", "customers"), ("legacy", "orders"), ("kept", "rooms")] {
+        let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+        payload.connection_definition_id = server.create_connection_definition().await.id;
+        payload.connection_platform = platform.to_string();
+        payload.path = path.to_string();
+
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-model-definitions",
+                Method::POST,
+                Some(&server.live_key),
+                Some(&serde_json::to_value(&payload).unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::OK);
+        ids.push(res.data["_id"].as_str().unwrap().to_string());
+    }
+
+    let bulk = |action: &'static str, filter: Value| {
+        let server = &server;
+        async move {
+            server
+                .send_request::<Value, Value>(
+                    &format!("v1/connection-model-definitions/bulk-{action}"),
+                    Method::POST,
+                    Some(&server.live_key),
+                    Some(&filter),
+                )
+                .await
+                .unwrap()
+        }
+    };
+
+    for filter in [json!({}), json!({ "platform": "", "ids": [] })] {
+        assert_eq!(bulk("delete", filter).await.code, StatusCode::BAD_REQUEST);
+    }
+
+    let res = bulk("delete", json!({ "platform": "legacy" })).await;
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 2);
+    assert_eq!(res.data["succeeded"], 2);
+    let mut deleted: Vec<&str> = res.data["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["id"].as_str().unwrap())
+        .collect();
+    deleted.sort();
+    let mut expected = vec![ids[0].as_str(), ids[1].as_str()];
+    expected.sort();
+    assert_eq!(deleted, expected);
+
+    let live_ids = || async {
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-model-definitions",
+                Method::GET,
+                Some(&server.live_key),
+                None,
+            )
+            .await
+            .unwrap();
+        let definitions: ReadResponse<ConnectionModelDefinition> =
+            serde_json::from_value(res.data).unwrap();
+        let mut ids: Vec<String> = definitions
+            .rows
+            .iter()
+            .map(|definition| definition.id.to_string())
+            .collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(live_ids().await, vec![ids[2].clone()]);
+
+    // Deleting again matches nothing, as they already are
+    let res = bulk("delete", json!({ "platform": "legacy" })).await;
+    assert_eq!(res.data["total"], 0);
+
+    // Only the ids answered with are restored, never everything of a platform
+    for filter in [json!({ "platform": "legacy" }), json!({ "ids": [] })] {
+        assert_eq!(bulk("restore", filter).await.code, StatusCode::BAD_REQUEST);
+    }
+
+    let res = bulk("restore", json!({ "ids": [ids[0]] })).await;
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["succeeded"], 1);
+
+    let mut expected = vec![ids[0].clone(), ids[2].clone()];
+    expected.sort();
+    assert_eq!(live_ids().await, expected);
+}
+
+#[tokio::test]
+async fn test_connection_model_definition_diff() {
+    let server = TestServer::new(None).await;
+
+    let mut ids = vec![];
+    for path in ["customers", "clients"] {
+        let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+        payload.connection_definition_id = server.create_connection_definition().await.id;
+        payload.path = path.to_string();
+
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-model-definitions",
+                Method::POST,
+                Some(&server.live_key),
+                Some(&serde_json::to_value(&payload).unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::OK);
+        ids.push(res.data["_id"].as_str().unwrap().to_string());
+    }
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!(
+                "v1/connection-model-definitions/diff?from={}&to={}",
+                ids[0], ids[1]
+            ),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let diff: connection_model_definition_diff::DefinitionDiff =
+        serde_json::from_value(res.data).unwrap();
+    assert_eq!(diff.from.to_string(), ids[0]);
+    assert!(diff
+        .changes
+        .iter()
+        .any(|change| change.path.ends_with(".path")
+            && change.from == Some(json!("customers"))
+            && change.to == Some(json!("clients"))));
+    assert!(diff.changes.iter().all(|change| change.path != "_id"));
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!(
+                "v1/connection-model-definitions/diff?from={}&to=missing",
+                ids[0]
+            ),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_connection_model_definition_flag_audit_trail() {
+    let server = TestServer::new(None).await;
+
+    let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+    payload.connection_definition_id = server.create_connection_definition().await.id;
+    payload.supported = Some(false);
+    payload.active = Some(false);
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&payload).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let model: ConnectionModelDefinition = serde_json::from_value(res.data).unwrap();
+
+    payload.supported = Some(true);
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connection-model-definitions/{}", model.id),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&payload).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!([{ "_id": model.id, "active": true }])),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connection-model-definitions/{}/audit", model.id),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 2);
+
+    // Newest first
+    let rows = res.data["rows"].as_array().unwrap();
+    assert_eq!(rows[0]["flag"], "active");
+    assert_eq!(rows[0]["oldValue"], false);
+    assert_eq!(rows[0]["newValue"], true);
+    assert_eq!(rows[1]["flag"], "supported");
+    assert!(rows[1]["actor"]["id"].is_string());
+}
+
+#[tokio::test]
+async fn test_connection_model_definition_json_patch() {
+    let server = TestServer::new(None).await;
+
+    let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+    payload.connection_definition_id = server.create_connection_definition().await.id;
+    payload.knowledge = Some("Only returns active hotels".to_string());
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&payload).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let model: ConnectionModelDefinition = serde_json::from_value(res.data).unwrap();
+
+    let headers = HashMap::from([
+        (
+            http::header::CONTENT_TYPE.to_string(),
+            connection_model_definition::JSON_PATCH_CONTENT_TYPE.to_string(),
+        ),
+        (http::header::AUTHORIZATION.to_string(), server.token.clone()),
+    ]);
+
+    let res = server
+        .send_request_with_headers::<Value, Value>(
+            &format!("v1/connection-model-definitions/{}", model.id),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!([
+                { "op": "replace", "path": "/title", "value": "Patched title" },
+                { "op": "remove", "path": "/knowledge" },
+            ])),
+            Some(headers.clone().into_iter().collect()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connection-model-definitions?_id={}", model.id),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    let response: ReadResponse<ConnectionModelDefinition> =
+        serde_json::from_value(res.data).unwrap();
+    let patched = response.rows.first().expect("Model not found");
+    assert_eq!(patched.title, "Patched title");
+    assert_eq!(patched.knowledge, None);
+    assert_eq!(patched.name, model.name);
+
+    // A patch producing an invalid definition is rejected without writing
+    let res = server
+        .send_request_with_headers::<Value, Value>(
+            &format!("v1/connection-model-definitions/{}", model.id),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!([
+                { "op": "replace", "path": "/title", "value": "Never written" },
+                { "op": "remove", "path": "/connectionPlatform" },
+            ])),
+            Some(headers.into_iter().collect()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_connection_model_definition_lifecycle() {
+    let server = TestServer::new(None).await;
+
+    let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+    payload.connection_definition_id = server.create_connection_definition().await.id;
+    payload.supported = Some(false);
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&payload).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let model: ConnectionModelDefinition = serde_json::from_value(res.data).unwrap();
+
+    let server = &server;
+    let platform = model.connection_platform.as_str();
+    let transition = |lifecycle: &'static str| async move {
+        server
+            .send_request::<Value, Value>(
+                &format!("v1/connection-model-definitions/{}/lifecycle", model.id),
+                Method::POST,
+                Some(&server.live_key),
+                Some(&json!({ "lifecycle": lifecycle })),
+            )
+            .await
+    };
+    let available_actions = || async move {
+        server
+            .send_request::<Value, Value>(
+                &format!("v1/available-actions/{platform}"),
+                Method::GET,
+                Some(&server.live_key),
+                None,
+            )
+            .await
+    };
+
+    // Drafts cannot skip publishing
+    let res = transition("deprecated").await.unwrap();
+    assert_eq!(res.code, StatusCode::CONFLICT);
+
+    let res = transition("published").await.unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["lifecycle"], "published");
+    assert_eq!(res.data["supported"], true);
+    assert_eq!(res.data["lifecycleTransition"]["from"], "draft");
+    assert!(res.data["lifecycleTransition"]["actor"]["id"].is_string());
+
+    let res = available_actions().await.unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 1);
+
+    let res = transition("deprecated").await.unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["lifecycle"], "deprecated");
+
+    let res = available_actions().await.unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 0);
+
+    let res = transition("published").await.unwrap();
+    assert_eq!(res.code, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_available_actions_ordering() {
+    let server = TestServer::new(None).await;
+    let platform: String = Faker.fake();
+
+    for title in ["Bravo", "Alpha", "Charlie"] {
+        let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+        payload.connection_definition_id = server.create_connection_definition().await.id;
+        payload.connection_platform = platform.clone();
+        payload.title = title.to_string();
+        payload.supported = Some(true);
+
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-model-definitions",
+                Method::POST,
+                Some(&server.live_key),
+                Some(&serde_json::to_value(&payload).unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::OK);
+    }
+
+    let server = &server;
+    let platform = platform.as_str();
+    let titles = |query: &'static str| async move {
+        let res = server
+            .send_request::<Value, Value>(
+                &format!("v1/available-actions/{platform}?{query}"),
+                Method::GET,
+                Some(&server.live_key),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::OK);
+
+        res.data["rows"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| row["title"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(titles("sort=title").await, ["Alpha", "Bravo", "Charlie"]);
+    assert_eq!(titles("sort=-title").await, ["Charlie", "Bravo", "Alpha"]);
+
+    // Pages of the default order add up to the whole listing
+    let all = titles("").await;
+    let mut paged = Vec::new();
+    for query in ["limit=1&skip=0", "limit=1&skip=1", "limit=1&skip=2"] {
+        paged.extend(titles(query).await);
+    }
+    assert_eq!(paged, all);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/available-actions/{platform}?sort=ownership"),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_connection_model_definition_sample_validation() {
+    let server = TestServer::new(None).await;
+
+    let mut payload: connection_model_definition::CreateRequest = Faker.fake();
+    payload.connection_definition_id = server.create_connection_definition().await.id;
+    payload.schemas.body = Some(
+        serde_json::from_value(json!({
+            "type": "object",
+            "required": ["hotelId"],
+            "properties": { "hotelId": { "type": "string" } }
+        }))
+        .unwrap(),
+    );
+    payload.samples.body = Some(json!({ "hotelId": 42 }));
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&payload).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.data["meta"]["errors"][0],
+        "Sample body does not match its schema: $.hotelId should be of type string"
+    );
+
+    payload.samples.body = Some(json!({ "hotelId": "42" }));
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&payload).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let model: ConnectionModelDefinition = serde_json::from_value(res.data).unwrap();
+
+    // Samples merged by a bulk update are checked against the stored schemas
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!([{ "_id": model.id, "samples": { "body": { "hotelId": 42 } } }])),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let results: Vec<connection_model_definition::BatchUpdateResult> =
+        serde_json::from_value(res.data).unwrap();
+    assert!(!results[0].success);
+
+    // And so are the samples of a patched definition
+    let headers = HashMap::from([
+        (
+            http::header::CONTENT_TYPE.to_string(),
+            connection_model_definition::JSON_PATCH_CONTENT_TYPE.to_string(),
+        ),
+        (
+            http::header::AUTHORIZATION.to_string(),
+            server.token.clone(),
+        ),
+    ]);
+    let res = server
+        .send_request_with_headers::<Value, Value>(
+            &format!("v1/connection-model-definitions/{}", model.id),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&json!([
+                { "op": "replace", "path": "/samples/body/hotelId", "value": 42 },
+            ])),
+            Some(headers.into_iter().collect()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::UNPROCESSABLE_ENTITY);
+}
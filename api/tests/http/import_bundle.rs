@@ -0,0 +1,96 @@
+use crate::context::TestServer;
+use api::logic::connection_model_definition;
+use fake::{Fake, Faker};
+use http::{Method, StatusCode};
+use osentities::{environment::Environment, prefix::IdPrefix, Id};
+use serde_json::{json, Value};
+
+async fn count_by_key(server: &TestServer, key: &str) -> u64 {
+    server
+        .send_request::<Value, Value>(
+            &format!("v1/connection-model-definitions?key={key}"),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap()
+        .data["total"]
+        .as_u64()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_import_bundle_stages_before_commit() {
+    let mut server = TestServer::new(None).await;
+    let (_, model_def) = server.create_connection(Environment::Live).await;
+
+    let mut item: connection_model_definition::CreateRequest = Faker.fake();
+    item.connection_definition_id = model_def.connection_definition_id;
+    item.connection_platform = "import-test".to_string();
+    item.platform_version = "v1".to_string();
+    item.model_name = "hotels".to_string();
+    item.path = "/hotels".to_string();
+    item.name = "listhotels".to_string();
+
+    let mut orphan: connection_model_definition::CreateRequest = Faker.fake();
+    orphan.connection_definition_id = Id::now(IdPrefix::ConnectionDefinition);
+
+    // An invalid item rejects the whole bundle and nothing is staged
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions/import",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({ "items": [item, orphan] })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["valid"], false);
+    assert!(res.data.get("stagedBundleId").is_none());
+    assert_eq!(res.data["report"][0]["status"], "valid");
+    assert_eq!(res.data["report"][1]["status"], "invalid");
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions/import",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({ "items": [item] })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.data["valid"], true);
+    assert_eq!(res.data["report"][0]["action"], "create");
+    let staged_bundle_id = res.data["stagedBundleId"].clone();
+    let key = res.data["report"][0]["key"].as_str().unwrap().to_string();
+
+    assert_eq!(count_by_key(&server, &key).await, 0);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions/import?commit=true",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({ "stagedBundleId": staged_bundle_id })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["committed"], true);
+    assert_eq!(res.data["report"][0]["status"], "committed");
+    assert_eq!(count_by_key(&server, &key).await, 1);
+
+    // A committed bundle is consumed
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions/import?commit=true",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({ "stagedBundleId": staged_bundle_id })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,153 @@
+use crate::context::TestServer;
+use http::{Method, StatusCode};
+use osentities::environment::Environment;
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_read_knowledge_only_mapped() {
+    let mut server = TestServer::new(None).await;
+    let (_connection, model_def) = server.create_connection(Environment::Live).await;
+
+    let res = server
+        .send_request::<Value, Value>("v1/knowledge", Method::GET, Some(&server.live_key), None)
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let unfiltered_total = res.data["total"].as_u64().unwrap();
+    assert!(unfiltered_total > 1);
+    assert_eq!(res.data["degraded"], false);
+
+    let mapping = json!({
+        "connectionModelDefinitionId": model_def.id,
+        "connectionPlatform": model_def.connection_platform,
+        "bindings": [{
+            "variableName": "hotel_id",
+            "targetParam": "hotelId",
+            "location": "QueryParam"
+        }]
+    });
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&mapping),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/knowledge?onlyMapped=true",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let rows = res.data["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["_id"], model_def.id.to_string());
+    assert!(rows[0]["knowledge"]
+        .as_str()
+        .unwrap()
+        .starts_with("IMPORTANT: "));
+    assert_eq!(res.data["total"], 1);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/knowledge?onlyMapped=false",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.data["total"].as_u64().unwrap(), unfiltered_total);
+}
+
+#[tokio::test]
+async fn test_read_knowledge_appends_the_override_of_the_connection() {
+    let mut server = TestServer::new(None).await;
+    let (connection, model_def) = server.create_connection(Environment::Live).await;
+    let addendum = "This tenant uses property codes, not names.";
+
+    let knowledge_of = |data: &Value| {
+        data["rows"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|row| row["_id"] == model_def.id.to_string())
+            .and_then(|row| row["knowledge"].as_str())
+            .map(ToOwned::to_owned)
+    };
+
+    let override_payload = json!({
+        "connectionId": connection.id,
+        "connectionModelDefinitionId": model_def.id,
+        "knowledge": addendum,
+    });
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/knowledge/overrides",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&override_payload),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/knowledge/overrides",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&override_payload),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CONFLICT);
+
+    let res = server
+        .send_request::<Value, Value>("v1/knowledge", Method::GET, Some(&server.live_key), None)
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let platform_knowledge = knowledge_of(&res.data);
+    assert!(!platform_knowledge
+        .as_deref()
+        .unwrap_or_default()
+        .contains(addendum));
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/knowledge?connectionKey={}", connection.key),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let expected = match platform_knowledge {
+        Some(knowledge) if !knowledge.is_empty() => format!("{knowledge}\n\n{addendum}"),
+        _ => addendum.to_string(),
+    };
+    assert_eq!(knowledge_of(&res.data), Some(expected));
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/knowledge?connectionKey=live::unknown::connection",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+}
@@ -2,8 +2,14 @@ pub mod auth;
 pub mod callback;
 pub mod connection;
 pub mod connection_retrieval;
+pub mod connection_webhook;
 pub mod crud;
+pub mod import_bundle;
+pub mod knowledge;
 pub mod pagination;
 pub mod passthrough;
+pub mod provision;
 pub mod schema;
+pub mod test_connection;
 pub mod unified;
+pub mod variable_mapping;
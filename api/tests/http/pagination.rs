@@ -37,15 +37,22 @@ async fn test_pagination() {
 
     let pipelines: Vec<CommonEnum> = enums.into_iter().rev().collect();
 
-    check_response(&server, 1, 0, &pipelines[..1]).await;
-    check_response(&server, 10, 0, &pipelines).await;
-    check_response(&server, 0, 10, &pipelines[10..]).await;
-    check_response(&server, 5, 0, &pipelines[..5]).await;
-    check_response(&server, 5, 5, &pipelines[5..]).await;
-    check_response(&server, 5, 10, &pipelines[10..]).await;
+    check_response(&server, 1, 0, &pipelines[..1], true, 10).await;
+    check_response(&server, 10, 0, &pipelines, false, 1).await;
+    check_response(&server, 0, 10, &pipelines[10..], false, 1).await;
+    check_response(&server, 5, 0, &pipelines[..5], true, 2).await;
+    check_response(&server, 5, 5, &pipelines[5..], false, 2).await;
+    check_response(&server, 5, 10, &pipelines[10..], false, 2).await;
 }
 
-async fn check_response(server: &TestServer, limit: u64, skip: u64, enums: &[CommonEnum]) {
+async fn check_response(
+    server: &TestServer,
+    limit: u64,
+    skip: u64,
+    enums: &[CommonEnum],
+    has_more: bool,
+    total_pages: u64,
+) {
     let res = server
         .send_request::<Value, Value>(
             &format!("v1/common-enums?limit={limit}&skip={skip}"),
@@ -63,4 +70,67 @@ async fn check_response(server: &TestServer, limit: u64, skip: u64, enums: &[Com
     assert_eq!(res.limit, limit);
     assert_eq!(res.skip, skip);
     assert_eq!(res.total, 10);
+    assert_eq!(res.has_more, has_more);
+    assert_eq!(res.total_pages, total_pages);
+}
+
+#[tokio::test]
+async fn test_oversized_limits_are_clamped() {
+    let server = TestServer::new(None).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/common-enums?limit=1000000",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res: ReadResponse<CommonEnum> = serde_json::from_value(res.data).unwrap();
+    assert_eq!(res.limit, 1000);
+}
+
+#[tokio::test]
+async fn test_default_page_size_applies_without_a_limit() {
+    let server = TestServer::new_with_env(None, &[("DEFAULT_PAGE_SIZE", "3")]).await;
+
+    for _ in 0..4 {
+        let req: CreateRequest = Faker.fake();
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/common-enums",
+                Method::POST,
+                Some(&server.live_key),
+                Some(&serde_json::to_value(&req).unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::OK);
+    }
+
+    let res = server
+        .send_request::<Value, Value>("v1/common-enums", Method::GET, Some(&server.live_key), None)
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res: ReadResponse<CommonEnum> = serde_json::from_value(res.data).unwrap();
+    assert_eq!(res.rows.len(), 3);
+    assert_eq!(res.limit, 3);
+    assert!(res.has_more);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/common-enums?limit=2",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    let res: ReadResponse<CommonEnum> = serde_json::from_value(res.data).unwrap();
+    assert_eq!(res.limit, 2);
 }
@@ -8,7 +8,7 @@ use http::{
 use mockito::Server;
 use osentities::{
     api_model_config::{AuthMethod, SamplesInput, SchemasInput},
-    connection_model_definition::CrudAction,
+    connection_model_definition::{CrudAction, CursorConfig, ExtractorConfig},
     environment::Environment,
 };
 use serde_json::Value;
@@ -37,7 +37,7 @@ async fn test_passthrough_api() {
     let create_model_definition_payload = CreateConnectionModelDefinitionRequest {
         id: None,
         connection_platform: connection.platform.to_string(),
-        connection_definition_id: conn_def.id,
+        connection_definition_id: conn_def.connection_definition_id,
         platform_version: conn_def.record_metadata.version.to_string(),
         title: Faker.fake(),
         name: Faker.fake(),
@@ -75,6 +75,7 @@ async fn test_passthrough_api() {
         active: Some(true),
         knowledge: None,
         tags: None,
+        request_transform: None,
     };
 
     let create_model_definition_response = server
@@ -166,3 +167,248 @@ async fn test_passthrough_api() {
 
     mock.assert_async().await;
 }
+
+#[tokio::test]
+async fn test_admission_metrics_report_passthrough_load() {
+    let server = TestServer::new(None).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/metrics/admission",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["inFlight"], 0);
+    assert_eq!(res.data["queued"], 0);
+    assert_eq!(
+        res.data["maxConcurrency"],
+        server.config.passthrough_max_concurrency
+    );
+}
+
+#[tokio::test]
+async fn test_telemetry_metrics_report_channel_drops() {
+    let server = TestServer::new(None).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/metrics/telemetry",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(res.code, StatusCode::OK);
+    let channels = res.data.as_array().unwrap();
+    assert_eq!(channels.len(), 2);
+    assert_eq!(channels[0]["name"], "event");
+    assert_eq!(
+        channels[1]["capacity"],
+        server.config.metric_save_channel_size
+    );
+    assert!(channels.iter().all(|channel| channel["dropped"] == 0));
+}
+
+#[tokio::test]
+async fn test_dead_letter_metrics_report_backlog() {
+    let server = TestServer::new(None).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/metrics/dead-letters",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["pending"], 0);
+    assert_eq!(res.data["exhausted"], 0);
+}
+
+#[tokio::test]
+async fn test_passthrough_missing_connection_header_has_error_code() {
+    let server = TestServer::new(None).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/passthrough/customers",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(res.code, StatusCode::BAD_REQUEST);
+    assert_eq!(res.data["errorCode"], "connection_header_missing");
+    assert_eq!(res.data["message"], "Connection header not found");
+}
+
+#[tokio::test]
+async fn test_passthrough_extracts_response_when_requested() {
+    let mut server = TestServer::new(None).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+
+    let mut upstream = Server::new_async().await;
+    let mock = upstream
+        .mock("GET", "/reservations")
+        .expect(2)
+        .with_status(200)
+        .with_body("{\"data\":[{\"id\":\"r1\"}],\"next\":null}")
+        .create_async()
+        .await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = conn_def.connection_definition_id;
+    definition.connection_platform = connection.platform.to_string();
+    definition.base_url = upstream.url();
+    definition.path = "reservations".to_string();
+    definition.auth_method = AuthMethod::None;
+    definition.http_method = Method::GET;
+    definition.headers = None;
+    definition.query_params = None;
+    definition.supported = Some(true);
+    definition.extractor_config = Some(ExtractorConfig {
+        pull_frequency: 5,
+        batch_size: 100,
+        cursor: CursorConfig {
+            param_name: None,
+            location: None,
+            format: None,
+            cursor_path: "_.body.next".to_string(),
+            data_path: "_.body.data".to_string(),
+            js_extractor_function: None,
+            reset_on_end: false,
+        },
+        limit: None,
+        sleep_after_finish: 0,
+        update_config: None,
+        enabled: true,
+    });
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let call = |extract: &'static str| {
+        server.send_request_with_headers::<Value, Value>(
+            "v1/passthrough/reservations",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+            Some(
+                vec![
+                    (
+                        "x-pica-connection-key".to_string(),
+                        connection.key.to_string(),
+                    ),
+                    ("x-pica-extract".to_string(), extract.to_string()),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        )
+    };
+
+    let res = call("true").await.unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data, serde_json::json!([{ "id": "r1" }]));
+
+    let res = call("false").await.unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["data"][0]["id"], "r1");
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_passthrough_rejects_hosts_off_the_egress_allowlist() {
+    let mut server = TestServer::new_with_env(None, &[("EGRESS_ALLOWED_HOSTS", "127.0.0.1")]).await;
+    let (connection, conn_def) = server.create_connection(Environment::Live).await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = conn_def.connection_definition_id;
+    definition.connection_platform = connection.platform.to_string();
+    definition.base_url = "http://blocked.example.com".to_string();
+    definition.path = "reservations".to_string();
+    definition.auth_method = AuthMethod::None;
+    definition.http_method = Method::GET;
+    definition.headers = None;
+    definition.query_params = None;
+    definition.supported = Some(true);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res = server
+        .send_request_with_headers::<Value, Value>(
+            "v1/passthrough/reservations",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+            Some(
+                vec![(
+                    "x-pica-connection-key".to_string(),
+                    connection.key.to_string(),
+                )]
+                .into_iter()
+                .collect(),
+            ),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_passthrough_rejects_bodies_nested_too_deep() {
+    let mut server = TestServer::new_with_env(None, &[("JSON_BODY_MAX_DEPTH", "8")]).await;
+    let (connection, _) = server.create_connection(Environment::Live).await;
+
+    let body = (0..9).fold(Value::Null, |inner, _| serde_json::json!([inner]));
+
+    let res = server
+        .send_request_with_headers::<Value, Value>(
+            "v1/passthrough/reservations",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&body),
+            Some(
+                vec![(
+                    "x-pica-connection-key".to_string(),
+                    connection.key.to_string(),
+                )]
+                .into_iter()
+                .collect(),
+            ),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::BAD_REQUEST);
+    assert_eq!(res.data["errorCode"], "body_too_complex");
+}
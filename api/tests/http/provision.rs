@@ -0,0 +1,208 @@
+use crate::context::TestServer;
+use api::logic::{connection_definition, connection_model_definition};
+use fake::{Fake, Faker};
+use http::{Method, StatusCode};
+use osentities::{
+    api_model_config::{SamplesInput, SchemasInput},
+    connection_definition::{ConnectionDefinition, ConnectionDefinitionType},
+};
+use serde_json::{json, Value};
+
+async fn connection_definition_without_test(server: &TestServer) -> ConnectionDefinition {
+    let mut payload: connection_definition::CreateRequest = Faker.fake();
+    payload.r#type = ConnectionDefinitionType::Api;
+    payload.test_connection = None;
+
+    let res = server
+        .send_request::<connection_definition::CreateRequest, ConnectionDefinition>(
+            "v1/connection-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&payload),
+        )
+        .await
+        .unwrap();
+    assert!(res.code.is_success());
+
+    res.data
+}
+
+async fn count(server: &TestServer, path: &str) -> u64 {
+    server
+        .send_request::<Value, Value>(path, Method::GET, Some(&server.live_key), None)
+        .await
+        .unwrap()
+        .data["total"]
+        .as_u64()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_provision_reuses_definitions_and_creates_mappings_of_the_caller() {
+    let server = TestServer::new(None).await;
+    let connection_definition = connection_definition_without_test(&server).await;
+
+    let mut definition: connection_model_definition::CreateRequest = Faker.fake();
+    definition.id = None;
+    definition.connection_definition_id = connection_definition.id;
+    definition.schemas = SchemasInput {
+        headers: None,
+        query_params: None,
+        path_params: None,
+        body: None,
+    };
+    definition.samples = SamplesInput {
+        headers: None,
+        query_params: None,
+        path_params: None,
+        body: None,
+    };
+    let key = definition.key();
+
+    let connection = json!({
+        "connectionDefinitionId": connection_definition.id,
+        "authFormData": { "API_KEY": "secret" },
+        "active": true,
+    });
+    let mapping = json!({
+        "definitionKey": key,
+        "bindings": [{
+            "variableName": "API_KEY",
+            "targetParam": "x-api-key",
+            "location": "Header",
+        }],
+    });
+    let provision = |definitions: Value| {
+        json!({
+            "connection": connection,
+            "definitions": definitions,
+            "mappings": [mapping],
+        })
+    };
+    let statuses = |data: &Value| {
+        data["report"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["status"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Definitions are shared by every account, so an API key cannot create one
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connections/provision",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&provision(json!([definition]))),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.data["provisioned"], false);
+    assert_eq!(statuses(&res.data), ["valid", "invalid", "valid"]);
+    assert_eq!(count(&server, "v1/connections").await, 0);
+
+    let res = server
+        .send_request::<connection_model_definition::CreateRequest, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&definition),
+        )
+        .await
+        .unwrap();
+    assert!(res.code.is_success());
+    let definition_id = res.data["_id"].clone();
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connections/provision",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&provision(json!([definition]))),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["provisioned"], true);
+    assert!(res.data["connection"]["key"].is_string());
+    assert_eq!(statuses(&res.data), ["created", "reused", "created"]);
+    assert_eq!(res.data["report"][1]["id"], definition_id);
+    let mapping_id = res.data["report"][2]["id"].clone();
+
+    // A second account of the same caller references the definition by key
+    // and gets the stored mapping instead of a duplicate
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connections/provision",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&provision(json!([{ "key": key }]))),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.data["provisioned"], true);
+    assert_eq!(statuses(&res.data), ["created", "reused", "reused"]);
+    assert_eq!(res.data["report"][1]["id"], definition_id);
+    assert_eq!(res.data["report"][2]["id"], mapping_id);
+
+    let mappings = server
+        .send_request::<Value, Value>(
+            &format!(
+                "v1/connection-variable-mappings?_id={}",
+                mapping_id.as_str().unwrap()
+            ),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_ne!(
+        mappings.data["rows"][0]["ownership"]["buildableId"],
+        json!("")
+    );
+
+    // Escaped keys contain `%`, so the key is encoded in the query
+    let query = reqwest::Url::parse_with_params("http://localhost", [("key", &key)]).unwrap();
+    assert_eq!(
+        count(
+            &server,
+            &format!("v1/connection-model-definitions?{}", query.query().unwrap())
+        )
+        .await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_provision_writes_nothing_when_the_request_is_invalid() {
+    let server = TestServer::new(None).await;
+    let connection_definition = connection_definition_without_test(&server).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connections/provision",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connection": {
+                    "connectionDefinitionId": connection_definition.id,
+                    "authFormData": {},
+                    "active": true,
+                },
+                "definitions": [{ "key": "api::missing::v1::rooms" }],
+                "mappings": [{ "definitionKey": "api::other", "bindings": [] }],
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["provisioned"], false);
+    assert!(res.data.get("connection").is_none());
+    assert_eq!(res.data["report"][0]["status"], "valid");
+    assert_eq!(res.data["report"][1]["status"], "invalid");
+    assert_eq!(res.data["report"][2]["status"], "invalid");
+
+    assert_eq!(count(&server, "v1/connections").await, 0);
+}
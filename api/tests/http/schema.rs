@@ -16,3 +16,25 @@ async fn test_connection_oauth_definition_schema_api() {
         .unwrap();
     assert_eq!(res.code, StatusCode::OK);
 }
+
+#[tokio::test]
+async fn test_create_request_schema_api() {
+    let server = TestServer::new(None).await;
+    let res = server
+        .send_request::<Value, Value>("v1/public/schema/create-request", Method::GET, None, None)
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let create = &res.data["createRequest"];
+    assert_eq!(create["properties"]["action"]["type"], "string");
+    assert!(create["properties"]["batchSize"].is_object());
+
+    let required = create["required"].as_array().unwrap();
+    assert!(required.contains(&Value::from("connectionPlatform")));
+    assert!(!required.contains(&Value::from("batchSize")));
+
+    let partial = &res.data["partialUpdateRequest"];
+    assert!(partial["properties"]["connectionPlatform"].is_object());
+    assert!(partial["required"].as_array().is_none_or(Vec::is_empty));
+}
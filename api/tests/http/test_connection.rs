@@ -0,0 +1,219 @@
+use crate::context::TestServer;
+use api::logic::connection_model_definition::CreateRequest as CreateConnectionModelDefinitionRequest;
+use fake::{Fake, Faker};
+use http::{header::AUTHORIZATION, Method, StatusCode};
+use mockito::Server;
+use mongodb::{bson::doc, Client};
+use osentities::{api_model_config::AuthMethod, environment::Environment, Secret, Store};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_batch_test_connection_reports_per_item_results() {
+    let mut server = TestServer::new(None).await;
+    let (connection, model_def) = server.create_connection(Environment::Live).await;
+
+    let mut upstream = Server::new_async().await;
+    let secret_key = Faker.fake::<String>();
+    let mock = upstream
+        .mock("GET", "/rooms")
+        .match_header(
+            AUTHORIZATION.as_str(),
+            format!("Bearer {secret_key}").as_str(),
+        )
+        .expect(2)
+        .with_status(200)
+        .with_body("{\"rooms\":[]}")
+        .create_async()
+        .await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = model_def.connection_definition_id;
+    definition.connection_platform = model_def.connection_platform.clone();
+    definition.base_url = upstream.url();
+    definition.path = "rooms".to_string();
+    definition.auth_method = AuthMethod::BearerToken { value: secret_key };
+    definition.http_method = Method::GET;
+    definition.headers = None;
+    definition.query_params = None;
+    definition.active = Some(false);
+
+    let mut definition_ids = vec![];
+    for _ in 0..2 {
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-model-definitions",
+                Method::POST,
+                Some(&server.live_key),
+                Some(&serde_json::to_value(&definition).unwrap()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::OK);
+        definition_ids.push(res.data["_id"].as_str().unwrap().to_string());
+    }
+
+    let missing_id = "conn_mod_def::AAAAAAAAAAA::AAAAAAAAAAAAAAAAAAAAAA";
+    let tests = definition_ids
+        .iter()
+        .map(String::as_str)
+        .chain([missing_id])
+        .map(|id| json!({ "id": id, "request": {} }))
+        .collect::<Vec<_>>();
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions/test",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionKey": connection.key,
+                "tests": tests,
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let results = res.data.as_array().unwrap();
+    assert_eq!(results.len(), 3);
+
+    for (result, id) in results.iter().zip(&definition_ids) {
+        assert_eq!(result["id"], id.as_str());
+        assert_eq!(result["success"], true);
+        assert_eq!(result["result"]["code"], 200);
+        assert_eq!(result["result"]["response"], "{\"rooms\":[]}");
+    }
+
+    assert_eq!(results[2]["id"], missing_id);
+    assert_eq!(results[2]["success"], false);
+    assert!(results[2]["error"].is_string());
+    mock.assert_async().await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions/test",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({ "connectionKey": "missing", "tests": [] })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_connection_rejects_unresolved_path_placeholders() {
+    let mut server = TestServer::new(None).await;
+    let (connection, model_def) = server.create_connection(Environment::Live).await;
+
+    let mut upstream = Server::new_async().await;
+    let secret_key = Faker.fake::<String>();
+    let mock = upstream
+        .mock("GET", "/rooms/r1/rates/bar")
+        .expect(1)
+        .with_status(200)
+        .with_body("{\"rates\":[]}")
+        .create_async()
+        .await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = model_def.connection_definition_id;
+    definition.connection_platform = model_def.connection_platform.clone();
+    definition.base_url = upstream.url();
+    definition.path = "rooms/{roomId}/rates/{{rateId}}".to_string();
+    definition.auth_method = AuthMethod::BearerToken { value: secret_key };
+    definition.http_method = Method::GET;
+    definition.headers = None;
+    definition.query_params = None;
+    definition.active = Some(false);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let path = format!(
+        "v1/connection-model-definitions/test/{}",
+        res.data["_id"].as_str().unwrap()
+    );
+
+    let res = server
+        .send_request::<Value, Value>(
+            &path,
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionKey": connection.key,
+                "request": { "pathParams": { "roomId": "r1" } },
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.data["meta"]["missing"], json!(["rateId"]));
+
+    // Placeholders can also be given as query params
+    let res = server
+        .send_request::<Value, Value>(
+            &path,
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionKey": connection.key,
+                "request": {
+                    "pathParams": { "roomId": "r1" },
+                    "queryParams": { "rateId": "bar" },
+                },
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["code"], 200);
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_connection_reports_missing_and_undecryptable_secrets_distinctly() {
+    let mut server = TestServer::new(None).await;
+    let (connection, model_def) = server.create_connection(Environment::Live).await;
+
+    let secrets = Client::with_uri_str(&server.config.db_config.control_db_url)
+        .await
+        .unwrap()
+        .database(&server.config.db_config.control_db_name)
+        .collection::<Secret>(&Store::Secrets.to_string());
+    let path = format!("v1/connection-model-definitions/test/{}", model_def.id);
+    let payload = json!({ "connectionKey": connection.key });
+
+    // Encrypted with another key or tampered with
+    secrets
+        .update_many(
+            doc! {},
+            doc! { "$set": { "encryptedSecret": "07".repeat(64) } },
+        )
+        .await
+        .unwrap();
+
+    let res = server
+        .send_request::<Value, Value>(&path, Method::POST, Some(&server.live_key), Some(&payload))
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(res.data["errorCode"], "secret_decryption_failed");
+
+    secrets.delete_many(doc! {}).await.unwrap();
+
+    let res = server
+        .send_request::<Value, Value>(&path, Method::POST, Some(&server.live_key), Some(&payload))
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+    assert_eq!(res.data["errorCode"], "secret_not_found");
+}
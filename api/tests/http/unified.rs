@@ -587,6 +587,7 @@ async fn create_connection_model_definition(
         active: Some(true),
         knowledge: None,
         tags: None,
+        request_transform: None,
     };
 
     let create_model_definition_response = server
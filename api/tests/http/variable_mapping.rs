@@ -0,0 +1,744 @@
+use crate::context::TestServer;
+use api::logic::connection_model_definition::CreateRequest as CreateConnectionModelDefinitionRequest;
+use fake::{Fake, Faker};
+use http::{header::AUTHORIZATION, Method, StatusCode};
+use mockito::{Matcher, Server};
+use osentities::{api_model_config::AuthMethod, environment::Environment};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_read_mappings_by_platform_groups_by_model_definition() {
+    let mut server = TestServer::new(None).await;
+    let (_connection, model_def) = server.create_connection(Environment::Live).await;
+
+    // Mappings of the definition in two environments make a single group
+    for environment in [None, Some("test")] {
+        let mapping = json!({
+            "connectionModelDefinitionId": model_def.id,
+            "connectionPlatform": model_def.connection_platform,
+            "environment": environment,
+            "bindings": [{
+                "variableName": "hotel_id",
+                "targetParam": "hotelId",
+                "location": "QueryParam"
+            }]
+        });
+
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-variable-mappings",
+                Method::POST,
+                Some(&server.live_key),
+                Some(&mapping),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, StatusCode::CREATED);
+    }
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!(
+                "v1/connection-variable-mappings/by-platform/{}",
+                model_def.connection_platform
+            ),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 1);
+
+    let rows = res.data["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["mappings"].as_array().unwrap().len(), 2);
+    assert_eq!(rows[0]["connectionModelDefinitionId"], model_def.id.to_string());
+    assert_eq!(rows[0]["modelName"], model_def.model_name);
+    assert_eq!(rows[0]["title"], model_def.title);
+    assert_eq!(rows[0]["mappings"][0]["bindings"][0]["variableName"], "hotel_id");
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings/by-platform/unknown-platform",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 0);
+    assert!(res.data["rows"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_mapping_test_endpoint_dispatches_resolved_request() {
+    let mut server = TestServer::new(None).await;
+    let (connection, model_def) = server.create_connection(Environment::Live).await;
+
+    let mut upstream = Server::new_async().await;
+    let secret_key = Faker.fake::<String>();
+    let mock = upstream
+        .mock("GET", "/hotels")
+        .match_header(
+            AUTHORIZATION.as_str(),
+            format!("Bearer {secret_key}").as_str(),
+        )
+        .expect(1)
+        .with_status(200)
+        .with_body("{\"hotels\":[]}")
+        .create_async()
+        .await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = model_def.connection_definition_id;
+    definition.connection_platform = model_def.connection_platform.clone();
+    definition.base_url = upstream.url();
+    definition.path = "hotels".to_string();
+    definition.auth_method = AuthMethod::BearerToken { value: secret_key };
+    definition.http_method = Method::GET;
+    definition.headers = None;
+    definition.query_params = None;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let definition_id = res.data["_id"].clone();
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionModelDefinitionId": definition_id,
+                "connectionPlatform": model_def.connection_platform,
+                "bindings": [{
+                    "variableName": "hotel_id",
+                    "targetParam": "hotelId",
+                    "location": "QueryParam"
+                }]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+    let mapping_id = res.data["_id"].as_str().unwrap().to_string();
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connection-variable-mappings/{mapping_id}/test"),
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionKey": connection.key,
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["request"]["method"], "GET");
+    assert_eq!(res.data["response"]["code"], 200);
+    assert_eq!(res.data["response"]["body"], "{\"hotels\":[]}");
+    mock.assert_async().await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings/conn_var_map::AAAAAAAAAAA::AAAAAAAAAAAAAAAAAAAAAA/test",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({ "connectionKey": connection.key })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_round_trip_validates_the_built_request_without_dispatching() {
+    let server = TestServer::new(None).await;
+
+    let mut upstream = Server::new_async().await;
+    let mock = upstream
+        .mock("GET", Matcher::Any)
+        .expect(0)
+        .create_async()
+        .await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.base_url = upstream.url();
+    definition.path = "hotels".to_string();
+    definition.auth_method = AuthMethod::BearerToken {
+        value: "{{ACCESS_TOKEN}}".to_string(),
+    };
+    definition.http_method = Method::GET;
+    definition.headers = None;
+    definition.query_params = None;
+
+    let round_trip = |expected_token: &str| {
+        json!({
+            "definition": definition,
+            "mapping": {
+                "bindings": [{
+                    "variableName": "hotel_id",
+                    "targetParam": "hotelId",
+                    "location": "QueryParam"
+                }]
+            },
+            "secret": { "ACCESS_TOKEN": "synthetic-token", "hotel_id": "h-1" },
+            "expected": {
+                "method": "GET",
+                "url": format!("{}/hotels?hotelId=h-1", upstream.url()),
+                "headers": { "Authorization": format!("Bearer {expected_token}") }
+            }
+        })
+    };
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings/round-trip",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&round_trip("synthetic-token")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["passed"], true);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings/round-trip",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&round_trip("other-token")),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["passed"], false);
+    assert_eq!(res.data["mismatches"][0]["field"], "headers.authorization");
+    assert_eq!(
+        res.data["mismatches"][0]["actual"],
+        "Bearer synthetic-token"
+    );
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_test_environment_mapping_does_not_apply_to_live_requests() {
+    let mut server = TestServer::new(None).await;
+    let (connection, model_def) = server.create_connection(Environment::Live).await;
+
+    let mut upstream = Server::new_async().await;
+    let secret_key = Faker.fake::<String>();
+    let mock = upstream
+        .mock("GET", "/hotels")
+        .match_header(
+            AUTHORIZATION.as_str(),
+            format!("Bearer {secret_key}").as_str(),
+        )
+        .expect(1)
+        .with_status(200)
+        .with_body("{\"hotels\":[]}")
+        .create_async()
+        .await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = model_def.connection_definition_id;
+    definition.connection_platform = model_def.connection_platform.clone();
+    definition.base_url = upstream.url();
+    definition.path = "hotels".to_string();
+    definition.auth_method = AuthMethod::BearerToken { value: secret_key };
+    definition.http_method = Method::GET;
+    definition.headers = None;
+    definition.query_params = None;
+    definition.supported = Some(true);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    // The secret of the connection has no hotel_id, so applying the mapping
+    // would fail the request
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionModelDefinitionId": res.data["_id"],
+                "connectionPlatform": model_def.connection_platform,
+                "environment": "test",
+                "bindings": [{
+                    "variableName": "hotel_id",
+                    "targetParam": "hotelId",
+                    "location": "QueryParam"
+                }]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+    assert_eq!(res.data["environment"], "test");
+    let mapping_id = res.data["_id"].as_str().unwrap().to_string();
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connection-variable-mappings/{mapping_id}/test"),
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({ "connectionKey": connection.key })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::BAD_REQUEST);
+
+    let res = server
+        .send_request_with_headers::<Value, Value>(
+            "v1/passthrough/hotels",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+            Some(
+                [(
+                    "x-pica-connection-key".to_string(),
+                    connection.key.to_string(),
+                )]
+                .into_iter()
+                .collect(),
+            ),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_mappings_are_unique_per_environment_and_keep_it_on_update() {
+    let mut server = TestServer::new(None).await;
+    let (_, model_def) = server.create_connection(Environment::Live).await;
+
+    let create = |environment: Option<&str>| {
+        json!({
+            "connectionModelDefinitionId": model_def.id,
+            "connectionPlatform": model_def.connection_platform,
+            "environment": environment,
+            "bindings": [{
+                "variableName": "hotel_id",
+                "targetParam": "hotelId",
+                "location": "QueryParam"
+            }]
+        })
+    };
+
+    for (environment, code) in [
+        (None, StatusCode::CREATED),
+        (Some("test"), StatusCode::CREATED),
+        (Some("test"), StatusCode::CONFLICT),
+        (None, StatusCode::CONFLICT),
+    ] {
+        let res = server
+            .send_request::<Value, Value>(
+                "v1/connection-variable-mappings",
+                Method::POST,
+                Some(&server.live_key),
+                Some(&create(environment)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.code, code, "{environment:?}");
+    }
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!(
+                "v1/connection-variable-mappings?connectionModelDefinitionId={}&environment=test",
+                model_def.id
+            ),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let mapping_id = res.data["rows"][0]["_id"].as_str().unwrap().to_string();
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connection-variable-mappings/{mapping_id}"),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&create(None)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connection-variable-mappings?_id={mapping_id}"),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.data["rows"][0]["environment"], "test");
+}
+
+#[tokio::test]
+async fn test_create_mapping_of_unknown_model_definition_is_not_found() {
+    let server = TestServer::new(None).await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionModelDefinitionId": "conn_mod_def::AAAAAAAAAAA::AAAAAAAAAAAAAAAAAAAAAA",
+                "connectionPlatform": "unknown-platform",
+                "bindings": [{
+                    "variableName": "hotel_id",
+                    "targetParam": "hotelId",
+                    "location": "QueryParam"
+                }]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings/by-platform/unknown-platform",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["total"], 0);
+}
+
+#[tokio::test]
+async fn test_update_mapping_to_unknown_model_definition_is_not_found() {
+    let mut server = TestServer::new(None).await;
+    let (_, model_def) = server.create_connection(Environment::Live).await;
+
+    let mapping = |definition_id: &str| {
+        json!({
+            "connectionModelDefinitionId": definition_id,
+            "connectionPlatform": model_def.connection_platform,
+            "bindings": [{
+                "variableName": "hotel_id",
+                "targetParam": "hotelId",
+                "location": "QueryParam"
+            }]
+        })
+    };
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&mapping(&model_def.id.to_string())),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+    let mapping_id = res.data["_id"].as_str().unwrap().to_string();
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!("v1/connection-variable-mappings/{mapping_id}"),
+            Method::PATCH,
+            Some(&server.live_key),
+            Some(&mapping(
+                "conn_mod_def::AAAAAAAAAAA::AAAAAAAAAAAAAAAAAAAAAA",
+            )),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_replace_bindings_by_definition_creates_then_replaces_the_mapping() {
+    let mut server = TestServer::new(None).await;
+    let (_connection, model_def) = server.create_connection(Environment::Live).await;
+    let path = format!(
+        "v1/connection-variable-mappings/by-definition/{}",
+        model_def.id
+    );
+
+    let res = server
+        .send_request::<Value, Value>(
+            &path,
+            Method::PUT,
+            Some(&server.live_key),
+            Some(&json!({
+                "bindings": [{
+                    "variableName": "hotel_id",
+                    "targetParam": "hotelId",
+                    "location": "QueryParam"
+                }]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(
+        res.data["connectionPlatform"],
+        model_def.connection_platform
+    );
+    let mapping_id = res.data["_id"].clone();
+
+    let res = server
+        .send_request::<Value, Value>(
+            &path,
+            Method::PUT,
+            Some(&server.live_key),
+            Some(&json!({
+                "bindings": [
+                    { "variableName": "hotel_id", "targetParam": "X-Hotel-Id", "location": "Header" },
+                    { "variableName": "region", "targetParam": "region", "location": "QueryParam" }
+                ]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["_id"], mapping_id);
+    assert_eq!(res.data["bindings"].as_array().unwrap().len(), 2);
+    assert_eq!(res.data["bindings"][0]["targetParam"], "X-Hotel-Id");
+
+    let res = server
+        .send_request::<Value, Value>(
+            &path,
+            Method::PUT,
+            Some(&server.live_key),
+            Some(&json!({
+                "bindings": [
+                    { "variableName": "hotel_id", "targetParam": "X-Hotel-Id", "location": "Header" },
+                    { "variableName": "hotel", "targetParam": "x-hotel-id", "location": "Header" }
+                ]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::BAD_REQUEST);
+
+    let res = server
+        .send_request::<Value, Value>(
+            &format!(
+                "v1/connection-variable-mappings/by-platform/{}",
+                model_def.connection_platform
+            ),
+            Method::GET,
+            Some(&server.live_key),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.data["total"], 1);
+    assert_eq!(
+        res.data["rows"][0]["mappings"][0]["bindings"][1]["targetParam"],
+        "region"
+    );
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings/by-definition/conn_mod_def::AAAAAAAAAAA::AAAAAAAAAAAAAAAAAAAAAA",
+            Method::PUT,
+            Some(&server.live_key),
+            Some(&json!({ "bindings": [] })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_replace_bindings_by_definition_replaces_the_mapping_of_an_environment() {
+    let mut server = TestServer::new(None).await;
+    let (_connection, model_def) = server.create_connection(Environment::Live).await;
+    let path = format!(
+        "v1/connection-variable-mappings/by-definition/{}",
+        model_def.id
+    );
+    let bindings = json!([{
+        "variableName": "hotel_id",
+        "targetParam": "hotelId",
+        "location": "QueryParam"
+    }]);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionModelDefinitionId": model_def.id,
+                "connectionPlatform": model_def.connection_platform,
+                "environment": "live",
+                "bindings": bindings,
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+    let live_id = res.data["_id"].clone();
+
+    // The only mapping is replaced rather than joined by a wildcard one
+    let res = server
+        .send_request::<Value, Value>(
+            &path,
+            Method::PUT,
+            Some(&server.live_key),
+            Some(&json!({ "bindings": [] })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_eq!(res.data["_id"], live_id);
+    assert_eq!(res.data["environment"], "live");
+
+    let res = server
+        .send_request::<Value, Value>(
+            &path,
+            Method::PUT,
+            Some(&server.live_key),
+            Some(&json!({ "bindings": bindings, "environment": "test" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    assert_ne!(res.data["_id"], live_id);
+
+    // With a mapping per environment, the one to replace must be named
+    let res = server
+        .send_request::<Value, Value>(
+            &path,
+            Method::PUT,
+            Some(&server.live_key),
+            Some(&json!({ "bindings": [] })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_creating_a_mapping_applies_it_to_the_next_request() {
+    let mut server = TestServer::new(None).await;
+    let (connection, model_def) = server.create_connection(Environment::Live).await;
+
+    let mut upstream = Server::new_async().await;
+    let unmapped = upstream
+        .mock("GET", "/rooms")
+        .match_header("x-api-version", mockito::Matcher::Missing)
+        .expect(1)
+        .with_status(200)
+        .with_body("{\"rooms\":[]}")
+        .create_async()
+        .await;
+    let mapped = upstream
+        .mock("GET", "/rooms")
+        .match_header("x-api-version", "2")
+        .expect(1)
+        .with_status(200)
+        .with_body("{\"rooms\":[]}")
+        .create_async()
+        .await;
+
+    let mut definition: CreateConnectionModelDefinitionRequest = Faker.fake();
+    definition.connection_definition_id = model_def.connection_definition_id;
+    definition.connection_platform = model_def.connection_platform.clone();
+    definition.base_url = upstream.url();
+    definition.path = "rooms".to_string();
+    definition.auth_method = AuthMethod::None;
+    definition.http_method = Method::GET;
+    definition.headers = None;
+    definition.query_params = None;
+    definition.supported = Some(true);
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-model-definitions",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&serde_json::to_value(&definition).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    let definition_id = res.data["_id"].clone();
+
+    let passthrough = || {
+        server.send_request_with_headers::<Value, Value>(
+            "v1/passthrough/rooms",
+            Method::GET,
+            Some(&server.live_key),
+            None,
+            Some(
+                [(
+                    "x-pica-connection-key".to_string(),
+                    connection.key.to_string(),
+                )]
+                .into_iter()
+                .collect(),
+            ),
+        )
+    };
+
+    // Caches that the definition has no mapping
+    let res = passthrough().await.unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    unmapped.assert_async().await;
+
+    let res = server
+        .send_request::<Value, Value>(
+            "v1/connection-variable-mappings",
+            Method::POST,
+            Some(&server.live_key),
+            Some(&json!({
+                "connectionModelDefinitionId": definition_id,
+                "connectionPlatform": model_def.connection_platform,
+                "bindings": [{
+                    "targetParam": "x-api-version",
+                    "location": "Header",
+                    "constant": "2"
+                }]
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.code, StatusCode::CREATED);
+
+    let res = passthrough().await.unwrap();
+    assert_eq!(res.code, StatusCode::OK);
+    mapped.assert_async().await;
+}
@@ -74,6 +74,9 @@ fn test_json_connection_model_definition() {
         record_metadata: RecordMetadata::test(),
         supported: false,
         knowledge: None,
+        lifecycle: None,
+        lifecycle_transition: None,
+        request_transform: None,
     };
 
     assert!(
@@ -183,6 +186,7 @@ fn test_json_connection() {
         environment: Environment::Live,
         platform: "platform".to_string().into(),
         secrets_service_id: "secrets-service-id".to_string(),
+        additional_secrets_service_ids: vec![],
         event_access_id: Id::test(IdPrefix::EventAccess),
         access_key: "access-key".to_string(),
         identity: Some("identity".to_string()),
@@ -211,6 +215,9 @@ fn test_json_connection() {
             expires_in: Some(100),
             expires_at: Some(100),
         }),
+        key_rotation: None,
+        request_signing_secret: None,
+        quota: None,
         record_metadata: RecordMetadata::test(),
     };
 
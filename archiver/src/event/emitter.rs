@@ -0,0 +1,57 @@
+use super::{finished::Finished, Event, SequencedEvent};
+use osentities::{Id, MongoStore, Unit};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// Stores the events of one archive run, numbering them from 1 in the order
+/// they are emitted. A number is used up even when storing its event fails, so
+/// consumers see the failure as a gap.
+pub struct Emitter {
+    archives: Arc<MongoStore<SequencedEvent>>,
+    reference: Id,
+    sequence: AtomicU64,
+}
+
+impl Emitter {
+    pub fn new(archives: Arc<MongoStore<SequencedEvent>>, reference: Id) -> Self {
+        Self {
+            archives,
+            reference,
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn emit(&self, event: Event) -> anyhow::Result<Unit> {
+        let sequence = self.next_sequence();
+        self.store(event, sequence).await
+    }
+
+    /// Emits the terminal `Finished` event, which carries the number of events
+    /// the run produced including itself
+    pub async fn finish(&self) -> anyhow::Result<Unit> {
+        let sequence = self.next_sequence();
+        self.store(
+            Event::Finished(Finished::new(self.reference, sequence)),
+            sequence,
+        )
+        .await
+    }
+
+    fn next_sequence(&self) -> u64 {
+        self.sequence.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    async fn store(&self, event: Event, sequence: u64) -> anyhow::Result<Unit> {
+        self.archives
+            .create_one(&SequencedEvent {
+                event,
+                run_reference: self.reference,
+                sequence,
+            })
+            .await?;
+
+        Ok(())
+    }
+}
@@ -10,14 +10,18 @@ pub struct Finished {
     id: Id,
     reference: Id,
     finished_at: DateTime<Utc>,
+    /// Number of events the run produced, this one included
+    #[serde(default)]
+    event_count: u64,
 }
 
 impl Finished {
-    pub fn new(id: Id) -> Self {
+    pub fn new(id: Id, event_count: u64) -> Self {
         Self {
             id: Id::now(IdPrefix::Archive),
             reference: id,
             finished_at: Utc::now(),
+            event_count,
         }
     }
 }
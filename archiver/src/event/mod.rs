@@ -2,6 +2,7 @@ pub mod chosen;
 pub mod completed;
 pub mod deleted;
 pub mod dumped;
+pub mod emitter;
 pub mod failed;
 pub mod finished;
 pub mod started;
@@ -43,6 +44,18 @@ pub enum Event {
     Deleted(Deleted),
 }
 
+/// An event as stored, with its position in the run that emitted it so
+/// consumers can order a run's events and detect missing ones
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SequencedEvent {
+    #[serde(flatten)]
+    pub event: Event,
+    /// Reference of the run, the id of its `Started` event
+    pub run_reference: Id,
+    pub sequence: u64,
+}
+
 impl Event {
     pub fn is_finished(&self) -> bool {
         matches!(self, Event::Finished(_))
@@ -63,3 +76,36 @@ impl EventMetadata for Event {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use osentities::prefix::IdPrefix;
+
+    #[test]
+    fn test_sequenced_event_keeps_event_shape() {
+        let started = Started::new("events".to_string());
+        let sequenced = SequencedEvent {
+            event: Event::Started(started.clone()),
+            run_reference: started.reference(),
+            sequence: 1,
+        };
+
+        let value = serde_json::to_value(&sequenced).unwrap();
+        assert_eq!(value["type"], "Started");
+        assert_eq!(value["_id"], started.reference().to_string());
+        assert_eq!(value["runReference"], started.reference().to_string());
+        assert_eq!(value["sequence"], 1);
+
+        // Stored events are still read as plain events
+        let event: Event = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(event.reference(), started.reference());
+
+        let round_trip: SequencedEvent = serde_json::from_value(value).unwrap();
+        assert_eq!(round_trip.sequence, 1);
+
+        let finished = Finished::new(Id::now(IdPrefix::Archive), 4);
+        let value = serde_json::to_value(Event::Finished(finished)).unwrap();
+        assert_eq!(value["eventCount"], 4);
+    }
+}
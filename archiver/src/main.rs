@@ -3,7 +3,6 @@ mod event;
 mod storage;
 
 use crate::domain::config::{ArchiverConfig, Mode};
-use crate::event::finished::Finished;
 use anyhow::{anyhow, Result};
 use bson::{doc, Document};
 use chrono::offset::LocalResult;
@@ -14,10 +13,11 @@ use event::chosen::DateChosen;
 use event::completed::Completed;
 use event::deleted::Deleted;
 use event::dumped::Dumped;
+use event::emitter::Emitter;
 use event::failed::Failed;
 use event::started::Started;
 use event::uploaded::Uploaded;
-use event::{Event, EventMetadata};
+use event::{Event, EventMetadata, SequencedEvent};
 use futures::future::ready;
 use futures::stream::{self, Stream};
 use futures::{StreamExt, TryStreamExt};
@@ -51,6 +51,8 @@ async fn main() -> Result<Unit> {
     // TODO: Add TTL to the archived events
     let archives: Arc<MongoStore<Event>> =
         Arc::new(MongoStore::new(&database, &Store::Archives).await?);
+    let sequenced_archives: Arc<MongoStore<SequencedEvent>> =
+        Arc::new(MongoStore::new(&database, &Store::Archives).await?);
 
     let store = Store::from_str(&config.event_collection_name).map_err(|e| anyhow::anyhow!(e))?;
     let target_store: Arc<MongoStore<Document>> =
@@ -58,14 +60,33 @@ async fn main() -> Result<Unit> {
 
     loop {
         let started = Started::new(config.event_collection_name.clone());
-        archives
-            .create_one(&Event::Started(started.clone()))
-            .await?;
+        let emitter = Emitter::new(sequenced_archives.clone(), started.reference());
+        emitter.emit(Event::Started(started.clone())).await?;
 
         let res = match config.mode {
-            Mode::Dump => dump(&config, &archives, &started, &storage, &target_store, false).await,
+            Mode::Dump => {
+                dump(
+                    &config,
+                    &archives,
+                    &emitter,
+                    &started,
+                    &storage,
+                    &target_store,
+                    false,
+                )
+                .await
+            }
             Mode::DumpDelete => {
-                dump(&config, &archives, &started, &storage, &target_store, true).await
+                dump(
+                    &config,
+                    &archives,
+                    &emitter,
+                    &started,
+                    &storage,
+                    &target_store,
+                    true,
+                )
+                .await
             }
             Mode::NoOp => Ok(()),
         }
@@ -75,13 +96,11 @@ async fn main() -> Result<Unit> {
 
         match res {
             Ok(_) => {
-                archives
-                    .create_one(&Event::Finished(Finished::new(started.reference())))
-                    .await?;
+                emitter.finish().await?;
             }
             Err(e) => {
-                archives
-                    .create_one(&Event::Failed(Failed::new(
+                emitter
+                    .emit(Event::Failed(Failed::new(
                         e.to_string(),
                         started.reference(),
                         started.started_at(),
@@ -99,6 +118,7 @@ async fn main() -> Result<Unit> {
 async fn dump(
     config: &Arc<ArchiverConfig>,
     archives: &Arc<MongoStore<Event>>,
+    emitter: &Emitter,
     started: &Started,
     storage: &Arc<impl Storage>,
     target_store: &Arc<MongoStore<Document>>,
@@ -137,8 +157,8 @@ async fn dump(
                     dumped.end_time()
                 );
 
-                archives
-                    .create_one(&Event::Deleted(Deleted::new(
+                emitter
+                    .emit(Event::Deleted(Deleted::new(
                         dumped.reference(),
                         dumped.start_time(),
                         dumped.end_time(),
@@ -225,8 +245,8 @@ async fn dump(
         return Ok(());
     }
 
-    archives
-        .create_one(&Event::DateChosen(DateChosen::new(
+    emitter
+        .emit(Event::DateChosen(DateChosen::new(
             started.reference(),
             start.timestamp_millis(),
             end.timestamp_millis(),
@@ -250,7 +270,7 @@ async fn dump(
 
             let saved = save(
                 config,
-                archives,
+                emitter,
                 storage,
                 target_store,
                 started,
@@ -311,7 +331,7 @@ async fn dump(
 
 async fn save(
     config: &ArchiverConfig,
-    archive: &MongoStore<Event>,
+    emitter: &Emitter,
     storage: &Arc<impl Storage>,
     target_store: &MongoStore<Document>,
     started_event: &Started,
@@ -374,8 +394,8 @@ async fn save(
         return Err(anyhow!("Command mongodump failed: {:?}", command));
     }
 
-    archive
-        .create_one(&Event::Dumped(Dumped::new(
+    emitter
+        .emit(Event::Dumped(Dumped::new(
             started_event.reference(),
             *start_time,
             *end_time,
@@ -396,8 +416,8 @@ async fn save(
         return Err(anyhow!("Failed to upload bson file: {e}"));
     }
 
-    archive
-        .create_one(&Event::Uploaded(Uploaded::new(
+    emitter
+        .emit(Event::Uploaded(Uploaded::new(
             started_event.reference(),
             *start_time,
             *end_time,
@@ -410,8 +430,8 @@ async fn save(
 
     let remote_path = format!("gs://{}/{}", config.gs_storage_bucket, name);
 
-    archive
-        .create_one(&Event::Completed(Completed::new(
+    emitter
+        .emit(Event::Completed(Completed::new(
             remote_path.clone(),
             started_event.reference(),
             *start_time,
@@ -7,8 +7,10 @@ use osentities::connection_definition::ConnectionDefinition;
 use osentities::connection_model_definition::ConnectionModelDefinition;
 use osentities::connection_model_schema::ConnectionModelSchema;
 use osentities::connection_oauth_definition::ConnectionOAuthDefinition;
+use osentities::connection_variable_mapping::ConnectionVariableMapping;
 use osentities::destination::Destination;
 use osentities::event_access::EventAccess;
+use osentities::flag::PlatformFlag;
 use osentities::{ApplicationError, Connection, Id, MongoStore, PicaError, Secret, Unit};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -84,6 +86,8 @@ where
 
     fn remove(&self, key: &K) -> impl Future<Output = Result<Unit, PicaError>>;
 
+    fn clear(&self) -> impl Future<Output = Result<Unit, PicaError>>;
+
     fn max_capacity(&self) -> u64;
 }
 
@@ -135,6 +139,11 @@ where
         Ok(())
     }
 
+    async fn clear(&self) -> Result<Unit, PicaError> {
+        self.inner.invalidate_all();
+        Ok(())
+    }
+
     fn max_capacity(&self) -> u64 {
         self.inner.policy().max_capacity().unwrap_or_default()
     }
@@ -154,3 +163,7 @@ pub type ConnectionModelDefinitionCacheIdKey = GenericCache<Id, ConnectionModelD
 pub type ConnectionDefinitionCache = GenericCache<Id, ConnectionDefinition>;
 pub type ConnectionHeaderCache = GenericCache<ConnectionHeaderKey, Connection>;
 pub type ConnectionCache = GenericCache<ConnectionKey, Connection>;
+pub type PlatformFlagCache = GenericCache<Arc<str>, PlatformFlag>;
+/// Every mapping of a model definition, by the definition id. Definitions
+/// without mappings are cached too, as an empty list.
+pub type ConnectionVariableMappingCache = GenericCache<Id, Vec<ConnectionVariableMapping>>;
@@ -1,4 +1,4 @@
-use crate::{secrets::SecretsConfig, InternalError, PicaError, SecretVersion};
+use crate::{secrets::SecretsConfig, InternalError, PicaError, PicaErrorCode, SecretVersion};
 use async_trait::async_trait;
 use base64::{prelude::BASE64_STANDARD, Engine};
 use chacha20poly1305::aead::generic_array::typenum::Unsigned;
@@ -70,18 +70,26 @@ impl IOSCrypto {
     }
 
     async fn decrypt(&self, encrypted_secret: String) -> Result<String, PicaError> {
-        let obsf = hex::decode(encrypted_secret).map_err(|_| {}).map_err(|_| {
-            InternalError::deserialize_error("The provided value is not a valid UTF-8 string", None)
-        })?;
+        let failed = |message: &str| {
+            InternalError::decryption_error(
+                message,
+                PicaErrorCode::SecretDecryptionFailed.subtype(),
+            )
+        };
+
+        let obsf = hex::decode(encrypted_secret)
+            .map_err(|_| failed("The encrypted secret is not valid hex"))?;
+        if obsf.len() < NonceSize::to_usize() {
+            return Err(failed("The encrypted secret is shorter than its nonce"));
+        }
         let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(&self.key));
         let (nonce, ciphertext) = obsf.split_at(NonceSize::to_usize());
         let nonce = GenericArray::from_slice(nonce);
         let plaintext = cipher.decrypt(nonce, ciphertext).map_err(|_| {
-            InternalError::deserialize_error("The provided value is not a valid UTF-8 string", None)
-        })?;
-        let plaintext = String::from_utf8(plaintext).map_err(|_| {
-            InternalError::deserialize_error("The provided value is not a valid UTF-8 string", None)
+            failed("The secret could not be decrypted, it was encrypted with another key or tampered with")
         })?;
+        let plaintext = String::from_utf8(plaintext)
+            .map_err(|_| failed("The decrypted secret is not a valid UTF-8 string"))?;
 
         Ok(plaintext)
     }
@@ -162,24 +170,28 @@ impl GoogleCryptoKms {
                     ciphertext: BASE64_STANDARD.decode(encrypted_secret.as_bytes())
                         .map_err(|e| {
                             debug!("Error decoding secret: {e}");
-                            InternalError::deserialize_error("The provided value is not a valid UTF-8 string", None)
+                            InternalError::decryption_error(
+                                "The encrypted secret is not valid base64",
+                                PicaErrorCode::SecretDecryptionFailed.subtype(),
+                            )
                         })?,
                     ..Default::default()
                 };
 
+                // KMS rejections are not told apart from KMS outages
                 let decriptes_bytes = self.client.decrypt(request, None).await.map_err(|e| {
                     debug!("Error decrypting secret: {e}");
                     InternalError::connection_error(
-                        "The provided value is not a valid UTF-8 string",
-                        None,
+                        &format!("Google KMS could not decrypt the secret: {}", e.message()),
+                        PicaErrorCode::SecretsUnavailable.subtype(),
                     )
                 })?;
 
                 let plaintext = String::from_utf8(decriptes_bytes.plaintext).map_err(|e| {
                     debug!("Error converting decrypted secret to string: {e}");
-                    InternalError::deserialize_error(
-                        "The provided value is not a valid UTF-8 string",
-                        None,
+                    InternalError::decryption_error(
+                        "The decrypted secret is not a valid UTF-8 string",
+                        PicaErrorCode::SecretDecryptionFailed.subtype(),
                     )
                 })?;
 
@@ -237,7 +249,12 @@ mod tests {
 
         let decrypted = crypto.decrypt(encrypted).await;
 
-        assert!(decrypted.is_err());
+        let error = decrypted.expect_err("Decrypted with another key");
+        assert_eq!(
+            error.error_code(),
+            Some(PicaErrorCode::SecretDecryptionFailed)
+        );
+        assert_eq!(error.status(), 500);
     }
 
     #[tokio::test]
@@ -258,5 +275,12 @@ mod tests {
         let decrypted = crypto.decrypt(tampered).await;
 
         assert!(decrypted.is_err());
+
+        let truncated = crypto.decrypt("00ff".to_owned()).await;
+
+        assert_eq!(
+            truncated.map_err(|e| e.error_code()),
+            Err(Some(PicaErrorCode::SecretDecryptionFailed))
+        );
     }
 }
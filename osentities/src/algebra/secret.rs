@@ -1,9 +1,12 @@
 use super::{CryptoExt, GoogleCryptoKms, IOSCrypto, MongoStore};
 use crate::{
-    prelude::secret::Secret, secrets::SecretsConfig, InternalError, PicaError, SecretVersion,
+    prelude::secret::{merge_secrets, Secret},
+    secrets::SecretsConfig,
+    Connection, InternalError, PicaError, PicaErrorCode, SecretVersion,
 };
 use async_trait::async_trait;
 use bson::doc;
+use futures::future::try_join_all;
 use secrecy::ExposeSecret;
 use serde_json::Value;
 
@@ -14,6 +17,69 @@ pub trait SecretExt: Send + Sync {
     async fn create(&self, secret: &Value, buildable_id: &str) -> Result<Secret, PicaError>;
 }
 
+/// Decrypts the secrets of a connection into the object injected into its
+/// requests
+pub async fn connection_secret(
+    client: &dyn SecretExt,
+    connection: &Connection,
+) -> Result<Value, PicaError> {
+    let primary = client
+        .get(&connection.secrets_service_id, &connection.ownership.id)
+        .await?
+        .as_object(&connection.key)?;
+
+    merge_additional_secrets(client, connection, primary).await
+}
+
+/// Merges the additional secrets of a connection into `primary`, the object
+/// of its main secret, which takes precedence over them
+pub async fn merge_additional_secrets(
+    client: &dyn SecretExt,
+    connection: &Connection,
+    primary: Value,
+) -> Result<Value, PicaError> {
+    if connection.additional_secrets_service_ids.is_empty() {
+        return Ok(primary);
+    }
+
+    let additional = try_join_all(connection.additional_secrets_service_ids.iter().map(
+        |id| async move {
+            let secret = client
+                .get(id, &connection.ownership.id)
+                .await?
+                .as_object(&connection.key)?;
+            Ok::<_, PicaError>((id.clone(), secret))
+        },
+    ))
+    .await?;
+
+    Ok(merge_secrets(
+        &connection.key,
+        std::iter::once((connection.secrets_service_id.clone(), primary)).chain(additional),
+    ))
+}
+
+/// Looks up the stored secret, telling a missing secret apart from a store
+/// that could not be reached
+async fn find_secret(
+    storage: &MongoStore<Secret>,
+    id: &str,
+    buildable_id: &str,
+) -> Result<Secret, PicaError> {
+    storage
+        .get_one(doc! { "_id": id, "buildableId": buildable_id })
+        .await
+        .map_err(|e| {
+            InternalError::connection_error(
+                &format!("Could not read the secret: {e}"),
+                PicaErrorCode::SecretsUnavailable.subtype(),
+            )
+        })?
+        .ok_or_else(|| {
+            InternalError::key_not_found("Secret", PicaErrorCode::SecretNotFound.subtype())
+        })
+}
+
 #[derive(Debug, Clone)]
 pub struct IOSKms {
     storage: MongoStore<Secret>,
@@ -33,11 +99,7 @@ impl IOSKms {
 #[async_trait]
 impl SecretExt for IOSKms {
     async fn get(&self, id: &str, buildable_id: &str) -> Result<Secret, PicaError> {
-        let secret = self
-            .storage
-            .get_one(doc! { "_id": id, "buildableId": buildable_id })
-            .await?
-            .ok_or_else(|| InternalError::key_not_found("Secret", None))?;
+        let secret = find_secret(&self.storage, id, buildable_id).await?;
 
         let encrypted_secret = secret.encrypted_secret().expose_secret().to_owned();
         let version = secret.version();
@@ -94,11 +156,7 @@ impl GoogleKms {
 #[async_trait]
 impl SecretExt for GoogleKms {
     async fn get(&self, id: &str, buildable_id: &str) -> Result<Secret, PicaError> {
-        let secret = self
-            .storage
-            .get_one(doc! { "_id": id, "buildableId": buildable_id })
-            .await?
-            .ok_or_else(|| InternalError::key_not_found("Secret", None))?;
+        let secret = find_secret(&self.storage, id, buildable_id).await?;
 
         let encrypted_secret = secret.encrypted_secret().expose_secret().to_owned();
         let version = secret.version();
@@ -134,3 +192,35 @@ impl SecretExt for GoogleKms {
         Ok(secret)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{secrets::SecretServiceProvider, Store};
+    use mongodb::Client;
+
+    #[tokio::test]
+    async fn unreachable_store_is_reported_as_unavailable() {
+        let database = Client::with_uri_str(
+            "mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=100&connectTimeoutMS=100",
+        )
+        .await
+        .expect("Failed to create client")
+        .database("secrets");
+        let storage = MongoStore::new(&database, &Store::Secrets)
+            .await
+            .expect("Failed to create store");
+        let config = SecretsConfig::default().with_provider(SecretServiceProvider::IosKms);
+        let kms = IOSKms::new(&config, storage)
+            .await
+            .expect("Failed to create IOSKms client");
+
+        let error = kms
+            .get("id", "buildable_id")
+            .await
+            .expect_err("Read a secret from an unreachable store");
+
+        assert_eq!(error.error_code(), Some(PicaErrorCode::SecretsUnavailable));
+        assert_eq!(error.status(), 502);
+    }
+}
@@ -10,6 +10,8 @@ use crate::{prelude::schema::json_schema::JsonSchema, InternalError, PicaError};
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct ApiModelConfig {
+    /// May contain `{variable}` placeholders, resolved from the connection's
+    /// variables when the request is dispatched
     pub base_url: String,
     pub path: String,
     pub auth_method: AuthMethod,
@@ -54,21 +56,21 @@ impl ApiModelConfig {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 pub struct ModelPaths {
     pub request: Option<RequestModelPaths>,
     pub response: Option<ResponseModelPaths>,
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct RequestModelPaths {
     pub object: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct ResponseModelPaths {
@@ -77,7 +79,7 @@ pub struct ResponseModelPaths {
     pub cursor: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct SamplesInput {
@@ -86,6 +88,7 @@ pub struct SamplesInput {
         skip_serializing_if = "Option::is_none",
         default
     )]
+    #[schemars(with = "Option<BTreeMap<String, String>>")]
     pub headers: Option<http::HeaderMap>,
     pub query_params: Option<Value>,
     pub path_params: Option<Value>,
@@ -93,7 +96,7 @@ pub struct SamplesInput {
     pub body: Option<Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct SchemasInput {
@@ -103,7 +106,7 @@ pub struct SchemasInput {
     pub body: Option<JsonSchema>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct ResponseBody {
@@ -113,11 +116,12 @@ pub struct ResponseBody {
         skip_serializing_if = "Option::is_none",
         default
     )]
+    #[schemars(with = "Option<BTreeMap<String, String>>")]
     pub headers: Option<http::HeaderMap>,
     pub body: Option<Value>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(tag = "type")]
 pub enum AuthMethod {
@@ -146,7 +150,7 @@ pub enum AuthMethod {
     None,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 pub enum OAuthLegacyHashAlgorithm {
     #[serde(rename = "HMAC-SHA1")]
@@ -1,13 +1,18 @@
-use super::api_model_config::ApiModelConfig;
+use super::{
+    api_model_config::ApiModelConfig, connection_model_definition_audit::AuditActor,
+    request_transform::RequestTransform, variable_injection::RequestParts,
+};
 use crate::{
+    constant::BODY_KEY,
     id::Id,
     prelude::{schema::common_model::CommonModel, shared::record_metadata::RecordMetadata},
+    ApplicationError, InternalError, PicaError,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use strum::{Display, EnumIter};
+use strum::{Display, EnumIter, EnumString};
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 pub enum ParameterLocation {
     QueryParameter,
@@ -53,11 +58,116 @@ pub struct ConnectionModelDefinition {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub knowledge: Option<String>,
 
+    /// Editorial state of the definition. Definitions created before the
+    /// lifecycle existed have none, see
+    /// [`ConnectionModelDefinition::current_lifecycle`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<DefinitionLifecycle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "dummy", dummy(default))]
+    pub lifecycle_transition: Option<LifecycleTransition>,
+
+    /// Applied to requests right before they are dispatched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "dummy", dummy(default))]
+    pub request_transform: Option<RequestTransform>,
+
     #[serde(flatten, default)]
     pub record_metadata: RecordMetadata,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+impl ConnectionModelDefinition {
+    /// The stored lifecycle, or for definitions without one, the state implied
+    /// by the `supported` flag
+    pub fn current_lifecycle(&self) -> DefinitionLifecycle {
+        self.lifecycle.unwrap_or(if self.supported {
+            DefinitionLifecycle::Published
+        } else {
+            DefinitionLifecycle::Draft
+        })
+    }
+
+    /// Moves the definition to the next lifecycle state and derives the
+    /// `supported`/`active` flags from it. Only `Draft -> Published ->
+    /// Deprecated` is allowed, states cannot be skipped or reverted.
+    pub fn transition_lifecycle(
+        &mut self,
+        to: DefinitionLifecycle,
+        actor: AuditActor,
+        changed_at: i64,
+    ) -> Result<(), PicaError> {
+        let from = self.current_lifecycle();
+
+        if from.next() != Some(to) {
+            return Err(ApplicationError::conflict(
+                &format!("Cannot move a definition from {from} to {to}"),
+                None,
+            ));
+        }
+
+        // Deprecated definitions keep serving existing callers, they are only
+        // hidden from the available actions
+        if to == DefinitionLifecycle::Published {
+            self.supported = true;
+            self.record_metadata.active = true;
+        }
+
+        self.lifecycle = Some(to);
+        self.lifecycle_transition = Some(LifecycleTransition {
+            from,
+            to,
+            actor,
+            changed_at,
+        });
+
+        Ok(())
+    }
+
+    /// The request with the definition's transform applied, unchanged when
+    /// the definition has none
+    pub fn transform_request(
+        &self,
+        parts: RequestParts,
+        secret: &Value,
+    ) -> Result<RequestParts, PicaError> {
+        match &self.request_transform {
+            Some(transform) => Ok(transform.apply(parts, secret)?),
+            None => Ok(parts),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize, Display, EnumString)]
+#[cfg_attr(feature = "dummy", derive(fake::Dummy))]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum DefinitionLifecycle {
+    Draft,
+    Published,
+    Deprecated,
+}
+
+impl DefinitionLifecycle {
+    pub fn next(self) -> Option<Self> {
+        match self {
+            DefinitionLifecycle::Draft => Some(DefinitionLifecycle::Published),
+            DefinitionLifecycle::Published => Some(DefinitionLifecycle::Deprecated),
+            DefinitionLifecycle::Deprecated => None,
+        }
+    }
+}
+
+/// Who moved a definition to its current lifecycle state and when (millis)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LifecycleTransition {
+    pub from: DefinitionLifecycle,
+    pub to: DefinitionLifecycle,
+    pub actor: AuditActor,
+    pub changed_at: i64,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct TestConnection {
@@ -74,7 +184,9 @@ impl Default for TestConnection {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Default)]
+#[derive(
+    Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Default, schemars::JsonSchema,
+)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub enum TestConnectionState {
@@ -92,6 +204,16 @@ pub enum TestConnectionState {
     Untested,
 }
 
+impl TestConnectionState {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TestConnectionState::Success { .. } => "success",
+            TestConnectionState::Failure { .. } => "failure",
+            TestConnectionState::Untested => "untested",
+        }
+    }
+}
+
 pub enum ConnectionModelDefinitionWithState {
     Populated(ConnectionModelDefinition),
     Unpopulated(ConnectionModelDefinition),
@@ -112,7 +234,7 @@ impl PlatformInfo {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct ExtractorConfig {
@@ -125,7 +247,41 @@ pub struct ExtractorConfig {
     pub enabled: bool,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+impl ExtractorConfig {
+    /// Selects the records out of a platform response body using the cursor's
+    /// `dataPath`, e.g. `_.body.data` unwraps a `{ "data": [...] }` envelope
+    pub fn extract_data(&self, body: &Value) -> Result<Value, PicaError> {
+        let path = match self.cursor.data_path.strip_prefix('_') {
+            Some(rest) => format!("${rest}"),
+            None => self.cursor.data_path.clone(),
+        };
+
+        let wrapped_body = json!({ BODY_KEY: body });
+        let mut selected = jsonpath_lib::select(&wrapped_body, &path).map_err(|e| {
+            InternalError::invalid_argument(
+                &format!("Invalid data path {}: {e}", self.cursor.data_path),
+                None,
+            )
+        })?;
+
+        match selected.len() {
+            1 => Ok(selected.remove(0).clone()),
+            0 => Err(InternalError::key_not_found(
+                &format!("Nothing found at data path {}", self.cursor.data_path),
+                None,
+            )),
+            n => Err(InternalError::invalid_argument(
+                &format!(
+                    "Expected one value at data path {} but found {n}",
+                    self.cursor.data_path
+                ),
+                None,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct CursorConfig {
@@ -138,7 +294,7 @@ pub struct CursorConfig {
     pub reset_on_end: bool,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct LimitConfig {
@@ -146,7 +302,7 @@ pub struct LimitConfig {
     pub location: ParameterLocation,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateConfig {
@@ -155,7 +311,7 @@ pub struct UpdateConfig {
     pub format: String,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct CrudMapping {
@@ -165,7 +321,18 @@ pub struct CrudMapping {
     pub to_common_model: Option<String>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Display, EnumIter)]
+#[derive(
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    Deserialize,
+    Serialize,
+    Display,
+    EnumIter,
+    schemars::JsonSchema,
+)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 #[strum(serialize_all = "camelCase")]
@@ -304,6 +471,19 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn test_lifecycle_only_moves_forward_one_state_at_a_time() {
+        use DefinitionLifecycle::*;
+
+        assert_eq!(Draft.next(), Some(Published));
+        assert_eq!(Published.next(), Some(Deprecated));
+        assert_eq!(Deprecated.next(), None);
+        assert_eq!(
+            serde_json::to_value(Published).unwrap(),
+            json!(Published.to_string())
+        );
+    }
+
     #[test]
     fn test_deserialize_auth_method() {
         let bearer_token = json!({
@@ -514,4 +694,47 @@ mod tests {
             panic!("Wrong api config type");
         }
     }
+
+    fn extractor_config(data_path: &str) -> ExtractorConfig {
+        ExtractorConfig {
+            pull_frequency: 5,
+            batch_size: 100,
+            cursor: CursorConfig {
+                param_name: None,
+                location: None,
+                format: None,
+                cursor_path: "_.body.next".to_string(),
+                data_path: data_path.to_string(),
+                js_extractor_function: None,
+                reset_on_end: false,
+            },
+            limit: None,
+            sleep_after_finish: 0,
+            update_config: None,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_extract_data_unwraps_envelope() {
+        let body = json!({ "data": [{ "id": 1 }], "next": null });
+
+        let data = extractor_config("_.body.data").extract_data(&body).unwrap();
+        assert_eq!(data, json!([{ "id": 1 }]));
+
+        let data = extractor_config("$.body.data[0]")
+            .extract_data(&body)
+            .unwrap();
+        assert_eq!(data, json!({ "id": 1 }));
+    }
+
+    #[test]
+    fn test_extract_data_fails_when_path_is_missing() {
+        let body = json!({ "items": [] });
+
+        assert!(extractor_config("_.body.data").extract_data(&body).is_err());
+        assert!(extractor_config("_.body.items[*]")
+            .extract_data(&json!({ "items": [1, 2] }))
+            .is_err());
+    }
 }
@@ -0,0 +1,130 @@
+use crate::{
+    id::{prefix::IdPrefix, Id},
+    prelude::shared::record_metadata::RecordMetadata,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, Display};
+
+/// Flags of a connection model definition whose transitions are audited
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize, Display, AsRefStr)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum AuditedFlag {
+    Supported,
+    Active,
+}
+
+/// Who made a change, taken from the JWT claims or the event access of the request
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditActor {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+impl AuditActor {
+    pub fn system() -> Self {
+        Self {
+            id: "system".to_string(),
+            email: None,
+        }
+    }
+}
+
+/// Append-only record of a `supported`/`active` transition of a connection
+/// model definition. Entries are never updated once written.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionModelDefinitionAudit {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    pub connection_model_definition_id: Id,
+    pub flag: AuditedFlag,
+    pub old_value: bool,
+    pub new_value: bool,
+    pub actor: AuditActor,
+    pub changed_at: i64,
+    #[serde(flatten, default)]
+    pub record_metadata: RecordMetadata,
+}
+
+impl ConnectionModelDefinitionAudit {
+    pub fn new(
+        connection_model_definition_id: Id,
+        flag: AuditedFlag,
+        old_value: bool,
+        new_value: bool,
+        actor: AuditActor,
+    ) -> Self {
+        Self {
+            id: Id::now(IdPrefix::ConnectionModelDefinitionAudit),
+            connection_model_definition_id,
+            flag,
+            old_value,
+            new_value,
+            actor,
+            changed_at: Utc::now().timestamp_millis(),
+            record_metadata: RecordMetadata::default(),
+        }
+    }
+
+    /// Entries for every audited flag that differs between the two states
+    pub fn transitions(
+        connection_model_definition_id: Id,
+        previous: (bool, bool),
+        current: (bool, bool),
+        actor: &AuditActor,
+    ) -> Vec<Self> {
+        [
+            (AuditedFlag::Supported, previous.0, current.0),
+            (AuditedFlag::Active, previous.1, current.1),
+        ]
+        .into_iter()
+        .filter(|(_, old_value, new_value)| old_value != new_value)
+        .map(|(flag, old_value, new_value)| {
+            Self::new(
+                connection_model_definition_id,
+                flag,
+                old_value,
+                new_value,
+                actor.clone(),
+            )
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions_only_include_changed_flags() {
+        let id = Id::test(IdPrefix::ConnectionModelDefinition);
+        let actor = AuditActor::system();
+
+        assert!(ConnectionModelDefinitionAudit::transitions(
+            id,
+            (true, false),
+            (true, false),
+            &actor
+        )
+        .is_empty());
+
+        let entries =
+            ConnectionModelDefinitionAudit::transitions(id, (true, false), (false, false), &actor);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].flag, AuditedFlag::Supported);
+        assert!(entries[0].old_value);
+        assert!(!entries[0].new_value);
+
+        let entries =
+            ConnectionModelDefinitionAudit::transitions(id, (false, false), (true, true), &actor);
+        assert_eq!(
+            entries.iter().map(|e| e.flag).collect::<Vec<_>>(),
+            vec![AuditedFlag::Supported, AuditedFlag::Active]
+        );
+    }
+}
@@ -1,9 +1,18 @@
+use super::{
+    passthrough_recording::is_sensitive,
+    variable_injection::{apply_bindings, RequestParts, ResolvedRequest},
+};
 use crate::{
     id::Id,
     prelude::shared::{ownership::Ownership, record_metadata::RecordMetadata},
     configuration::environment::Environment,
+    ApplicationError, PicaError, REDACTED,
 };
+use http::{header::CONTENT_TYPE, HeaderValue};
 use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use strum::Display;
+use thiserror::Error as ThisError;
 
 /// Mapping between connection variables and model definition parameters.
 /// Defines how per-connection variables are substituted into API calls.
@@ -28,19 +37,154 @@ pub struct ConnectionVariableMapping {
     /// Ownership information for multi-tenancy
     pub ownership: Ownership,
     
-    /// Environment (test/live)
-    pub environment: Environment,
+    /// Environment (test/live) of the connections the mapping applies to,
+    /// every environment when `None`
+    #[serde(default)]
+    pub environment: Option<Environment>,
+
+    /// `Content-Type` requests are sent with once the bindings are applied,
+    /// replacing the caller's and the definition's. The body is re-encoded
+    /// to match, e.g. for a platform expecting a form from JSON callers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
     
     #[serde(flatten, default)]
     pub record_metadata: RecordMetadata,
 }
 
+impl ConnectionVariableMapping {
+    /// Whether the mapping applies to requests made in `environment`
+    pub fn applies_to(&self, environment: Environment) -> bool {
+        self.environment.is_none_or(|own| own == environment)
+    }
+
+    /// Whether the mapping applies to the connections of `owner`. Mappings
+    /// without an owner are platform-level and apply to every connection.
+    pub fn applies_to_owner(&self, owner: &str) -> bool {
+        self.ownership.id.is_empty() || *self.ownership.id == *owner
+    }
+
+    /// The mapping to apply to a connection of `owner` in `environment` among
+    /// those of a model definition. The owner's own mappings are preferred
+    /// over platform-level ones, then one scoped to the environment over one
+    /// applying to every environment.
+    pub fn resolve(
+        mappings: impl IntoIterator<Item = Self>,
+        environment: Environment,
+        owner: &str,
+    ) -> Option<Self> {
+        mappings
+            .into_iter()
+            .filter(|mapping| mapping.applies_to(environment) && mapping.applies_to_owner(owner))
+            .min_by_key(|mapping| {
+                (
+                    mapping.ownership.id.is_empty(),
+                    mapping.environment.is_none(),
+                )
+            })
+    }
+
+    /// Applies the bindings to a request, then the content type when the
+    /// mapping sets one. The body is re-encoded before the bindings are
+    /// applied, so body fields are injected the way it is sent.
+    pub fn apply(&self, parts: RequestParts, secret: &Value) -> Result<ResolvedRequest, PicaError> {
+        let Some(content_type) = self.content_type.as_deref() else {
+            return apply_bindings(parts, &self.bindings, secret);
+        };
+        let content_type = Self::check_content_type(content_type)?;
+
+        let mut resolved = apply_bindings(
+            parts.with_content_type(content_type.clone()),
+            &self.bindings,
+            secret,
+        )?;
+        resolved.parts.headers.insert(CONTENT_TYPE, content_type);
+
+        Ok(resolved)
+    }
+
+    pub fn check_content_type(content_type: &str) -> Result<HeaderValue, PicaError> {
+        HeaderValue::from_str(content_type).map_err(|_| {
+            ApplicationError::bad_request(
+                &format!("Content type {content_type} is not a valid header value"),
+                None,
+            )
+        })
+    }
+
+    /// Rejects bindings without a target, those injecting neither or both of
+    /// a variable and a constant, those conditioned on a path param, which
+    /// cannot be matched, and bindings injecting into the same
+    /// parameter under the same condition, as only the last of them would
+    /// take effect. Header names are compared ignoring case.
+    pub fn check_bindings(bindings: &[VariableBinding]) -> Result<(), PicaError> {
+        fn target(
+            binding: &VariableBinding,
+        ) -> (&ParameterLocation, String, &Option<BindingCondition>) {
+            let param = match binding.location {
+                ParameterLocation::Header => binding.target_param.to_ascii_lowercase(),
+                _ => binding.target_param.clone(),
+            };
+            (&binding.location, param, &binding.condition)
+        }
+
+        for (index, binding) in bindings.iter().enumerate() {
+            let invalid = |reason: &str| {
+                Err(ApplicationError::bad_request(
+                    &format!("Binding {index} {reason}"),
+                    None,
+                ))
+            };
+
+            if binding.target_param.trim().is_empty() {
+                return invalid("has no targetParam");
+            }
+
+            if binding
+                .condition
+                .as_ref()
+                .is_some_and(|condition| condition.location == ParameterLocation::PathParam)
+            {
+                return invalid(
+                    "cannot be conditioned on a path param, the path is a template until \
+                     variables are injected",
+                );
+            }
+
+            match (binding.variable_name.is_empty(), &binding.constant) {
+                (true, None) => return invalid("needs either a variableName or a constant"),
+                (false, Some(_)) => {
+                    return invalid("cannot set both a variableName and a constant")
+                }
+                _ => {}
+            }
+
+            if bindings[..index]
+                .iter()
+                .any(|earlier| target(earlier) == target(binding))
+            {
+                return Err(ApplicationError::bad_request(
+                    &format!(
+                        "Binding {index} injects into {:?} '{}' like an earlier binding",
+                        binding.location, binding.target_param
+                    ),
+                    None,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// A single binding that maps a connection variable to a target parameter
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 #[serde(rename_all = "camelCase")]
 pub struct VariableBinding {
-    /// Name of the variable in secrets (e.g., "hotel_id", "SALESFORCE_DOMAIN")
+    /// Name of the variable in secrets (e.g., "hotel_id", "SALESFORCE_DOMAIN").
+    /// Left empty by bindings injecting a `constant`.
+    #[serde(default)]
     pub variable_name: String,
     
     /// Target parameter name in the API call (e.g., "id", "domain")
@@ -49,13 +193,341 @@ pub struct VariableBinding {
     /// Where to inject the value
     pub location: ParameterLocation,
 
+    /// Fixed value injected instead of a secret variable, e.g. an `apiVersion`
+    /// every connection of the definition sends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "dummy", dummy(default))]
+    pub constant: Option<Value>,
+
     /// How to inject the value
     #[serde(default)]
     pub strategy: InjectionStrategy,
 
+    /// How a JSON object injected into a `BodyField` combines with an object
+    /// the client already sent at the target
+    #[serde(default)]
+    pub merge: BodyMerge,
+
     /// Data type of the variable (for conversion)
     #[serde(default)]
     pub data_type: VariableDataType,
+
+    /// Whether connections using the mapping must have the variable in their
+    /// secret. Optional variables are skipped silently when missing.
+    #[serde(default = "default_required")]
+    pub required: bool,
+
+    /// When set, the variable is only injected into requests matching it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "dummy", dummy(default))]
+    pub condition: Option<BindingCondition>,
+
+    /// Whether the injected value is redacted from diagnostics: mapping
+    /// tests, passthrough recordings and knowledge annotations. When unset,
+    /// headers named like credentials (`Authorization`, `X-Api-Key`, ...) are
+    /// secret and every other binding is not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "dummy", dummy(default))]
+    pub is_secret: Option<bool>,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// Predicate over another parameter of the request, e.g. inject
+/// `location_id` only when the `type` query param equals `pickup`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BindingCondition {
+    /// Name of the parameter the predicate is evaluated against. Body fields
+    /// may be dotted paths, like `target_param`.
+    pub param: String,
+    /// Where to read the parameter. Path params cannot be matched, as the path
+    /// is a template until the variables are injected.
+    pub location: ParameterLocation,
+    #[serde(flatten)]
+    pub operator: ConditionOperator,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "operator", rename_all = "camelCase")]
+pub enum ConditionOperator {
+    /// The parameter is present, whatever its value
+    Exists,
+    /// The parameter's string form equals `value`
+    Equals { value: String },
+    /// The parameter's string form is one of `values`
+    In { values: Vec<String> },
+}
+
+impl ConditionOperator {
+    /// Whether a parameter, `None` when absent, satisfies the operator
+    pub fn matches(&self, value: Option<&str>) -> bool {
+        match (self, value) {
+            (_, None) => false,
+            (ConditionOperator::Exists, Some(_)) => true,
+            (ConditionOperator::Equals { value: expected }, Some(value)) => value == expected,
+            (ConditionOperator::In { values }, Some(value)) => {
+                values.iter().any(|expected| expected == value)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for BindingCondition {
+    /// e.g. `QueryParam 'type' equals 'pickup'`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} '{}' ", self.location, self.param)?;
+
+        match &self.operator {
+            ConditionOperator::Exists => write!(f, "is present"),
+            ConditionOperator::Equals { value } => write!(f, "equals '{value}'"),
+            ConditionOperator::In { values } => write!(
+                f,
+                "is one of {}",
+                values
+                    .iter()
+                    .map(|value| format!("'{value}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl VariableBinding {
+    /// Whether the value is rendered as [`REDACTED`](crate::REDACTED) wherever
+    /// it is shown, see `is_secret`
+    pub fn is_secret(&self) -> bool {
+        self.is_secret.unwrap_or_else(|| {
+            self.location == ParameterLocation::Header && is_sensitive(&self.target_param)
+        })
+    }
+
+    /// Coerces the raw variable value into the binding's `data_type`. The
+    /// error of a secret binding does not carry its value.
+    pub fn coerce(&self, raw: &Value) -> Result<Value, VariableCoercionError> {
+        self.data_type
+            .coerce(raw)
+            .map_err(|reason| VariableCoercionError {
+                variable_name: self.variable_name.clone(),
+                target_param: self.target_param.clone(),
+                expected: self.data_type.clone(),
+                value: if self.is_secret() {
+                    REDACTED.to_string()
+                } else {
+                    raw.to_string()
+                },
+                reason,
+            })
+    }
+
+    /// The coerced value as it should be injected at the binding's location:
+    /// the typed JSON value for body fields, its string form (e.g. `true`,
+    /// `123`) for path params, query params and headers.
+    pub fn injectable_value(&self, raw: &Value) -> Result<Value, VariableCoercionError> {
+        let value = self.coerce(raw)?;
+
+        Ok(match self.location {
+            ParameterLocation::BodyField => value,
+            ParameterLocation::PathParam
+            | ParameterLocation::QueryParam
+            | ParameterLocation::Header => match value {
+                Value::String(_) => value,
+                value => Value::String(value.to_string()),
+            },
+        })
+    }
+
+    /// Injects `value` into a JSON body at the binding's `target_param`, read
+    /// as a dotted path (`reservation.guest.id`, `items.0.sku`). Missing
+    /// intermediate nodes are created: arrays when the next segment is an
+    /// index, objects otherwise. Traversing through a scalar, or indexing past
+    /// the end of an array by more than one, is an error and leaves the body
+    /// untouched.
+    pub fn inject_into_body(
+        &self,
+        body: &mut Value,
+        value: Value,
+    ) -> Result<(), VariableInjectionError> {
+        let error = |reason: String| VariableInjectionError {
+            target_param: self.target_param.clone(),
+            reason,
+        };
+
+        let segments: Vec<&str> = self.target_param.split('.').collect();
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(error("path contains an empty segment".to_string()));
+        }
+
+        // Validate the whole path first so a failure never leaves
+        // half-created intermediate nodes behind
+        let mut node = Some(&*body);
+        for (depth, segment) in segments.iter().enumerate() {
+            node = match node {
+                None => None,
+                Some(Value::Object(map)) => map.get(*segment),
+                Some(Value::Array(items)) => {
+                    let index = parse_index(segment).ok_or_else(|| {
+                        error(format!(
+                            "'{}' is an array and '{segment}' is not an index",
+                            segments[..depth].join(".")
+                        ))
+                    })?;
+                    if index > items.len() {
+                        return Err(error(format!(
+                            "index {index} is out of bounds for '{}' of length {}",
+                            segments[..depth].join("."),
+                            items.len()
+                        )));
+                    }
+                    items.get(index)
+                }
+                Some(other) if depth == 0 => {
+                    return Err(error(format!("body is {}, not an object", json_type_name(other))))
+                }
+                Some(other) => {
+                    return Err(error(format!(
+                        "'{}' is {}, not an object or array",
+                        segments[..depth].join("."),
+                        json_type_name(other)
+                    )))
+                }
+            };
+        }
+
+        if let (Some(existing), InjectionStrategy::Append) = (node, &self.strategy) {
+            if !matches!(existing, Value::Array(_) | Value::String(_)) {
+                return Err(error(format!(
+                    "cannot append to {}",
+                    json_type_name(existing)
+                )));
+            }
+        }
+
+        let (last, parents) = segments.split_last().expect("split always yields a segment");
+        let mut node = body;
+        for (depth, segment) in parents.iter().enumerate() {
+            let next_is_index = parse_index(segments[depth + 1]).is_some();
+            node = child_or_insert(node, segment, || {
+                if next_is_index {
+                    Value::Array(vec![])
+                } else {
+                    Value::Object(Default::default())
+                }
+            });
+        }
+
+        let exists = match &*node {
+            Value::Object(map) => map.contains_key(*last),
+            Value::Array(items) => parse_index(last).is_some_and(|index| index < items.len()),
+            _ => false,
+        };
+
+        match (&self.strategy, exists) {
+            (InjectionStrategy::Strict | InjectionStrategy::Fallback, true)
+                if self.merge == BodyMerge::Deep =>
+            {
+                let existing = child_or_insert(node, last, || Value::Null);
+                deep_merge(existing, value, self.strategy == InjectionStrategy::Strict);
+            }
+            (InjectionStrategy::Fallback, true) => {}
+            (InjectionStrategy::Append, true) => {
+                let existing = child_or_insert(node, last, || Value::Null);
+                match existing {
+                    Value::Array(items) => items.push(value),
+                    Value::String(s) => {
+                        let appended = value
+                            .as_str()
+                            .map(str::to_string)
+                            .unwrap_or_else(|| value.to_string());
+                        *s = format!("{s},{appended}");
+                    }
+                    _ => unreachable!("checked while validating the path"),
+                }
+            }
+            _ => *child_or_insert(node, last, || Value::Null) = value,
+        }
+
+        Ok(())
+    }
+}
+
+/// Merges the fields of `value` into `target` when both are objects, the
+/// value of a field set on both sides being `value`'s when `overwrite`
+fn deep_merge(target: &mut Value, value: Value, overwrite: bool) {
+    match (target, value) {
+        (Value::Object(target), Value::Object(fields)) => {
+            for (key, value) in fields {
+                match target.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value, overwrite),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, value) if overwrite => *target = value,
+        _ => {}
+    }
+}
+
+fn parse_index(segment: &str) -> Option<usize> {
+    segment.parse().ok()
+}
+
+/// Returns the child at `segment`, inserting `default()` when missing. The
+/// path must have been validated against `node` beforehand.
+fn child_or_insert<'a>(
+    node: &'a mut Value,
+    segment: &str,
+    default: impl FnOnce() -> Value,
+) -> &'a mut Value {
+    match node {
+        Value::Object(map) => map.entry(segment.to_string()).or_insert_with(default),
+        Value::Array(items) => {
+            let index = parse_index(segment).expect("validated array index");
+            if index == items.len() {
+                items.push(default());
+            }
+            &mut items[index]
+        }
+        _ => unreachable!("validated path only traverses objects and arrays"),
+    }
+}
+
+/// A body field path that cannot be applied to the request body
+#[derive(ThisError, Debug, Clone, PartialEq, Eq)]
+#[error("Cannot inject body field '{target_param}': {reason}")]
+pub struct VariableInjectionError {
+    pub target_param: String,
+    pub reason: String,
+}
+
+impl From<VariableInjectionError> for PicaError {
+    fn from(error: VariableInjectionError) -> Self {
+        ApplicationError::bad_request(&error.to_string(), None)
+    }
+}
+
+/// A stored variable that cannot be read as the data type its binding expects
+#[derive(ThisError, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "Variable '{variable_name}' bound to '{target_param}' expected a {expected} value but got {value}: {reason}"
+)]
+pub struct VariableCoercionError {
+    pub variable_name: String,
+    pub target_param: String,
+    pub expected: VariableDataType,
+    pub value: String,
+    pub reason: String,
+}
+
+impl From<VariableCoercionError> for PicaError {
+    fn from(error: VariableCoercionError) -> Self {
+        ApplicationError::unprocessable_entity(&error.to_string(), None)
+    }
 }
 
 /// Where to inject the variable value in the API request
@@ -68,32 +540,53 @@ pub enum ParameterLocation {
     QueryParam,
     /// HTTP header: X-Hotel-Id: 123
     Header,
-    /// JSON body field: {"hotelId": "123"}
+    /// JSON body field: {"hotelId": "123"}. `target_param` may be a dotted
+    /// path into nested objects and arrays, e.g. `reservation.guest.id`
     BodyField,
 }
 
-/// Strategy for injecting the variable
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+/// Strategy for injecting the variable. JSON objects injected into a
+/// `BodyField` may be merged with the client's instead, see [`BodyMerge`].
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Deserialize, Serialize)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 pub enum InjectionStrategy {
     /// Always overwrite user input (Default, Secure)
+    #[default]
     Strict,
     /// Only inject if parameter is missing (Flexible)
     Fallback,
-    /// Append to existing value (for Lists)
+    /// Append to existing value (for Lists), injecting it when missing:
+    /// - `QueryParam`: adds another `key=value` pair, repeating the key
+    /// - `Header`: comma-joins onto the existing value, `; ` for `Cookie`, or
+    ///   adds a header line when the header is already sent on several lines
+    /// - `BodyField`: pushes onto a JSON array, comma-joins onto a JSON
+    ///   string and repeats the key of a form field
+    /// - `PathParam`: overwrites like `Strict`, path params have one value
     Append,
 }
 
-impl Default for InjectionStrategy {
-    fn default() -> Self {
-        Self::Strict
-    }
+/// How a JSON object injected into a `BodyField` combines with the object at
+/// the target. Only objects on both sides are merged, any other value is
+/// injected according to the `InjectionStrategy` alone.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[cfg_attr(feature = "dummy", derive(fake::Dummy))]
+pub enum BodyMerge {
+    /// Replaces the target object as a whole under `Strict`, and keeps it
+    /// untouched under `Fallback`
+    #[default]
+    Shallow,
+    /// Recursively merges the injected object into the target, so the fields
+    /// the client sent next to the injected ones survive. Where both set a
+    /// field, `Strict` keeps the injected value and `Fallback` the client's.
+    /// `Append` ignores it.
+    Deep,
 }
 
 /// Expected data type of the variable
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Deserialize, Serialize, Display)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 pub enum VariableDataType {
+    #[default]
     String,
     Number,
     Boolean,
@@ -101,9 +594,55 @@ pub enum VariableDataType {
     Json,
 }
 
-impl Default for VariableDataType {
-    fn default() -> Self {
-        Self::String
+impl VariableDataType {
+    /// Parses a stored value, usually a string from the connection's form
+    /// data, into this type. Returns the reason when the value does not fit.
+    pub fn coerce(&self, raw: &Value) -> Result<Value, String> {
+        match (self, raw) {
+            (_, Value::Null) => Err("value is null".to_string()),
+            (VariableDataType::String, Value::String(_)) => Ok(raw.clone()),
+            (VariableDataType::String, raw) => Ok(Value::String(raw.to_string())),
+            (VariableDataType::Number, Value::Number(_)) => Ok(raw.clone()),
+            (VariableDataType::Number, Value::String(s)) => {
+                let s = s.trim();
+                if let Ok(n) = s.parse::<i64>() {
+                    Ok(Value::from(n))
+                } else {
+                    s.parse::<f64>()
+                        .ok()
+                        .and_then(Number::from_f64)
+                        .map(Value::Number)
+                        .ok_or_else(|| "not a finite number".to_string())
+                }
+            }
+            (VariableDataType::Boolean, Value::Bool(_)) => Ok(raw.clone()),
+            (VariableDataType::Boolean, Value::String(s)) => {
+                match s.trim().to_ascii_lowercase().as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Err("not true or false".to_string()),
+                }
+            }
+            (VariableDataType::Json, Value::Object(_) | Value::Array(_)) => Ok(raw.clone()),
+            (VariableDataType::Json, Value::String(s)) => match serde_json::from_str(s) {
+                Ok(value @ (Value::Object(_) | Value::Array(_))) => Ok(value),
+                Ok(_) => Err("not a JSON object or array".to_string()),
+                Err(e) => Err(format!("invalid JSON ({e})")),
+            },
+            (VariableDataType::Json, _) => Err("not a JSON object or array".to_string()),
+            (_, _) => Err(format!("unexpected JSON {}", json_type_name(raw))),
+        }
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
     }
 }
 
@@ -118,6 +657,13 @@ mod tests {
             variable_name: "hotel_id".to_string(),
             target_param: "id".to_string(),
             location: ParameterLocation::PathParam,
+            constant: None,
+            strategy: InjectionStrategy::Strict,
+            merge: BodyMerge::Shallow,
+            data_type: VariableDataType::String,
+            required: true,
+            condition: None,
+            is_secret: None,
         };
 
         let json_val = serde_json::to_value(&binding).unwrap();
@@ -140,4 +686,402 @@ mod tests {
         let body_field: ParameterLocation = serde_json::from_value(json!("BodyField")).unwrap();
         assert_eq!(body_field, ParameterLocation::BodyField);
     }
+
+    #[test]
+    fn test_resolve_prefers_the_mapping_of_the_environment() {
+        let mapping = |environment: Option<Environment>| ConnectionVariableMapping {
+            id: Id::test(crate::prefix::IdPrefix::ConnectionVariableMapping),
+            connection_model_definition_id: Id::test(
+                crate::prefix::IdPrefix::ConnectionModelDefinition,
+            ),
+            connection_platform: "blaze".to_string(),
+            bindings: vec![],
+            ownership: Ownership::default(),
+            environment,
+            content_type: None,
+            record_metadata: RecordMetadata::default(),
+        };
+
+        let test_only = mapping(Some(Environment::Test));
+        assert_eq!(
+            ConnectionVariableMapping::resolve([test_only.clone()], Environment::Live, "owner"),
+            None
+        );
+
+        let wildcard = mapping(None);
+        let live = mapping(Some(Environment::Live));
+        let mappings = [wildcard.clone(), test_only.clone(), live.clone()];
+        assert_eq!(
+            ConnectionVariableMapping::resolve(mappings.clone(), Environment::Live, "owner"),
+            Some(live.clone())
+        );
+        assert_eq!(
+            ConnectionVariableMapping::resolve(mappings, Environment::Test, "owner"),
+            Some(test_only)
+        );
+        assert_eq!(
+            ConnectionVariableMapping::resolve([wildcard.clone()], Environment::Live, "owner"),
+            Some(wildcard.clone())
+        );
+
+        // A mapping of the owner wins over platform-level ones, and never
+        // applies to the connections of another owner
+        let mut owned = wildcard.clone();
+        owned.ownership.id = "owner".into();
+        let mappings = [live.clone(), owned.clone()];
+        assert_eq!(
+            ConnectionVariableMapping::resolve(mappings.clone(), Environment::Live, "owner"),
+            Some(owned)
+        );
+        assert_eq!(
+            ConnectionVariableMapping::resolve(mappings, Environment::Live, "other"),
+            Some(live)
+        );
+    }
+
+    #[test]
+    fn test_check_bindings_rejects_duplicate_targets() {
+        let header = |target_param: &str, condition: Option<BindingCondition>| VariableBinding {
+            target_param: target_param.to_string(),
+            condition,
+            ..binding(VariableDataType::String, ParameterLocation::Header)
+        };
+        let condition = BindingCondition {
+            param: "type".to_string(),
+            location: ParameterLocation::QueryParam,
+            operator: ConditionOperator::Exists,
+        };
+
+        assert!(ConnectionVariableMapping::check_bindings(&[
+            header("X-Hotel-Id", None),
+            header("X-Hotel-Id", Some(condition.clone())),
+            binding(VariableDataType::String, ParameterLocation::QueryParam),
+            binding(VariableDataType::String, ParameterLocation::BodyField),
+        ])
+        .is_ok());
+
+        let error = ConnectionVariableMapping::check_bindings(&[
+            header("X-Hotel-Id", Some(condition.clone())),
+            binding(VariableDataType::String, ParameterLocation::QueryParam),
+            header("x-hotel-id", Some(condition)),
+        ])
+        .unwrap_err();
+        assert_eq!(error.status(), 400);
+        assert!(error.to_string().contains("Binding 2"));
+    }
+
+    #[test]
+    fn test_check_bindings_rejects_bindings_without_a_target_or_a_single_value() {
+        let constant = VariableBinding {
+            variable_name: String::new(),
+            constant: Some(json!("2023-10")),
+            ..binding(VariableDataType::String, ParameterLocation::QueryParam)
+        };
+        assert!(ConnectionVariableMapping::check_bindings(std::slice::from_ref(&constant)).is_ok());
+
+        for invalid in [
+            VariableBinding {
+                target_param: " ".to_string(),
+                ..binding(VariableDataType::String, ParameterLocation::QueryParam)
+            },
+            VariableBinding {
+                constant: None,
+                ..constant.clone()
+            },
+            VariableBinding {
+                variable_name: "hotel_id".to_string(),
+                ..constant
+            },
+            VariableBinding {
+                condition: Some(BindingCondition {
+                    param: "hotelId".to_string(),
+                    location: ParameterLocation::PathParam,
+                    operator: ConditionOperator::Exists,
+                }),
+                ..binding(VariableDataType::String, ParameterLocation::QueryParam)
+            },
+        ] {
+            let error = ConnectionVariableMapping::check_bindings(&[invalid]).unwrap_err();
+            assert_eq!(error.status(), 400);
+        }
+    }
+
+    fn binding(data_type: VariableDataType, location: ParameterLocation) -> VariableBinding {
+        VariableBinding {
+            variable_name: "hotel_id".to_string(),
+            target_param: "hotelId".to_string(),
+            location,
+            constant: None,
+            strategy: InjectionStrategy::Strict,
+            merge: BodyMerge::Shallow,
+            data_type,
+            required: true,
+            condition: None,
+            is_secret: None,
+        }
+    }
+
+    #[test]
+    fn test_coerce_string() {
+        let binding = binding(VariableDataType::String, ParameterLocation::BodyField);
+
+        assert_eq!(binding.coerce(&json!("abc")).unwrap(), json!("abc"));
+        assert_eq!(binding.coerce(&json!(123)).unwrap(), json!("123"));
+        assert!(binding.coerce(&Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_coerce_number() {
+        let binding = binding(VariableDataType::Number, ParameterLocation::BodyField);
+
+        assert_eq!(binding.coerce(&json!("123")).unwrap(), json!(123));
+        assert_eq!(binding.coerce(&json!(" -4.5 ")).unwrap(), json!(-4.5));
+        assert_eq!(binding.coerce(&json!(7)).unwrap(), json!(7));
+
+        for bad in [json!("abc"), json!("NaN"), json!("inf"), json!(true), json!([1])] {
+            let error = binding.coerce(&bad).unwrap_err();
+            assert_eq!(error.variable_name, "hotel_id");
+            assert_eq!(error.target_param, "hotelId");
+            assert_eq!(error.expected, VariableDataType::Number);
+            assert_eq!(error.value, bad.to_string());
+        }
+    }
+
+    #[test]
+    fn test_coerce_boolean() {
+        let binding = binding(VariableDataType::Boolean, ParameterLocation::BodyField);
+
+        assert_eq!(binding.coerce(&json!("true")).unwrap(), json!(true));
+        assert_eq!(binding.coerce(&json!("FALSE")).unwrap(), json!(false));
+        assert_eq!(binding.coerce(&json!(false)).unwrap(), json!(false));
+
+        for bad in [json!("yes"), json!("1"), json!(1)] {
+            let error = binding.coerce(&bad).unwrap_err();
+            assert_eq!(error.expected, VariableDataType::Boolean);
+            assert_eq!(error.value, bad.to_string());
+        }
+    }
+
+    #[test]
+    fn test_coerce_json() {
+        let binding = binding(VariableDataType::Json, ParameterLocation::BodyField);
+
+        assert_eq!(
+            binding.coerce(&json!("{\"a\":[1,2]}")).unwrap(),
+            json!({ "a": [1, 2] })
+        );
+        assert_eq!(binding.coerce(&json!([1, 2])).unwrap(), json!([1, 2]));
+
+        let error = binding.coerce(&json!("{not json")).unwrap_err();
+        assert!(error.reason.starts_with("invalid JSON"));
+
+        let error = binding.coerce(&json!("42")).unwrap_err();
+        assert_eq!(error.reason, "not a JSON object or array");
+
+        assert!(binding.coerce(&json!(42)).is_err());
+    }
+
+    #[test]
+    fn test_injectable_value_depends_on_location() {
+        let raw = json!("123");
+
+        for location in [
+            ParameterLocation::PathParam,
+            ParameterLocation::QueryParam,
+            ParameterLocation::Header,
+        ] {
+            let binding = binding(VariableDataType::Number, location);
+            assert_eq!(binding.injectable_value(&raw).unwrap(), json!("123"));
+        }
+
+        let body = binding(VariableDataType::Number, ParameterLocation::BodyField);
+        assert_eq!(body.injectable_value(&raw).unwrap(), json!(123));
+
+        let query = binding(VariableDataType::Boolean, ParameterLocation::QueryParam);
+        assert_eq!(query.injectable_value(&json!("TRUE")).unwrap(), json!("true"));
+    }
+
+    #[test]
+    fn test_coercion_error_message_identifies_binding() {
+        let binding = binding(VariableDataType::Number, ParameterLocation::QueryParam);
+        let error = binding.injectable_value(&json!("abc")).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Variable 'hotel_id' bound to 'hotelId' expected a Number value but got \"abc\": not a finite number"
+        );
+    }
+
+    #[test]
+    fn test_coercion_error_of_secret_binding_redacts_value() {
+        let binding = VariableBinding {
+            is_secret: Some(true),
+            ..binding(VariableDataType::Number, ParameterLocation::Header)
+        };
+        let error = binding.injectable_value(&json!("sk_live_abc")).unwrap_err();
+
+        assert_eq!(error.value, REDACTED);
+        assert!(!error.to_string().contains("sk_live_abc"));
+        assert!(!PicaError::from(error).to_string().contains("sk_live_abc"));
+    }
+
+    fn body_binding(target_param: &str, strategy: InjectionStrategy) -> VariableBinding {
+        VariableBinding {
+            target_param: target_param.to_string(),
+            strategy,
+            ..binding(VariableDataType::String, ParameterLocation::BodyField)
+        }
+    }
+
+    #[test]
+    fn test_inject_into_body_top_level() {
+        let mut body = json!({ "name": "Ada" });
+        body_binding("hotelId", InjectionStrategy::Strict)
+            .inject_into_body(&mut body, json!("h1"))
+            .unwrap();
+
+        assert_eq!(body, json!({ "name": "Ada", "hotelId": "h1" }));
+    }
+
+    #[test]
+    fn test_inject_into_body_creates_nested_objects() {
+        let mut body = json!({ "reservation": { "nights": 2 } });
+        body_binding("reservation.guest.id", InjectionStrategy::Strict)
+            .inject_into_body(&mut body, json!(42))
+            .unwrap();
+
+        assert_eq!(
+            body,
+            json!({ "reservation": { "nights": 2, "guest": { "id": 42 } } })
+        );
+    }
+
+    #[test]
+    fn test_inject_into_body_array_indices() {
+        let mut body = json!({ "items": [{ "sku": "a" }, { "qty": 1 }] });
+        body_binding("items.1.sku", InjectionStrategy::Strict)
+            .inject_into_body(&mut body, json!("b"))
+            .unwrap();
+        assert_eq!(body, json!({ "items": [{ "sku": "a" }, { "qty": 1, "sku": "b" }] }));
+
+        body_binding("items.2.sku", InjectionStrategy::Strict)
+            .inject_into_body(&mut body, json!("c"))
+            .unwrap();
+        assert_eq!(body["items"][2], json!({ "sku": "c" }));
+
+        let mut body = json!({});
+        body_binding("lines.0.sku", InjectionStrategy::Strict)
+            .inject_into_body(&mut body, json!("x"))
+            .unwrap();
+        assert_eq!(body, json!({ "lines": [{ "sku": "x" }] }));
+    }
+
+    #[test]
+    fn test_inject_into_body_strategies() {
+        let mut body = json!({ "guest": { "id": "user", "tags": ["vip"], "note": "a" } });
+
+        body_binding("guest.id", InjectionStrategy::Fallback)
+            .inject_into_body(&mut body, json!("injected"))
+            .unwrap();
+        body_binding("guest.email", InjectionStrategy::Fallback)
+            .inject_into_body(&mut body, json!("e@x.com"))
+            .unwrap();
+        body_binding("guest.tags", InjectionStrategy::Append)
+            .inject_into_body(&mut body, json!("hotel"))
+            .unwrap();
+        body_binding("guest.note", InjectionStrategy::Append)
+            .inject_into_body(&mut body, json!("b"))
+            .unwrap();
+
+        assert_eq!(
+            body,
+            json!({ "guest": {
+                "id": "user",
+                "email": "e@x.com",
+                "tags": ["vip", "hotel"],
+                "note": "a,b"
+            } })
+        );
+    }
+
+    #[test]
+    fn test_inject_into_body_rejects_invalid_paths() {
+        let original = json!({ "reservation": { "guest": "Ada" }, "items": [1], "count": 3 });
+
+        for (path, strategy) in [
+            ("reservation.guest.id", InjectionStrategy::Strict),
+            ("items.sku", InjectionStrategy::Strict),
+            ("items.5", InjectionStrategy::Strict),
+            ("reservation..id", InjectionStrategy::Strict),
+            ("count", InjectionStrategy::Append),
+        ] {
+            let mut body = original.clone();
+            let error = body_binding(path, strategy)
+                .inject_into_body(&mut body, json!("x"))
+                .unwrap_err();
+
+            assert_eq!(error.target_param, path);
+            assert_eq!(body, original, "{path} must not modify the body");
+        }
+
+        let error = body_binding("reservation.guest.id", InjectionStrategy::Strict)
+            .inject_into_body(&mut original.clone(), json!("x"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cannot inject body field 'reservation.guest.id': 'reservation.guest' is string, not an object or array"
+        );
+    }
+
+    #[test]
+    fn test_apply_sends_the_mapping_content_type() {
+        let mapping = ConnectionVariableMapping {
+            id: Id::test(crate::prefix::IdPrefix::ConnectionVariableMapping),
+            connection_model_definition_id: Id::test(
+                crate::prefix::IdPrefix::ConnectionModelDefinition,
+            ),
+            connection_platform: "blaze".to_string(),
+            bindings: vec![VariableBinding {
+                variable_name: "hotel_id".to_string(),
+                target_param: "hotelId".to_string(),
+                location: ParameterLocation::BodyField,
+                constant: None,
+                strategy: InjectionStrategy::Strict,
+                merge: BodyMerge::Shallow,
+                data_type: VariableDataType::String,
+                required: true,
+                condition: None,
+                is_secret: None,
+            }],
+            ownership: Ownership::default(),
+            environment: None,
+            content_type: Some("application/x-www-form-urlencoded".to_string()),
+            record_metadata: RecordMetadata::default(),
+        };
+        let parts = RequestParts {
+            headers: [(CONTENT_TYPE, HeaderValue::from_static("application/json"))]
+                .into_iter()
+                .collect(),
+            body: Some(br#"{"room":"101"}"#.to_vec()),
+            ..Default::default()
+        };
+
+        let resolved = mapping
+            .apply(parts.clone(), &json!({ "hotel_id": "h 1" }))
+            .unwrap();
+        assert_eq!(
+            resolved.parts.headers[CONTENT_TYPE],
+            "application/x-www-form-urlencoded"
+        );
+        assert_eq!(
+            resolved.parts.body.as_deref(),
+            Some(&b"room=101&hotelId=h+1"[..])
+        );
+
+        let invalid = ConnectionVariableMapping {
+            content_type: Some("application/json\n".to_string()),
+            ..mapping
+        };
+        assert_eq!(invalid.apply(parts, &json!({})).unwrap_err().status(), 400);
+    }
 }
@@ -0,0 +1,161 @@
+use crate::{
+    configuration::environment::Environment,
+    id::{prefix::IdPrefix, Id},
+    prelude::shared::{ownership::Ownership, record_metadata::RecordMetadata},
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use strum::{AsRefStr, Display};
+
+/// Connection lifecycle events that webhooks can subscribe to
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize, Display, AsRefStr)]
+#[cfg_attr(feature = "dummy", derive(fake::Dummy))]
+pub enum ConnectionLifecycleEventType {
+    #[serde(rename = "connection.created")]
+    #[strum(serialize = "connection.created")]
+    ConnectionCreated,
+    #[serde(rename = "connection.deleted")]
+    #[strum(serialize = "connection.deleted")]
+    ConnectionDeleted,
+    #[serde(rename = "connectionModelDefinition.testConnectionStatusChanged")]
+    #[strum(serialize = "connectionModelDefinition.testConnectionStatusChanged")]
+    TestConnectionStatusChanged,
+}
+
+/// A lifecycle event waiting to be delivered to the matching webhooks.
+/// Events without ownership concern platform-level records (e.g. model
+/// definitions) and are delivered to every subscribed webhook.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionLifecycleEvent {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    pub r#type: ConnectionLifecycleEventType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<Ownership>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<Environment>,
+    pub data: Value,
+    pub occurred_at: i64,
+}
+
+impl ConnectionLifecycleEvent {
+    pub fn new(
+        r#type: ConnectionLifecycleEventType,
+        ownership: Option<Ownership>,
+        environment: Option<Environment>,
+        data: Value,
+    ) -> Self {
+        Self {
+            id: Id::now(IdPrefix::Event),
+            r#type,
+            ownership,
+            environment,
+            data,
+            occurred_at: Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+/// A customer-registered URL receiving signed POSTs for the subscribed
+/// connection lifecycle events.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "dummy", derive(fake::Dummy))]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionWebhook {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    pub url: String,
+    pub event_types: Vec<ConnectionLifecycleEventType>,
+    /// Shared secret used to sign every delivery, never returned by the API
+    pub signing_secret: String,
+    pub ownership: Ownership,
+    pub environment: Environment,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_delivery: Option<WebhookDelivery>,
+    #[serde(flatten, default)]
+    pub record_metadata: RecordMetadata,
+}
+
+impl ConnectionWebhook {
+    /// Hex encoded HMAC-SHA256 of `{timestamp}.{body}` keyed by the signing secret
+    pub fn sign(&self, timestamp: i64, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.signing_secret.as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "dummy", derive(fake::Dummy))]
+#[serde(rename_all = "camelCase")]
+pub enum WebhookDeliveryStatus {
+    Delivered,
+    Failed,
+}
+
+/// Outcome of the most recent delivery attempt sequence for a webhook
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "dummy", derive(fake::Dummy))]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    pub event_id: Id,
+    pub event_type: ConnectionLifecycleEventType,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub delivered_at: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_event_type_serialization() {
+        assert_eq!(
+            serde_json::to_value(ConnectionLifecycleEventType::ConnectionCreated).unwrap(),
+            json!("connection.created")
+        );
+        assert_eq!(
+            ConnectionLifecycleEventType::TestConnectionStatusChanged.to_string(),
+            "connectionModelDefinition.testConnectionStatusChanged"
+        );
+    }
+
+    #[test]
+    fn test_sign_is_deterministic_and_keyed() {
+        let webhook = ConnectionWebhook {
+            id: Id::test(IdPrefix::ConnectionWebhook),
+            url: "https://example.com/hooks".to_string(),
+            event_types: vec![ConnectionLifecycleEventType::ConnectionCreated],
+            signing_secret: "secret".to_string(),
+            ownership: Ownership::default(),
+            environment: Environment::Live,
+            last_delivery: None,
+            record_metadata: RecordMetadata::default(),
+        };
+
+        let signature = webhook.sign(1700000000000, b"{}");
+        assert_eq!(signature, webhook.sign(1700000000000, b"{}"));
+        assert_eq!(signature.len(), 64);
+        assert_ne!(signature, webhook.sign(1700000000001, b"{}"));
+
+        let other = ConnectionWebhook {
+            signing_secret: "other".to_string(),
+            ..webhook
+        };
+        assert_ne!(signature, other.sign(1700000000000, b"{}"));
+    }
+}
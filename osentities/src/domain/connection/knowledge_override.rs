@@ -0,0 +1,68 @@
+use crate::{
+    configuration::environment::Environment,
+    id::Id,
+    prelude::shared::{ownership::Ownership, record_metadata::RecordMetadata},
+};
+use serde::{Deserialize, Serialize};
+
+/// Connection-specific addendum to the shared knowledge of a model
+/// definition, e.g. "this tenant uses property codes, not names". It is
+/// appended to the platform knowledge when knowledge is read for the
+/// connection, leaving the platform knowledge itself untouched.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnowledgeOverride {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    pub connection_id: Id,
+    pub connection_model_definition_id: Id,
+    pub knowledge: String,
+    pub ownership: Ownership,
+    pub environment: Environment,
+    #[serde(flatten, default)]
+    pub record_metadata: RecordMetadata,
+}
+
+impl KnowledgeOverride {
+    /// The platform knowledge of the definition followed by the override
+    pub fn merge_into(&self, knowledge: Option<String>) -> String {
+        match knowledge {
+            Some(knowledge) if !knowledge.is_empty() => {
+                format!("{knowledge}\n\n{}", self.knowledge)
+            }
+            _ => self.knowledge.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prefix::IdPrefix;
+
+    #[test]
+    fn test_override_is_appended_to_platform_knowledge() {
+        let knowledge_override = KnowledgeOverride {
+            id: Id::test(IdPrefix::KnowledgeOverride),
+            connection_id: Id::test(IdPrefix::Connection),
+            connection_model_definition_id: Id::test(IdPrefix::ConnectionModelDefinition),
+            knowledge: "Use property codes, not names.".to_string(),
+            ownership: Ownership::default(),
+            environment: Environment::Live,
+            record_metadata: RecordMetadata::default(),
+        };
+
+        assert_eq!(
+            knowledge_override.merge_into(Some("Lists the properties.".to_string())),
+            "Lists the properties.\n\nUse property codes, not names."
+        );
+        assert_eq!(
+            knowledge_override.merge_into(None),
+            "Use property codes, not names."
+        );
+        assert_eq!(
+            knowledge_override.merge_into(Some(String::new())),
+            "Use property codes, not names."
+        );
+    }
+}
@@ -1,9 +1,16 @@
 pub mod api_model_config;
 pub mod connection_definition;
 pub mod connection_model_definition;
+pub mod connection_model_definition_audit;
 pub mod connection_model_schema;
 pub mod connection_oauth_definition;
 pub mod connection_variable_mapping;
+pub mod connection_webhook;
+pub mod knowledge_override;
+pub mod passthrough_recording;
+pub mod request_signature;
+pub mod request_transform;
+pub mod variable_injection;
 
 use super::{
     configuration::environment::Environment,
@@ -35,6 +42,11 @@ pub struct Connection {
     pub environment: Environment,
     pub platform: Arc<str>,
     pub secrets_service_id: String,
+    /// Further secrets merged into the one of `secrets_service_id`, e.g. an
+    /// API key kept apart from OAuth tokens. Where they set the same field,
+    /// the main secret wins, then these in order.
+    #[serde(default)]
+    pub additional_secrets_service_ids: Vec<String>,
     pub event_access_id: Id,
     pub access_key: String,
     pub identity: Option<String>,
@@ -48,6 +60,15 @@ pub struct Connection {
     pub has_error: bool,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub key_rotation: Option<KeyRotation>,
+    /// When set, passthrough requests must carry a valid `PICA-Signature`,
+    /// over a recent `PICA-Timestamp` and a `PICA-Nonce` not used before
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub request_signing_secret: Option<String>,
+    /// When set, passthrough requests over the quota are rejected with a 429
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub quota: Option<Quota>,
     #[serde(flatten, default)]
     pub record_metadata: RecordMetadata,
 }
@@ -57,6 +78,55 @@ impl Connection {
         self.has_error = true;
         self.error = Some(error.to_string());
     }
+
+    /// Whether `key` authenticates this connection at `now` (millis), either
+    /// as the current key or as the previous key of an unexpired rotation.
+    pub fn accepts_key(&self, key: &str, now: i64) -> bool {
+        self.key.as_ref() == key
+            || self
+                .key_rotation
+                .as_ref()
+                .is_some_and(|rotation| rotation.accepts(key, now))
+    }
+}
+
+/// Tracks the key a connection had before its last rotation. The previous key
+/// keeps authenticating until `expires_at` (millis) or until the rotation is
+/// finalized, whichever comes first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyRotation {
+    pub previous_key: Arc<str>,
+    pub rotated_at: i64,
+    pub expires_at: i64,
+}
+
+impl KeyRotation {
+    pub fn accepts(&self, key: &str, now: i64) -> bool {
+        self.previous_key.as_ref() == key && now < self.expires_at
+    }
+}
+
+/// Caps the passthrough requests a connection can make within a rolling
+/// window of `window_secs` seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quota {
+    pub limit: u64,
+    pub window_secs: u64,
+    #[serde(default)]
+    pub scope: QuotaScope,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QuotaScope {
+    /// Only the requests made through this connection count
+    #[default]
+    Connection,
+    /// The requests made through all of the owner's connections to the same
+    /// platform count
+    Platform,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -104,6 +174,11 @@ pub struct SanitizedConnection {
     pub environment: Environment,
     pub platform: Arc<str>,
     pub secrets_service_id: String,
+    /// Further secrets merged into the one of `secrets_service_id`, e.g. an
+    /// API key kept apart from OAuth tokens. Where they set the same field,
+    /// the main secret wins, then these in order.
+    #[serde(default)]
+    pub additional_secrets_service_ids: Vec<String>,
     pub event_access_id: Id,
     pub identity: Option<String>,
     pub identity_type: Option<ConnectionIdentityType>,
@@ -118,6 +193,10 @@ pub struct SanitizedConnection {
     pub error: Option<String>,
     #[serde(default)]
     pub connection_definition_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub key_rotation: Option<KeyRotation>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub quota: Option<Quota>,
     #[serde(flatten, default)]
     pub record_metadata: RecordMetadata,
 }
@@ -135,6 +214,7 @@ impl From<Connection> for SanitizedConnection {
             environment: conn.environment,
             platform: conn.platform,
             secrets_service_id: conn.secrets_service_id,
+            additional_secrets_service_ids: conn.additional_secrets_service_ids,
             event_access_id: conn.event_access_id,
             identity: conn.identity,
             identity_type: conn.identity_type,
@@ -145,6 +225,8 @@ impl From<Connection> for SanitizedConnection {
             has_error: conn.has_error,
             error: conn.error,
             connection_definition_name: None,
+            key_rotation: conn.key_rotation,
+            quota: conn.quota,
             record_metadata: conn.record_metadata,
         }
     }
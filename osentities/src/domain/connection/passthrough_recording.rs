@@ -0,0 +1,404 @@
+use super::connection_variable_mapping::VariableBinding;
+use crate::{
+    configuration::environment::Environment,
+    id::{prefix::IdPrefix, Id},
+    prelude::shared::{ownership::Ownership, record_metadata::RecordMetadata},
+    Connection, REDACTED,
+};
+use http::{HeaderMap, Method};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use strum::{AsRefStr, EnumString};
+
+/// Headers, query params and JSON fields whose lowercased name contains one
+/// of these are redacted before a recording is stored
+const SENSITIVE_NAMES: [&str; 10] = [
+    "authorization",
+    "cookie",
+    "token",
+    "secret",
+    "password",
+    "apikey",
+    "api_key",
+    "api-key",
+    "signature",
+    "credential",
+];
+
+/// Whether passthrough requests are dispatched as usual, also recorded, or
+/// answered from earlier recordings without reaching the platform
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, EnumString, AsRefStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum VcrMode {
+    #[default]
+    Off,
+    Record,
+    Replay,
+}
+
+/// A passthrough request and the platform's response, stored with secrets
+/// redacted so connector tests can replay it offline. Besides names that look
+/// like credentials, the params injected by secret bindings are redacted. Recordings are looked
+/// up by `fingerprint`, taken over the request before redaction.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PassthroughRecording {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    pub fingerprint: String,
+    pub connection_id: Id,
+    pub platform: String,
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+    pub ownership: Ownership,
+    pub environment: Environment,
+    #[serde(flatten, default)]
+    pub record_metadata: RecordMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query_params: BTreeMap<String, String>,
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+/// The response as returned by passthrough. JSON object and array bodies are
+/// kept as JSON so they can be redacted and read, other bodies as (lossy)
+/// UTF-8 text.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: Value,
+}
+
+impl PassthroughRecording {
+    pub fn new(
+        connection: &Connection,
+        fingerprint: String,
+        request: RecordedRequest,
+        response: RecordedResponse,
+    ) -> Self {
+        Self {
+            id: Id::now(IdPrefix::PassthroughRecording),
+            fingerprint,
+            connection_id: connection.id,
+            platform: connection.platform.to_string(),
+            request,
+            response,
+            ownership: connection.ownership.clone(),
+            environment: connection.environment,
+            record_metadata: RecordMetadata::default(),
+        }
+    }
+}
+
+impl RecordedRequest {
+    pub fn new(
+        method: &Method,
+        path: &str,
+        query_params: &HashMap<String, String>,
+        headers: &HeaderMap,
+        body: &[u8],
+        secret_names: &[String],
+    ) -> Self {
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            query_params: query_params
+                .iter()
+                .map(|(name, value)| (name.clone(), redact_named(name, value, secret_names)))
+                .collect(),
+            headers: redact_headers(headers, secret_names),
+            body: (!body.is_empty()).then(|| redact_value(recorded_body(body), secret_names)),
+        }
+    }
+}
+
+impl RecordedResponse {
+    pub fn new(status: u16, headers: &HeaderMap, body: &[u8], secret_names: &[String]) -> Self {
+        Self {
+            status,
+            headers: redact_headers(headers, secret_names),
+            body: redact_value(recorded_body(body), secret_names),
+        }
+    }
+
+    /// The body as it is replayed
+    pub fn body_bytes(&self) -> Vec<u8> {
+        match &self.body {
+            Value::String(text) => text.as_bytes().to_vec(),
+            body => serde_json::to_vec(body).unwrap_or_default(),
+        }
+    }
+}
+
+/// Identifies a passthrough request of a connection by its method, path,
+/// query params and body. Headers are left out as they carry per-call values
+/// such as tracing ids.
+pub fn request_fingerprint(
+    connection_id: &Id,
+    method: &Method,
+    path: &str,
+    query_params: &HashMap<String, String>,
+    body: &[u8],
+) -> String {
+    let query: BTreeMap<_, _> = query_params.iter().collect();
+
+    let mut hasher = Sha256::new();
+    hasher.update(connection_id.to_string().as_bytes());
+    hasher.update(b"\n");
+    hasher.update(method.as_str().to_uppercase().as_bytes());
+    hasher.update(b"\n");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    for (name, value) in query {
+        hasher.update(name.as_bytes());
+        hasher.update(b"=");
+        hasher.update(value.as_bytes());
+        hasher.update(b"&");
+    }
+    hasher.update(b"\n");
+    hasher.update(body);
+
+    hex::encode(hasher.finalize())
+}
+
+/// Whether a header, query param or JSON field name looks like it holds a
+/// credential
+pub(crate) fn is_sensitive(name: &str) -> bool {
+    let name = name.to_lowercase();
+
+    SENSITIVE_NAMES
+        .iter()
+        .any(|sensitive| name.contains(sensitive))
+}
+
+/// Names of the params injected by the secret bindings among `bindings`, the
+/// last segment of dotted body paths being the JSON field name
+pub fn secret_param_names<'a>(
+    bindings: impl IntoIterator<Item = &'a VariableBinding>,
+) -> Vec<String> {
+    bindings
+        .into_iter()
+        .filter(|binding| binding.is_secret())
+        .filter_map(|binding| binding.target_param.rsplit('.').next())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn is_redacted(name: &str, secret_names: &[String]) -> bool {
+    is_sensitive(name)
+        || secret_names
+            .iter()
+            .any(|secret| secret.eq_ignore_ascii_case(name))
+}
+
+fn redact_named(name: &str, value: &str, secret_names: &[String]) -> String {
+    if is_redacted(name, secret_names) {
+        REDACTED.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn redact_headers(headers: &HeaderMap, secret_names: &[String]) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                redact_named(
+                    name.as_str(),
+                    &String::from_utf8_lossy(value.as_bytes()),
+                    secret_names,
+                ),
+            )
+        })
+        .collect()
+}
+
+fn recorded_body(body: &[u8]) -> Value {
+    match serde_json::from_slice(body) {
+        Ok(value @ (Value::Object(_) | Value::Array(_))) => value,
+        _ => Value::String(String::from_utf8_lossy(body).into_owned()),
+    }
+}
+
+fn redact_value(value: Value, secret_names: &[String]) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, item)| {
+                    let item = if is_redacted(&key, secret_names) {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact_value(item, secret_names)
+                    };
+
+                    (key, item)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| redact_value(item, secret_names))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection_variable_mapping::ParameterLocation;
+    use http::HeaderValue;
+    use serde_json::json;
+
+    #[test]
+    fn test_vcr_mode_parsing() {
+        assert_eq!("Record".parse::<VcrMode>().unwrap(), VcrMode::Record);
+        assert_eq!("replay".parse::<VcrMode>().unwrap(), VcrMode::Replay);
+        assert!("rewind".parse::<VcrMode>().is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_query_param_order() {
+        let id = Id::now(IdPrefix::Connection);
+        let query = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        let a = request_fingerprint(
+            &id,
+            &Method::GET,
+            "/customers",
+            &query(&[("a", "1"), ("b", "2")]),
+            b"",
+        );
+        let b = request_fingerprint(
+            &id,
+            &Method::GET,
+            "/customers",
+            &query(&[("b", "2"), ("a", "1")]),
+            b"",
+        );
+        let other = request_fingerprint(
+            &id,
+            &Method::POST,
+            "/customers",
+            &query(&[("a", "1"), ("b", "2")]),
+            b"",
+        );
+
+        assert_eq!(a, b);
+        assert_ne!(a, other);
+    }
+
+    #[test]
+    fn test_recordings_redact_secrets() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer abc"));
+        headers.insert("x-api-key", HeaderValue::from_static("abc"));
+        headers.insert("accept", HeaderValue::from_static("application/json"));
+
+        let query = HashMap::from([
+            ("access_token".to_string(), "abc".to_string()),
+            ("limit".to_string(), "10".to_string()),
+        ]);
+        let body = json!({ "user": { "password": "abc", "name": "Ada" }, "tokens": ["abc"] });
+
+        let request = RecordedRequest::new(
+            &Method::POST,
+            "/login",
+            &query,
+            &headers,
+            &serde_json::to_vec(&body).unwrap(),
+            &[],
+        );
+
+        assert_eq!(request.headers["authorization"], REDACTED);
+        assert_eq!(request.headers["x-api-key"], REDACTED);
+        assert_eq!(request.headers["accept"], "application/json");
+        assert_eq!(request.query_params["access_token"], REDACTED);
+        assert_eq!(request.query_params["limit"], "10");
+        assert_eq!(
+            request.body,
+            Some(json!({ "user": { "password": REDACTED, "name": "Ada" }, "tokens": REDACTED }))
+        );
+    }
+
+    #[test]
+    fn test_response_body_round_trip() {
+        let json = RecordedResponse::new(200, &HeaderMap::new(), br#"{"id":1}"#, &[]);
+        assert_eq!(json.body, json!({ "id": 1 }));
+        assert_eq!(json.body_bytes(), br#"{"id":1}"#);
+
+        for body in [&b"plain text"[..], b"\"quoted\"", b"42"] {
+            let text = RecordedResponse::new(200, &HeaderMap::new(), body, &[]);
+            assert_eq!(text.body_bytes(), body);
+        }
+    }
+
+    #[test]
+    fn test_recordings_redact_params_of_secret_bindings() {
+        let binding = |target_param: &str, location, is_secret| VariableBinding {
+            variable_name: "hotel_id".to_string(),
+            target_param: target_param.to_string(),
+            location,
+            constant: None,
+            strategy: Default::default(),
+            merge: Default::default(),
+            data_type: Default::default(),
+            required: true,
+            condition: None,
+            is_secret,
+        };
+        let secret_names = secret_param_names(&[
+            binding("X-Hotel-Id", ParameterLocation::Header, Some(true)),
+            binding(
+                "reservation.hotelId",
+                ParameterLocation::BodyField,
+                Some(true),
+            ),
+            binding("hotel", ParameterLocation::QueryParam, None),
+        ]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-hotel-id", HeaderValue::from_static("h1"));
+        let query = HashMap::from([("hotel".to_string(), "h1".to_string())]);
+        let body = json!({ "reservation": { "hotelId": "h1", "nights": 2 } });
+
+        let request = RecordedRequest::new(
+            &Method::POST,
+            "/reservations",
+            &query,
+            &headers,
+            &serde_json::to_vec(&body).unwrap(),
+            &secret_names,
+        );
+
+        assert_eq!(request.headers["x-hotel-id"], REDACTED);
+        assert_eq!(request.query_params["hotel"], "h1");
+        assert_eq!(
+            request.body,
+            Some(json!({ "reservation": { "hotelId": REDACTED, "nights": 2 } }))
+        );
+    }
+}
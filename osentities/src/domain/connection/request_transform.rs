@@ -0,0 +1,721 @@
+//! Request transforms of model definitions, for the edits templates and
+//! bindings cannot express, e.g. renaming a body field or signing the body.
+//! A transform is data, not code: a list of steps over a closed set of
+//! expressions, without loops nor IO, whose evaluation is bounded by the
+//! limits below.
+
+use super::{
+    connection_variable_mapping::{
+        BodyMerge, InjectionStrategy, ParameterLocation, VariableBinding, VariableDataType,
+    },
+    variable_injection::{resolve_variable, BodyEncoding, RequestParts},
+};
+use crate::{ApplicationError, PicaError, PicaErrorCode};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use http::{header::CONTENT_TYPE, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use thiserror::Error as ThisError;
+
+/// Most steps of a transform
+pub const MAX_TRANSFORM_STEPS: usize = 32;
+/// Most expressions across the steps of a transform
+pub const MAX_TRANSFORM_EXPRESSIONS: usize = 256;
+/// Longest string a concatenation or encoding may produce, in bytes
+pub const MAX_TRANSFORM_VALUE_LENGTH: usize = 64 * 1024;
+/// Longest a transform runs before it is aborted
+pub const MAX_TRANSFORM_DURATION: Duration = Duration::from_millis(50);
+
+/// Steps applied in order to a request right before it is dispatched, once
+/// bindings are injected. The definition's own headers and its
+/// authentication are added afterwards.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestTransform {
+    pub steps: Vec<TransformStep>,
+}
+
+/// The part of a request a step reads or writes
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
+pub enum TransformLocation {
+    Header,
+    /// Set replaces every value of the key
+    QueryParam,
+    /// A dot-separated path into a JSON body, or the name of a form field
+    BodyField,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum TransformStep {
+    /// Sets the parameter, replacing any value it has
+    Set {
+        location: TransformLocation,
+        param: String,
+        value: Expression,
+    },
+    /// Moves the parameter to another name in the same location, if it is set
+    Rename {
+        location: TransformLocation,
+        param: String,
+        to: String,
+    },
+    /// Removes the parameter, if it is set
+    Remove {
+        location: TransformLocation,
+        param: String,
+    },
+}
+
+/// A value computed from the request as transformed by the previous steps
+/// and from the connection's secret. Strings are used as they are, `null` as
+/// an empty string and any other value as its JSON text.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Expression {
+    Literal(Value),
+    /// A variable of the connection's secret, failing the transform when
+    /// missing
+    Variable(String),
+    /// `null` when the request has no such header
+    Header(String),
+    /// The first value of the key, `null` when the request has none
+    QueryParam(String),
+    /// `null` when the body has no such field
+    BodyField(String),
+    /// The body as it is sent, as text
+    Body,
+    /// The current Unix time
+    Now(TimeUnit),
+    Concat(Vec<Expression>),
+    Lowercase(Box<Expression>),
+    Uppercase(Box<Expression>),
+    Base64(Box<Expression>),
+    Hash {
+        algorithm: DigestAlgorithm,
+        value: Box<Expression>,
+        #[serde(default)]
+        encoding: DigestEncoding,
+    },
+    Hmac {
+        algorithm: DigestAlgorithm,
+        key: Box<Expression>,
+        message: Box<Expression>,
+        #[serde(default)]
+        encoding: DigestEncoding,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum TimeUnit {
+    Seconds,
+    Milliseconds,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Deserialize, Serialize, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum DigestEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// A transform that is over its limits or cannot be applied to a request
+#[derive(ThisError, Debug, Clone, PartialEq, Eq)]
+#[error("Request transform failed at step {step}: {reason}")]
+pub struct TransformError {
+    pub step: usize,
+    pub reason: String,
+}
+
+impl From<TransformError> for PicaError {
+    fn from(error: TransformError) -> Self {
+        ApplicationError::unprocessable_entity(
+            &error.to_string(),
+            PicaErrorCode::TransformFailed.subtype(),
+        )
+    }
+}
+
+impl RequestTransform {
+    /// Checks the transform against the limits and its header names, so that
+    /// a definition is rejected before any request is transformed with it
+    pub fn check(&self) -> Result<(), TransformError> {
+        if self.steps.len() > MAX_TRANSFORM_STEPS {
+            return Err(TransformError {
+                step: MAX_TRANSFORM_STEPS,
+                reason: format!("a transform has at most {MAX_TRANSFORM_STEPS} steps"),
+            });
+        }
+
+        let mut expressions = 0;
+        for (step, transform_step) in self.steps.iter().enumerate() {
+            let error = |reason: String| TransformError { step, reason };
+
+            let (location, names) = match transform_step {
+                TransformStep::Set {
+                    location,
+                    param,
+                    value,
+                } => {
+                    expressions += value.size();
+                    (location, vec![param])
+                }
+                TransformStep::Rename {
+                    location,
+                    param,
+                    to,
+                } => (location, vec![param, to]),
+                TransformStep::Remove { location, param } => (location, vec![param]),
+            };
+
+            if expressions > MAX_TRANSFORM_EXPRESSIONS {
+                return Err(error(format!(
+                    "a transform has at most {MAX_TRANSFORM_EXPRESSIONS} expressions"
+                )));
+            }
+
+            for name in names {
+                if name.is_empty() {
+                    return Err(error("parameter name is empty".to_string()));
+                }
+                if *location == TransformLocation::Header && HeaderName::from_str(name).is_err() {
+                    return Err(error(format!("'{name}' is not a valid header name")));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn apply(
+        &self,
+        parts: RequestParts,
+        secret: &Value,
+    ) -> Result<RequestParts, TransformError> {
+        self.check()?;
+
+        let mut request = Transformed {
+            parts,
+            fields: None,
+            edited: false,
+            secret,
+            deadline: Instant::now() + MAX_TRANSFORM_DURATION,
+        };
+
+        for (step, transform_step) in self.steps.iter().enumerate() {
+            request
+                .apply(transform_step)
+                .map_err(|reason| TransformError { step, reason })?;
+        }
+
+        request.encode_body();
+        Ok(request.parts)
+    }
+}
+
+impl Expression {
+    /// Number of expressions evaluated to compute this one
+    fn size(&self) -> usize {
+        1 + match self {
+            Expression::Concat(values) => values.iter().map(Expression::size).sum(),
+            Expression::Lowercase(value)
+            | Expression::Uppercase(value)
+            | Expression::Base64(value)
+            | Expression::Hash { value, .. } => value.size(),
+            Expression::Hmac { key, message, .. } => key.size() + message.size(),
+            _ => 0,
+        }
+    }
+}
+
+/// A request being transformed
+struct Transformed<'a> {
+    parts: RequestParts,
+    /// The body, decoded once a step reads or writes one of its fields
+    fields: Option<Value>,
+    /// Whether `fields` changed since the body was last encoded
+    edited: bool,
+    secret: &'a Value,
+    deadline: Instant,
+}
+
+impl Transformed<'_> {
+    fn apply(&mut self, step: &TransformStep) -> Result<(), String> {
+        match step {
+            TransformStep::Set {
+                location,
+                param,
+                value,
+            } => {
+                let value = self.evaluate(value)?;
+                self.set(*location, param, value)
+            }
+            TransformStep::Rename {
+                location,
+                param,
+                to,
+            } => match self.take(*location, param)? {
+                Some(value) => self.set(*location, to, value),
+                None => Ok(()),
+            },
+            TransformStep::Remove { location, param } => {
+                self.take(*location, param)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn set(
+        &mut self,
+        location: TransformLocation,
+        param: &str,
+        value: Value,
+    ) -> Result<(), String> {
+        match location {
+            TransformLocation::Header => {
+                let name = HeaderName::from_str(param)
+                    .map_err(|_| format!("'{param}' is not a valid header name"))?;
+                let value = HeaderValue::from_str(&text(&value)).map_err(|_| {
+                    format!("the value of header '{param}' is not valid in a header")
+                })?;
+                self.parts.headers.insert(name, value);
+            }
+            TransformLocation::QueryParam => {
+                let value = text(&value);
+                let params = &mut self.parts.query_params;
+                match params.iter().position(|(key, _)| key == param) {
+                    Some(first) => {
+                        params[first].1 = value;
+                        let mut index = 0;
+                        params.retain(|(key, _)| {
+                            index += 1;
+                            index - 1 <= first || key != param
+                        });
+                    }
+                    None => params.push((param.to_string(), value)),
+                }
+            }
+            TransformLocation::BodyField => {
+                let binding = VariableBinding {
+                    variable_name: String::new(),
+                    target_param: param.to_string(),
+                    location: ParameterLocation::BodyField,
+                    constant: None,
+                    strategy: InjectionStrategy::Strict,
+                    merge: BodyMerge::Shallow,
+                    data_type: VariableDataType::String,
+                    required: false,
+                    condition: None,
+                    is_secret: None,
+                };
+                binding
+                    .inject_into_body(self.fields()?, value)
+                    .map_err(|e| e.reason)?;
+                self.edited = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn take(&mut self, location: TransformLocation, param: &str) -> Result<Option<Value>, String> {
+        Ok(match location {
+            TransformLocation::Header => {
+                self.parts.headers.remove(param).map(|value| {
+                    Value::String(String::from_utf8_lossy(value.as_bytes()).into_owned())
+                })
+            }
+            TransformLocation::QueryParam => {
+                let first = self.query_param(param);
+                self.parts.query_params.retain(|(key, _)| key != param);
+                first
+            }
+            TransformLocation::BodyField => {
+                let taken = take_field(self.fields()?, param);
+                self.edited |= taken.is_some();
+                taken
+            }
+        })
+    }
+
+    fn query_param(&self, param: &str) -> Option<Value> {
+        self.parts
+            .query_params
+            .iter()
+            .find(|(key, _)| key == param)
+            .map(|(_, value)| Value::String(value.clone()))
+    }
+
+    /// The decoded body, an empty object when the request has none
+    fn fields(&mut self) -> Result<&mut Value, String> {
+        if self.fields.is_none() {
+            let fields = match self.parts.body.as_deref() {
+                None => Value::Object(Default::default()),
+                Some(body) => BodyEncoding::of(&self.parts.headers)
+                    .decode(body)
+                    .ok_or_else(|| "the body is neither JSON nor a form".to_string())?,
+            };
+            self.fields = Some(fields);
+        }
+
+        Ok(self.fields.get_or_insert_with(Value::default))
+    }
+
+    /// Writes edited fields back into the body, labelled as JSON when the
+    /// request had no body type
+    fn encode_body(&mut self) {
+        if !self.edited {
+            return;
+        }
+
+        if let Some(fields) = &self.fields {
+            if !self.parts.headers.contains_key(CONTENT_TYPE) {
+                self.parts
+                    .headers
+                    .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            }
+            self.parts.body = Some(BodyEncoding::of(&self.parts.headers).encode(fields));
+        }
+        self.edited = false;
+    }
+
+    fn evaluate(&mut self, expression: &Expression) -> Result<Value, String> {
+        if Instant::now() > self.deadline {
+            return Err(format!(
+                "ran longer than {}ms",
+                MAX_TRANSFORM_DURATION.as_millis()
+            ));
+        }
+
+        let value = match expression {
+            Expression::Literal(value) => value.clone(),
+            Expression::Variable(name) => resolve_variable(self.secret, name)
+                .cloned()
+                .ok_or_else(|| format!("connection variable '{name}' is missing"))?,
+            Expression::Header(name) => self
+                .parts
+                .headers
+                .get(name.as_str())
+                .map(|value| Value::String(String::from_utf8_lossy(value.as_bytes()).into_owned()))
+                .unwrap_or_default(),
+            Expression::QueryParam(name) => self.query_param(name).unwrap_or_default(),
+            Expression::BodyField(path) => field(self.fields()?, path).cloned().unwrap_or_default(),
+            Expression::Body => {
+                self.encode_body();
+                self.parts
+                    .body
+                    .as_deref()
+                    .map(|body| Value::String(String::from_utf8_lossy(body).into_owned()))
+                    .unwrap_or_default()
+            }
+            Expression::Now(TimeUnit::Seconds) => Value::from(Utc::now().timestamp()),
+            Expression::Now(TimeUnit::Milliseconds) => Value::from(Utc::now().timestamp_millis()),
+            Expression::Concat(values) => {
+                let mut concatenated = String::new();
+                for value in values {
+                    concatenated.push_str(&text(&self.evaluate(value)?));
+                    bounded(&concatenated)?;
+                }
+                Value::String(concatenated)
+            }
+            Expression::Lowercase(value) => {
+                Value::String(text(&self.evaluate(value)?).to_lowercase())
+            }
+            Expression::Uppercase(value) => {
+                Value::String(text(&self.evaluate(value)?).to_uppercase())
+            }
+            Expression::Base64(value) => {
+                let encoded = STANDARD.encode(text(&self.evaluate(value)?));
+                bounded(&encoded)?;
+                Value::String(encoded)
+            }
+            Expression::Hash {
+                algorithm,
+                value,
+                encoding,
+            } => {
+                let value = text(&self.evaluate(value)?);
+                let digest = match algorithm {
+                    DigestAlgorithm::Sha1 => Sha1::digest(value).to_vec(),
+                    DigestAlgorithm::Sha256 => Sha256::digest(value).to_vec(),
+                    DigestAlgorithm::Sha512 => Sha512::digest(value).to_vec(),
+                };
+                Value::String(encoding.encode(&digest))
+            }
+            Expression::Hmac {
+                algorithm,
+                key,
+                message,
+                encoding,
+            } => {
+                let key = text(&self.evaluate(key)?);
+                let message = text(&self.evaluate(message)?);
+                let signature = match algorithm {
+                    DigestAlgorithm::Sha1 => hmac::<Hmac<Sha1>>(&key, &message),
+                    DigestAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(&key, &message),
+                    DigestAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(&key, &message),
+                }?;
+                Value::String(encoding.encode(&signature))
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+impl DigestEncoding {
+    fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            DigestEncoding::Hex => hex::encode(bytes),
+            DigestEncoding::Base64 => STANDARD.encode(bytes),
+        }
+    }
+}
+
+fn hmac<M: Mac + hmac::digest::KeyInit>(key: &str, message: &str) -> Result<Vec<u8>, String> {
+    let mut mac = <M as Mac>::new_from_slice(key.as_bytes())
+        .map_err(|_| "the HMAC key is not valid".to_string())?;
+    mac.update(message.as_bytes());
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn bounded(value: &str) -> Result<(), String> {
+    if value.len() > MAX_TRANSFORM_VALUE_LENGTH {
+        Err(format!(
+            "a value is longer than {MAX_TRANSFORM_VALUE_LENGTH} bytes"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+fn field<'a>(body: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(body, |node, segment| match node {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn take_field(body: &mut Value, path: &str) -> Option<Value> {
+    let (parent, last) = match path.rsplit_once('.') {
+        Some((parents, last)) => (
+            parents
+                .split('.')
+                .try_fold(body, |node, segment| match node {
+                    Value::Object(map) => map.get_mut(segment),
+                    Value::Array(items) => items.get_mut(segment.parse::<usize>().ok()?),
+                    _ => None,
+                })?,
+            last,
+        ),
+        None => (body, path),
+    };
+
+    match parent {
+        Value::Object(map) => map.shift_remove(last),
+        Value::Array(items) => {
+            let index = last
+                .parse::<usize>()
+                .ok()
+                .filter(|index| *index < items.len())?;
+            Some(items.remove(index))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderMap;
+    use serde_json::json;
+
+    fn transform(steps: Value) -> RequestTransform {
+        serde_json::from_value(json!({ "steps": steps })).expect("Failed to parse transform")
+    }
+
+    fn request(body: Value) -> RequestParts {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        RequestParts {
+            path: "/reservations".to_string(),
+            headers,
+            query_params: vec![
+                ("page".to_string(), "1".to_string()),
+                ("tag".to_string(), "a".to_string()),
+                ("tag".to_string(), "b".to_string()),
+            ],
+            body: Some(body.to_string().into_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_transform_renames_fields_and_signs_the_body() {
+        let transform = transform(json!([
+            { "op": "rename", "location": "BodyField", "param": "guestName", "to": "guest.name" },
+            { "op": "remove", "location": "BodyField", "param": "debug" },
+            { "op": "set", "location": "QueryParam", "param": "tag", "value": { "literal": "c" } },
+            {
+                "op": "set",
+                "location": "Header",
+                "param": "X-Signature",
+                "value": {
+                    "hmac": {
+                        "algorithm": "sha256",
+                        "key": { "variable": "SIGNING_KEY" },
+                        "message": "body",
+                    }
+                }
+            },
+        ]));
+
+        let parts = transform
+            .apply(
+                request(json!({ "guestName": "Ada", "debug": true, "nights": 2 })),
+                &json!({ "SIGNING_KEY": "key" }),
+            )
+            .unwrap();
+
+        let body = String::from_utf8(parts.body.unwrap()).unwrap();
+        assert_eq!(body, r#"{"nights":2,"guest":{"name":"Ada"}}"#);
+        assert_eq!(
+            parts.headers["x-signature"],
+            hex::encode(hmac::<Hmac<Sha256>>("key", &body).unwrap()).as_str()
+        );
+        assert_eq!(
+            parts.query_params,
+            [
+                ("page".to_string(), "1".to_string()),
+                ("tag".to_string(), "c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transform_computes_values_from_the_request() {
+        let transform = transform(json!([
+            {
+                "op": "set",
+                "location": "BodyField",
+                "param": "reference",
+                "value": {
+                    "concat": [
+                        { "uppercase": { "bodyField": "hotel.code" } },
+                        { "literal": "-" },
+                        { "queryParam": "page" },
+                        { "header": "x-missing" },
+                    ]
+                }
+            },
+            {
+                "op": "set",
+                "location": "Header",
+                "param": "Digest",
+                "value": {
+                    "hash": { "algorithm": "sha256", "value": { "literal": "" }, "encoding": "base64" }
+                }
+            },
+        ]));
+
+        let parts = transform
+            .apply(request(json!({ "hotel": { "code": "grd" } })), &json!({}))
+            .unwrap();
+
+        assert_eq!(
+            serde_json::from_slice::<Value>(&parts.body.unwrap()).unwrap(),
+            json!({ "hotel": { "code": "grd" }, "reference": "GRD-1" })
+        );
+        assert_eq!(
+            parts.headers["digest"],
+            "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+    }
+
+    #[test]
+    fn test_transform_failures_are_typed() {
+        let missing_variable = transform(json!([
+            { "op": "remove", "location": "Header", "param": "x-debug" },
+            { "op": "set", "location": "Header", "param": "x-key", "value": { "variable": "API_KEY" } },
+        ]));
+        let error = missing_variable
+            .apply(request(json!({})), &json!({}))
+            .unwrap_err();
+        assert_eq!(error.step, 1);
+        assert!(error.reason.contains("API_KEY"));
+
+        let error = PicaError::from(error);
+        assert_eq!(error.status(), 422);
+        assert_eq!(error.error_code(), Some(PicaErrorCode::TransformFailed));
+
+        let mut form = request(json!({}));
+        form.body = Some(b"<xml/>".to_vec());
+        form.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
+        let set_field = transform(json!([
+            { "op": "set", "location": "BodyField", "param": "a", "value": { "literal": 1 } },
+        ]));
+        assert!(set_field.apply(form, &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_transform_limits_are_enforced() {
+        let set = json!({ "op": "remove", "location": "QueryParam", "param": "page" });
+        let too_many_steps = transform(Value::Array(vec![set; MAX_TRANSFORM_STEPS + 1]));
+        assert_eq!(
+            too_many_steps.check().unwrap_err().step,
+            MAX_TRANSFORM_STEPS
+        );
+
+        let wide = json!({ "concat": vec![json!({ "literal": "a" }); MAX_TRANSFORM_EXPRESSIONS] });
+        let too_many_expressions = transform(json!([
+            { "op": "set", "location": "Header", "param": "x-wide", "value": wide },
+        ]));
+        assert!(too_many_expressions.check().is_err());
+
+        let invalid_header = transform(json!([
+            { "op": "remove", "location": "Header", "param": "not a header" },
+        ]));
+        assert!(invalid_header.check().is_err());
+
+        // Doubling the body a few times goes over the length of a value
+        let mut doubled = json!("body");
+        for _ in 0..6 {
+            doubled = json!({ "concat": [doubled.clone(), doubled] });
+        }
+        let too_long = transform(json!([
+            { "op": "set", "location": "Header", "param": "x-long", "value": doubled },
+        ]));
+        let body = json!({ "padding": "x".repeat(2048) });
+        let error = too_long.apply(request(body), &json!({})).unwrap_err();
+        assert!(error.reason.contains("longer than"));
+    }
+}
@@ -0,0 +1,1359 @@
+//! Interpretation of [`VariableBinding`]s, shared by every call site that
+//! injects connection variables into a request or describes them, so the
+//! behavior is defined once.
+
+use super::connection_variable_mapping::{
+    BindingCondition, InjectionStrategy, ParameterLocation, VariableBinding, VariableDataType,
+};
+use crate::{ApplicationError, PicaError, REDACTED};
+use http::{
+    header::{CONTENT_TYPE, COOKIE},
+    HeaderMap, HeaderName, HeaderValue,
+};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+
+/// The parts of an outgoing request that bindings can be injected into.
+/// `path` is the definition's path template, e.g. `/hotels/{id}`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestParts {
+    pub path: String,
+    pub headers: HeaderMap,
+    /// In request order, a key repeats once per value
+    pub query_params: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl RequestParts {
+    /// Sets the `Content-Type` of the definition, when it has one, as it
+    /// replaces the caller's at dispatch and decides how the body is injected
+    pub fn with_definition_content_type(mut self, headers: Option<&HeaderMap>) -> Self {
+        if let Some(content_type) = headers.and_then(|headers| headers.get(CONTENT_TYPE)) {
+            self.headers.insert(CONTENT_TYPE, content_type.clone());
+        }
+
+        self
+    }
+
+    /// Sends the body as `content_type`, re-encoding it when that changes its
+    /// [`BodyEncoding`]. Bodies that cannot be decoded are left as they are.
+    pub fn with_content_type(mut self, content_type: HeaderValue) -> Self {
+        let from = BodyEncoding::of(&self.headers);
+        self.headers.insert(CONTENT_TYPE, content_type);
+        let to = BodyEncoding::of(&self.headers);
+
+        if from != to {
+            if let Some(body) = self.body.as_deref().and_then(|body| from.decode(body)) {
+                self.body = Some(to.encode(&body));
+            }
+        }
+
+        self
+    }
+}
+
+/// How a request body is encoded, by its `Content-Type`. Bodies of any other
+/// type are read as JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyEncoding {
+    Json,
+    /// `application/x-www-form-urlencoded`, arrays being repeated keys
+    Form,
+}
+
+impl BodyEncoding {
+    pub fn of(headers: &HeaderMap) -> Self {
+        let is_form = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|mime| {
+                mime.trim()
+                    .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+            });
+
+        if is_form {
+            BodyEncoding::Form
+        } else {
+            BodyEncoding::Json
+        }
+    }
+
+    /// Serializes a body given as JSON. Form bodies are built from the fields
+    /// of an object, a string being sent as is.
+    pub fn encode(self, body: &Value) -> Vec<u8> {
+        match (self, body) {
+            (BodyEncoding::Form, Value::Object(fields)) => encode_form(
+                fields
+                    .iter()
+                    .flat_map(|(name, value)| {
+                        form_values(value)
+                            .into_iter()
+                            .map(move |value| (name.clone(), value))
+                    })
+                    .collect::<Vec<_>>()
+                    .as_slice(),
+            ),
+            (BodyEncoding::Form, Value::String(body)) => body.as_bytes().to_vec(),
+            _ => body.to_string().into_bytes(),
+        }
+    }
+
+    /// Reads a body as JSON, form fields as an object whose repeated keys are
+    /// arrays
+    pub fn decode(self, body: &[u8]) -> Option<Value> {
+        match self {
+            BodyEncoding::Json => serde_json::from_slice(body).ok(),
+            BodyEncoding::Form => {
+                let mut fields = serde_json::Map::new();
+
+                for (name, value) in decode_form(body) {
+                    match fields.get_mut(&name) {
+                        Some(Value::Array(values)) => values.push(Value::String(value)),
+                        Some(first) => {
+                            *first = Value::Array(vec![first.take(), Value::String(value)])
+                        }
+                        None => {
+                            fields.insert(name, Value::String(value));
+                        }
+                    }
+                }
+
+                Some(Value::Object(fields))
+            }
+        }
+    }
+}
+
+/// The request parts after injection, along with the string form of every
+/// value injected by a secret binding, so callers can redact them, and the
+/// bindings skipped because the request did not match their condition
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResolvedRequest {
+    pub parts: RequestParts,
+    pub secret_values: Vec<String>,
+    pub unmet_conditions: Vec<UnmetCondition>,
+}
+
+/// A conditional binding that was not injected, with the value its condition
+/// was evaluated against (`None` when the parameter is absent)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnmetCondition {
+    pub variable_name: String,
+    pub target_param: String,
+    pub condition: BindingCondition,
+    pub actual: Option<String>,
+}
+
+/// How a bound parameter is handled, for readers deciding whether they need
+/// to supply it themselves
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Annotation {
+    pub target_param: String,
+    pub location: ParameterLocation,
+    pub strategy: InjectionStrategy,
+    pub description: String,
+    pub is_secret: bool,
+    /// The value injected by constant bindings, [`REDACTED`] when secret
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+impl Display for Annotation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "'{}' ({})", self.target_param, self.description)
+    }
+}
+
+/// Looks up a connection variable in the decrypted secret. Variables captured
+/// by an OAuth or form based connection flow take precedence over top-level keys.
+pub fn resolve_variable<'a>(secret: &'a Value, variable_name: &str) -> Option<&'a Value> {
+    if let Some(payload) = secret.get("OAUTH_REQUEST_PAYLOAD") {
+        payload.get("formData").and_then(|fd| fd.get(variable_name))
+    } else if let Some(fd) = secret.get("auth_form_data") {
+        fd.get(variable_name)
+    } else {
+        secret.get(variable_name)
+    }
+}
+
+/// Required bindings whose variable is not in the decrypted secret. Bindings
+/// injecting a constant never miss their value.
+pub fn missing_variables<'a>(
+    bindings: &'a [VariableBinding],
+    secret: &Value,
+) -> Vec<&'a VariableBinding> {
+    bindings
+        .iter()
+        .filter(|binding| binding.required && binding_value(binding, secret).is_none())
+        .collect()
+}
+
+/// The raw value a binding injects, its constant when it has one, otherwise
+/// its variable from the secret
+fn binding_value<'a>(binding: &'a VariableBinding, secret: &'a Value) -> Option<&'a Value> {
+    match &binding.constant {
+        Some(constant) => Some(constant),
+        None => resolve_variable(secret, &binding.variable_name),
+    }
+}
+
+/// Injects the variables of `secret` into the request following each binding.
+/// Path params are substituted into the path template, the other locations
+/// are written into the headers, query params or body, a form or JSON body
+/// depending on its [`BodyEncoding`]. Bindings with a constant inject it
+/// whatever the secret holds, bindings whose variable is missing from the
+/// secret are skipped, as are header values that are not valid in a header,
+/// missing bodies and JSON bodies that do not parse. Conditions are evaluated
+/// in binding order against the request as injected so far.
+pub fn apply_bindings(
+    mut parts: RequestParts,
+    bindings: &[VariableBinding],
+    secret: &Value,
+) -> Result<ResolvedRequest, PicaError> {
+    let mut secret_values = Vec::new();
+    let mut unmet_conditions = Vec::new();
+
+    for binding in bindings {
+        if let Some(condition) = &binding.condition {
+            let actual = request_param(&parts, condition);
+
+            if !condition.operator.matches(actual.as_deref()) {
+                unmet_conditions.push(UnmetCondition {
+                    variable_name: binding.variable_name.clone(),
+                    target_param: binding.target_param.clone(),
+                    condition: condition.clone(),
+                    actual,
+                });
+                continue;
+            }
+        }
+
+        let Some(raw) = binding_value(binding, secret) else {
+            continue;
+        };
+
+        let value = binding.injectable_value(raw)?;
+        let value_str = value
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| value.to_string());
+
+        match binding.location {
+            // Path params always overwrite, they identify the resource
+            ParameterLocation::PathParam => {
+                parts.path = parts
+                    .path
+                    .replace(&format!("{{{}}}", binding.target_param), &value_str);
+            }
+            ParameterLocation::QueryParam => {
+                inject_pairs(&mut parts.query_params, binding, vec![value_str.clone()])
+            }
+            ParameterLocation::Header => {
+                inject_header(&mut parts.headers, binding, &value_str);
+            }
+            ParameterLocation::BodyField => match BodyEncoding::of(&parts.headers) {
+                BodyEncoding::Form => {
+                    let Some(body) = parts.body.as_deref() else {
+                        continue;
+                    };
+
+                    let mut fields = decode_form(body);
+                    inject_pairs(&mut fields, binding, form_values(&value));
+                    parts.body = Some(encode_form(&fields));
+                }
+                BodyEncoding::Json => {
+                    let Some(mut body) = parts
+                        .body
+                        .as_deref()
+                        .and_then(|body| serde_json::from_slice::<Value>(body).ok())
+                    else {
+                        continue;
+                    };
+
+                    binding.inject_into_body(&mut body, value)?;
+
+                    if let Ok(bytes) = serde_json::to_vec(&body) {
+                        parts.body = Some(bytes);
+                        label_as_json(&mut parts.headers);
+                    }
+                }
+            },
+        }
+
+        if binding.is_secret() && !value_str.is_empty() {
+            secret_values.push(value_str);
+        }
+    }
+
+    Ok(ResolvedRequest {
+        parts,
+        secret_values,
+        unmet_conditions,
+    })
+}
+
+/// Substitutes the `{variable}` placeholders of a definition's base url, e.g.
+/// `https://{instance}.salesforce.com`, with connection variables read as
+/// strings. Every placeholder is required: a variable missing from the secret
+/// or whose value would leave its url component (`/`, `?`, `@`, ...) fails
+/// with a 422. Braces around anything but a variable name are kept as is, and
+/// `\{` keeps a literal one, e.g. `\{tenant}` for a url holding `{tenant}`.
+pub fn resolve_base_url(base_url: &str, secret: &Value) -> Result<String, PicaError> {
+    let mut resolved = String::with_capacity(base_url.len());
+    let mut rest = base_url;
+
+    while let Some(start) = rest.find('{') {
+        let (before, after) = rest.split_at(start);
+
+        if let Some(before) = before.strip_suffix('\\') {
+            resolved.push_str(before);
+            resolved.push('{');
+            rest = &after[1..];
+            continue;
+        }
+
+        resolved.push_str(before);
+
+        let Some(name) = after[1..]
+            .find('}')
+            .map(|end| &after[1..=end])
+            .filter(|name| is_variable_name(name))
+        else {
+            resolved.push('{');
+            rest = &after[1..];
+            continue;
+        };
+
+        let value = resolve_variable(secret, name)
+            .ok_or_else(|| format!("connection variable '{name}' is missing"))
+            .and_then(|raw| {
+                VariableDataType::String
+                    .coerce(raw)
+                    .map_err(|reason| format!("connection variable '{name}' {reason}"))
+            })
+            .and_then(|value| {
+                let value = value.as_str().map(str::to_string).unwrap_or_default();
+                if value.is_empty() {
+                    Err(format!("connection variable '{name}' is empty"))
+                } else if value.contains(|c: char| "/?#@\\{}".contains(c) || c.is_whitespace()) {
+                    Err(format!(
+                        "connection variable '{name}' contains characters not allowed in a url"
+                    ))
+                } else {
+                    Ok(value)
+                }
+            })
+            .map_err(|reason| {
+                ApplicationError::unprocessable_entity(
+                    &format!("Could not resolve base url {base_url}: {reason}"),
+                    None,
+                )
+            })?;
+
+        resolved.push_str(&value);
+        rest = &after[name.len() + 2..];
+    }
+
+    resolved.push_str(rest);
+
+    Ok(resolved)
+}
+
+/// Everything but the unreserved characters of RFC 3986 is encoded in a
+/// value filled into a path, so it always stays a single segment
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Substitutes the `{name}` placeholders of a definition's path with
+/// `value(name)`, percent-encoded, leaving `{{name}}` ones to the template
+/// rendered at dispatch, and returns the names of the placeholders of either
+/// form that `value` has none for, without repeats. Braces around anything
+/// but a variable name, e.g. template helpers, are kept as is.
+pub fn fill_path_placeholders(
+    path: &str,
+    value: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<String>) {
+    let mut filled = String::with_capacity(path.len());
+    let mut missing: Vec<String> = Vec::new();
+    let mut rest = path;
+
+    while let Some(start) = rest.find('{') {
+        let (before, after) = rest.split_at(start);
+        filled.push_str(before);
+
+        let templated = after.starts_with("{{");
+        let (open, close) = if templated { ("{{", "}}") } else { ("{", "}") };
+
+        let inner = &after[open.len()..];
+        let Some(end) = inner
+            .find(close)
+            .filter(|end| is_variable_name(inner[..*end].trim()))
+        else {
+            filled.push_str(open);
+            rest = inner;
+            continue;
+        };
+        let name = inner[..end].trim();
+        let placeholder = open.len() + end + close.len();
+
+        let value = value(name);
+        if value.is_none() && !missing.iter().any(|missing| missing == name) {
+            missing.push(name.to_string());
+        }
+
+        match value {
+            Some(value) if !templated => filled.extend(utf8_percent_encode(&value, PATH_SEGMENT)),
+            _ => filled.push_str(&after[..placeholder]),
+        }
+        rest = &after[placeholder..];
+    }
+
+    filled.push_str(rest);
+
+    (filled, missing)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Bodies without a JSON `Content-Type` are read as JSON too, so once a
+/// binding rewrote one it is labelled as what it now is
+fn label_as_json(headers: &mut HeaderMap) {
+    let is_json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| {
+            let mime = mime.trim().to_ascii_lowercase();
+            mime == "application/json" || mime.ends_with("+json")
+        });
+
+    if !is_json {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+}
+
+/// String form of the parameter a condition is evaluated against
+fn request_param(parts: &RequestParts, condition: &BindingCondition) -> Option<String> {
+    match condition.location {
+        ParameterLocation::PathParam => None,
+        ParameterLocation::QueryParam => parts
+            .query_params
+            .iter()
+            .find(|(key, _)| *key == condition.param)
+            .map(|(_, value)| value.clone()),
+        ParameterLocation::Header => parts
+            .headers
+            .get(&condition.param)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        ParameterLocation::BodyField => {
+            let encoding = BodyEncoding::of(&parts.headers);
+            if encoding == BodyEncoding::Form {
+                return decode_form(parts.body.as_deref()?)
+                    .into_iter()
+                    .find(|(name, _)| *name == condition.param)
+                    .map(|(_, value)| value);
+            }
+
+            let body = serde_json::from_slice::<Value>(parts.body.as_deref()?).ok()?;
+
+            let value = condition
+                .param
+                .split('.')
+                .try_fold(&body, |node, segment| match node {
+                    Value::Object(map) => map.get(segment),
+                    Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+                    _ => None,
+                })?;
+
+            match value {
+                Value::Null => None,
+                Value::String(s) => Some(s.clone()),
+                value => Some(value.to_string()),
+            }
+        }
+    }
+}
+
+/// One annotation per binding, in binding order
+pub fn describe_bindings(bindings: &[VariableBinding]) -> Vec<Annotation> {
+    bindings
+        .iter()
+        .map(|binding| Annotation {
+            target_param: binding.target_param.clone(),
+            location: binding.location.clone(),
+            strategy: binding.strategy.clone(),
+            description: describe_binding(binding),
+            is_secret: binding.is_secret(),
+            value: binding.constant.as_ref().map(|constant| {
+                if binding.is_secret() {
+                    Value::String(REDACTED.to_string())
+                } else {
+                    constant.clone()
+                }
+            }),
+        })
+        .collect()
+}
+
+/// Conditional bindings only apply to some requests, which the description
+/// states so that readers still supply the parameter to the others
+fn describe_binding(binding: &VariableBinding) -> String {
+    let description = match binding.strategy {
+        InjectionStrategy::Strict => "auto-filled, do NOT ask user",
+        InjectionStrategy::Fallback => "has default, only ask if user wants to override",
+        InjectionStrategy::Append => "partially pre-filled, user may add more",
+    };
+
+    match &binding.condition {
+        Some(condition) => format!("when {condition}: {description}; otherwise not filled"),
+        None => description.to_string(),
+    }
+}
+
+fn decode_form(body: &[u8]) -> Vec<(String, String)> {
+    form_urlencoded::parse(body).into_owned().collect()
+}
+
+fn encode_form(fields: &[(String, String)]) -> Vec<u8> {
+    form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(fields)
+        .finish()
+        .into_bytes()
+}
+
+/// The values of a form field, one per item of an array
+fn form_values(value: &Value) -> Vec<String> {
+    let as_string = |value: &Value| match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+
+    match value {
+        Value::Array(items) => items.iter().map(as_string).collect(),
+        value => vec![as_string(value)],
+    }
+}
+
+/// Form fields and query params are flat, `target_param` is the key as is.
+/// Existing keys are replaced in place, and appended to by repeating the key.
+fn inject_pairs(pairs: &mut Vec<(String, String)>, binding: &VariableBinding, values: Vec<String>) {
+    let name = &binding.target_param;
+    let injected = values.into_iter().map(|value| (name.clone(), value));
+    let position = pairs.iter().position(|(key, _)| key == name);
+
+    match (&binding.strategy, position) {
+        (InjectionStrategy::Fallback, Some(_)) => {}
+        (InjectionStrategy::Append, Some(_)) => pairs.extend(injected),
+        (_, position) => {
+            let position = position.unwrap_or(pairs.len());
+            pairs.retain(|(key, _)| key != name);
+            pairs.splice(position..position, injected);
+        }
+    }
+}
+
+fn inject_header(headers: &mut HeaderMap, binding: &VariableBinding, value: &str) {
+    let (Ok(name), Ok(value)) = (
+        HeaderName::from_str(&binding.target_param),
+        HeaderValue::from_str(value),
+    ) else {
+        return;
+    };
+
+    match binding.strategy {
+        InjectionStrategy::Strict => {
+            headers.insert(name, value);
+        }
+        InjectionStrategy::Fallback => {
+            if !headers.contains_key(&name) {
+                headers.insert(name, value);
+            }
+        }
+        InjectionStrategy::Append => {
+            let mut existing = headers.get_all(&name).iter();
+            let joined = match (existing.next(), existing.next()) {
+                (Some(existing), None) => join_header(&name, existing, &value),
+                _ => None,
+            };
+
+            // Headers already sent on several lines, or whose value is not
+            // valid text, get one more line
+            match joined {
+                Some(joined) => {
+                    headers.insert(name, joined);
+                }
+                None => {
+                    headers.append(name, value);
+                }
+            }
+        }
+    }
+}
+
+/// Joins two values of a header into one, as list headers are comma
+/// separated but cookies are separated by `; `
+fn join_header(
+    name: &HeaderName,
+    existing: &HeaderValue,
+    value: &HeaderValue,
+) -> Option<HeaderValue> {
+    let separator = if name == COOKIE { "; " } else { "," };
+    let (existing, value) = (existing.to_str().ok()?, value.to_str().ok()?);
+
+    HeaderValue::from_str(&format!("{existing}{separator}{value}")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection_variable_mapping::{BodyMerge, ConditionOperator, VariableDataType};
+    use serde_json::json;
+    use InjectionStrategy::*;
+    use ParameterLocation::*;
+
+    fn binding(
+        variable_name: &str,
+        target_param: &str,
+        location: ParameterLocation,
+        strategy: InjectionStrategy,
+    ) -> VariableBinding {
+        VariableBinding {
+            variable_name: variable_name.to_string(),
+            target_param: target_param.to_string(),
+            location,
+            constant: None,
+            strategy,
+            merge: BodyMerge::Shallow,
+            data_type: VariableDataType::String,
+            required: true,
+            condition: None,
+            is_secret: None,
+        }
+    }
+
+    fn parts() -> RequestParts {
+        RequestParts {
+            path: "/hotels/{hotelId}/rooms".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn query_param<'a>(parts: &'a RequestParts, key: &str) -> Option<&'a str> {
+        parts
+            .query_params
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn test_resolve_variable_precedence() {
+        let oauth = json!({
+            "OAUTH_REQUEST_PAYLOAD": { "formData": { "hotel_id": "oauth" } },
+            "auth_form_data": { "hotel_id": "form" },
+            "hotel_id": "top"
+        });
+        assert_eq!(resolve_variable(&oauth, "hotel_id"), Some(&json!("oauth")));
+
+        let form = json!({ "auth_form_data": { "hotel_id": "form" }, "hotel_id": "top" });
+        assert_eq!(resolve_variable(&form, "hotel_id"), Some(&json!("form")));
+
+        let top = json!({ "hotel_id": "top" });
+        assert_eq!(resolve_variable(&top, "hotel_id"), Some(&json!("top")));
+
+        // Nested sources do not fall back to top-level keys
+        let no_form_value = json!({ "auth_form_data": {}, "hotel_id": "top" });
+        assert_eq!(resolve_variable(&no_form_value, "hotel_id"), None);
+    }
+
+    #[test]
+    fn test_apply_path_param() {
+        let bindings = [VariableBinding {
+            is_secret: Some(true),
+            ..binding("hotel_id", "hotelId", PathParam, Fallback)
+        }];
+
+        let resolved = apply_bindings(parts(), &bindings, &json!({ "hotel_id": "h1" })).unwrap();
+
+        assert_eq!(resolved.parts.path, "/hotels/h1/rooms");
+        assert_eq!(resolved.secret_values, vec!["h1".to_string()]);
+    }
+
+    #[test]
+    fn test_only_secret_values_are_collected() {
+        let secret = json!({ "hotel_id": "h1", "token": "t1", "brand": "b1" });
+        let bindings = [
+            binding("hotel_id", "hotelId", PathParam, Strict),
+            binding("token", "Authorization", Header, Strict),
+            VariableBinding {
+                is_secret: Some(false),
+                ..binding("token", "x-api-key", Header, Strict)
+            },
+            VariableBinding {
+                is_secret: Some(true),
+                ..binding("brand", "brand", QueryParam, Strict)
+            },
+            VariableBinding {
+                variable_name: String::new(),
+                constant: Some(json!("k1")),
+                is_secret: Some(true),
+                ..binding("", "x-partner-key", Header, Strict)
+            },
+        ];
+
+        let resolved = apply_bindings(parts(), &bindings, &secret).unwrap();
+
+        assert_eq!(resolved.secret_values, vec!["t1", "b1", "k1"]);
+
+        let annotations = describe_bindings(&bindings);
+        let secrets: Vec<bool> = annotations.iter().map(|a| a.is_secret).collect();
+        assert_eq!(secrets, vec![false, true, false, true, true]);
+        assert_eq!(annotations[4].value, Some(json!(REDACTED)));
+    }
+
+    #[test]
+    fn test_apply_query_param_strategies() {
+        let secret = json!({ "a": "1", "b": "2", "c": "3", "d": "4" });
+        let mut parts = parts();
+        parts.query_params = ["strict", "fallback", "append", "strict"]
+            .into_iter()
+            .map(|key| (key.to_string(), "user".to_string()))
+            .collect();
+
+        let bindings = [
+            binding("a", "strict", QueryParam, Strict),
+            binding("b", "fallback", QueryParam, Fallback),
+            binding("c", "append", QueryParam, Append),
+            binding("d", "new", QueryParam, Append),
+        ];
+
+        let query_params = apply_bindings(parts, &bindings, &secret)
+            .unwrap()
+            .parts
+            .query_params;
+
+        // Strict collapses the repeated key into its value, in place, while
+        // Append repeats the key
+        let expected = [
+            ("strict", "1"),
+            ("fallback", "user"),
+            ("append", "user"),
+            ("append", "3"),
+            ("new", "4"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        assert_eq!(query_params, expected);
+    }
+
+    #[test]
+    fn test_apply_header_strategies() {
+        let secret = json!({ "a": "1", "b": "2", "c": "3", "bad": "line\nbreak" });
+        let mut parts = parts();
+        for name in ["x-strict", "x-fallback", "x-append"] {
+            parts.headers.insert(name, HeaderValue::from_static("user"));
+        }
+
+        let bindings = [
+            binding("a", "x-strict", Header, Strict),
+            binding("b", "x-fallback", Header, Fallback),
+            binding("c", "x-append", Header, Append),
+            binding("a", "not a header", Header, Strict),
+            binding("bad", "x-bad", Header, Strict),
+        ];
+
+        let headers = apply_bindings(parts, &bindings, &secret)
+            .unwrap()
+            .parts
+            .headers;
+
+        assert_eq!(headers["x-strict"], "1");
+        assert_eq!(headers["x-fallback"], "user");
+        assert_eq!(headers["x-append"], "user,3");
+        assert!(!headers.contains_key("x-bad"));
+        assert_eq!(headers.len(), 3);
+    }
+
+    #[test]
+    fn test_append_to_headers_by_their_semantics() {
+        let secret = json!({ "tag": "b", "cookie": "session=1", "bytes": "c" });
+        let mut parts = parts();
+        parts
+            .headers
+            .insert(COOKIE, HeaderValue::from_static("theme=dark"));
+        parts
+            .headers
+            .append("x-tag", HeaderValue::from_static("a1"));
+        parts
+            .headers
+            .append("x-tag", HeaderValue::from_static("a2"));
+        parts
+            .headers
+            .insert("x-bytes", HeaderValue::from_bytes(b"\xff").unwrap());
+
+        let bindings = [
+            binding("cookie", "cookie", Header, Append),
+            binding("tag", "x-tag", Header, Append),
+            binding("bytes", "x-bytes", Header, Append),
+            binding("tag", "x-new", Header, Append),
+        ];
+
+        let headers = apply_bindings(parts, &bindings, &secret)
+            .unwrap()
+            .parts
+            .headers;
+
+        assert_eq!(headers[COOKIE], "theme=dark; session=1");
+        let lines = |name: &str| -> Vec<&[u8]> {
+            headers
+                .get_all(name)
+                .iter()
+                .map(HeaderValue::as_bytes)
+                .collect()
+        };
+        assert_eq!(lines("x-tag"), vec![&b"a1"[..], b"a2", b"b"]);
+        assert_eq!(lines("x-bytes"), vec![&b"\xff"[..], b"c"]);
+        assert_eq!(lines("x-new"), vec![&b"b"[..]]);
+    }
+
+    #[test]
+    fn test_apply_body_field() {
+        let mut parts = parts();
+        parts.body = Some(br#"{"guest":{"name":"Ada"}}"#.to_vec());
+
+        let bindings = [VariableBinding {
+            data_type: VariableDataType::Number,
+            ..binding("guest_id", "guest.id", BodyField, Strict)
+        }];
+
+        let resolved = apply_bindings(parts, &bindings, &json!({ "guest_id": "42" })).unwrap();
+        let body: Value = serde_json::from_slice(&resolved.parts.body.unwrap()).unwrap();
+
+        assert_eq!(body, json!({ "guest": { "name": "Ada", "id": 42 } }));
+    }
+
+    #[test]
+    fn test_deep_merge_of_overlapping_nested_objects() {
+        let secret = json!({
+            "guest": { "loyalty": { "tier": "gold", "id": "L-1" }, "country": "PT" }
+        });
+        let body = br#"{"guest":{"name":"Ada","loyalty":{"tier":"silver","since":2019}}}"#;
+        let inject = |strategy, merge| {
+            let mut parts = parts();
+            parts.body = Some(body.to_vec());
+            let bindings = [VariableBinding {
+                data_type: VariableDataType::Json,
+                merge,
+                ..binding("guest", "guest", BodyField, strategy)
+            }];
+
+            let resolved = apply_bindings(parts, &bindings, &secret).unwrap();
+            serde_json::from_slice::<Value>(&resolved.parts.body.unwrap()).unwrap()
+        };
+
+        assert_eq!(
+            inject(Strict, BodyMerge::Deep),
+            json!({ "guest": {
+                "name": "Ada",
+                "loyalty": { "tier": "gold", "since": 2019, "id": "L-1" },
+                "country": "PT",
+            }})
+        );
+        assert_eq!(
+            inject(Fallback, BodyMerge::Deep),
+            json!({ "guest": {
+                "name": "Ada",
+                "loyalty": { "tier": "silver", "since": 2019, "id": "L-1" },
+                "country": "PT",
+            }})
+        );
+
+        assert_eq!(
+            inject(Strict, BodyMerge::Shallow),
+            json!({ "guest": secret["guest"] })
+        );
+        assert_eq!(
+            inject(Fallback, BodyMerge::Shallow),
+            serde_json::from_slice::<Value>(body).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deep_merge_replaces_values_that_are_not_objects() {
+        let mut parts = parts();
+        parts.body = Some(br#"{"guest":{"tags":["vip"],"loyalty":"none"}}"#.to_vec());
+
+        let bindings = [VariableBinding {
+            data_type: VariableDataType::Json,
+            merge: BodyMerge::Deep,
+            ..binding("guest", "guest", BodyField, Strict)
+        }];
+        let secret = json!({ "guest": { "tags": ["new"], "loyalty": { "tier": "gold" } } });
+
+        let resolved = apply_bindings(parts, &bindings, &secret).unwrap();
+        let body: Value = serde_json::from_slice(&resolved.parts.body.unwrap()).unwrap();
+
+        assert_eq!(
+            body,
+            json!({ "guest": { "tags": ["new"], "loyalty": { "tier": "gold" } } })
+        );
+    }
+
+    #[test]
+    fn test_apply_skips_body_fields_without_a_json_body() {
+        let bindings = [binding("guest_id", "guestId", BodyField, Strict)];
+        let secret = json!({ "guest_id": "42" });
+
+        let resolved = apply_bindings(parts(), &bindings, &secret).unwrap();
+        assert_eq!(resolved.parts.body, None);
+
+        let mut form = parts();
+        form.body = Some(b"guest=1".to_vec());
+        let resolved = apply_bindings(form, &bindings, &secret).unwrap();
+        assert_eq!(resolved.parts.body, Some(b"guest=1".to_vec()));
+    }
+
+    #[test]
+    fn test_apply_body_fields_of_form_bodies() {
+        let mut parts = parts();
+        parts.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded; charset=utf-8"),
+        );
+        parts.body = Some(b"strict=user&fallback=user&append=user&other=1".to_vec());
+
+        let secret = json!({ "a": "a b&c", "b": "2", "c": "3", "rooms": ["101", "102"] });
+        let bindings = [
+            binding("a", "strict", BodyField, Strict),
+            binding("b", "fallback", BodyField, Fallback),
+            binding("c", "append", BodyField, Append),
+            VariableBinding {
+                data_type: VariableDataType::Json,
+                ..binding("rooms", "room", BodyField, Strict)
+            },
+        ];
+
+        let resolved = apply_bindings(parts, &bindings, &secret).unwrap();
+        let body = resolved.parts.body.unwrap();
+
+        assert_eq!(
+            String::from_utf8(body.clone()).unwrap(),
+            "strict=a+b%26c&fallback=user&append=user&other=1&append=3&room=101&room=102"
+        );
+        assert_eq!(
+            BodyEncoding::Form.decode(&body),
+            Some(json!({
+                "strict": "a b&c",
+                "fallback": "user",
+                "append": ["user", "3"],
+                "other": "1",
+                "room": ["101", "102"],
+            }))
+        );
+    }
+
+    #[test]
+    fn test_form_bodies_follow_the_definition_content_type() {
+        let definition_headers = HeaderMap::from_iter([(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        )]);
+        let mut parts = parts().with_definition_content_type(Some(&definition_headers));
+        assert_eq!(BodyEncoding::of(&parts.headers), BodyEncoding::Form);
+
+        parts.body = Some(BodyEncoding::Form.encode(&json!({ "type": "pickup", "ids": [1, 2] })));
+        assert_eq!(parts.body.as_deref(), Some(&b"type=pickup&ids=1&ids=2"[..]));
+
+        let bindings = [VariableBinding {
+            condition: Some(BindingCondition {
+                param: "type".to_string(),
+                location: BodyField,
+                operator: ConditionOperator::Equals {
+                    value: "pickup".to_string(),
+                },
+            }),
+            ..binding("location_id", "location", BodyField, Strict)
+        }];
+
+        let resolved = apply_bindings(parts, &bindings, &json!({ "location_id": "l1" })).unwrap();
+
+        assert!(resolved.unmet_conditions.is_empty());
+        assert_eq!(
+            resolved.parts.body.as_deref(),
+            Some(&b"type=pickup&ids=1&ids=2&location=l1"[..])
+        );
+    }
+
+    #[test]
+    fn test_apply_skips_missing_variables() {
+        let bindings = [binding("hotel_id", "hotelId", PathParam, Strict)];
+
+        let resolved = apply_bindings(parts(), &bindings, &json!({})).unwrap();
+
+        assert_eq!(resolved.parts, parts());
+        assert!(resolved.secret_values.is_empty());
+    }
+
+    #[test]
+    fn test_missing_variables_only_reports_required_bindings() {
+        let bindings = [
+            binding("hotel_id", "hotelId", PathParam, Strict),
+            binding("chain_id", "chainId", QueryParam, Strict),
+            VariableBinding {
+                required: false,
+                ..binding("brand_id", "brandId", QueryParam, Fallback)
+            },
+        ];
+
+        let missing = missing_variables(&bindings, &json!({ "hotel_id": "h1" }));
+        let names: Vec<&str> = missing.iter().map(|b| b.variable_name.as_str()).collect();
+
+        assert_eq!(names, vec!["chain_id"]);
+
+        let form = json!({ "auth_form_data": { "chain_id": "c1" }, "hotel_id": "h1" });
+        let names: Vec<&str> = missing_variables(&bindings, &form)
+            .iter()
+            .map(|b| b.variable_name.as_str())
+            .collect();
+
+        assert_eq!(names, vec!["hotel_id"]);
+    }
+
+    #[test]
+    fn test_apply_injects_constants_without_a_secret() {
+        let bindings = [VariableBinding {
+            variable_name: String::new(),
+            constant: Some(json!("2023-10")),
+            ..binding("", "apiVersion", QueryParam, Strict)
+        }];
+
+        let resolved = apply_bindings(parts(), &bindings, &json!({})).unwrap();
+
+        assert_eq!(query_param(&resolved.parts, "apiVersion"), Some("2023-10"));
+        assert!(resolved.secret_values.is_empty());
+        assert!(missing_variables(&bindings, &json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_apply_fails_on_values_of_the_wrong_type() {
+        let bindings = [VariableBinding {
+            data_type: VariableDataType::Number,
+            ..binding("hotel_id", "hotelId", QueryParam, Strict)
+        }];
+
+        assert!(apply_bindings(parts(), &bindings, &json!({ "hotel_id": "abc" })).is_err());
+    }
+
+    #[test]
+    fn test_apply_fails_on_invalid_body_paths() {
+        let mut parts = parts();
+        parts.body = Some(br#"{"guest":"Ada"}"#.to_vec());
+
+        let bindings = [binding("guest_id", "guest.id", BodyField, Strict)];
+
+        assert!(apply_bindings(parts, &bindings, &json!({ "guest_id": "42" })).is_err());
+    }
+
+    fn conditional(
+        target_param: &str,
+        param: &str,
+        location: ParameterLocation,
+        operator: ConditionOperator,
+    ) -> VariableBinding {
+        VariableBinding {
+            condition: Some(BindingCondition {
+                param: param.to_string(),
+                location,
+                operator,
+            }),
+            ..binding("location_id", target_param, QueryParam, Strict)
+        }
+    }
+
+    #[test]
+    fn test_conditions_on_query_params_and_headers() {
+        let secret = json!({ "location_id": "loc-1" });
+        let mut parts = parts();
+        parts
+            .query_params
+            .push(("type".to_string(), "pickup".to_string()));
+        parts
+            .headers
+            .insert("x-channel", HeaderValue::from_static("web"));
+
+        let equals = |value: &str| ConditionOperator::Equals {
+            value: value.to_string(),
+        };
+        let bindings = [
+            conditional("a", "type", QueryParam, equals("pickup")),
+            conditional("b", "type", QueryParam, equals("delivery")),
+            conditional(
+                "c",
+                "x-channel",
+                Header,
+                ConditionOperator::In {
+                    values: vec!["app".to_string(), "web".to_string()],
+                },
+            ),
+            conditional("d", "x-missing", Header, ConditionOperator::Exists),
+            conditional("e", "id", PathParam, ConditionOperator::Exists),
+        ];
+
+        let resolved = apply_bindings(parts, &bindings, &secret).unwrap();
+
+        assert_eq!(query_param(&resolved.parts, "a"), Some("loc-1"));
+        assert_eq!(query_param(&resolved.parts, "c"), Some("loc-1"));
+        for skipped in ["b", "d", "e"] {
+            assert_eq!(query_param(&resolved.parts, skipped), None);
+        }
+
+        let unmet = &resolved.unmet_conditions;
+        assert_eq!(
+            unmet
+                .iter()
+                .map(|u| u.target_param.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b", "d", "e"]
+        );
+        assert_eq!(unmet[0].actual.as_deref(), Some("pickup"));
+        assert_eq!(unmet[1].actual, None);
+    }
+
+    #[test]
+    fn test_conditions_on_body_fields() {
+        let secret = json!({ "location_id": "loc-1" });
+        let mut parts = parts();
+        parts.body = Some(br#"{"order":{"type":"pickup","items":[{"qty":2}]}}"#.to_vec());
+
+        let bindings = [
+            conditional(
+                "a",
+                "order.type",
+                BodyField,
+                ConditionOperator::Equals {
+                    value: "pickup".to_string(),
+                },
+            ),
+            conditional(
+                "b",
+                "order.items.0.qty",
+                BodyField,
+                ConditionOperator::In {
+                    values: vec!["1".to_string(), "2".to_string()],
+                },
+            ),
+            conditional("c", "order.items.1", BodyField, ConditionOperator::Exists),
+        ];
+
+        let resolved = apply_bindings(parts, &bindings, &secret).unwrap();
+
+        assert!(query_param(&resolved.parts, "a").is_some());
+        assert!(query_param(&resolved.parts, "b").is_some());
+        assert!(query_param(&resolved.parts, "c").is_none());
+        assert_eq!(resolved.unmet_conditions.len(), 1);
+    }
+
+    #[test]
+    fn test_conditions_see_earlier_injections() {
+        let secret = json!({ "location_id": "loc-1" });
+        let bindings = [
+            binding("location_id", "type", QueryParam, Strict),
+            conditional("a", "type", QueryParam, ConditionOperator::Exists),
+        ];
+
+        let resolved = apply_bindings(parts(), &bindings, &secret).unwrap();
+
+        assert!(query_param(&resolved.parts, "a").is_some());
+        assert!(resolved.unmet_conditions.is_empty());
+    }
+
+    #[test]
+    fn test_condition_serialization() {
+        let condition: BindingCondition = serde_json::from_value(json!({
+            "param": "type",
+            "location": "QueryParam",
+            "operator": "in",
+            "values": ["pickup", "curbside"]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            condition.operator,
+            ConditionOperator::In {
+                values: vec!["pickup".to_string(), "curbside".to_string()]
+            }
+        );
+
+        let exists = json!({ "param": "x-id", "location": "Header", "operator": "exists" });
+        let condition: BindingCondition = serde_json::from_value(exists.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&condition).unwrap(), exists);
+    }
+
+    #[test]
+    fn test_describe_bindings() {
+        let bindings = [
+            binding("a", "hotelId", PathParam, Strict),
+            binding("b", "currency", QueryParam, Fallback),
+            binding("c", "tags", BodyField, Append),
+            VariableBinding {
+                condition: Some(BindingCondition {
+                    param: "type".to_string(),
+                    location: QueryParam,
+                    operator: ConditionOperator::In {
+                        values: vec!["pickup".to_string(), "drop".to_string()],
+                    },
+                }),
+                ..binding("d", "locationId", QueryParam, Strict)
+            },
+        ];
+
+        let annotations = describe_bindings(&bindings);
+
+        assert_eq!(
+            annotations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "'hotelId' (auto-filled, do NOT ask user)",
+                "'currency' (has default, only ask if user wants to override)",
+                "'tags' (partially pre-filled, user may add more)",
+                "'locationId' (when QueryParam 'type' is one of 'pickup', 'drop': auto-filled, \
+                 do NOT ask user; otherwise not filled)",
+            ]
+        );
+        assert_eq!(annotations[1].location, QueryParam);
+        assert_eq!(annotations[2].strategy, Append);
+    }
+
+    #[test]
+    fn test_resolve_base_url() {
+        let secret = json!({ "instance": "acme", "region": "eu", "port": 8443 });
+
+        assert_eq!(
+            resolve_base_url("https://{instance}.{region}.salesforce.com", &secret).unwrap(),
+            "https://acme.eu.salesforce.com"
+        );
+        assert_eq!(
+            resolve_base_url("https://api.example.com:{port}/v1", &secret).unwrap(),
+            "https://api.example.com:8443/v1"
+        );
+        assert_eq!(
+            resolve_base_url("https://api.example.com/{ not a variable }/{", &secret).unwrap(),
+            "https://api.example.com/{ not a variable }/{"
+        );
+        assert_eq!(
+            resolve_base_url(r"https://api.example.com/\{tenant}/{instance}", &secret).unwrap(),
+            "https://api.example.com/{tenant}/acme"
+        );
+    }
+
+    #[test]
+    fn test_fill_path_placeholders() {
+        let values = |name: &str| (name == "hotelId").then(|| "h1".to_string());
+
+        assert_eq!(
+            fill_path_placeholders("/hotels/{hotelId}/rooms/{{ hotelId }}", values),
+            ("/hotels/h1/rooms/{{ hotelId }}".to_string(), vec![])
+        );
+        assert_eq!(
+            fill_path_placeholders("/rooms/{roomId}/{{rateId}}/{roomId}", values),
+            (
+                "/rooms/{roomId}/{{rateId}}/{roomId}".to_string(),
+                vec!["roomId".to_string(), "rateId".to_string()]
+            )
+        );
+        assert_eq!(
+            fill_path_placeholders("/rooms/{roomId}", |_| Some("../a b/c?d#e".to_string())),
+            ("/rooms/..%2Fa%20b%2Fc%3Fd%23e".to_string(), vec![])
+        );
+        assert_eq!(
+            fill_path_placeholders("/{{#if a}}x{{/if}}/{ not a variable }/{", values),
+            (
+                "/{{#if a}}x{{/if}}/{ not a variable }/{".to_string(),
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn test_resolve_base_url_rejects_unusable_variables() {
+        let secret = json!({ "instance": "acme.com/evil?", "empty": "", "nothing": null });
+
+        for base_url in [
+            "https://{missing}.salesforce.com",
+            "https://{instance}.salesforce.com",
+            "https://{empty}.salesforce.com",
+            "https://{nothing}.salesforce.com",
+        ] {
+            let err = resolve_base_url(base_url, &secret).unwrap_err();
+            assert_eq!(err.status(), 422, "{base_url}");
+        }
+    }
+
+    #[test]
+    fn test_rewritten_bodies_are_labelled_as_json() {
+        let bindings = [binding("guest_id", "guestId", BodyField, Strict)];
+        let secret = json!({ "guest_id": "42" });
+
+        let mut plain = parts();
+        plain
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        plain.body = Some(br#"{"guest":1}"#.to_vec());
+
+        let resolved = apply_bindings(plain, &bindings, &secret).unwrap();
+        assert_eq!(resolved.parts.headers[CONTENT_TYPE], "application/json");
+        assert_eq!(
+            resolved.parts.body.as_deref(),
+            Some(&br#"{"guest":1,"guestId":"42"}"#[..])
+        );
+
+        let mut vendor = parts();
+        vendor.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/vnd.api+json; charset=utf-8"),
+        );
+        vendor.body = Some(b"{}".to_vec());
+
+        let resolved = apply_bindings(vendor, &bindings, &secret).unwrap();
+        assert_eq!(
+            resolved.parts.headers[CONTENT_TYPE],
+            "application/vnd.api+json; charset=utf-8"
+        );
+    }
+
+    #[test]
+    fn test_with_content_type_re_encodes_the_body() {
+        let mut json_parts = parts();
+        json_parts.body = Some(br#"{"type":"pickup","ids":[1,2]}"#.to_vec());
+
+        let form = json_parts.with_content_type(HeaderValue::from_static(
+            "application/x-www-form-urlencoded",
+        ));
+        assert_eq!(form.body.as_deref(), Some(&b"type=pickup&ids=1&ids=2"[..]));
+
+        let json_parts = form.with_content_type(HeaderValue::from_static("application/json"));
+        assert_eq!(
+            json_parts.body.as_deref(),
+            Some(&br#"{"type":"pickup","ids":["1","2"]}"#[..])
+        );
+    }
+}
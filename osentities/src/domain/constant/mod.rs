@@ -5,6 +5,10 @@ pub const TOTAL_KEY: &str = "total";
 pub const DAILY_KEY: &str = "daily";
 pub const MONTHLY_KEY: &str = "monthly";
 pub const PLATFORMS_KEY: &str = "platforms";
+pub const STATUSES_KEY: &str = "statuses";
+pub const LATENCIES_KEY: &str = "latencies";
+pub const RETRIES_KEY: &str = "retries";
+pub const CIRCUIT_OPEN_KEY: &str = "circuitOpen";
 pub const CREATED_AT_KEY: &str = "createdAt";
 
 // Mongo filter constants
@@ -34,9 +38,22 @@ pub const APP_LABEL: &str = "app";
 pub const DATABASE_TYPE_LABEL: &str = "database-type";
 pub const JWT_SECRET_REF_KEY: &str = "jwt-secret";
 pub const JWT_SECRET_REF_NAME: &str = "event-secrets";
+pub const REDACTED: &str = "***";
 
 // Header constants
 pub const PICA_PASSTHROUGH_HEADER: &str = "x-pica-passthrough";
+pub const PICA_EXTRACT_HEADER: &str = "x-pica-extract";
+pub const PICA_EXTRACT_WARNING_HEADER: &str = "x-pica-extract-warning";
+pub const PICA_WEBHOOK_ID_HEADER: &str = "x-pica-webhook-id";
+pub const PICA_WEBHOOK_EVENT_HEADER: &str = "x-pica-webhook-event";
+pub const PICA_WEBHOOK_TIMESTAMP_HEADER: &str = "x-pica-webhook-timestamp";
+pub const PICA_WEBHOOK_SIGNATURE_HEADER: &str = "x-pica-webhook-signature";
+pub const PICA_SIGNATURE_HEADER: &str = "pica-signature";
+pub const PICA_TIMESTAMP_HEADER: &str = "pica-timestamp";
+pub const PICA_NONCE_HEADER: &str = "pica-nonce";
+pub const PICA_QUOTA_REMAINING_HEADER: &str = "pica-quota-remaining";
+pub const PICA_RETRY_BUDGET_HEADER: &str = "pica-retry-budget";
+pub const PICA_VCR_HEADER: &str = "x-pica-vcr";
 
 // Encryption constants
 pub const HASH_LENGTH: usize = 32;
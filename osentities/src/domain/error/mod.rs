@@ -10,9 +10,10 @@ use std::convert::AsRef;
 use std::{
     error::Error as StdError,
     fmt::{Debug, Display, Formatter, Result as FmtResult},
+    str::FromStr,
     sync::Arc,
 };
-use strum::AsRefStr;
+use strum::{AsRefStr, EnumString, IntoStaticStr};
 use thiserror::Error as ThisError;
 
 pub trait ErrorMeta {
@@ -64,6 +65,94 @@ impl Display for ErrorKey {
     }
 }
 
+/// Stable, machine-readable reasons surfaced as `errorCode` in error
+/// responses, so clients can branch on them instead of matching messages.
+/// The numeric `code` is kept for backwards compatibility.
+///
+/// | `errorCode`                 | Status | Meaning                                             |
+/// |-----------------------------|--------|-----------------------------------------------------|
+/// | `connection_header_missing` | 400    | The connection key header was not sent              |
+/// | `connection_header_empty`   | 400    | The connection key header was sent without a key    |
+/// | `auth_header_missing`       | 400    | The secret key header was not sent                  |
+/// | `invalid_connection_key`    | 400    | The connection key header is not valid text         |
+/// | `connection_not_found`      | 404    | No connection matches the key for this account      |
+/// | `signature_invalid`         | 401    | The request signature is missing or does not match  |
+/// | `upstream_timeout`          | 504    | The platform did not respond in time                |
+/// | `upstream_unreachable`      | 502    | The platform could not be reached                   |
+/// | `token_missing`             | 401    | No bearer token was sent                            |
+/// | `token_malformed`           | 401    | The bearer token could not be parsed                |
+/// | `token_expired`             | 401    | The bearer token has expired                        |
+/// | `token_invalid`             | 403    | The bearer token failed validation                  |
+/// | `quota_exceeded`            | 429    | The connection used up its passthrough quota        |
+/// | `platform_disabled`         | 503    | Passthrough is switched off for the platform        |
+/// | `upstream_rate_limited`     | 429    | The platform rate limit is used up until it resets  |
+/// | `secret_not_object`         | 422    | The connection secret is not a JSON object          |
+/// | `signature_expired`         | 401    | The signed timestamp is outside the allowed skew    |
+/// | `nonce_reused`              | 401    | The signed nonce was already used, a replay         |
+/// | `secret_not_found`          | 404    | The connection secret does not exist                |
+/// | `secret_decryption_failed`  | 500    | The connection secret could not be decrypted        |
+/// | `secrets_unavailable`       | 502    | The secrets store or KMS could not be reached       |
+/// | `retry_budget_exhausted`    | 504    | Retries of the request used up its retry budget     |
+/// | `body_too_complex`          | 400    | The JSON body is nested too deep or too wide        |
+/// | `mappings_unavailable`      | 503    | Variable mappings could not be loaded               |
+/// | `transform_failed`          | 422    | The request transform of the definition failed      |
+///
+/// Codes are passed as the error `subtype`, so they also appear at the end
+/// of the error `key`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    AsRefStr,
+    EnumString,
+    IntoStaticStr,
+    Serialize,
+)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PicaErrorCode {
+    ConnectionHeaderMissing,
+    ConnectionHeaderEmpty,
+    AuthHeaderMissing,
+    InvalidConnectionKey,
+    ConnectionNotFound,
+    SignatureInvalid,
+    UpstreamTimeout,
+    UpstreamUnreachable,
+    TokenMissing,
+    TokenMalformed,
+    TokenExpired,
+    TokenInvalid,
+    QuotaExceeded,
+    PlatformDisabled,
+    UpstreamRateLimited,
+    SecretNotObject,
+    SignatureExpired,
+    NonceReused,
+    SecretNotFound,
+    SecretDecryptionFailed,
+    SecretsUnavailable,
+    RetryBudgetExhausted,
+    BodyTooComplex,
+    MappingsUnavailable,
+    TransformFailed,
+}
+
+impl PicaErrorCode {
+    pub fn subtype(self) -> Option<&'static str> {
+        Some(self.into())
+    }
+}
+
+fn is_error_code(subtype: &str) -> bool {
+    PicaErrorCode::from_str(subtype).is_ok()
+}
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize)]
 pub struct ErrorMessage(String);
 
@@ -889,6 +978,20 @@ impl Debug for ApplicationError {
 impl From<InternalError> for ApplicationError {
     fn from(error: InternalError) -> Self {
         match error {
+            // Upstream and secrets failures tagged with a stable code keep it,
+            // so clients can tell a slow platform apart from an unreachable one
+            // and a corrupt secret apart from a secrets outage
+            InternalError::Timeout { subtype, .. }
+            | InternalError::ConnectionError { subtype, .. }
+            | InternalError::DecryptionError { subtype, .. }
+                if subtype.as_deref().is_some_and(is_error_code) =>
+            {
+                ApplicationError::InternalServerError {
+                    message: "An unknown error occurred".into(),
+                    subtype,
+                    meta: None,
+                }
+            }
             InternalError::Timeout { .. }
             | InternalError::ConnectionError { .. }
             | InternalError::IOErr { .. }
@@ -951,7 +1054,16 @@ pub enum PicaError {
 
 impl From<reqwest::Error> for PicaError {
     fn from(err: reqwest::Error) -> Self {
-        InternalError::io_err(&err.to_string(), None)
+        if err.is_timeout() {
+            InternalError::timeout(&err.to_string(), PicaErrorCode::UpstreamTimeout.subtype())
+        } else if err.is_connect() {
+            InternalError::connection_error(
+                &err.to_string(),
+                PicaErrorCode::UpstreamUnreachable.subtype(),
+            )
+        } else {
+            InternalError::io_err(&err.to_string(), None)
+        }
     }
 }
 
@@ -1278,10 +1390,19 @@ impl PicaError {
         }
     }
 
+    /// The stable code of this error, if it was raised with one
+    pub fn error_code(&self) -> Option<PicaErrorCode> {
+        let key = self.key().to_string();
+        let (_, subtype) = key.rsplit_once("::")?;
+
+        PicaErrorCode::from_str(subtype).ok()
+    }
+
     pub(crate) fn as_json(&self) -> serde_json::Value {
         json!({
             "type": self.as_ref(),
             "code": self.code().as_u16(),
+            "errorCode": self.error_code(),
             "status": StatusCode::from(self).as_u16(),
             "key": self.key().to_string(),
             "message": self.message().to_string(),
@@ -1426,4 +1547,79 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn error_code_is_exposed_in_json_body() {
+        let err = ApplicationError::bad_request(
+            "Connection header not found",
+            PicaErrorCode::ConnectionHeaderMissing.subtype(),
+        );
+        let body = err.as_application().as_json();
+
+        assert_eq!(body["errorCode"], "connection_header_missing");
+        assert_eq!(body["code"], 2000);
+        assert_eq!(body["message"], "Connection header not found");
+        assert_eq!(
+            body["key"],
+            "err::application::bad_request::connection_header_missing"
+        );
+    }
+
+    #[test]
+    fn free_form_subtypes_have_no_error_code() {
+        let err = ApplicationError::bad_request("test", Some("invalid_pagination"));
+        assert_eq!(err.error_code(), None);
+        assert_eq!(err.as_json()["errorCode"], Value::Null);
+
+        let err = ApplicationError::not_found("test", None);
+        assert_eq!(err.error_code(), None);
+    }
+
+    #[test]
+    fn upstream_error_codes_survive_conversion_to_application_errors() {
+        let err = InternalError::timeout("test", PicaErrorCode::UpstreamTimeout.subtype());
+        let body = err.as_application().as_json();
+
+        assert_eq!(err.status(), 504);
+        assert_eq!(body["errorCode"], "upstream_timeout");
+        assert_eq!(body["message"], "An unknown error occurred");
+
+        let err = InternalError::timeout("test", Some("mongo"));
+        assert_eq!(err.as_application().error_code(), None);
+    }
+
+    #[test]
+    fn secrets_error_codes_keep_distinct_statuses() {
+        let cases = [
+            (
+                InternalError::key_not_found("Secret", PicaErrorCode::SecretNotFound.subtype()),
+                404,
+                "secret_not_found",
+            ),
+            (
+                InternalError::decryption_error(
+                    "test",
+                    PicaErrorCode::SecretDecryptionFailed.subtype(),
+                ),
+                500,
+                "secret_decryption_failed",
+            ),
+            (
+                InternalError::connection_error(
+                    "test",
+                    PicaErrorCode::SecretsUnavailable.subtype(),
+                ),
+                502,
+                "secrets_unavailable",
+            ),
+        ];
+
+        for (err, status, code) in cases {
+            assert_eq!(err.status(), status);
+            assert_eq!(err.as_application().as_json()["errorCode"], code);
+        }
+
+        let err = InternalError::decryption_error("test", None);
+        assert_eq!(err.as_application().error_code(), None);
+    }
 }
@@ -0,0 +1,67 @@
+use super::Event;
+use crate::{id::Id, prelude::shared::record_metadata::RecordMetadata};
+use serde::{Deserialize, Serialize};
+
+/// Cap on the exponent of the redelivery backoff, about a day at a one
+/// minute base delay
+const MAX_BACKOFF_EXPONENT: u32 = 10;
+
+/// An event that could not be handed to the event store, kept with the reason
+/// until it is redelivered. Keyed by the event's id, so an event is never
+/// dead-lettered twice.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetterEvent {
+    #[serde(rename = "_id")]
+    pub id: Id,
+    pub event: Event,
+    pub reason: String,
+    /// Failed redeliveries so far
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Epoch millis before which the event is not redelivered
+    pub next_attempt_at: i64,
+    #[serde(flatten, default)]
+    pub record_metadata: RecordMetadata,
+}
+
+impl DeadLetterEvent {
+    pub fn new(event: Event, reason: &str, now: i64) -> Self {
+        Self {
+            id: event.id,
+            event,
+            reason: reason.to_string(),
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: now,
+            record_metadata: RecordMetadata::default(),
+        }
+    }
+
+    /// When to try again after one more failed redelivery
+    pub fn next_attempt_after_failure(&self, base_delay_millis: u64, now: i64) -> i64 {
+        let delay = redelivery_delay_millis(self.attempts, base_delay_millis);
+
+        now.saturating_add(i64::try_from(delay).unwrap_or(i64::MAX))
+    }
+}
+
+/// Delay before the redelivery following `attempts` failed ones,
+/// `base_delay_millis` doubling with every attempt
+pub fn redelivery_delay_millis(attempts: u32, base_delay_millis: u64) -> u64 {
+    base_delay_millis.saturating_mul(1 << attempts.min(MAX_BACKOFF_EXPONENT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redelivery_backoff_doubles_up_to_a_cap() {
+        assert_eq!(redelivery_delay_millis(0, 1_000), 1_000);
+        assert_eq!(redelivery_delay_millis(3, 1_000), 8_000);
+        assert_eq!(redelivery_delay_millis(40, 1_000), 1_024_000);
+        assert_eq!(redelivery_delay_millis(40, u64::MAX), u64::MAX);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod dead_letter;
 pub mod emitted_events;
 pub mod event_access;
 pub mod event_state;
@@ -40,6 +40,10 @@ impl Id {
         Self { prefix, time, uuid }
     }
 
+    pub fn prefix(&self) -> IdPrefix {
+        self.prefix
+    }
+
     pub fn test(prefix: IdPrefix) -> Self {
         Self {
             prefix,
@@ -36,6 +36,11 @@ pub enum IdPrefix {
     EarlyAccess,
     Task,
     ConnectionVariableMapping,
+    ConnectionWebhook,
+    StagedBundle,
+    ConnectionModelDefinitionAudit,
+    PassthroughRecording,
+    KnowledgeOverride,
 }
 
 impl Display for IdPrefix {
@@ -73,6 +78,11 @@ impl Display for IdPrefix {
             IdPrefix::EarlyAccess => write!(f, "ea"),
             IdPrefix::Task => write!(f, "task"),
             IdPrefix::ConnectionVariableMapping => write!(f, "conn_var_map"),
+            IdPrefix::ConnectionWebhook => write!(f, "conn_wh"),
+            IdPrefix::StagedBundle => write!(f, "stg_bndl"),
+            IdPrefix::ConnectionModelDefinitionAudit => write!(f, "conn_mod_def_audit"),
+            IdPrefix::PassthroughRecording => write!(f, "pt_rec"),
+            IdPrefix::KnowledgeOverride => write!(f, "knw_ovr"),
         }
     }
 }
@@ -114,6 +124,11 @@ impl TryFrom<&str> for IdPrefix {
             "ea" => Ok(IdPrefix::EarlyAccess),
             "task" => Ok(IdPrefix::Task),
             "conn_var_map" => Ok(IdPrefix::ConnectionVariableMapping),
+            "conn_wh" => Ok(IdPrefix::ConnectionWebhook),
+            "stg_bndl" => Ok(IdPrefix::StagedBundle),
+            "conn_mod_def_audit" => Ok(IdPrefix::ConnectionModelDefinitionAudit),
+            "pt_rec" => Ok(IdPrefix::PassthroughRecording),
+            "knw_ovr" => Ok(IdPrefix::KnowledgeOverride),
             _ => Err(InternalError::invalid_argument(
                 &format!("Invalid ID prefix: {}", s),
                 None,
@@ -157,6 +172,11 @@ impl From<IdPrefix> for String {
             IdPrefix::EarlyAccess => "ea".to_string(),
             IdPrefix::Task => "task".to_string(),
             IdPrefix::ConnectionVariableMapping => "conn_var_map".to_string(),
+            IdPrefix::ConnectionWebhook => "conn_wh".to_string(),
+            IdPrefix::StagedBundle => "stg_bndl".to_string(),
+            IdPrefix::ConnectionModelDefinitionAudit => "conn_mod_def_audit".to_string(),
+            IdPrefix::PassthroughRecording => "pt_rec".to_string(),
+            IdPrefix::KnowledgeOverride => "knw_ovr".to_string(),
         }
     }
 }
@@ -0,0 +1,34 @@
+use crate::connection_model_definition_audit::AuditActor;
+use serde::{Deserialize, Serialize};
+
+/// Operator switches for a platform, looked up by platform name. Platforms
+/// without a stored flag behave as if every switch is off.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformFlag {
+    pub platform: String,
+    #[serde(default)]
+    pub passthrough_disabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Overrides the default `Retry-After` sent while passthrough is disabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<AuditActor>,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+impl PlatformFlag {
+    pub fn enabled(platform: &str) -> Self {
+        Self {
+            platform: platform.to_string(),
+            passthrough_disabled: false,
+            reason: None,
+            retry_after_secs: None,
+            updated_by: None,
+            updated_at: 0,
+        }
+    }
+}
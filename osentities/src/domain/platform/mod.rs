@@ -1,3 +1,4 @@
+pub mod flag;
 pub mod page;
 pub mod r#type;
 
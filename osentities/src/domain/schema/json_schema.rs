@@ -7,13 +7,14 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 pub struct JsonSchema {
     #[serde(rename = "type")]
     pub type_name: String,
-    #[serde(default = "HashMap::new")]
+    #[serde(default)]
     pub properties: HashMap<String, Property>,
+    #[cfg_attr(feature = "dummy", dummy(default))]
     pub required: Option<Vec<String>>,
     pub path: Option<String>,
 
@@ -197,6 +198,24 @@ impl JsonSchema {
         schemas
     }
 
+    /// Checks `value` against the schema, returning one message per mismatch.
+    /// Types other than the JSON Schema ones are not checked.
+    pub fn validate(&self, value: &Value) -> Vec<String> {
+        let mut errors = vec![];
+        validate_value(
+            "$",
+            &self.type_name,
+            Some(&self.properties),
+            self.required.as_deref().unwrap_or_default(),
+            self.items.as_deref(),
+            None,
+            value,
+            &mut errors,
+        );
+
+        errors
+    }
+
     pub fn insert(&mut self, name: String, r#type: String, path: String) {
         self.properties.insert(
             name,
@@ -212,6 +231,83 @@ impl JsonSchema {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
+fn validate_value(
+    path: &str,
+    type_name: &str,
+    properties: Option<&HashMap<String, Property>>,
+    required: &[String],
+    items: Option<&Property>,
+    variants: Option<&[String]>,
+    value: &Value,
+    errors: &mut Vec<String>,
+) {
+    let matches = match type_name.to_ascii_lowercase().as_str() {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "null" => value.is_null(),
+        _ => return,
+    };
+
+    if !matches {
+        errors.push(format!("{path} should be of type {type_name}"));
+        return;
+    }
+
+    match value {
+        Value::String(text) => {
+            if let Some(variants) = variants.filter(|v| !v.is_empty()) {
+                if !variants.contains(text) {
+                    errors.push(format!("{path} should be one of {}", variants.join(", ")));
+                }
+            }
+        }
+        Value::Object(object) => {
+            for name in required {
+                if !object.contains_key(name) {
+                    errors.push(format!("{path}.{name} is required"));
+                }
+            }
+
+            for (name, item) in object {
+                if let Some(property) = properties.and_then(|p| p.get(name)) {
+                    validate_value(
+                        &format!("{path}.{name}"),
+                        &property.r#type,
+                        property.properties.as_ref(),
+                        &[],
+                        property.items.as_deref(),
+                        property.r#enum.as_deref(),
+                        item,
+                        errors,
+                    );
+                }
+            }
+        }
+        Value::Array(elements) => {
+            if let Some(items) = items {
+                for (index, element) in elements.iter().enumerate() {
+                    validate_value(
+                        &format!("{path}[{index}]"),
+                        &items.r#type,
+                        items.properties.as_ref(),
+                        &[],
+                        items.items.as_deref(),
+                        items.r#enum.as_deref(),
+                        element,
+                        errors,
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
 impl TryFrom<CommonModel> for JsonSchema {
     type Error = PicaError;
 
@@ -231,7 +327,7 @@ impl TryFrom<CommonModel> for JsonSchema {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, schemars::JsonSchema)]
 #[cfg_attr(feature = "dummy", derive(fake::Dummy))]
 pub struct Property {
     #[serde(rename = "type")]
@@ -761,4 +857,41 @@ mod tests {
 
         assert!(serde_json::to_value(&schema).is_ok());
     }
+
+    #[test]
+    fn test_validate_reports_mismatches() {
+        let schema: JsonSchema = serde_json::from_value(json!({
+            "type": "object",
+            "required": ["id", "status"],
+            "properties": {
+                "id": { "type": "integer" },
+                "status": { "type": "string", "enum": ["open", "closed"] },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "extra": { "type": "decimal" }
+            }
+        }))
+        .unwrap();
+
+        assert!(schema
+            .validate(&json!({ "id": 1, "status": "open", "tags": ["a"], "extra": "1.0" }))
+            .is_empty());
+
+        assert_eq!(
+            schema.validate(&json!({ "id": "1", "status": "pending", "tags": ["a", 2] })),
+            vec![
+                "$.id should be of type integer".to_string(),
+                "$.status should be one of open, closed".to_string(),
+                "$.tags[1] should be of type string".to_string(),
+            ]
+        );
+
+        assert_eq!(
+            schema.validate(&json!({ "id": 1 })),
+            vec!["$.status is required".to_string()]
+        );
+        assert_eq!(
+            schema.validate(&json!([])),
+            vec!["$ should be of type object".to_string()]
+        );
+    }
 }
@@ -2,7 +2,7 @@ pub mod database_secret;
 pub mod hashed_secret;
 pub mod oauth_secret;
 
-use crate::{InternalError, PicaError};
+use crate::{ApplicationError, InternalError, PicaError, PicaErrorCode};
 use chrono::Utc;
 use secrecy::SecretString;
 use serde::{Deserialize, Serialize};
@@ -82,6 +82,22 @@ impl Secret {
             .map_err(|e| InternalError::deserialize_error(&e.to_string(), None))
     }
 
+    /// The secret as a JSON object, the only shape path params, headers and
+    /// variables can be injected from. `connection` names the connection in
+    /// the error raised for any other shape, e.g. an empty secret.
+    pub fn as_object(&self, connection: &str) -> Result<Value, PicaError> {
+        match self.as_value()? {
+            value @ Value::Object(_) => Ok(value),
+            value => Err(ApplicationError::unprocessable_entity(
+                &format!(
+                    "The secret of connection {connection} is {}, expected a JSON object",
+                    kind(&value)
+                ),
+                PicaErrorCode::SecretNotObject.subtype(),
+            )),
+        }
+    }
+
     pub fn version(&self) -> Option<SecretVersion> {
         self.version
     }
@@ -97,6 +113,98 @@ impl Secret {
     pub fn encrypted_secret(&self) -> SecretString {
         SecretString::from(self.encrypted_secret.clone())
     }
+
+    /// The same secret holding `value` instead, e.g. once merged with the
+    /// additional secrets of its connection
+    pub fn with_value(&self, value: &Value) -> Self {
+        Self {
+            encrypted_secret: value.to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Deep merges the decrypted secrets of a connection, given as `(secret id,
+/// object)` from the highest precedence to the lowest. Objects are merged
+/// member by member; any other value set by two secrets is taken from the one
+/// that comes first, and the conflict is logged by path and secret id only.
+pub fn merge_secrets(
+    connection: &str,
+    secrets: impl IntoIterator<Item = (String, Value)>,
+) -> Value {
+    let mut secrets = secrets.into_iter();
+    let Some((_, mut merged)) = secrets.next() else {
+        return Value::Object(Default::default());
+    };
+
+    for (id, secret) in secrets {
+        merge_into(connection, &id, &mut merged, secret, "");
+    }
+
+    merged
+}
+
+fn merge_into(connection: &str, id: &str, target: &mut Value, source: Value, path: &str) {
+    match (target, source) {
+        (Value::Object(target), Value::Object(source)) => {
+            for (key, value) in source {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                match target.get_mut(&key) {
+                    Some(existing) => merge_into(connection, id, existing, value, &path),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, source) if *target != source => {
+            tracing::warn!(
+                "Secret {id} of connection {connection} also sets {path}, keeping the value of a secret of higher precedence"
+            );
+        }
+        _ => {}
+    }
+}
+
+/// Dotted paths of every leaf of a decrypted secret, e.g. `CLIENT.ID`, so its
+/// shape can be shown without its values. Arrays and empty objects are leaves.
+pub fn secret_leaf_paths(secret: &Value) -> Vec<String> {
+    let mut paths = Vec::new();
+    collect_leaf_paths(secret, "", &mut paths);
+    paths
+}
+
+fn collect_leaf_paths(value: &Value, path: &str, paths: &mut Vec<String>) {
+    match value {
+        Value::Object(object) if !object.is_empty() => {
+            for (key, value) in object {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                collect_leaf_paths(value, &path, paths);
+            }
+        }
+        _ if !path.is_empty() => paths.push(path.to_string()),
+        _ => {}
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::String(s) if s.trim().is_empty() => "empty",
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
 }
 
 #[cfg(test)]
@@ -140,4 +248,77 @@ mod tests {
         let custom_secret: CustomSecret = secret.decode().expect("Failed to decode secret");
         assert_eq!(custom_secret.secret_key, "brand_new_secret");
     }
+
+    #[test]
+    fn test_should_reject_secrets_that_are_not_objects() {
+        let secret = json!({"SECRET_KEY": "brand_new_secret"});
+        let secret = Secret::new(secret.to_string(), None, "buildable_id".to_string(), None);
+        assert!(secret.as_object("conn-key").unwrap().is_object());
+
+        for payload in ["", "brand_new_secret", "[\"brand_new_secret\"]", "42"] {
+            let secret = Secret::new(payload.to_string(), None, "buildable_id".to_string(), None);
+            let err = secret.as_object("conn-key").unwrap_err();
+
+            assert_eq!(err.error_code(), Some(PicaErrorCode::SecretNotObject));
+            assert!(err.to_string().contains("conn-key"));
+        }
+    }
+
+    #[test]
+    fn test_should_merge_secrets_by_precedence() {
+        let oauth = json!({
+            "ACCESS_TOKEN": "token",
+            "CLIENT": { "ID": "client", "REGION": "eu" },
+        });
+        let api_key = json!({
+            "API_KEY": "key",
+            "CLIENT": { "REGION": "us", "TENANT": "tenant" },
+        });
+        let fallback = json!({ "API_KEY": "stale", "CLIENT": "flat" });
+
+        let merged = merge_secrets(
+            "conn-key",
+            [
+                ("oauth".to_string(), oauth),
+                ("api-key".to_string(), api_key),
+                ("fallback".to_string(), fallback),
+            ],
+        );
+
+        assert_eq!(
+            merged,
+            json!({
+                "ACCESS_TOKEN": "token",
+                "API_KEY": "key",
+                "CLIENT": { "ID": "client", "REGION": "eu", "TENANT": "tenant" },
+            })
+        );
+        assert_eq!(
+            merge_secrets("conn-key", [("only".to_string(), json!({ "A": 1 }))]),
+            json!({ "A": 1 })
+        );
+    }
+
+    #[test]
+    fn test_should_list_the_paths_of_secret_leaves() {
+        let secret = json!({
+            "API_KEY": "key",
+            "CLIENT": { "ID": "client", "SCOPES": ["read"], "EXTRA": {} },
+            "OAUTH_REQUEST_PAYLOAD": { "formData": { "hotel_id": "h1" } },
+        });
+
+        let mut paths = secret_leaf_paths(&secret);
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "API_KEY",
+                "CLIENT.EXTRA",
+                "CLIENT.ID",
+                "CLIENT.SCOPES",
+                "OAUTH_REQUEST_PAYLOAD.formData.hotel_id",
+            ]
+        );
+        assert!(secret_leaf_paths(&json!("flat")).is_empty());
+    }
 }
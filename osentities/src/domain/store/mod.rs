@@ -102,5 +102,21 @@ generate_stores!(
     Clients,
     "clients",
     ConnectionVariableMappings,
-    "connection-variable-mappings"
+    "connection-variable-mappings",
+    ConnectionWebhooks,
+    "connection-webhooks",
+    StagedBundles,
+    "staged-bundles",
+    ConnectionModelDefinitionAudits,
+    "connection-model-definition-audits",
+    PlatformFlags,
+    "platform-flags",
+    PassthroughRecordings,
+    "passthrough-recordings",
+    DeadLetterEvents,
+    "dead-letter-events",
+    KnowledgeOverrides,
+    "knowledge-overrides",
+    Migrations,
+    "migrations"
 );
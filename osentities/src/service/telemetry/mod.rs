@@ -3,15 +3,19 @@ use axum::body::Body;
 use axum::extract::Request;
 use axum::middleware::Next;
 use axum::response::IntoResponse;
-use http::StatusCode;
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry::trace::TraceContextExt;
 use opentelemetry::{global, trace::TracerProvider};
 use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
 use opentelemetry_sdk::runtime::Tokio;
 use opentelemetry_sdk::trace::TracerProvider as OtelTracerProvider;
 use tracing::subscriber::set_global_default;
+use tracing::Span;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
 use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
 
@@ -132,3 +136,87 @@ impl Drop for OtelGuard {
         }
     }
 }
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+/// Makes `span` part of the trace a caller sent in the W3C `traceparent` and
+/// `tracestate` headers. Without a valid `traceparent` the span keeps its
+/// own parent.
+pub fn set_trace_parent(span: &Span, headers: &HeaderMap) {
+    let context = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+
+    if context.span().span_context().is_valid() {
+        span.set_parent(context);
+    }
+}
+
+/// Writes the trace context of `span` as W3C `traceparent` and `tracestate`
+/// headers, so the service a request is sent to records its spans as children
+/// of `span`. Nothing is written when traces are not exported.
+pub fn inject_trace_context(span: &Span, headers: &mut HeaderMap) {
+    TraceContextPropagator::new().inject_context(&span.context(), &mut HeaderInjector(headers));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::info_span;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    #[test]
+    fn test_outgoing_requests_continue_the_incoming_trace() {
+        let provider = OtelTracerProvider::builder().build();
+        let subscriber = Registry::default().with(OpenTelemetryLayer::new(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut incoming = HeaderMap::new();
+            incoming.insert(
+                "traceparent",
+                HeaderValue::from_str(&format!("00-{TRACE_ID}-00f067aa0ba902b7-01")).unwrap(),
+            );
+            incoming.insert("tracestate", HeaderValue::from_static("vendor=1"));
+
+            let span = info_span!("passthrough");
+            set_trace_parent(&span, &incoming);
+
+            let mut outgoing = HeaderMap::new();
+            inject_trace_context(&span, &mut outgoing);
+
+            let traceparent = outgoing["traceparent"].to_str().unwrap();
+            assert!(traceparent.starts_with(&format!("00-{TRACE_ID}-")));
+            assert!(!traceparent.contains("00f067aa0ba902b7"));
+            assert_eq!(outgoing["tracestate"], "vendor=1");
+
+            // Without an incoming trace the span starts one of its own
+            let span = info_span!("passthrough");
+            set_trace_parent(&span, &HeaderMap::new());
+            let mut outgoing = HeaderMap::new();
+            inject_trace_context(&span, &mut outgoing);
+            assert!(!outgoing["traceparent"].to_str().unwrap().contains(TRACE_ID));
+        });
+    }
+}
@@ -5,12 +5,11 @@ use osentities::{
     api_model_config::{ApiModelConfig, AuthMethod, OAuthLegacyHashAlgorithm},
     oauth_secret::OAuthLegacySecret,
     prelude::oauth_secret::OAuthSecret,
-    AuthorizationType, InternalError, Nonce, OAuthData, PicaError, SignableRequest,
+    AuthorizationType, InternalError, Nonce, OAuthData, PicaError, PicaErrorCode, SignableRequest,
     SignatureMethod, SigningKey,
 };
-use reqwest::{Client, Response, Url};
+use reqwest::{Client, Request, Response, Url};
 use serde_json::Value;
-use std::collections::HashMap;
 
 #[derive(Debug, Clone, Builder)]
 pub struct CallerClient<'a> {
@@ -28,18 +27,52 @@ impl<'a> CallerClient<'a> {
         }
     }
 
+    /// Url the request goes to, before query params are added
+    pub fn endpoint(&self) -> String {
+        if self.config.base_url.ends_with('/') || self.config.path.starts_with('/') {
+            format!("{}{}", self.config.base_url, self.config.path)
+        } else {
+            format!("{}/{}", self.config.base_url, self.config.path)
+        }
+    }
+
     pub async fn make_request(
         &self,
         payload: Option<Vec<u8>>,
         secret: Option<&Value>,
         headers: Option<HeaderMap>,
-        query_params: Option<&HashMap<String, String>>,
+        query_params: Option<&[(String, String)]>,
     ) -> Result<Response, PicaError> {
-        let endpoint = if self.config.base_url.ends_with('/') || self.config.path.starts_with('/') {
-            format!("{}{}", self.config.base_url, self.config.path)
-        } else {
-            format!("{}/{}", self.config.base_url, self.config.path)
-        };
+        let request = self.build_request(payload, secret, headers, query_params)?;
+
+        let res = self.client.execute(request).await.map_err(|e| {
+            let message = format!("Failed to send request: {}", e);
+
+            if e.is_timeout() {
+                InternalError::timeout(&message, PicaErrorCode::UpstreamTimeout.subtype())
+            } else if e.is_connect() {
+                InternalError::connection_error(
+                    &message,
+                    PicaErrorCode::UpstreamUnreachable.subtype(),
+                )
+            } else {
+                InternalError::io_err(&message, Some("reqwest::Error"))
+            }
+        })?;
+
+        Ok(res)
+    }
+
+    /// The request `make_request` sends, authentication included, built
+    /// without touching the network
+    pub fn build_request(
+        &self,
+        payload: Option<Vec<u8>>,
+        secret: Option<&Value>,
+        headers: Option<HeaderMap>,
+        query_params: Option<&[(String, String)]>,
+    ) -> Result<Request, PicaError> {
+        let endpoint = self.endpoint();
 
         let mut request_builder = self.client.request(self.action.clone(), &endpoint);
 
@@ -114,7 +147,7 @@ impl<'a> CallerClient<'a> {
 
                 let mut signable_request_params = IndexMap::new();
                 if let Some(custom_query_params) = query_params {
-                    signable_request_params.extend(custom_query_params.clone());
+                    signable_request_params.extend(custom_query_params.iter().cloned());
                 }
                 if let Some(model_query_params) = &self.config.query_params {
                     signable_request_params.extend(model_query_params.clone());
@@ -156,14 +189,12 @@ impl<'a> CallerClient<'a> {
             AuthMethod::None => request_builder,
         };
 
-        let res = request_builder.send().await.map_err(|e| {
-            InternalError::io_err(
-                &format!("Failed to send request: {}", e),
+        request_builder.build().map_err(|e| {
+            InternalError::invalid_argument(
+                &format!("Failed to build request: {}", e),
                 Some("reqwest::Error"),
             )
-        })?;
-
-        Ok(res)
+        })
     }
 }
 
@@ -239,6 +270,9 @@ mod tests {
             mapping: None,
             supported: true,
             knowledge: None,
+            lifecycle: None,
+            lifecycle_transition: None,
+            request_transform: None,
         };
 
         let client = Client::new();
@@ -315,6 +349,9 @@ mod tests {
             mapping: None,
             supported: true,
             knowledge: None,
+            lifecycle: None,
+            lifecycle_transition: None,
+            request_transform: None,
         };
 
         let client = Client::new();
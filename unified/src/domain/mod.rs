@@ -198,3 +198,8 @@ pub struct UnifiedCache {
     ttl: u64,
     key: String,
 }
+
+/// Set on a platform response when the request had to be sent again, e.g.
+/// after a rejected token was refreshed. Holds the number of resends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestRetries(pub u32);
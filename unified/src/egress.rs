@@ -0,0 +1,416 @@
+use osentities::{ApplicationError, InternalError, PicaError};
+use reqwest::{redirect, Client, ClientBuilder, Proxy, StatusCode, Url};
+use std::{
+    convert::Infallible,
+    fmt::{self, Display, Formatter},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
+/// Comma separated list of the hosts calls to platforms may go to, matched
+/// case-insensitively. `*.example.com` matches every subdomain of
+/// `example.com` but not `example.com` itself. An empty list allows every host.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllowedHosts(Vec<String>);
+
+impl AllowedHosts {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn allows(&self, host: &str) -> bool {
+        if self.0.is_empty() {
+            return true;
+        }
+
+        let host = host.trim_end_matches('.').to_lowercase();
+
+        self.0
+            .iter()
+            .any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+                None => *allowed == host,
+            })
+    }
+}
+
+impl FromStr for AllowedHosts {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            s.split(',')
+                .map(|host| host.trim().trim_end_matches('.').to_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+        ))
+    }
+}
+
+impl Display for AllowedHosts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join(","))
+    }
+}
+
+/// Which redirects of a platform are followed. The others are returned to the
+/// caller as they are, and so is the last one once `max_redirects` were
+/// followed. Redirects to hosts off the allowlist are never followed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectPolicy {
+    /// Most redirects followed in a call, 0 returning the first one
+    pub max_redirects: usize,
+    /// Follows redirects to another host than the one redirecting
+    pub cross_host: bool,
+    /// Only follows 307 and 308, which keep the method and body, as a 301,
+    /// 302 or 303 may turn the request into a GET without a body
+    pub preserve_method: bool,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max_redirects: 5,
+            cross_host: false,
+            preserve_method: false,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// Whether to follow a redirect with `status` from `previous`, the urls
+    /// requested so far, to `next`
+    pub fn follows(
+        &self,
+        status: StatusCode,
+        previous: &[Url],
+        next: &Url,
+        allowed_hosts: &AllowedHosts,
+    ) -> bool {
+        if previous.len() > self.max_redirects {
+            return false;
+        }
+
+        if self.preserve_method
+            && !matches!(
+                status,
+                StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT
+            )
+        {
+            return false;
+        }
+
+        let Some(host) = next.host_str() else {
+            return false;
+        };
+
+        if !allowed_hosts.allows(host) {
+            return false;
+        }
+
+        self.cross_host
+            || previous
+                .last()
+                .and_then(Url::host_str)
+                .is_some_and(|previous| previous.eq_ignore_ascii_case(host))
+    }
+}
+
+/// Where calls to connected platforms may go and how they get there
+#[derive(Debug, Clone, Default)]
+pub struct EgressConfig {
+    /// HTTP(S) proxy every call goes through, e.g. `http://proxy.internal:3128`
+    pub proxy_url: Option<String>,
+    pub allowed_hosts: AllowedHosts,
+    pub redirects: RedirectPolicy,
+}
+
+impl EgressConfig {
+    /// Client for calls to platforms, routed through the proxy when there is one
+    /// and following redirects by the redirect policy
+    pub fn client(&self) -> Result<Client, PicaError> {
+        self.builder()?.build().map_err(|e| {
+            InternalError::configuration_error(&format!("Could not build http client: {e}"), None)
+        })
+    }
+
+    /// Builder of [`EgressConfig::client`], for callers adding their own settings
+    pub fn builder(&self) -> Result<ClientBuilder, PicaError> {
+        let redirects = self.redirects.clone();
+        let allowed_hosts = self.allowed_hosts.clone();
+        let mut builder = Client::builder().redirect(redirect::Policy::custom(move |attempt| {
+            if redirects.follows(
+                attempt.status(),
+                attempt.previous(),
+                attempt.url(),
+                &allowed_hosts,
+            ) {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        }));
+
+        if let Some(proxy_url) = &self.proxy_url {
+            let proxy = Proxy::all(proxy_url).map_err(|e| {
+                InternalError::configuration_error(&format!("Invalid egress proxy: {e}"), None)
+            })?;
+            builder = builder.proxy(proxy);
+        }
+
+        Ok(builder)
+    }
+
+    /// Fails with a 403 unless the host of `url` is allowed. Run on the final
+    /// url, once the base url and path are templated.
+    pub fn check(&self, url: &str) -> Result<(), PicaError> {
+        if self.allowed_hosts.is_empty() {
+            return Ok(());
+        }
+
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string));
+
+        match host {
+            Some(host) if self.allowed_hosts.allows(&host) => Ok(()),
+            Some(host) => Err(ApplicationError::forbidden(
+                &format!("Calls to host {host} are not allowed"),
+                None,
+            )),
+            None => Err(ApplicationError::forbidden(
+                &format!("Calls to {url} are not allowed, it has no host"),
+                None,
+            )),
+        }
+    }
+}
+
+/// Whether `ip` only reaches the host itself, the private network or the
+/// cloud provider, e.g. its metadata service at `169.254.169.254`
+pub fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal_v4(ip),
+            None => is_internal_v6(ip),
+        },
+    }
+}
+
+fn is_internal_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // 0.0.0.0/8, reaching the host itself on most systems
+        || a == 0
+        // 100.64.0.0/10, shared address space also used by metadata services
+        || (a == 100 && (b & 0b1100_0000) == 64)
+}
+
+fn is_internal_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // fc00::/7, unique local addresses
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10, link local addresses
+        || (first & 0xffc0) == 0xfe80
+}
+
+/// Resolves the host of `url` and fails with a 400 when it has none, cannot be
+/// resolved or resolves to an internal address. Connections should go to the
+/// returned addresses, so that the host cannot resolve elsewhere in between.
+pub async fn resolve_public(url: &Url) -> Result<Vec<SocketAddr>, PicaError> {
+    let Some(host) = url.host_str() else {
+        return Err(ApplicationError::bad_request(
+            &format!("Calls to {url} are not allowed, it has no host"),
+            None,
+        ));
+    };
+    let port = url.port_or_known_default().unwrap_or_default();
+    // Brackets around IPv6 addresses are not part of the address
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| {
+            ApplicationError::bad_request(&format!("Could not resolve {host}: {e}"), None)
+        })?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(ApplicationError::bad_request(
+            &format!("Could not resolve {host}"),
+            None,
+        ));
+    }
+
+    if let Some(addr) = addrs.iter().find(|addr| is_internal(addr.ip())) {
+        return Err(ApplicationError::bad_request(
+            &format!(
+                "Calls to {host} are not allowed, it resolves to the internal address {}",
+                addr.ip()
+            ),
+            None,
+        ));
+    }
+
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_hosts_match_exact_and_wildcard_entries() {
+        let hosts: AllowedHosts = " API.Stripe.com, *.example.com,".parse().unwrap();
+
+        assert_eq!(hosts.to_string(), "api.stripe.com,*.example.com");
+        assert!(hosts.allows("api.stripe.com"));
+        assert!(hosts.allows("API.STRIPE.COM."));
+        assert!(hosts.allows("eu.api.example.com"));
+        assert!(!hosts.allows("example.com"));
+        assert!(!hosts.allows("badexample.com"));
+        assert!(!hosts.allows("stripe.com"));
+    }
+
+    #[test]
+    fn test_check_rejects_hosts_off_the_allowlist() {
+        let config = EgressConfig {
+            allowed_hosts: "api.stripe.com".parse().unwrap(),
+            ..Default::default()
+        };
+
+        assert!(config.check("https://api.stripe.com/v1/customers").is_ok());
+        assert!(config.check("https://api.stripe.com:8443/v1").is_ok());
+
+        for url in [
+            "https://api.stripe.com.evil.io/v1",
+            "https://api.stripe.com@evil.io/v1",
+            "not a url",
+        ] {
+            let error = config.check(url).unwrap_err();
+            assert_eq!(error.status(), 403, "{url}");
+        }
+
+        assert!(EgressConfig::default().check("https://anywhere.io").is_ok());
+    }
+
+    #[test]
+    fn test_invalid_proxy_is_a_configuration_error() {
+        let config = EgressConfig {
+            proxy_url: Some("not a proxy".to_string()),
+            ..Default::default()
+        };
+
+        assert!(config.client().is_err());
+        assert!(EgressConfig::default().client().is_ok());
+    }
+
+    #[test]
+    fn test_redirect_policy_bounds_hops_hosts_and_methods() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        let allowed_hosts: AllowedHosts = "api.stripe.com,files.stripe.com".parse().unwrap();
+        let origin = [url("https://api.stripe.com/v1/files")];
+        let policy = RedirectPolicy::default();
+
+        let same_host = url("https://API.stripe.com/v2/files");
+        let other_host = url("https://files.stripe.com/f_1");
+        let off_allowlist = url("http://169.254.169.254/latest/meta-data");
+
+        assert!(policy.follows(StatusCode::FOUND, &origin, &same_host, &allowed_hosts));
+        assert!(!policy.follows(StatusCode::FOUND, &origin, &other_host, &allowed_hosts));
+
+        let hops = vec![origin[0].clone(); 6];
+        assert!(policy.follows(StatusCode::FOUND, &hops[..5], &same_host, &allowed_hosts));
+        assert!(!policy.follows(StatusCode::FOUND, &hops, &same_host, &allowed_hosts));
+
+        let cross_host = RedirectPolicy {
+            cross_host: true,
+            ..Default::default()
+        };
+        assert!(cross_host.follows(StatusCode::FOUND, &origin, &other_host, &allowed_hosts));
+        assert!(!cross_host.follows(StatusCode::FOUND, &origin, &off_allowlist, &allowed_hosts));
+        assert!(cross_host.follows(
+            StatusCode::FOUND,
+            &origin,
+            &off_allowlist,
+            &AllowedHosts::default()
+        ));
+
+        let preserve_method = RedirectPolicy {
+            preserve_method: true,
+            ..Default::default()
+        };
+        for status in [
+            StatusCode::TEMPORARY_REDIRECT,
+            StatusCode::PERMANENT_REDIRECT,
+        ] {
+            assert!(preserve_method.follows(status, &origin, &same_host, &allowed_hosts));
+        }
+        for status in [
+            StatusCode::MOVED_PERMANENTLY,
+            StatusCode::FOUND,
+            StatusCode::SEE_OTHER,
+        ] {
+            assert!(!preserve_method.follows(status, &origin, &same_host, &allowed_hosts));
+        }
+
+        let none = RedirectPolicy {
+            max_redirects: 0,
+            ..Default::default()
+        };
+        assert!(!none.follows(StatusCode::FOUND, &origin, &same_host, &allowed_hosts));
+    }
+
+    #[test]
+    fn test_internal_addresses_are_recognized() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.100.100.200",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_internal(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in ["93.184.216.34", "100.128.0.1", "2606:4700::1111"] {
+            assert!(!is_internal(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_public_rejects_internal_hosts() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost/hook",
+        ] {
+            let error = resolve_public(&Url::parse(url).unwrap()).await.unwrap_err();
+            assert_eq!(error.status(), 400, "{url}");
+        }
+
+        let addrs = resolve_public(&Url::parse("https://93.184.216.34/hook").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(addrs, vec!["93.184.216.34:443".parse().unwrap()]);
+    }
+}
@@ -1,5 +1,8 @@
 pub mod algebra;
 pub mod client;
 pub mod domain;
+pub mod egress;
 pub mod helper;
+pub mod oauth;
+pub mod retry_budget;
 pub mod unified;
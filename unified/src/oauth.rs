@@ -0,0 +1,269 @@
+use chrono::{Duration, Utc};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use osentities::{
+    algebra::TemplateExt,
+    api_model_config::{ApiModelConfig, ContentType},
+    connection_oauth_definition::{Computation, ComputeRequest},
+    Connection, ErrorMeta, InternalError, OAuth, PicaError,
+};
+use reqwest::{Client, Request};
+use serde_json::{to_string_pretty, Value};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    str::FromStr,
+    sync::Arc,
+};
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::error;
+
+/// Seconds shaved off the platform's token lifetime so a token is refreshed
+/// before the platform starts rejecting it
+const EXPIRY_MARGIN_SECS: i64 = 120;
+const REFRESH_LOCK_STRIPES: usize = 64;
+
+/// Unix timestamp (in seconds) after which a token issued now should be refreshed
+pub fn expires_at(expires_in: i32) -> i64 {
+    Utc::now()
+        .checked_add_signed(Duration::seconds(expires_in as i64))
+        .unwrap_or_else(Utc::now)
+        .checked_sub_signed(Duration::seconds(EXPIRY_MARGIN_SECS))
+        .unwrap_or_else(Utc::now)
+        .timestamp()
+}
+
+pub fn is_oauth_enabled(oauth: Option<&OAuth>) -> bool {
+    matches!(oauth, Some(OAuth::Enabled { .. }))
+}
+
+/// Whether the stored access token of an OAuth connection is past its expiry
+pub fn is_token_expired(oauth: Option<&OAuth>, now: i64) -> bool {
+    matches!(
+        oauth,
+        Some(OAuth::Enabled {
+            expires_at: Some(expires_at),
+            ..
+        }) if *expires_at <= now
+    )
+}
+
+/// Serializes token refreshes per connection, so concurrent requests hitting
+/// an expired token trigger a single refresh. Connections share a fixed set of
+/// locks to keep memory bounded.
+#[derive(Clone)]
+pub struct OAuthRefreshLocks {
+    stripes: Arc<[Mutex<()>]>,
+}
+
+impl Default for OAuthRefreshLocks {
+    fn default() -> Self {
+        Self {
+            stripes: (0..REFRESH_LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+        }
+    }
+}
+
+impl OAuthRefreshLocks {
+    pub async fn lock(&self, connection: &Connection) -> MutexGuard<'_, ()> {
+        let mut hasher = DefaultHasher::new();
+        connection.id.to_string().hash(&mut hasher);
+
+        self.stripes[hasher.finish() as usize % self.stripes.len()]
+            .lock()
+            .await
+    }
+}
+
+/// Builds the request for an OAuth `init` or `refresh` step, rendering the
+/// configured headers, query params and body with the output of the step's
+/// computation. `client` is the one executing it, e.g. the egress client for
+/// token refreshes.
+pub fn oauth_request(
+    client: &Client,
+    config: &ApiModelConfig,
+    compute: &ComputeRequest,
+    payload: &Value,
+    template: &impl TemplateExt,
+) -> Result<Request, PicaError> {
+    let computation = compute
+        .computation
+        .clone()
+        .map(|computation| computation.compute::<Computation>(payload))
+        .transpose()
+        .map_err(|e| {
+            error!("Failed to compute oauth payload: {}", e);
+            InternalError::script_error(e.message().as_ref(), None)
+        })?;
+
+    let headers = header(config, computation.as_ref(), template)?;
+    let query = query(config, computation.as_ref(), template)?;
+    let body = body(payload, computation.as_ref(), template)?;
+
+    let request = client.post(config.uri()).headers(headers);
+
+    let request = match config.content {
+        Some(ContentType::Json) => request.json(&body).query(&query),
+        Some(ContentType::Form) => request.form(&body).query(&query),
+        _ => request.query(&query),
+    };
+
+    request.build().map_err(|e| {
+        error!("Failed to build static request: {}", e);
+        InternalError::unknown(&e.to_string(), None)
+    })
+}
+
+fn query(
+    config: &ApiModelConfig,
+    computation: Option<&Computation>,
+    template: &impl TemplateExt,
+) -> Result<Option<Value>, PicaError> {
+    let query_params = config.query_params.as_ref().map(|query_params| {
+        let mut map = HashMap::new();
+        for (key, value) in query_params {
+            let key = key.to_string();
+            let value = value.as_str();
+
+            map.insert(key, value.to_string());
+        }
+        map
+    });
+
+    match query_params {
+        Some(query_params) => {
+            let payload = computation.and_then(|computation| computation.clone().query_params);
+
+            let query_params_str = to_string_pretty(&query_params).map_err(|e| {
+                error!("Failed to serialize query params: {}", e);
+                InternalError::serialize_error(&e.to_string(), None)
+            })?;
+
+            let query_params = template.render(&query_params_str, payload.as_ref())?;
+
+            let query_params: BTreeMap<String, String> = serde_json::from_str(&query_params)
+                .map_err(|e| {
+                    error!("Failed to deserialize query params: {}", e);
+                    InternalError::deserialize_error(&e.to_string(), None)
+                })?;
+
+            Ok(Some(serde_json::to_value(query_params).map_err(|e| {
+                error!("Failed to serialize query params: {}", e);
+                InternalError::serialize_error(&e.to_string(), None)
+            })?))
+        }
+        None => Ok(None),
+    }
+}
+
+fn body(
+    payload: &Value,
+    computation: Option<&Computation>,
+    template: &impl TemplateExt,
+) -> Result<Option<Value>, PicaError> {
+    let body = computation.and_then(|computation| computation.clone().body);
+
+    match body {
+        Some(body) => {
+            let body_str = to_string_pretty(&body).map_err(|e| {
+                error!("Failed to serialize body: {}", e);
+                InternalError::serialize_error(&e.to_string(), None)
+            })?;
+
+            let body = template.render(&body_str, Some(payload))?;
+
+            Ok(Some(serde_json::from_str(&body).map_err(|e| {
+                error!("Failed to deserialize body: {}", e);
+                InternalError::deserialize_error(&e.to_string(), None)
+            })?))
+        }
+        None => Ok(None),
+    }
+}
+
+fn header(
+    config: &ApiModelConfig,
+    computation: Option<&Computation>,
+    template: &impl TemplateExt,
+) -> Result<HeaderMap, PicaError> {
+    let headers = config.headers.as_ref().and_then(|headers| {
+        let mut map = HashMap::new();
+        for (key, value) in headers {
+            let key = key.to_string();
+            let value = value.to_str().ok()?;
+
+            map.insert(key, value.to_string());
+        }
+        Some(map)
+    });
+
+    match headers {
+        Some(headers) => {
+            let payload = computation.and_then(|computation| computation.clone().headers);
+
+            let headers_str = to_string_pretty(&headers).map_err(|e| {
+                error!("Failed to serialize headers: {}", e);
+                InternalError::serialize_error(&e.to_string(), None)
+            })?;
+
+            let headers = template.render(&headers_str, payload.as_ref())?;
+
+            let headers: BTreeMap<String, String> =
+                serde_json::from_str(&headers).map_err(|e| {
+                    error!("Failed to deserialize headers: {}", e);
+                    InternalError::deserialize_error(&e.to_string(), None)
+                })?;
+
+            headers
+                .iter()
+                .try_fold(HeaderMap::new(), |mut header_map, (key, value)| {
+                    let key = HeaderName::from_str(key).map_err(|e| {
+                        error!("Failed to parse header name: {}", e);
+                        InternalError::invalid_argument(&e.to_string(), None)
+                    })?;
+
+                    let value = HeaderValue::from_str(value).map_err(|e| {
+                        error!("Failed to parse header value: {}", e);
+                        InternalError::invalid_argument(&e.to_string(), None)
+                    })?;
+
+                    header_map.insert(key, value);
+
+                    Ok(header_map)
+                })
+        }
+        None => Ok(HeaderMap::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use osentities::id::{prefix::IdPrefix, Id};
+
+    #[test]
+    fn detects_expired_tokens() {
+        let now = Utc::now().timestamp();
+        let enabled = |expires_at| {
+            Some(OAuth::Enabled {
+                connection_oauth_definition_id: Id::now(IdPrefix::ConnectionOAuthDefinition),
+                expires_in: Some(3600),
+                expires_at,
+            })
+        };
+
+        assert!(is_token_expired(enabled(Some(now - 1)).as_ref(), now));
+        assert!(!is_token_expired(enabled(Some(now + 60)).as_ref(), now));
+        assert!(!is_token_expired(enabled(None).as_ref(), now));
+        assert!(!is_token_expired(Some(&OAuth::Disabled), now));
+        assert!(!is_token_expired(None, now));
+    }
+
+    #[test]
+    fn expiry_keeps_a_safety_margin() {
+        let now = Utc::now().timestamp();
+        let at = expires_at(3600);
+
+        assert!(at <= now + 3600 - EXPIRY_MARGIN_SECS);
+        assert!(at >= now + 3600 - EXPIRY_MARGIN_SECS - 1);
+    }
+}
@@ -0,0 +1,119 @@
+use osentities::{InternalError, PicaError, PicaErrorCode};
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+/// Bounds what every retry source together may add to one request: the
+/// number of extra attempts and the time spent waiting or resending. Once
+/// either runs out, the next retry fails fast with a 504 instead of stacking
+/// more latency on the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryBudget {
+    max_attempts: u32,
+    max_delay_millis: u64,
+    attempts: u32,
+    delay_millis: u64,
+}
+
+impl RetryBudget {
+    pub fn new(max_attempts: u32, max_delay_millis: u64) -> Self {
+        Self {
+            max_attempts,
+            max_delay_millis,
+            attempts: 0,
+            delay_millis: 0,
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn delay_millis(&self) -> u64 {
+        self.delay_millis
+    }
+
+    /// Spends an extra attempt before it is made
+    pub fn attempt(&mut self) -> Result<(), PicaError> {
+        if self.attempts >= self.max_attempts || self.delay_millis >= self.max_delay_millis {
+            return Err(self.exhausted());
+        }
+
+        self.attempts += 1;
+        Ok(())
+    }
+
+    /// Spends a wait before it is waited out
+    pub fn delay(&mut self, millis: u64) -> Result<(), PicaError> {
+        if self.delay_millis.saturating_add(millis) > self.max_delay_millis {
+            return Err(self.exhausted());
+        }
+
+        self.delay_millis += millis;
+        Ok(())
+    }
+
+    /// Accounts for the time an attempt took once it is made, so the sources
+    /// retrying after it see less of the budget left
+    pub fn record(&mut self, elapsed: Duration) {
+        self.delay_millis = self
+            .delay_millis
+            .saturating_add(elapsed.as_millis().try_into().unwrap_or(u64::MAX));
+    }
+
+    fn exhausted(&self) -> PicaError {
+        InternalError::timeout(
+            &format!("The retry budget of the request is used up ({self})"),
+            PicaErrorCode::RetryBudgetExhausted.subtype(),
+        )
+    }
+}
+
+/// The consumed budget, as surfaced in the `pica-retry-budget` header
+impl Display for RetryBudget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "attempts={}/{}; delay-millis={}/{}",
+            self.attempts, self.max_attempts, self.delay_millis, self.max_delay_millis
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::StatusCode;
+
+    #[test]
+    fn sources_share_one_budget() {
+        let mut budget = RetryBudget::new(1, 1000);
+
+        budget.delay(600).unwrap();
+        budget.attempt().unwrap();
+        budget.record(Duration::from_millis(300));
+        assert_eq!(budget.to_string(), "attempts=1/1; delay-millis=900/1000");
+
+        let error = budget.attempt().unwrap_err();
+        assert_eq!(StatusCode::from(&error), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(
+            error.error_code(),
+            Some(PicaErrorCode::RetryBudgetExhausted)
+        );
+
+        // A wait that would overrun the budget is refused up front
+        assert!(budget.delay(101).is_err());
+        budget.delay(100).unwrap();
+        assert_eq!(budget.delay_millis(), 1000);
+    }
+
+    #[test]
+    fn time_spent_exhausts_the_budget_before_the_attempts_do() {
+        let mut budget = RetryBudget::new(3, 500);
+
+        budget.record(Duration::from_millis(500));
+        assert!(budget.attempt().is_err());
+        assert_eq!(budget.attempts(), 0);
+    }
+}
@@ -2,13 +2,16 @@ use crate::domain::{ResponseCrudToMapBuilder, ResponseCrudToMapRequest};
 use crate::{
     algebra::jsruntime::JSRuntimeImpl,
     client::CallerClient,
-    domain::{RequestCrud, ResponseCrud, UnifiedMetadata, UnifiedMetadataBuilder},
+    domain::{RequestCrud, RequestRetries, ResponseCrud, UnifiedMetadata, UnifiedMetadataBuilder},
+    egress::EgressConfig,
     helper::{match_route, template_route},
+    oauth::{expires_at, is_oauth_enabled, is_token_expired, oauth_request, OAuthRefreshLocks},
+    retry_budget::RetryBudget,
 };
 use bson::doc;
 use cache::local::{
     ConnectionCache, ConnectionModelDefinitionDestinationCache, ConnectionModelSchemaCache,
-    LocalCacheExt, SecretCache,
+    ConnectionVariableMappingCache, LocalCacheExt, SecretCache,
 };
 use chrono::Utc;
 use futures::{
@@ -16,19 +19,18 @@ use futures::{
     FutureExt,
 };
 use handlebars::Handlebars;
-use http::{HeaderMap, HeaderName, HeaderValue, Response, StatusCode};
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, Response, StatusCode};
 use mongodb::{
     options::{Collation, CollationStrength, FindOneOptions},
     Client,
 };
 use osentities::{
-    algebra::JsonExt,
+    algebra::{DefaultTemplate, JsonExt, TemplateExt},
     api_model_config::{ModelPaths, RequestModelPaths},
     connection_model_definition::{ConnectionModelDefinition, CrudAction, PlatformInfo},
     connection_model_schema::ConnectionModelSchema,
-    connection_variable_mapping::{
-        ConnectionVariableMapping, InjectionStrategy, ParameterLocation, VariableDataType,
-    },
+    connection_oauth_definition::{ConnectionOAuthDefinition, OAuthResponse},
+    connection_variable_mapping::ConnectionVariableMapping,
     constant::*,
     database::DatabaseConfig,
     destination::{Action, Destination},
@@ -36,13 +38,19 @@ use osentities::{
     error::InternalError,
     hashed_secret::HashedSecret,
     id::{prefix::IdPrefix, Id},
+    merge_additional_secrets,
+    oauth_secret::OAuthSecret,
     prelude::{MongoStore, TimedExt},
-    ApplicationError, Connection, ErrorMeta, PicaError, Secret, SecretExt, Store,
+    variable_injection::{resolve_base_url, RequestParts},
+    ApplicationError, Connection, ErrorMeta, OAuth, PicaError, Secret, SecretExt, Store,
 };
 use serde_json::{json, Number, Value};
-use std::{collections::HashMap, str::FromStr, sync::Arc};
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Instant};
 use tracing::error;
 
+/// Headers, query params and body of a request dispatched to a platform
+type DestinationRequest = (HeaderMap, HashMap<String, String>, Option<Vec<u8>>);
+
 pub struct UnifiedResponse {
     pub response: Response<Value>,
     pub metadata: UnifiedMetadata,
@@ -64,16 +72,21 @@ pub struct UnifiedDestination {
     pub connection_model_definitions_store: MongoStore<ConnectionModelDefinition>,
     pub connection_model_schemas_cache: ConnectionModelSchemaCache,
     pub connection_model_schemas_store: MongoStore<ConnectionModelSchema>,
+    pub connection_variable_mappings_cache: ConnectionVariableMappingCache,
     pub connection_variable_mappings_store: MongoStore<ConnectionVariableMapping>,
+    pub connection_oauth_definitions_store: MongoStore<ConnectionOAuthDefinition>,
+    pub oauth_refresh_locks: OAuthRefreshLocks,
     pub secrets_client: Arc<dyn SecretExt + Sync + Send>,
     pub secrets_cache: SecretCache,
     pub http_client: reqwest::Client,
+    pub egress: EgressConfig,
 }
 
 pub struct UnifiedCacheTTLs {
     pub connection_cache_ttl_secs: u64,
     pub connection_model_definition_cache_ttl_secs: u64,
     pub connection_model_schema_cache_ttl_secs: u64,
+    pub connection_variable_mapping_cache_ttl_secs: u64,
     pub secret_cache_ttl_secs: u64,
 }
 
@@ -83,8 +96,9 @@ impl UnifiedDestination {
         cache_size: u64,
         secrets_client: Arc<dyn SecretExt + Sync + Send>,
         cache_ttls: UnifiedCacheTTLs,
+        egress: EgressConfig,
     ) -> Result<Self, PicaError> {
-        let http_client = reqwest::Client::new();
+        let http_client = egress.client()?;
         let connections_cache =
             ConnectionCache::new(cache_size, cache_ttls.connection_cache_ttl_secs);
         let connection_model_definitions_cache = ConnectionModelDefinitionDestinationCache::new(
@@ -95,6 +109,10 @@ impl UnifiedDestination {
             cache_size,
             cache_ttls.connection_model_schema_cache_ttl_secs,
         );
+        let connection_variable_mappings_cache = ConnectionVariableMappingCache::new(
+            cache_size,
+            cache_ttls.connection_variable_mapping_cache_ttl_secs,
+        );
         let secrets_cache = SecretCache::new(cache_size, cache_ttls.secret_cache_ttl_secs);
 
         let client = Client::with_uri_str(&db_config.control_db_url)
@@ -115,6 +133,8 @@ impl UnifiedDestination {
             MongoStore::new(&db, &Store::ConnectionModelSchemas).await?;
         let connection_variable_mappings_store =
             MongoStore::new(&db, &Store::ConnectionVariableMappings).await?;
+        let connection_oauth_definitions_store =
+            MongoStore::new(&db, &Store::ConnectionOAuthDefinitions).await?;
 
         Ok(Self {
             connections_cache,
@@ -123,13 +143,41 @@ impl UnifiedDestination {
             connection_model_definitions_store,
             connection_model_schemas_cache,
             connection_model_schemas_store,
+            connection_variable_mappings_cache,
             connection_variable_mappings_store,
+            connection_oauth_definitions_store,
+            oauth_refresh_locks: OAuthRefreshLocks::default(),
             secrets_client,
             secrets_cache,
             http_client,
+            egress,
         })
     }
 
+    /// Mappings of a model definition (platform level), cached until their
+    /// TTL or until a mapping of the definition is written
+    pub async fn get_connection_variable_mappings(
+        &self,
+        connection_model_definition_id: &Id,
+    ) -> Result<Vec<ConnectionVariableMapping>, PicaError> {
+        self.connection_variable_mappings_cache
+            .get_or_insert_with_fn(connection_model_definition_id, || async {
+                self.connection_variable_mappings_store
+                    .get_many(
+                        Some(doc! {
+                            "connectionModelDefinitionId": connection_model_definition_id.to_string(),
+                            "deleted": false,
+                        }),
+                        None,
+                        None,
+                        None,
+                        None,
+                    )
+                    .await
+            })
+            .await
+    }
+
     pub async fn get_connection_model_definition(
         &self,
         destination: &Destination,
@@ -240,10 +288,12 @@ impl UnifiedDestination {
             })?),
         };
 
+        let query_params: Vec<_> = params.get_query_params().clone().into_iter().collect();
+
         self.execute_model_definition(
             config,
             params.get_headers().to_owned(),
-            params.get_query_params(),
+            &query_params,
             secret,
             context,
         )
@@ -254,28 +304,33 @@ impl UnifiedDestination {
         &self,
         config: &ConnectionModelDefinition,
         headers: HeaderMap,
-        query_params: &HashMap<String, String>,
+        query_params: &[(String, String)],
         secret: &Value,
         context: Option<Vec<u8>>,
     ) -> Result<reqwest::Response, PicaError> {
-        let renderer = Handlebars::new();
-
-        let config_str = serde_json::to_string(&config)
-            .map_err(|e| InternalError::invalid_argument(&e.to_string(), None))?;
-
-        let config = renderer
-            .render_template(&config_str, secret)
-            .map_err(|e| InternalError::invalid_argument(&e.to_string(), None))?;
-
-        let config: ConnectionModelDefinition = serde_json::from_str(&config)
-            .map_err(|e| InternalError::invalid_argument(&e.to_string(), None))?;
+        let config = render_model_definition(config, secret)?;
+        let request = config.transform_request(
+            RequestParts {
+                headers,
+                query_params: query_params.to_vec(),
+                body: context,
+                ..Default::default()
+            },
+            secret,
+        )?;
 
         match config.platform_info {
             PlatformInfo::Api(ref c) => {
                 let api_caller = CallerClient::new(c, config.action, &self.http_client);
+                self.egress.check(&api_caller.endpoint())?;
 
                 let response = api_caller
-                    .make_request(context, Some(secret), Some(headers), Some(query_params))
+                    .make_request(
+                        request.body,
+                        Some(secret),
+                        Some(request.headers),
+                        Some(&request.query_params),
+                    )
                     .await?;
 
                 Ok(response)
@@ -341,7 +396,7 @@ impl UnifiedDestination {
                     .action(action.to_string())
                     .common_model(config.mapping.as_ref().map(|m| m.common_model_name.clone()).unwrap_or_default());
 
-                let secret = insert_action_id(secret.as_value()?, id.as_ref());
+                let secret = insert_action_id(secret.as_object(&connection.key)?, id.as_ref());
 
                 // Namespace for js scripts
                 let jsruntime = JSRuntimeImpl;
@@ -541,6 +596,7 @@ impl UnifiedDestination {
         headers: HeaderMap,
         query_params: HashMap<String, String>,
         context: Option<Vec<u8>>,
+        retry_budget: &mut RetryBudget,
     ) -> Result<reqwest::Response, PicaError> {
         let connection = if let Some(connection) = connection {
             connection
@@ -580,166 +636,132 @@ impl UnifiedDestination {
             ));
         }
 
-        let secret = self
-            .secrets_cache
-            .get_or_insert_with_fn(connection.as_ref(), || async {
-                match self
-                    .secrets_client
-                    .get(&connection.secrets_service_id, &connection.ownership.id)
-                    .map(|v| Some(v).transpose())
-                    .await
-                {
-                    Ok(Some(c)) => Ok(c),
-                    Ok(None) => Err(InternalError::key_not_found("Secrets", None)),
-                    Err(e) => Err(InternalError::connection_error(
-                        format!("Failed to get secret: {}", e.message().as_ref()).as_str(),
-                        None,
-                    )),
+        let stored_mapping = ConnectionVariableMapping::resolve(
+            self.get_connection_variable_mappings(&config.id).await?,
+            connection.environment,
+            &connection.ownership.id,
+        );
+
+        // Expired OAuth tokens are refreshed up front, a failed refresh still
+        // lets the platform decide whether the stored token is usable
+        let mut refreshed = false;
+        let connection = if is_token_expired(connection.oauth.as_ref(), Utc::now().timestamp()) {
+            match self.refresh_oauth_token(&connection).await {
+                Ok(updated) => {
+                    refreshed = true;
+                    updated
                 }
-            })
+                Err(e) => {
+                    error!(
+                        "Could not refresh expired token for connection {}: {e}",
+                        connection.id
+                    );
+                    connection
+                }
+            }
+        } else {
+            connection
+        };
+
+        let secret_value = self.get_secret_value(&connection).await?;
+
+        // A token the platform rejects is refreshed once and the request retried
+        let request = (headers, query_params, context);
+        let retry =
+            (!refreshed && is_oauth_enabled(connection.oauth.as_ref())).then(|| request.clone());
+
+        let response = self
+            .send_destination_request(
+                &config,
+                stored_mapping.as_ref(),
+                destination,
+                &secret_value,
+                request,
+            )
             .await?;
 
-        let secret_value = secret.as_value()?;
+        let Some(request) = retry else {
+            return Ok(response);
+        };
+
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
 
+        retry_budget.attempt()?;
+        let started = Instant::now();
 
+        let connection = match self.refresh_oauth_token(&connection).await {
+            Ok(updated) => updated,
+            Err(e) => {
+                error!(
+                    "Could not refresh rejected token for connection {}: {e}",
+                    connection.id
+                );
+                return Ok(response);
+            }
+        };
 
-        let stored_mapping = self
-            .connection_variable_mappings_store
-            .get_many(
-                Some(doc! {
-                    // Lookup by model definition only (Platform Level)
-                    "connectionModelDefinitionId": config.id.to_string(),
-                }),
-                None,
-                None,
-                None,
-                None,
+        let secret_value = self.get_secret_value(&connection).await?;
+
+        let mut response = self
+            .send_destination_request(
+                &config,
+                stored_mapping.as_ref(),
+                destination,
+                &secret_value,
+                request,
             )
-            .await?
-            .first()
-            .cloned();
+            .await?;
+        retry_budget.record(started.elapsed());
+        response.extensions_mut().insert(RequestRetries(1));
 
-        let (mut headers, mut query_params, mut context) = (headers, query_params, context);
-        // We might need to modify the config (path), so we unwrap the Arc or clone
-        let mut config = config.as_ref().clone();
+        Ok(response)
+    }
 
-        if let Some(mapping) = stored_mapping {
+    async fn send_destination_request(
+        &self,
+        config: &ConnectionModelDefinition,
+        stored_mapping: Option<&ConnectionVariableMapping>,
+        destination: &Destination,
+        secret_value: &Value,
+        request: DestinationRequest,
+    ) -> Result<reqwest::Response, PicaError> {
+        let (mut headers, query_params, mut context) = request;
+        let mut query_params: Vec<_> = query_params.into_iter().collect();
+        // We might need to modify the config (path), so we clone it
+        let mut config = config.clone();
 
-            for binding in mapping.bindings {
-                // Extract variable value from secret
-                let variable_value = if let Some(payload) = secret_value.get("OAUTH_REQUEST_PAYLOAD") {
-                    payload
-                        .get("formData")
-                        .and_then(|fd| fd.get(&binding.variable_name))
-                } else if let Some(fd) = secret_value.get("auth_form_data") {
-                    fd.get(&binding.variable_name)
-                } else {
-                    secret_value.get(&binding.variable_name)
-                };
+        if let Some(mapping) = stored_mapping {
+            let PlatformInfo::Api(ref mut api_config) = config.platform_info;
 
-                if let Some(val) = variable_value {
-                    let mut target_value_json = match binding.data_type {
-                        VariableDataType::String => json!(val.as_str().map(|x| x.to_string()).unwrap_or_else(|| val.to_string())),
-                        VariableDataType::Number => {
-                            let s = val.as_str().map(|x| x.to_string()).unwrap_or_else(|| val.to_string());
-                            if let Ok(n) = s.parse::<i64>() {
-                                json!(n)
-                            } else if let Ok(n) = s.parse::<f64>() {
-                                json!(n)
-                            } else {
-                                json!(s)
-                            }
-                        },
-                        VariableDataType::Boolean => {
-                             let s = val.as_str().map(|x| x.to_string()).unwrap_or_else(|| val.to_string());
-                             json!(s.parse::<bool>().unwrap_or(false))
-                        },
-                        VariableDataType::Json => {
-                             // Assuming the secret value is JSON-compatible or a raw object.
-                             // If it's a string representation of JSON, parse it.
-                             let s = val.as_str().unwrap_or("");
-                             serde_json::from_str(s).unwrap_or_else(|_| val.clone())
-                        }
-                    };
-
-                    match binding.location {
-                        ParameterLocation::PathParam => {
-                             let s = target_value_json.as_str().map(|x| x.to_string()).unwrap_or_else(|| target_value_json.to_string());
-                             let PlatformInfo::Api(ref mut api_config) = config.platform_info;
-                             // Path Params usually always overwrite (Strict) due to resource identity.
-                             api_config.path = api_config.path.replace(&format!("{{{}}}", binding.target_param), &s);
-                        }
-                        ParameterLocation::QueryParam => {
-                             let s_val = target_value_json.as_str().map(|x| x.to_string()).unwrap_or_else(|| target_value_json.to_string());
-                             match binding.strategy {
-                                 InjectionStrategy::Strict => { query_params.insert(binding.target_param, s_val); },
-                                 InjectionStrategy::Fallback => { query_params.entry(binding.target_param).or_insert(s_val); },
-                                 InjectionStrategy::Append => {
-                                     query_params.entry(binding.target_param)
-                                         .and_modify(|existing| *existing = format!("{},{}", existing, s_val))
-                                         .or_insert(s_val);
-                                 }
-                             }
-                        }
-                        ParameterLocation::Header => {
-                             if let Ok(header_name) = HeaderName::from_str(&binding.target_param) {
-                                 let s_val = target_value_json.as_str().map(|x| x.to_string()).unwrap_or_else(|| target_value_json.to_string());
-                                 
-                                 if let Ok(header_value) = HeaderValue::from_str(&s_val) {
-                                     match binding.strategy {
-                                         InjectionStrategy::Strict => { headers.insert(header_name, header_value); },
-                                         InjectionStrategy::Fallback => { if !headers.contains_key(&header_name) { headers.insert(header_name, header_value); } },
-                                         InjectionStrategy::Append => {
-                                             // Headers support multiple values, but reqwest HeaderMap treats them as list?
-                                             // Or we append to string value?
-                                             // For robustness, comma-append string value.
-                                             if let Some(existing) = headers.get_mut(&header_name) {
-                                                 if let Ok(existing_str) = existing.to_str() {
-                                                     let new_val_str = format!("{},{}", existing_str, s_val);
-                                                      if let Ok(new_header_val) = HeaderValue::from_str(&new_val_str) {
-                                                          *existing = new_header_val;
-                                                      }
-                                                 }
-                                             } else {
-                                                 headers.insert(header_name, header_value);
-                                             }
-                                         }
-                                     }
-                                 }
-                             }
-                        }
-                        ParameterLocation::BodyField => {
-                            if let Some(body_bytes) = &context {
-                                if let Ok(mut json_body) = serde_json::from_slice::<Value>(body_bytes) {
-                                    if let Value::Object(ref mut map) = json_body {
-                                        match binding.strategy {
-                                            InjectionStrategy::Strict => { map.insert(binding.target_param, target_value_json); },
-                                            InjectionStrategy::Fallback => { map.entry(binding.target_param).or_insert(target_value_json); },
-                                            InjectionStrategy::Append => {
-                                                if let Some(existing) = map.get_mut(&binding.target_param) {
-                                                    if let Value::Array(arr) = existing {
-                                                        arr.push(target_value_json);
-                                                    } else if let Value::String(s) = existing {
-                                                        let s_val = target_value_json.as_str().unwrap_or("").to_string();
-                                                         let new_s = format!("{},{}", s, s_val);
-                                                         *existing = json!(new_s);
-                                                    }
-                                                } else {
-                                                    map.insert(binding.target_param, target_value_json);
-                                                }
-                                            }
-                                        }
+            let parts = RequestParts {
+                path: std::mem::take(&mut api_config.path),
+                headers,
+                query_params,
+                body: context,
+            }
+            .with_definition_content_type(api_config.headers.as_ref());
 
-                                        if let Ok(new_bytes) = serde_json::to_vec(&json_body) {
-                                            context = Some(new_bytes);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+            // The definition's headers are sent last, so its content type
+            // would replace the mapping's
+            if mapping.content_type.is_some() {
+                if let Some(headers) = api_config.headers.as_mut() {
+                    headers.remove(CONTENT_TYPE);
                 }
             }
+
+            let resolved = mapping.apply(parts, secret_value).inspect_err(|e| {
+                error!(
+                    "Could not inject variables for model definition {}: {e}",
+                    config.id
+                );
+            })?;
+
+            api_config.path = resolved.parts.path;
+            headers = resolved.parts.headers;
+            query_params = resolved.parts.query_params;
+            context = resolved.parts.body;
         }
 
         // Template the route for passthrough actions
@@ -759,12 +781,188 @@ impl UnifiedDestination {
             &templated_config,
             headers,
             &query_params,
-            &secret_value,
+            secret_value,
             context,
         )
         .await
     }
 
+    /// The main secret of a connection, merged with its additional ones
+    async fn get_secret_value(&self, connection: &Connection) -> Result<Value, PicaError> {
+        self.get_secret(connection)
+            .await?
+            .as_object(&connection.key)
+    }
+
+    /// The secret of a connection as cached: its main secret merged with its
+    /// additional ones, so these are only read again once the entry expires
+    async fn get_secret(&self, connection: &Connection) -> Result<Secret, PicaError> {
+        self.secrets_cache
+            .get_or_insert_with_fn(connection, || async {
+                let primary = match self
+                    .secrets_client
+                    .get(&connection.secrets_service_id, &connection.ownership.id)
+                    .map(|v| Some(v).transpose())
+                    .await
+                {
+                    Ok(Some(c)) => c,
+                    Ok(None) => return Err(InternalError::key_not_found("Secrets", None)),
+                    // Missing, undecryptable and unreachable secrets keep
+                    // their own codes
+                    Err(e) if e.error_code().is_some() => return Err(e),
+                    Err(e) => {
+                        return Err(InternalError::connection_error(
+                            format!("Failed to get secret: {}", e.message().as_ref()).as_str(),
+                            None,
+                        ))
+                    }
+                };
+
+                self.with_additional_secrets(connection, primary).await
+            })
+            .await
+    }
+
+    async fn with_additional_secrets(
+        &self,
+        connection: &Connection,
+        primary: Secret,
+    ) -> Result<Secret, PicaError> {
+        if connection.additional_secrets_service_ids.is_empty() {
+            return Ok(primary);
+        }
+
+        let merged = merge_additional_secrets(
+            self.secrets_client.as_ref(),
+            connection,
+            primary.as_object(&connection.key)?,
+        )
+        .await?;
+
+        Ok(primary.with_value(&merged))
+    }
+
+    /// Exchanges the refresh token of an OAuth connection for a new access
+    /// token, stores it as a new secret and points the connection at it.
+    /// Callers racing on the same connection wait for the first refresh and
+    /// reuse its result.
+    pub async fn refresh_oauth_token(
+        &self,
+        connection: &Connection,
+    ) -> Result<Arc<Connection>, PicaError> {
+        let Some(OAuth::Enabled {
+            connection_oauth_definition_id,
+            ..
+        }) = &connection.oauth
+        else {
+            return Err(InternalError::invalid_argument(
+                "Connection does not use OAuth",
+                None,
+            ));
+        };
+
+        let _guard = self.oauth_refresh_locks.lock(connection).await;
+
+        let latest = self
+            .connections_store
+            .get_one_by_id(&connection.id.to_string())
+            .await?
+            .ok_or_else(|| InternalError::key_not_found("Connection", None))?;
+
+        if latest.secrets_service_id != connection.secrets_service_id
+            || latest.oauth != connection.oauth
+        {
+            tracing::debug!(
+                "Token for connection {} was already refreshed",
+                connection.id
+            );
+            return Ok(Arc::new(latest));
+        }
+
+        let definition = self
+            .connection_oauth_definitions_store
+            .get_one_by_id(&connection_oauth_definition_id.to_string())
+            .await?
+            .ok_or_else(|| InternalError::key_not_found("Connection OAuth definition", None))?;
+
+        let secret: OAuthSecret = self
+            .secrets_client
+            .get(&latest.secrets_service_id, &latest.ownership.id)
+            .await?
+            .decode()?;
+        let payload = secret.as_json();
+
+        let template = DefaultTemplate::default();
+        let definition = if definition.is_full_template_enabled {
+            template.render_as(&definition, Some(&payload))?
+        } else {
+            definition
+        };
+
+        let request = oauth_request(
+            &self.http_client,
+            &definition.configuration.refresh,
+            &definition.compute.refresh,
+            &payload,
+            &template,
+        )?;
+
+        self.egress.check(request.url().as_str())?;
+        let response = self.http_client.execute(request).await?;
+        if !response.status().is_success() {
+            return Err(InternalError::io_err(
+                &format!("Token refresh was rejected with {}", response.status()),
+                None,
+            ));
+        }
+
+        let response = response.json::<Value>().await.map_err(|e| {
+            InternalError::deserialize_error(&e.to_string(), Some("oauth_refresh_response"))
+        })?;
+
+        let decoded: OAuthResponse = definition.compute.refresh.response.compute(&response)?;
+        let refreshed = secret.from_refresh(decoded, None, None, response);
+
+        let stored = self
+            .secrets_client
+            .create(&refreshed.as_json(), &latest.ownership.id)
+            .await?;
+
+        let oauth = OAuth::Enabled {
+            connection_oauth_definition_id: *connection_oauth_definition_id,
+            expires_in: Some(refreshed.expires_in),
+            expires_at: Some(expires_at(refreshed.expires_in)),
+        };
+        let oauth_bson = bson::to_bson(&oauth)
+            .map_err(|e| InternalError::serialize_error(&e.to_string(), None))?;
+
+        self.connections_store
+            .update_one(
+                &latest.id.to_string(),
+                doc! {
+                    "$set": {
+                        "secretsServiceId": stored.id(),
+                        "oauth": oauth_bson,
+                        "updatedAt": Utc::now().timestamp_millis(),
+                    }
+                },
+            )
+            .await?;
+
+        let mut updated = latest;
+        updated.secrets_service_id = stored.id();
+        updated.oauth = Some(oauth);
+
+        self.connections_cache.remove(&updated.key).await?;
+        self.secrets_cache.remove(&updated).await?;
+        let cached = self.with_additional_secrets(&updated, stored).await?;
+        self.secrets_cache.insert(&updated, &cached).await?;
+
+        tracing::info!("Refreshed OAuth token for connection {}", updated.id);
+
+        Ok(Arc::new(updated))
+    }
+
     async fn get_dependencies(
         &self,
         key: &Destination,
@@ -788,23 +986,7 @@ impl UnifiedDestination {
                 }
             });
 
-        let secret_fut = self
-            .secrets_cache
-            .get_or_insert_with_fn(connection, || async {
-                match self
-                    .secrets_client
-                    .get(&connection.secrets_service_id, &connection.ownership.id)
-                    .map(|v| Some(v).transpose())
-                    .await
-                {
-                    Ok(Some(c)) => Ok(c),
-                    Ok(None) => Err(InternalError::key_not_found("secret", None)),
-                    Err(e) => Err(InternalError::connection_error(
-                        format!("Failed to get secret: {}", e.message().as_ref()).as_str(),
-                        None,
-                    )),
-                }
-            });
+        let secret_fut = self.get_secret(connection);
 
         let schema_key: (Arc<str>, Arc<str>) = (connection.platform.clone(), name.into());
 
@@ -840,6 +1022,35 @@ impl UnifiedDestination {
     }
 }
 
+/// The model definition as it is dispatched, its templates rendered with the
+/// secret and the connection variables of its base url resolved
+pub fn render_model_definition(
+    config: &ConnectionModelDefinition,
+    secret: &Value,
+) -> Result<ConnectionModelDefinition, PicaError> {
+    let renderer = Handlebars::new();
+
+    let config_str = serde_json::to_string(&config)
+        .map_err(|e| InternalError::invalid_argument(&e.to_string(), None))?;
+
+    let config = renderer
+        .render_template(&config_str, secret)
+        .map_err(|e| InternalError::invalid_argument(&e.to_string(), None))?;
+
+    let mut config: ConnectionModelDefinition = serde_json::from_str(&config)
+        .map_err(|e| InternalError::invalid_argument(&e.to_string(), None))?;
+
+    let PlatformInfo::Api(ref mut api_config) = config.platform_info;
+    api_config.base_url = resolve_base_url(&api_config.base_url, secret).inspect_err(|e| {
+        error!(
+            "Could not resolve base url of model definition {}: {e}",
+            config.id
+        );
+    })?;
+
+    Ok(config)
+}
+
 fn build_unified_response(
     config: ConnectionModelDefinition,
     metadata: &mut UnifiedMetadataBuilder,